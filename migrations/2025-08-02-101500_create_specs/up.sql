@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE TABLE "specs"(
+	"id" TEXT NOT NULL PRIMARY KEY,
+	"session_id" TEXT NOT NULL,
+	"version" INT4 NOT NULL,
+	"content" TEXT NOT NULL,
+	"diff" TEXT,
+	"accepted_at" TIMESTAMPTZ,
+	"created_at" TIMESTAMPTZ NOT NULL,
+	FOREIGN KEY ("session_id") REFERENCES "sessions"("id"),
+	UNIQUE ("session_id", "version")
+);
@@ -0,0 +1,20 @@
+-- Your SQL goes here
+CREATE TABLE "plans"(
+	"id" TEXT NOT NULL PRIMARY KEY,
+	"session_id" TEXT NOT NULL,
+	"spec_id" TEXT NOT NULL,
+	"status" INT4 NOT NULL,
+	"root" JSONB NOT NULL,
+	"approved_at" TIMESTAMPTZ,
+	"created_at" TIMESTAMPTZ NOT NULL,
+	"modified_at" TIMESTAMPTZ NOT NULL,
+	FOREIGN KEY ("session_id") REFERENCES "sessions"("id"),
+	FOREIGN KEY ("spec_id") REFERENCES "specs"("id")
+);
+
+CREATE TABLE "user_settings"(
+	"user_id" TEXT NOT NULL PRIMARY KEY,
+	"auto_approve_plans" BOOLEAN NOT NULL DEFAULT FALSE,
+	"modified_at" TIMESTAMPTZ NOT NULL,
+	FOREIGN KEY ("user_id") REFERENCES "users"("id")
+);
@@ -0,0 +1,15 @@
+-- Your SQL goes here
+CREATE TABLE "file_actions"(
+	"id" TEXT NOT NULL PRIMARY KEY,
+	"session_id" TEXT NOT NULL,
+	"action_type" INT4 NOT NULL,
+	"path" TEXT NOT NULL,
+	"before" TEXT,
+	"after" TEXT,
+	"diff" TEXT NOT NULL,
+	"reverts" TEXT,
+	"reverted_at" TIMESTAMPTZ,
+	"created_at" TIMESTAMPTZ NOT NULL,
+	FOREIGN KEY ("session_id") REFERENCES "sessions"("id"),
+	FOREIGN KEY ("reverts") REFERENCES "file_actions"("id")
+);
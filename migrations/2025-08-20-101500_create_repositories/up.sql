@@ -0,0 +1,26 @@
+-- Your SQL goes here
+CREATE TABLE "repositories"(
+	"id" TEXT NOT NULL PRIMARY KEY,
+	"workspace_id" TEXT NOT NULL,
+	"name" TEXT NOT NULL,
+	"source" TEXT NOT NULL,
+	"default_branch" TEXT NOT NULL,
+	"created_at" TIMESTAMPTZ NOT NULL,
+	"merge_upstream" BOOLEAN NOT NULL DEFAULT FALSE,
+	FOREIGN KEY ("workspace_id") REFERENCES "workspaces"("id"),
+	UNIQUE ("workspace_id", "name")
+);
+
+CREATE TABLE "worktrees"(
+	"id" TEXT NOT NULL PRIMARY KEY,
+	"session_id" TEXT NOT NULL,
+	"repository_id" TEXT NOT NULL,
+	"branch" TEXT NOT NULL,
+	"base_commit" TEXT NOT NULL,
+	"status" INT4 NOT NULL,
+	"merge_commit" TEXT,
+	"created_at" TIMESTAMPTZ NOT NULL,
+	"modified_at" TIMESTAMPTZ NOT NULL,
+	FOREIGN KEY ("session_id") REFERENCES "sessions"("id"),
+	FOREIGN KEY ("repository_id") REFERENCES "repositories"("id") ON DELETE CASCADE
+);
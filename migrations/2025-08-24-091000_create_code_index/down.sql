@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS "code_symbols";
+DROP TABLE IF EXISTS "code_files";
@@ -0,0 +1,27 @@
+-- Your SQL goes here
+CREATE TABLE "code_files"(
+	"id" TEXT NOT NULL PRIMARY KEY,
+	"worktree_id" TEXT NOT NULL,
+	"path" TEXT NOT NULL,
+	"language" INT4 NOT NULL,
+	"hash" TEXT NOT NULL,
+	"indexed_at" TIMESTAMPTZ NOT NULL,
+	FOREIGN KEY ("worktree_id") REFERENCES "worktrees"("id") ON DELETE CASCADE,
+	UNIQUE ("worktree_id", "path")
+);
+
+CREATE TABLE "code_symbols"(
+	"id" TEXT NOT NULL PRIMARY KEY,
+	"file_id" TEXT NOT NULL,
+	"worktree_id" TEXT NOT NULL,
+	"name" TEXT NOT NULL,
+	"kind" INT4 NOT NULL,
+	"definition" BOOL NOT NULL,
+	"line" INT4 NOT NULL,
+	"column" INT4 NOT NULL,
+	"end_line" INT4 NOT NULL,
+	"signature" TEXT,
+	FOREIGN KEY ("file_id") REFERENCES "code_files"("id") ON DELETE CASCADE
+);
+
+CREATE INDEX "code_symbols_worktree_id_name_idx" ON "code_symbols"("worktree_id", "name");
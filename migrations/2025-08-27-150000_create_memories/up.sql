@@ -0,0 +1,19 @@
+-- Your SQL goes here
+CREATE TABLE "memories"(
+	"id" TEXT NOT NULL PRIMARY KEY,
+	"workspace_id" TEXT NOT NULL,
+	"kind" INT4 NOT NULL,
+	"content" TEXT NOT NULL,
+	"source_session_id" TEXT,
+	"source_entry_id" TEXT,
+	"confidence" FLOAT4 NOT NULL,
+	"embedding" FLOAT4[],
+	"expires_at" TIMESTAMPTZ,
+	"created_at" TIMESTAMPTZ NOT NULL,
+	"modified_at" TIMESTAMPTZ NOT NULL,
+	FOREIGN KEY ("workspace_id") REFERENCES "workspaces"("id") ON DELETE CASCADE,
+	FOREIGN KEY ("source_session_id") REFERENCES "sessions"("id") ON DELETE SET NULL,
+	FOREIGN KEY ("source_entry_id") REFERENCES "timeline_entries"("id") ON DELETE SET NULL
+);
+
+CREATE INDEX "memories_workspace_id_idx" ON "memories"("workspace_id");
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER IF EXISTS "timeline_entries_search" ON "timeline_entries";
+DROP FUNCTION IF EXISTS "index_timeline_entry";
+DROP TABLE IF EXISTS "timeline_search";
+DROP FUNCTION IF EXISTS "timeline_entry_text";
@@ -0,0 +1,57 @@
+-- Your SQL goes here
+-- The searchable text of a timeline entry, without ids and other values
+-- nobody searches for
+CREATE FUNCTION "timeline_entry_text"("content" JSONB) RETURNS TEXT AS $$
+	SELECT concat_ws(E'\n',
+		"content" ->> 'message',
+		"content" ->> 'question',
+		"content" ->> 'response',
+		"content" ->> 'path',
+		CASE WHEN "content" ->> 'type' = 'spec' THEN "content" ->> 'content' END,
+		(SELECT string_agg("value" #>> '{}', E'\n')
+			FROM jsonb_path_query("content", 'lax $.options[*]') AS "value"),
+		(SELECT string_agg("value" #>> '{}', E'\n')
+			FROM jsonb_path_query("content", 'lax $.lines[*]') AS "value"),
+		(SELECT string_agg("value" #>> '{}', E'\n')
+			FROM jsonb_path_query("content" -> 'root', 'strict $.**.goal') AS "value"),
+		(SELECT string_agg("value" #>> '{}', E'\n')
+			FROM jsonb_path_query("content" -> 'root', 'strict $.**.description') AS "value")
+	)
+$$ LANGUAGE SQL IMMUTABLE;
+
+CREATE TABLE "timeline_search"(
+	"entry_id" TEXT NOT NULL PRIMARY KEY,
+	"text" TEXT NOT NULL,
+	"embedding" FLOAT4[],
+	-- Failed embeddings, entries are given up on after a few
+	"attempts" INTEGER NOT NULL DEFAULT 0,
+	FOREIGN KEY ("entry_id") REFERENCES "timeline_entries"("id") ON DELETE CASCADE
+);
+
+CREATE INDEX "timeline_search_text_idx" ON "timeline_search"
+	USING GIN (to_tsvector('english', "text"));
+
+-- Entries without text, e.g. statuses, are not searchable. A changed text
+-- has to be embedded again, and gets a fresh try.
+CREATE FUNCTION "index_timeline_entry"() RETURNS TRIGGER AS $$
+DECLARE
+	"entry_text" TEXT := timeline_entry_text(NEW."content");
+BEGIN
+	IF "entry_text" = '' THEN
+		DELETE FROM "timeline_search" WHERE "entry_id" = NEW."id";
+	ELSE
+		INSERT INTO "timeline_search"("entry_id", "text") VALUES (NEW."id", "entry_text")
+		ON CONFLICT ("entry_id") DO UPDATE SET "text" = EXCLUDED."text", "embedding" = NULL, "attempts" = 0
+			WHERE "timeline_search"."text" IS DISTINCT FROM EXCLUDED."text";
+	END IF;
+	RETURN NEW;
+END
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER "timeline_entries_search"
+	AFTER INSERT OR UPDATE OF "content" ON "timeline_entries"
+	FOR EACH ROW EXECUTE FUNCTION "index_timeline_entry"();
+
+INSERT INTO "timeline_search"("entry_id", "text")
+SELECT "id", timeline_entry_text("content") FROM "timeline_entries"
+WHERE timeline_entry_text("content") <> '';
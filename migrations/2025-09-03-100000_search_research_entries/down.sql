@@ -0,0 +1,18 @@
+-- This file should undo anything in `up.sql`
+CREATE OR REPLACE FUNCTION "timeline_entry_text"("content" JSONB) RETURNS TEXT AS $$
+	SELECT concat_ws(E'\n',
+		"content" ->> 'message',
+		"content" ->> 'question',
+		"content" ->> 'response',
+		"content" ->> 'path',
+		CASE WHEN "content" ->> 'type' = 'spec' THEN "content" ->> 'content' END,
+		(SELECT string_agg("value" #>> '{}', E'\n')
+			FROM jsonb_path_query("content", 'lax $.options[*]') AS "value"),
+		(SELECT string_agg("value" #>> '{}', E'\n')
+			FROM jsonb_path_query("content", 'lax $.lines[*]') AS "value"),
+		(SELECT string_agg("value" #>> '{}', E'\n')
+			FROM jsonb_path_query("content" -> 'root', 'strict $.**.goal') AS "value"),
+		(SELECT string_agg("value" #>> '{}', E'\n')
+			FROM jsonb_path_query("content" -> 'root', 'strict $.**.description') AS "value")
+	)
+$$ LANGUAGE SQL IMMUTABLE;
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE TABLE "web_pages" (
+    "workspace_id" TEXT NOT NULL REFERENCES "workspaces"("id") ON DELETE CASCADE,
+    "url" TEXT NOT NULL,
+    "title" TEXT NOT NULL,
+    "markdown" TEXT NOT NULL,
+    "scraped_at" TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY ("workspace_id", "url")
+);
+
+CREATE INDEX "web_pages_text_idx" ON "web_pages"
+    USING GIN (to_tsvector('english', "title" || ' ' || "markdown"));
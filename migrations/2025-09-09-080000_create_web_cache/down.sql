@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS "page_cache";
+DROP TABLE IF EXISTS "search_cache";
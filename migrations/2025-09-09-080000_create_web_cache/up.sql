@@ -0,0 +1,23 @@
+-- Your SQL goes here
+CREATE TABLE "search_cache"(
+	"workspace_id" TEXT NOT NULL,
+	"query_key" TEXT NOT NULL,
+	"page" INT4 NOT NULL,
+	"query" TEXT NOT NULL,
+	"results" JSONB NOT NULL,
+	"fetched_at" TIMESTAMPTZ NOT NULL,
+	PRIMARY KEY ("workspace_id", "query_key", "page"),
+	FOREIGN KEY ("workspace_id") REFERENCES "workspaces"("id") ON DELETE CASCADE
+);
+
+CREATE TABLE "page_cache"(
+	"workspace_id" TEXT NOT NULL,
+	"url_key" TEXT NOT NULL,
+	"url" TEXT NOT NULL,
+	"title" TEXT NOT NULL,
+	"html" TEXT NOT NULL,
+	"markdown" TEXT NOT NULL,
+	"fetched_at" TIMESTAMPTZ NOT NULL,
+	PRIMARY KEY ("workspace_id", "url_key"),
+	FOREIGN KEY ("workspace_id") REFERENCES "workspaces"("id") ON DELETE CASCADE
+);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS "attachment_chunks";
+DROP TABLE IF EXISTS "attachments";
@@ -0,0 +1,30 @@
+-- Your SQL goes here
+-- Files that belong to a session, e.g. screenshots shown on its timeline or
+-- documents uploaded to it. Documents keep their file name, which helps
+-- telling their format.
+CREATE TABLE "attachments"(
+	"id" TEXT NOT NULL PRIMARY KEY,
+	"session_id" TEXT NOT NULL,
+	"media_type" TEXT NOT NULL,
+	"data" BYTEA NOT NULL,
+	"created_at" TIMESTAMPTZ NOT NULL,
+	"name" TEXT,
+	FOREIGN KEY ("session_id") REFERENCES "sessions"("id") ON DELETE CASCADE
+);
+
+-- The chunks of a document, stored when it is uploaded so searches need not
+-- convert it again. They are embedded when first searched by meaning.
+CREATE TABLE "attachment_chunks"(
+	"attachment_id" TEXT NOT NULL,
+	"id" TEXT NOT NULL,
+	"position" INTEGER NOT NULL,
+	"start" INTEGER NOT NULL,
+	"end" INTEGER NOT NULL,
+	"text" TEXT NOT NULL,
+	"headings" TEXT[] NOT NULL,
+	"page" INTEGER,
+	"tokens" INTEGER NOT NULL,
+	"embedding" FLOAT4[],
+	PRIMARY KEY ("attachment_id", "id"),
+	FOREIGN KEY ("attachment_id") REFERENCES "attachments"("id") ON DELETE CASCADE
+);
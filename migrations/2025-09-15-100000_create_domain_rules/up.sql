@@ -0,0 +1,11 @@
+-- Your SQL goes here
+-- Which domains may be fetched for a workspace
+CREATE TABLE "domain_rules"(
+	"id" TEXT NOT NULL PRIMARY KEY,
+	"workspace_id" TEXT NOT NULL,
+	"domain" TEXT NOT NULL,
+	"access" INT4 NOT NULL,
+	"created_at" TIMESTAMPTZ NOT NULL,
+	UNIQUE ("workspace_id", "domain"),
+	FOREIGN KEY ("workspace_id") REFERENCES "workspaces"("id") ON DELETE CASCADE
+);
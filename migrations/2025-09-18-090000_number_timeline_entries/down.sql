@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX "timeline_entries_session_seq";
+ALTER TABLE "timeline_entries" DROP COLUMN "seq";
@@ -0,0 +1,17 @@
+-- Your SQL goes here
+-- Entries are numbered in the order they are stored, so a client can resume
+-- after the last entry it has seen even when several share a timestamp
+CREATE SEQUENCE "timeline_entries_seq_seq";
+ALTER TABLE "timeline_entries" ADD COLUMN "seq" BIGINT;
+UPDATE "timeline_entries" SET "seq" = "numbered"."seq"
+FROM (
+	SELECT "id", row_number() OVER (ORDER BY "created_at", "id") AS "seq"
+	FROM "timeline_entries"
+) AS "numbered"
+WHERE "timeline_entries"."id" = "numbered"."id";
+SELECT setval('timeline_entries_seq_seq', COALESCE(max("seq"), 0) + 1, false) FROM "timeline_entries";
+ALTER TABLE "timeline_entries"
+	ALTER COLUMN "seq" SET DEFAULT nextval('timeline_entries_seq_seq'),
+	ALTER COLUMN "seq" SET NOT NULL;
+ALTER SEQUENCE "timeline_entries_seq_seq" OWNED BY "timeline_entries"."seq";
+CREATE UNIQUE INDEX "timeline_entries_session_seq" ON "timeline_entries"("session_id", "seq");
@@ -0,0 +1,103 @@
+use crate::prelude::*;
+
+use std::sync::Arc;
+
+use indoc::indoc;
+use tera::Context;
+use tera::Tera;
+
+use crate::agent::AgentResponse;
+use crate::driver::editor::EditOperation;
+use crate::driver::llm::Llm;
+
+const PROMPT: &str = indoc! {r#"
+    You are the Editor. You change a single file according to the instructions.
+
+    {% if exists and content %}
+    Reply with one or more search/replace blocks and nothing else:
+    <<<<<<< SEARCH
+    lines copied exactly from the current file
+    =======
+    the lines that replace them
+    >>>>>>> REPLACE
+
+    Every search part has to match exactly one place of the file, include enough surrounding lines to make it unique.
+    Keep the blocks small, unchanged parts of the file do not need to be repeated.
+
+    <file path="{{ path }}">
+    {{ content }}
+    </file>
+    {% elif exists %}
+    The file `{{ path }}` is empty. Reply with its whole content and nothing else.
+    {% else %}
+    The file `{{ path }}` does not exist yet. Reply with its whole content and nothing else.
+    {% endif %}
+
+    {% if related %}
+    Keep the change consistent with the code it relates to:
+    <related_code>
+    {{ related }}
+    </related_code>
+
+    {% endif %}
+    {% if memories %}
+    Follow what is known about the workspace:
+    <workspace_memories>
+    {{ memories }}
+    </workspace_memories>
+
+    {% endif %}
+    <instructions>
+    {{ instructions }}
+    </instructions>
+"#};
+
+/// Drafts the edit of the file at `path`, `content` is absent for new files.
+/// `related` lists definitions and usages from the code index, `memories`
+/// the conventions and preferences recalled for the workspace.
+pub async fn edit(
+    llm: Arc<dyn Llm>,
+    path: &str,
+    content: Option<&str>,
+    instructions: &str,
+    related: Option<&str>,
+    memories: Option<&str>,
+) -> Result<AgentResponse<EditOperation>> {
+    let rendered = prompt(path, content, instructions, related, memories)?;
+    let response = llm.generate(None, &rendered).await?;
+    Ok(AgentResponse {
+        response: EditOperation::parse(&response.generation)?,
+        metadata: response.metadata,
+    })
+}
+
+fn prompt(
+    path: &str,
+    content: Option<&str>,
+    instructions: &str,
+    related: Option<&str>,
+    memories: Option<&str>,
+) -> Result<String> {
+    let mut context = Context::new();
+    context.insert("path", path);
+    // An empty file exists all the same, it is written over rather than created
+    context.insert("exists", &content.is_some());
+    context.insert("content", &content);
+    context.insert("related", &related);
+    context.insert("memories", &memories);
+    context.insert("instructions", instructions);
+    Ok(Tera::one_off(PROMPT, &context, false)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prompt() {
+        let prompt = |content| prompt("src/lib.rs", content, "Add a test", None, None).unwrap();
+        assert!(prompt(Some("fn main() {}\n")).contains("<<<<<<< SEARCH"));
+        assert!(prompt(Some("")).contains("`src/lib.rs` is empty"));
+        assert!(prompt(None).contains("`src/lib.rs` does not exist yet"));
+    }
+}
@@ -0,0 +1,103 @@
+use crate::prelude::*;
+
+use std::sync::Arc;
+
+use indoc::indoc;
+use mirabel_core::models::memory::MemoryKind;
+use serde::Deserialize;
+use tera::Context;
+use tera::Tera;
+
+use crate::agent::AgentResponse;
+use crate::agent::unfence;
+use crate::driver::llm::Llm;
+
+const PROMPT: &str = indoc! {r#"
+    You are the Memory. You pick out what is worth remembering about a workspace from a message a user sent, so future sessions in the workspace know it without asking again.
+
+    Remember only lasting knowledge of these kinds:
+    - `fact`: how the project is, e.g. "The backend uses PostgreSQL 16".
+    - `preference`: what the users like, e.g. "Prefers small pull requests".
+    - `convention`: how things are done, e.g. "Test files live next to the code they test".
+
+    Leave out requests for the task at hand, questions, and anything that is already remembered.
+    Each memory is a single self-contained sentence. `confidence` is between 0 and 1, lower when the user only implies it.
+    `expiresInDays` is for knowledge that only holds for a while and can be left out.
+
+    Reply with a JSON array of the following shape and nothing else, an empty array when there is nothing to remember:
+    [
+        { "kind": "convention", "content": "...", "confidence": 0.9, "expiresInDays": 30 }
+    ]
+
+    {% if known %}
+    <already_remembered>
+    {% for memory in known %}
+    - {{ memory }}
+    {% endfor %}
+    </already_remembered>
+
+    {% endif %}
+    <message>
+    {{ message }}
+    </message>
+"#};
+
+/// A memory as the agent proposes it
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryDraft {
+    pub kind: MemoryKind,
+    pub content: String,
+    pub confidence: f32,
+    pub expires_in_days: Option<u32>,
+}
+
+/// Picks out what is worth remembering from `message`, `known` are memories
+/// of the workspace related to it
+pub async fn extract(
+    llm: Arc<dyn Llm>,
+    message: &str,
+    known: Vec<String>,
+) -> Result<AgentResponse<Vec<MemoryDraft>>> {
+    let mut context = Context::new();
+    context.insert("message", message);
+    context.insert("known", &known);
+    let rendered = Tera::one_off(PROMPT, &context, false)?;
+    let response = llm.generate(None, &rendered).await?;
+    Ok(AgentResponse {
+        response: parse_drafts(&response.generation)?,
+        metadata: response.metadata,
+    })
+}
+
+fn parse_drafts(generation: &str) -> Result<Vec<MemoryDraft>> {
+    let drafts: Vec<MemoryDraft> = serde_json::from_str(unfence(generation))?;
+    Ok(drafts
+        .into_iter()
+        .filter(|draft| !draft.content.trim().is_empty())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_drafts() {
+        let drafts = parse_drafts(indoc! {r#"
+            ```json
+            [
+                { "kind": "convention", "content": "Commit messages are in English", "confidence": 0.9 },
+                { "kind": "fact", "content": "The demo runs until Friday", "confidence": 0.6, "expiresInDays": 4 },
+                { "kind": "preference", "content": " ", "confidence": 1 }
+            ]
+            ```
+        "#})
+        .unwrap();
+        assert_eq!(drafts.len(), 2);
+        assert_eq!(drafts[0].kind, MemoryKind::Convention);
+        assert_eq!(drafts[1].expires_in_days, Some(4));
+        assert!(parse_drafts("[]").unwrap().is_empty());
+        assert!(parse_drafts("Nothing to remember.").is_err());
+    }
+}
@@ -1,6 +1,11 @@
 use crate::driver::llm::LlmResponseMetadata;
 
+pub mod editor;
+pub mod memory;
+pub mod planner;
+pub mod researcher;
 pub mod router;
+pub mod spec_creator;
 pub mod title_generation;
 
 pub struct AgentResponse<T> {
@@ -13,3 +18,17 @@ impl<T> AgentResponse<T> {
         AgentResponse { response, metadata }
     }
 }
+
+/// Models like to wrap their answer in a code block despite being told not
+/// to. The opening fence can name a language, the indentation of the answer
+/// is kept for code.
+pub(crate) fn unfence(generation: &str) -> &str {
+    let trimmed = generation.trim();
+    let Some(rest) = trimmed.strip_prefix("```") else {
+        return generation.trim_matches('\n');
+    };
+    rest.split_once('\n')
+        .and_then(|(_, body)| body.trim_end().strip_suffix("```"))
+        .map(|body| body.trim_matches('\n'))
+        .unwrap_or(trimmed)
+}
@@ -0,0 +1,217 @@
+use crate::prelude::*;
+
+use std::sync::Arc;
+
+use indoc::indoc;
+use mirabel_core::models::plan::Step;
+use mirabel_core::models::plan::StepAction;
+use mirabel_core::models::plan::Workflow;
+use serde::Deserialize;
+use tera::Context;
+use tera::Tera;
+
+use crate::agent::AgentResponse;
+use crate::agent::unfence;
+use crate::driver::llm::Llm;
+
+const PROMPT: &str = indoc! {r#"
+    You are the Planner. You turn an accepted spec into a hierarchical plan that an agent with a shell and an editor can execute.
+
+    A plan is a workflow. A workflow has a goal, optional sub-workflows and steps. Sub-workflows run before the steps of their parent.
+    A step is a single action of one of the following types:
+    - `shell`: run a `command` in the project directory.
+    - `edit`: change the file at `path` according to `instructions`.
+    - `verify`: run a `command` that checks the work, e.g. tests.
+    - `research`: look up a `query` on the web, e.g. the documentation of a library.
+    - `screenshot`: take a screenshot of the web page at `url`, e.g. to check how it looks.
+    - `documents`: look up a `query` in the documents the users attached to the session, e.g. a specification.
+    - `other`: anything else, described in the step.
+
+    Reply with a single JSON object of the following shape and nothing else:
+    {
+        "goal": "...",
+        "estimateMinutes": 10,
+        "workflows": [ ...workflows of the same shape... ],
+        "steps": [
+            { "description": "...", "action": { "type": "shell", "command": "..." }, "estimateMinutes": 1, "dependsOn": [0] }
+        ]
+    }
+
+    `dependsOn` lists the indices of earlier steps of the same workflow a step waits for and can be left out.
+    Estimates are optional.
+
+    {% if repo_map %}
+    The code the plan works on is outlined below, paths start at the project directory. Use them for `edit` steps.
+    <repo_map>
+    {{ repo_map }}
+    </repo_map>
+
+    {% endif %}
+    {% if memories %}
+    Take into account what is known about the workspace:
+    <workspace_memories>
+    {{ memories }}
+    </workspace_memories>
+
+    {% endif %}
+    {% if feedback %}
+    <feedback_on_previous_plan>
+    {{ feedback }}
+    </feedback_on_previous_plan>
+    {% endif %}
+
+    <spec>
+    {{ spec }}
+    </spec>
+"#};
+
+const DETOUR_PROMPT: &str = indoc! {r#"
+    You are the Planner. While executing a plan, the step below failed. Suggest a single step that clears the way, so the failed step succeeds when it is retried afterwards.
+
+    A step is a single action of one of the following types:
+    - `shell`: run a `command` in the project directory.
+    - `edit`: change the file at `path` according to `instructions`.
+    - `verify`: run a `command` that checks the work, e.g. tests.
+    - `research`: look up a `query` on the web, e.g. the documentation of a library.
+    - `screenshot`: take a screenshot of the web page at `url`, e.g. to check how it looks.
+    - `documents`: look up a `query` in the documents the users attached to the session, e.g. a specification.
+    - `other`: anything else, described in the step.
+
+    Reply with a single JSON object of the following shape and nothing else:
+    { "description": "...", "action": { "type": "shell", "command": "..." }, "estimateMinutes": 1 }
+
+    <failed_step>
+    {{ step }}
+    </failed_step>
+
+    <failure>
+    {{ failure }}
+    </failure>
+"#};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WorkflowDraft {
+    goal: String,
+    estimate_minutes: Option<u32>,
+    #[serde(default)]
+    workflows: Vec<WorkflowDraft>,
+    #[serde(default)]
+    steps: Vec<StepDraft>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StepDraft {
+    description: String,
+    action: StepAction,
+    estimate_minutes: Option<u32>,
+    #[serde(default)]
+    depends_on: Vec<usize>,
+}
+
+impl From<WorkflowDraft> for Workflow {
+    fn from(draft: WorkflowDraft) -> Self {
+        let mut workflow = Workflow::new(draft.goal);
+        workflow.estimate_secs = draft.estimate_minutes.map(|minutes| minutes * 60);
+        workflow.workflows = draft.workflows.into_iter().map(Workflow::from).collect();
+        for (index, draft) in draft.steps.into_iter().enumerate() {
+            let mut step = Step::new(draft.description, draft.action);
+            step.estimate_secs = draft.estimate_minutes.map(|minutes| minutes * 60);
+            // Only earlier steps can be waited for, anything else would never run
+            step.depends_on = draft
+                .depends_on
+                .into_iter()
+                .filter(|dependency| *dependency < index)
+                .map(|dependency| workflow.steps[dependency].id.clone())
+                .collect();
+            workflow.steps.push(step);
+        }
+        workflow
+    }
+}
+
+/// Drafts the root workflow of a plan for `spec`, `feedback` is why the
+/// previous plan was rejected. `repo_map` outlines the code to work on,
+/// `memories` is what the workspace remembers about the spec.
+pub async fn plan(
+    llm: Arc<dyn Llm>,
+    spec: String,
+    feedback: Option<String>,
+    repo_map: Option<String>,
+    memories: Option<String>,
+) -> Result<AgentResponse<Workflow>> {
+    let mut context = Context::new();
+    context.insert("spec", &spec);
+    context.insert("feedback", &feedback);
+    context.insert("repo_map", &repo_map);
+    context.insert("memories", &memories);
+    let rendered = Tera::one_off(PROMPT, &context, false)?;
+    let response = llm.generate(None, &rendered).await?;
+    Ok(AgentResponse {
+        response: parse_plan(&response.generation)?,
+        metadata: response.metadata,
+    })
+}
+
+/// Drafts a step to insert before `step`, which failed with `failure`
+pub async fn detour(llm: Arc<dyn Llm>, step: &Step, failure: &str) -> Result<AgentResponse<Step>> {
+    let mut context = Context::new();
+    context.insert("step", &serde_json::to_string(step)?);
+    context.insert("failure", failure);
+    let rendered = Tera::one_off(DETOUR_PROMPT, &context, false)?;
+    let response = llm.generate(None, &rendered).await?;
+    let draft: StepDraft = serde_json::from_str(unfence(&response.generation))?;
+    let mut detour = Step::new(draft.description, draft.action);
+    detour.estimate_secs = draft.estimate_minutes.map(|minutes| minutes * 60);
+    Ok(AgentResponse {
+        response: detour,
+        metadata: response.metadata,
+    })
+}
+
+fn parse_plan(generation: &str) -> Result<Workflow> {
+    let draft: WorkflowDraft = serde_json::from_str(unfence(generation))?;
+    Ok(draft.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_plan() {
+        let root = parse_plan(indoc! {r#"
+            ```json
+            {
+                "goal": "Add a history page",
+                "workflows": [
+                    {
+                        "goal": "Backend",
+                        "estimateMinutes": 5,
+                        "steps": [
+                            { "description": "Add route", "action": { "type": "edit", "path": "src/main.rs", "instructions": "Add /history" } }
+                        ]
+                    }
+                ],
+                "steps": [
+                    { "description": "Build", "action": { "type": "shell", "command": "cargo build" }, "estimateMinutes": 2 },
+                    { "description": "Test", "action": { "type": "verify", "command": "cargo test" }, "dependsOn": [0, 3] }
+                ]
+            }
+            ```
+        "#})
+        .unwrap();
+
+        assert_eq!(root.goal, "Add a history page");
+        assert_eq!(root.workflows[0].estimate_secs, Some(300));
+        assert_eq!(root.estimate(), Some(420));
+        assert_eq!(root.steps[1].depends_on, vec![root.steps[0].id.clone()]);
+        assert_eq!(root.steps_in_order().len(), 3);
+    }
+
+    #[test]
+    fn test_parse_plan_invalid() {
+        assert!(parse_plan("I cannot plan this.").is_err());
+    }
+}
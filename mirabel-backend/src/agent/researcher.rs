@@ -0,0 +1,95 @@
+use crate::prelude::*;
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use indoc::indoc;
+use serde::Serialize;
+use tera::Context;
+use tera::Tera;
+
+use crate::agent::AgentResponse;
+use crate::driver::llm::Llm;
+
+const PROMPT: &str = indoc! {r#"
+    You are the Researcher. You answer a question using only the numbered web sources below.
+
+    Cite the sources a statement is based on right after it with their numbers in square brackets, e.g. [1] or [1, 3].
+    Do not use knowledge that is not in the sources. When the sources do not answer the question, say so.
+    Reply in markdown with the answer and nothing else.
+
+    {% for source in sources %}
+    <source index="{{ source.index }}" title="{{ source.title }}" url="{{ source.url }}">
+    {% for passage in source.passages %}
+    {{ passage }}
+
+    {% endfor %}
+    </source>
+
+    {% endfor %}
+    <question>
+    {{ query }}
+    </question>
+"#};
+
+/// The passages of a source the answer may use
+#[derive(Debug, Clone, Serialize)]
+pub struct Excerpt {
+    /// The number the source is cited with
+    pub index: u32,
+    pub title: String,
+    pub url: String,
+    pub passages: Vec<String>,
+}
+
+/// Answers `query` from the excerpts, citing them by their index
+pub async fn answer(
+    llm: Arc<dyn Llm>,
+    query: &str,
+    excerpts: &[Excerpt],
+) -> Result<AgentResponse<String>> {
+    let mut context = Context::new();
+    context.insert("query", query);
+    context.insert("sources", excerpts);
+    let rendered = Tera::one_off(PROMPT, &context, false)?;
+    let response = llm.generate(None, &rendered).await?;
+    Ok(AgentResponse {
+        response: response.generation.trim().to_string(),
+        metadata: response.metadata,
+    })
+}
+
+/// The source numbers an answer cites, e.g. `[1]` or `[2, 3]`
+pub fn cited(answer: &str) -> BTreeSet<u32> {
+    let mut cited = BTreeSet::new();
+    let mut rest = answer;
+    while let Some(start) = rest.find('[') {
+        rest = &rest[start + 1..];
+        let Some(stop) = rest.find(']') else {
+            break;
+        };
+        let numbers: Option<Vec<u32>> = rest[..stop]
+            .split(',')
+            .map(|number| number.trim().parse().ok())
+            .collect();
+        if let Some(numbers) = numbers {
+            // Sources are numbered from 1, `[0]` is code
+            cited.extend(numbers.into_iter().filter(|number| *number > 0));
+        }
+        rest = &rest[stop..];
+    }
+    cited
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cited() {
+        let answer = "Tokio schedules tasks [1]. It uses work stealing [2, 3].\n\n\
+            See [the docs](https://tokio.rs) and `a[0]`, [x] or [1,2";
+        assert_eq!(cited(answer), BTreeSet::from([1, 2, 3]));
+        assert!(cited("No sources answer this.").is_empty());
+    }
+}
@@ -0,0 +1,98 @@
+use crate::prelude::*;
+
+use std::sync::Arc;
+
+use indoc::indoc;
+use tera::Context;
+use tera::Tera;
+
+use crate::agent::AgentResponse;
+use crate::agent::unfence;
+use crate::driver::llm::Llm;
+
+const QUESTION_PREFIX: &str = "QUESTION:";
+
+const PROMPT: &str = indoc! {"
+    You are the Spec Creator. Together with the user you write a spec, a structured markdown document describing the requirements of their task.
+
+    Based on the conversation and the current spec, do exactly one of the following:
+    - If something essential for the spec is unclear, ask the user a single question. Reply with `QUESTION:` followed by the question.
+    - Otherwise reply with the complete updated spec in markdown.
+
+    The spec should:
+    - Start with a level 1 heading naming the task.
+    - List the requirements the user stated, grouped under level 2 headings.
+    - Keep everything from the current spec that the user did not ask to change.
+
+    The spec should not:
+    - include requirements the user did not state or agree to.
+    - include implementation details unless the user asked for them.
+    - be wrapped in a code block or surrounded by any other text.
+
+    <current_spec>
+    {% if spec %}
+    {{ spec }}
+    {% else %}
+    There is no spec yet.
+    {% endif %}
+    </current_spec>
+
+    <messages>
+    {% for message in messages %}
+        {{ message.0 }}: {{ message.1 }}
+    {% endfor %}
+    </messages>
+"};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpecStep {
+    /// Something has to be clarified before the spec can be (re)drafted
+    Question(String),
+    /// The complete new version of the spec
+    Draft(String),
+}
+
+pub async fn spec_step(
+    llm: Arc<dyn Llm>,
+    spec: Option<String>,
+    messages: Vec<(String, String)>,
+) -> Result<AgentResponse<SpecStep>> {
+    let mut context = Context::new();
+    context.insert("spec", &spec);
+    context.insert("messages", &messages);
+    let rendered = Tera::one_off(PROMPT, &context, false)?;
+    let response = llm.generate(None, &rendered).await?;
+    Ok(AgentResponse {
+        response: parse_step(&response.generation),
+        metadata: response.metadata,
+    })
+}
+
+fn parse_step(generation: &str) -> SpecStep {
+    let generation = generation.trim();
+    if let Some(question) = generation.strip_prefix(QUESTION_PREFIX) {
+        return SpecStep::Question(question.trim().to_string());
+    }
+    SpecStep::Draft(unfence(generation).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_step() {
+        assert_eq!(
+            parse_step("QUESTION: Which users should see the page?\n"),
+            SpecStep::Question("Which users should see the page?".into())
+        );
+        assert_eq!(
+            parse_step("```markdown\n# History Page\n\n## Scope\n```"),
+            SpecStep::Draft("# History Page\n\n## Scope".into())
+        );
+        assert_eq!(
+            parse_step("# History Page"),
+            SpecStep::Draft("# History Page".into())
+        );
+    }
+}
@@ -1,60 +1,460 @@
 use std::env;
+use std::fs;
+use std::ops::Deref;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::time::Duration;
 
-use deadpool::unmanaged::Object;
-use deadpool::unmanaged::Pool;
+use deadpool::Runtime;
+use deadpool::managed::Manager;
+use deadpool::managed::Metrics;
+use deadpool::managed::Object;
+use deadpool::managed::Pool;
+use deadpool::managed::PoolError;
+use deadpool::managed::RecycleError;
+use deadpool::managed::RecycleResult;
+use deadpool::managed::TimeoutType;
+use deadpool::managed::Timeouts;
 use fantoccini::Client;
 use fantoccini::ClientBuilder;
+use fantoccini::wd::Capabilities;
+use futures::future::select_ok;
 use log::debug;
-use log::error;
+use log::info;
+use log::warn;
+use serde_json::json;
+use tokio::runtime::Handle;
+use tokio::time;
 
+use crate::driver::policy::USER_AGENT_ENV;
 use crate::prelude::*;
+use crate::utils::env::parse_env;
 
+/// Every variable starting with it holds a WebDriver host, as `url` or
+/// `url concurrency`
+const HOST_ENV_PREFIX: &str = "WEBDRIVER_HOST";
+const HOSTS_FILE_ENV: &str = "BROWSER_HOSTS_FILE";
+const CONCURRENCY_ENV: &str = "BROWSER_CONCURRENCY";
+const ACQUIRE_TIMEOUT_SECS_ENV: &str = "BROWSER_ACQUIRE_TIMEOUT_SECS";
+const DISCOVERY_SECS_ENV: &str = "BROWSER_DISCOVERY_SECS";
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long a browser may take to show it is still alive
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+const BLANK_PAGE: &str = "about:blank";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrowserConfig {
+    /// Sessions opened on a host that does not say otherwise
+    pub concurrency: usize,
+    /// How long to wait for a browser while the others are busy
+    pub acquire_timeout: Duration,
+    /// How often the hosts are looked up again
+    pub discovery_interval: Duration,
+    /// Lists more hosts, one `url` or `url concurrency` per line
+    pub hosts_file: Option<PathBuf>,
+    /// Replaces the user agent of the browsers, which otherwise keep their
+    /// own
+    pub user_agent: Option<String>,
+}
+
+impl Default for BrowserConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: 1,
+            acquire_timeout: Duration::from_secs(60),
+            discovery_interval: Duration::from_secs(30),
+            hosts_file: None,
+            user_agent: None,
+        }
+    }
+}
+
+impl BrowserConfig {
+    pub fn from_env() -> Result<Self> {
+        let default = Self::default();
+        Ok(Self {
+            concurrency: parse_env(CONCURRENCY_ENV)?.unwrap_or(default.concurrency),
+            acquire_timeout: parse_env(ACQUIRE_TIMEOUT_SECS_ENV)?
+                .map(Duration::from_secs)
+                .unwrap_or(default.acquire_timeout),
+            discovery_interval: parse_env(DISCOVERY_SECS_ENV)?
+                .map(Duration::from_secs)
+                .unwrap_or(default.discovery_interval),
+            hosts_file: env::var(HOSTS_FILE_ENV).ok().map(PathBuf::from),
+            user_agent: env::var(USER_AGENT_ENV).ok(),
+        })
+    }
+}
+
+/// Opens sessions on a WebDriver host, and checks they are still alive
+/// before they are handed out again
+pub struct WebDriver {
+    url: String,
+    user_agent: Option<String>,
+}
+
+impl Manager for WebDriver {
+    type Type = Client;
+    type Error = Error;
+
+    async fn create(&self) -> Result<Client> {
+        let mut builder = ClientBuilder::native();
+        if let Some(user_agent) = &self.user_agent {
+            builder.capabilities(user_agent_capabilities(user_agent));
+        }
+        let client = builder.connect(&self.url).await?;
+        debug!("Opened a browser session on {}", self.url);
+        Ok(client)
+    }
+
+    async fn recycle(&self, client: &mut Client, _: &Metrics) -> RecycleResult<Error> {
+        client.current_url().await.map_err(|err| {
+            warn!("A browser session on {} is gone: {err}", self.url);
+            RecycleError::Backend(err.into())
+        })?;
+        Ok(())
+    }
+}
+
+struct Host {
+    url: String,
+    pool: Pool<WebDriver>,
+}
+
+impl Host {
+    fn new(url: String, concurrency: usize, user_agent: Option<String>) -> Result<Self> {
+        let driver = WebDriver {
+            url: url.clone(),
+            user_agent,
+        };
+        let pool = Pool::builder(driver)
+            .max_size(concurrency)
+            .runtime(Runtime::Tokio1)
+            .create_timeout(Some(CONNECT_TIMEOUT))
+            .recycle_timeout(Some(CHECK_TIMEOUT))
+            .build()
+            .map_err(|err| Error::Deadpool(err.to_string()))?;
+        Ok(Self { url, pool })
+    }
+}
+
+/// The WebDriver hosts and their sessions. Sessions are opened when they are
+/// needed, checked before they are handed out and opened again when they
+/// died. Hosts are looked up again while running, so browsers started later
+/// are used too.
 pub struct Browsers {
-    size: usize,
-    pool: Pool<Client>,
+    config: BrowserConfig,
+    hosts: Mutex<Vec<Host>>,
 }
 
 impl Browsers {
-    pub async fn new() -> Result<Self> {
-        let mut clients = Vec::new();
-        for var in env::vars().filter(|(k, _)| k.starts_with("WEBDRIVER_HOST")) {
-            let res = ClientBuilder::native()
-                .connect(&var.1)
-                .await
-                .map_err(Box::new);
-            match res {
-                Ok(client) => {
-                    debug!("Connected to browser found in ({}:{})", var.0, var.1);
-                    clients.push(client)
+    pub fn new(config: BrowserConfig) -> Result<Self> {
+        let browsers = Self {
+            config,
+            hosts: Mutex::new(Vec::new()),
+        };
+        browsers.discover()?;
+        Ok(browsers)
+    }
+
+    pub fn from_env() -> Result<Self> {
+        Self::new(BrowserConfig::from_env()?)
+    }
+
+    /// Waits for a free browser as long as configured
+    pub async fn acquire(&self) -> Result<Browser> {
+        self.acquire_timeout(self.config.acquire_timeout).await
+    }
+
+    /// Takes a browser from the least busy host. When every host is busy the
+    /// first browser given back is taken, fails when none is within `timeout`.
+    pub async fn acquire_timeout(&self, timeout: Duration) -> Result<Browser> {
+        let mut pools = self.pools();
+        if pools.is_empty() {
+            return Err(Error::NoAvailableBrowser);
+        }
+        pools.sort_by_key(|pool| {
+            let status = pool.status();
+            (status.size - status.available) * 100 / status.max_size.max(1)
+        });
+        let client = time::timeout(timeout, async {
+            for pool in &pools {
+                let right_away = Timeouts {
+                    wait: Some(Duration::ZERO),
+                    ..pool.timeouts()
+                };
+                match pool.timeout_get(&right_away).await {
+                    Ok(client) => return Ok(client),
+                    Err(PoolError::Timeout(TimeoutType::Wait)) => {}
+                    Err(err) => warn!("Could not open a browser session: {err}"),
+                }
+            }
+            let waits = pools.iter().map(|pool| Box::pin(pool.get()));
+            Ok::<_, Error>(select_ok(waits).await?.0)
+        })
+        .await
+        .map_err(|_| Error::NoAvailableBrowser)??;
+        Ok(Browser {
+            client: Some(client),
+        })
+    }
+
+    /// Looks up the hosts again. New hosts are added, hosts no longer listed
+    /// are dropped once their browsers are given back.
+    pub fn discover(&self) -> Result<()> {
+        let found = self.find_hosts()?;
+        let mut hosts = self.lock_hosts()?;
+        hosts.retain(|host| {
+            let listed = found.iter().any(|(url, _)| *url == host.url);
+            if !listed {
+                info!("Browser host {} is no longer listed", host.url);
+                host.pool.close();
+            }
+            listed
+        });
+        for (url, concurrency) in found {
+            match hosts.iter().find(|host| host.url == url) {
+                Some(host) if host.pool.status().max_size != concurrency => {
+                    info!("Browser host {url} now takes {concurrency} sessions");
+                    host.pool.resize(concurrency);
                 }
-                Err(e) => {
-                    error!(
-                        "Failed to connect to browser found in ({}:{}): {:?}",
-                        var.0, var.1, e
-                    );
+                Some(_) => {}
+                None => {
+                    info!("Found browser host {url} for {concurrency} sessions");
+                    hosts.push(Host::new(url, concurrency, self.config.user_agent.clone())?);
                 }
             }
         }
-        let size = clients.len();
-        let pool = Pool::new(size);
-        for client in clients {
-            pool.add(client).await.map_err(|(_, e)| e)?;
-        }
-        Ok(Self { size, pool })
+        Ok(())
     }
 
-    pub async fn acquire(&self) -> Result<Object<Client>> {
-        Ok(self.pool.get().await?)
+    /// Looks up the hosts again every discovery interval
+    pub async fn run_discovery(&self) {
+        let mut interval = time::interval(self.config.discovery_interval);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            if let Err(err) = self.discover() {
+                warn!("Could not look up the browser hosts: {err}");
+            }
+        }
     }
 
-    pub async fn close(self) -> Result<()> {
-        debug!("Closing {} browsers", self.size);
-        for i in 0..self.size {
-            let client = self.pool.remove().await?;
-            client.close().await?;
-            debug!("Closed browser {}/{}", i + 1, self.size);
+    /// Closes the idle sessions and every session given back from now on
+    pub async fn close(&self) -> Result<()> {
+        let hosts: Vec<Host> = self.lock_hosts()?.drain(..).collect();
+        for host in hosts {
+            let idle = host.pool.retain(|_, _| false).removed;
+            host.pool.close();
+            debug!("Closing {} browsers on {}", idle.len(), host.url);
+            for client in idle {
+                if let Err(err) = client.close().await {
+                    warn!("Could not close a browser on {}: {err}", host.url);
+                }
+            }
         }
-        debug!("Closed {} browsers", self.size);
         Ok(())
     }
+
+    /// Hosts from the environment and the hosts file with their concurrency,
+    /// each once
+    fn find_hosts(&self) -> Result<Vec<(String, usize)>> {
+        let mut lines: Vec<String> = env::vars()
+            .filter(|(name, _)| name.starts_with(HOST_ENV_PREFIX))
+            .map(|(_, value)| value)
+            .collect();
+        if let Some(path) = &self.config.hosts_file {
+            match fs::read_to_string(path) {
+                Ok(content) => lines.extend(content.lines().map(str::to_string)),
+                Err(err) => warn!("Could not read browser hosts from {path:?}: {err}"),
+            }
+        }
+        let mut hosts: Vec<(String, usize)> = Vec::new();
+        for line in lines {
+            let Some((url, concurrency)) = parse_host(&line)? else {
+                continue;
+            };
+            if hosts.iter().all(|(known, _)| *known != url) {
+                hosts.push((url, concurrency.unwrap_or(self.config.concurrency)));
+            }
+        }
+        Ok(hosts)
+    }
+
+    fn pools(&self) -> Vec<Pool<WebDriver>> {
+        self.lock_hosts()
+            .map(|hosts| hosts.iter().map(|host| host.pool.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    fn lock_hosts(&self) -> Result<MutexGuard<'_, Vec<Host>>> {
+        self.hosts
+            .lock()
+            .map_err(|err| Error::PoisonedLock(err.to_string()))
+    }
+}
+
+/// A browser taken from the pool. It is given back when dropped, after its
+/// cookies, storage and extra windows are cleared, so the next scrape starts
+/// from a blank page. Browsers that cannot be reset are closed instead.
+pub struct Browser {
+    client: Option<Object<WebDriver>>,
+}
+
+impl Deref for Browser {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        self.client
+            .as_ref()
+            .expect("The browser is only taken when dropped")
+    }
+}
+
+impl Drop for Browser {
+    fn drop(&mut self) {
+        let Some(client) = self.client.take() else {
+            return;
+        };
+        // Without a runtime it cannot be reset, so it is not given back
+        let Ok(runtime) = Handle::try_current() else {
+            drop(Object::take(client));
+            return;
+        };
+        runtime.spawn(async move {
+            if let Err(err) = reset(&client).await {
+                warn!("Closing a browser that could not be reset: {err}");
+                let _ = Object::take(client).close().await;
+            }
+        });
+    }
+}
+
+async fn reset(client: &Client) -> Result<()> {
+    client.delete_all_cookies().await?;
+    // Pages may deny access to their storage, it is cleared where allowed
+    let _ = client
+        .execute(
+            "try { localStorage.clear(); sessionStorage.clear(); } catch (e) {}",
+            Vec::new(),
+        )
+        .await;
+    let windows = client.windows().await?;
+    if let Some((first, others)) = windows.split_first() {
+        for window in others {
+            client.switch_to_window(window.clone()).await?;
+            client.close_window().await?;
+        }
+        client.switch_to_window(first.clone()).await?;
+    }
+    client.goto(BLANK_PAGE).await?;
+    Ok(())
+}
+
+/// Reads `url` or `url concurrency`, blank lines and `#` comments are skipped
+fn parse_host(line: &str) -> Result<Option<(String, Option<usize>)>> {
+    let line = line.split('#').next().unwrap_or_default();
+    let mut parts = line.split_whitespace();
+    let Some(url) = parts.next() else {
+        return Ok(None);
+    };
+    let concurrency = parts.next().map(str::parse).transpose()?;
+    if parts.next().is_some() || concurrency == Some(0) {
+        return Err(Error::Generic(format!(
+            "Browser hosts are given as 'url' or 'url concurrency', not '{}'",
+            line.trim()
+        )));
+    }
+    Ok(Some((url.to_string(), concurrency)))
+}
+
+/// Sets the user agent of Chrome and Firefox, each ignores the options of
+/// the other
+fn user_agent_capabilities(user_agent: &str) -> Capabilities {
+    let mut capabilities = Capabilities::new();
+    capabilities.insert(
+        "goog:chromeOptions".into(),
+        json!({ "args": [format!("--user-agent={user_agent}")] }),
+    );
+    capabilities.insert(
+        "moz:firefoxOptions".into(),
+        json!({ "prefs": { "general.useragent.override": user_agent } }),
+    );
+    capabilities
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_host() {
+        let host = |line| parse_host(line).unwrap();
+        assert_eq!(
+            host("http://localhost:4444"),
+            Some(("http://localhost:4444".to_string(), None))
+        );
+        assert_eq!(
+            host("  http://chrome:4444   3 # the big one"),
+            Some(("http://chrome:4444".to_string(), Some(3)))
+        );
+        assert_eq!(host(""), None);
+        assert_eq!(host("# http://localhost:4444"), None);
+        assert!(parse_host("http://localhost:4444 many").is_err());
+        assert!(parse_host("http://localhost:4444 0").is_err());
+        assert!(parse_host("http://localhost:4444 2 3").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_discover() {
+        let path = env::temp_dir().join("mirabel-browser-hosts");
+        fs::write(&path, "http://first:4444 2\nhttp://second:4444\n").unwrap();
+        let browsers = Browsers::new(BrowserConfig {
+            concurrency: 4,
+            hosts_file: Some(path.clone()),
+            ..BrowserConfig::default()
+        })
+        .unwrap();
+        let sizes = |browsers: &Browsers| -> Vec<(String, usize)> {
+            browsers
+                .lock_hosts()
+                .unwrap()
+                .iter()
+                .filter(|host| !host.url.contains("localhost"))
+                .map(|host| (host.url.clone(), host.pool.status().max_size))
+                .collect()
+        };
+        assert_eq!(
+            sizes(&browsers),
+            vec![
+                ("http://first:4444".to_string(), 2),
+                ("http://second:4444".to_string(), 4)
+            ]
+        );
+
+        // Hosts started later are found, stopped ones are dropped
+        fs::write(&path, "http://second:4444 1\nhttp://third:4444\n").unwrap();
+        browsers.discover().unwrap();
+        assert_eq!(
+            sizes(&browsers),
+            vec![
+                ("http://second:4444".to_string(), 1),
+                ("http://third:4444".to_string(), 4)
+            ]
+        );
+        fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_acquire_without_hosts() {
+        let browsers = Browsers {
+            config: BrowserConfig::default(),
+            hosts: Mutex::new(Vec::new()),
+        };
+        assert!(matches!(
+            browsers.acquire_timeout(Duration::from_secs(1)).await,
+            Err(Error::NoAvailableBrowser)
+        ));
+    }
 }
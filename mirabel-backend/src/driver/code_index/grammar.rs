@@ -0,0 +1,104 @@
+use std::sync::LazyLock;
+
+use mirabel_core::models::code_index::CodeLanguage;
+use tree_sitter::Language;
+use tree_sitter::Query;
+
+use crate::prelude::*;
+
+/// Definitions and references the tag queries of the grammar crates miss
+const RUST_TAGS: &str = r#"
+(const_item
+    name: (identifier) @name) @definition.constant
+
+(static_item
+    name: (identifier) @name) @definition.constant
+
+(call_expression
+    function: (scoped_identifier
+        name: (identifier) @name)) @reference.call
+
+(type_identifier) @name @reference.type
+"#;
+
+const TYPESCRIPT_TAGS: &str = r#"
+(type_alias_declaration
+  name: (type_identifier) @name) @definition.type
+
+(enum_declaration
+  name: (identifier) @name) @definition.class
+"#;
+
+/// A tree-sitter grammar with the query that finds the tags of its files.
+/// TypeScript has two grammars, one of them for JSX.
+pub(super) struct Grammar {
+    pub language: Language,
+    pub query: Query,
+}
+
+static RUST: LazyLock<Result<Grammar>> = LazyLock::new(|| {
+    Grammar::new(
+        tree_sitter_rust::LANGUAGE.into(),
+        &[tree_sitter_rust::TAGS_QUERY, RUST_TAGS],
+    )
+});
+static PYTHON: LazyLock<Result<Grammar>> = LazyLock::new(|| {
+    Grammar::new(
+        tree_sitter_python::LANGUAGE.into(),
+        &[tree_sitter_python::TAGS_QUERY],
+    )
+});
+static JAVASCRIPT: LazyLock<Result<Grammar>> = LazyLock::new(|| {
+    Grammar::new(
+        tree_sitter_javascript::LANGUAGE.into(),
+        &[tree_sitter_javascript::TAGS_QUERY],
+    )
+});
+static TYPESCRIPT: LazyLock<Result<Grammar>> = LazyLock::new(|| {
+    Grammar::new(
+        tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+        &TYPESCRIPT_QUERIES,
+    )
+});
+static TSX: LazyLock<Result<Grammar>> = LazyLock::new(|| {
+    Grammar::new(
+        tree_sitter_typescript::LANGUAGE_TSX.into(),
+        &TYPESCRIPT_QUERIES,
+    )
+});
+static GO: LazyLock<Result<Grammar>> = LazyLock::new(|| {
+    Grammar::new(
+        tree_sitter_go::LANGUAGE.into(),
+        &[tree_sitter_go::TAGS_QUERY],
+    )
+});
+
+// TypeScript is JavaScript with types, its own tag query only covers the types
+const TYPESCRIPT_QUERIES: [&str; 3] = [
+    tree_sitter_javascript::TAGS_QUERY,
+    tree_sitter_typescript::TAGS_QUERY,
+    TYPESCRIPT_TAGS,
+];
+
+impl Grammar {
+    fn new(language: Language, queries: &[&str]) -> Result<Self> {
+        let query = Query::new(&language, &queries.join("\n"))
+            .map_err(|err| Error::TreeSitter(err.to_string()))?;
+        Ok(Self { language, query })
+    }
+
+    /// The grammar for the file at `path`, compiled on first use
+    pub fn for_file(path: &str, language: CodeLanguage) -> Result<&'static Grammar> {
+        let grammar = match language {
+            CodeLanguage::Rust => &RUST,
+            CodeLanguage::Python => &PYTHON,
+            CodeLanguage::JavaScript => &JAVASCRIPT,
+            CodeLanguage::TypeScript if path.ends_with(".tsx") => &TSX,
+            CodeLanguage::TypeScript => &TYPESCRIPT,
+            CodeLanguage::Go => &GO,
+        };
+        grammar
+            .as_ref()
+            .map_err(|err| Error::TreeSitter(err.to_string()))
+    }
+}
@@ -0,0 +1,389 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::Path;
+
+use ignore::WalkBuilder;
+use log::warn;
+use mirabel_core::models::code_index::CodeLanguage;
+use mirabel_core::models::code_index::CodeSymbol;
+use mirabel_core::models::code_index::SymbolKind;
+use sha2::Digest;
+use sha2::Sha256;
+use streaming_iterator::StreamingIterator;
+use tree_sitter::Parser;
+use tree_sitter::QueryCursor;
+
+use crate::prelude::*;
+
+mod grammar;
+
+use grammar::Grammar;
+
+/// Larger files are mostly generated or data, neither helps understanding
+const MAX_FILE_SIZE: u64 = 512 * 1024;
+const MAX_SIGNATURE_LENGTH: usize = 160;
+
+/// A source file read from the disk, with what is needed to tell whether it
+/// changed since it was indexed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceFile {
+    /// Relative to the root it was read from, separated by `/`
+    pub path: String,
+    pub language: CodeLanguage,
+    pub content: String,
+    pub hash: String,
+}
+
+/// A definition or reference found in a source file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedSymbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub definition: bool,
+    pub line: i32,
+    pub column: i32,
+    pub end_line: i32,
+    pub signature: Option<String>,
+}
+
+impl SourceFile {
+    /// Reads the file at `path` below `root`. `None` when it does not exist,
+    /// is too large, or is not in a language that can be indexed.
+    pub fn read(root: &Path, path: &str) -> Result<Option<Self>> {
+        let Some(language) = CodeLanguage::from_path(path) else {
+            return Ok(None);
+        };
+        let file = root.join(path);
+        match std::fs::metadata(&file) {
+            Ok(metadata) if metadata.is_file() && metadata.len() <= MAX_FILE_SIZE => {}
+            Ok(_) => return Ok(None),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        }
+        // Binary files with a source extension are not worth an error
+        let Ok(content) = String::from_utf8(std::fs::read(&file)?) else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            path: path.to_string(),
+            language,
+            hash: hash(&content),
+            content,
+        }))
+    }
+
+    /// The definitions and references in the file, in the order they appear
+    pub fn parse(&self) -> Result<Vec<ParsedSymbol>> {
+        let grammar = Grammar::for_file(&self.path, self.language)?;
+        let mut parser = Parser::new();
+        parser
+            .set_language(&grammar.language)
+            .map_err(|err| Error::TreeSitter(err.to_string()))?;
+        let Some(tree) = parser.parse(&self.content, None) else {
+            return Ok(Vec::new());
+        };
+
+        let source = self.content.as_bytes();
+        let names = grammar.query.capture_names();
+        let mut symbols: Vec<ParsedSymbol> = Vec::new();
+        let mut seen: HashMap<(bool, usize, usize), usize> = HashMap::new();
+        let mut cursor = QueryCursor::new();
+        let mut matches = cursor.matches(&grammar.query, tree.root_node(), source);
+        while let Some(found) = matches.next() {
+            let mut name = None;
+            let mut tag = None;
+            for capture in found.captures {
+                let capture_name = names[capture.index as usize];
+                if capture_name == "name" {
+                    name = Some(capture.node);
+                } else if let Some((role, kind)) = capture_name.split_once('.') {
+                    tag = SymbolKind::from_tag(kind)
+                        .filter(|_| matches!(role, "definition" | "reference"))
+                        .map(|kind| (role == "definition", kind, capture.node));
+                }
+            }
+            let (Some(name), Some((definition, kind, node))) = (name, tag) else {
+                continue;
+            };
+            let Ok(text) = name.utf8_text(source) else {
+                continue;
+            };
+            let start = name.start_position();
+            let symbol = ParsedSymbol {
+                name: text.to_string(),
+                kind,
+                definition,
+                line: start.row as i32 + 1,
+                column: start.column as i32 + 1,
+                end_line: node.end_position().row as i32 + 1,
+                signature: definition
+                    .then(|| node.utf8_text(source).ok().map(signature))
+                    .flatten(),
+            };
+            // Several patterns can match the same name, e.g. a method is a
+            // function as well, the more specific kind wins
+            match seen.get(&(definition, start.row, start.column)) {
+                Some(&index) => {
+                    if symbols[index].kind == SymbolKind::Function {
+                        symbols[index].kind = kind;
+                    }
+                }
+                None => {
+                    seen.insert((definition, start.row, start.column), symbols.len());
+                    symbols.push(symbol);
+                }
+            }
+        }
+
+        // The name of a definition is no reference to itself
+        let definitions: HashSet<(i32, i32)> = symbols
+            .iter()
+            .filter(|symbol| symbol.definition)
+            .map(|symbol| (symbol.line, symbol.column))
+            .collect();
+        symbols.retain(|symbol| {
+            symbol.definition || !definitions.contains(&(symbol.line, symbol.column))
+        });
+        symbols.sort_by_key(|symbol| (symbol.line, symbol.column, !symbol.definition));
+        Ok(symbols)
+    }
+}
+
+/// The files below `root` that can be indexed, relative to it. Hidden and
+/// ignored files are left out, like git would.
+pub fn walk(root: &Path) -> Vec<String> {
+    let mut paths = Vec::new();
+    for entry in WalkBuilder::new(root).require_git(false).build() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                warn!("Skipping a file while walking {}: {err}", root.display());
+                continue;
+            }
+        };
+        if !entry.file_type().is_some_and(|kind| kind.is_file()) {
+            continue;
+        }
+        let Ok(relative) = entry.path().strip_prefix(root) else {
+            continue;
+        };
+        let path = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        if CodeLanguage::from_path(&path).is_some() {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    paths
+}
+
+pub fn hash(content: &str) -> String {
+    Sha256::digest(content.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// An outline of the definitions of a codebase. Files whose definitions are
+/// referenced the most come first, files are left out once the map would be
+/// longer than `budget` characters.
+pub fn repo_map(
+    files: &[(String, Vec<CodeSymbol>)],
+    references: &HashMap<String, u32>,
+    budget: usize,
+) -> String {
+    let mut ranked: Vec<(u32, &String, &Vec<CodeSymbol>)> = files
+        .iter()
+        .filter(|(_, definitions)| !definitions.is_empty())
+        .map(|(path, definitions)| {
+            let score = definitions
+                .iter()
+                .map(|definition| references.get(&definition.name).copied().unwrap_or(0))
+                .sum();
+            (score, path, definitions)
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+
+    let mut map = String::new();
+    for (index, (_, path, definitions)) in ranked.iter().enumerate() {
+        let mut block = format!("{path}:\n");
+        for definition in definitions.iter() {
+            let line = definition.signature.as_deref().unwrap_or(&definition.name);
+            block.push_str(&format!("  {}: {line}\n", definition.line));
+        }
+        if map.len() + block.len() > budget {
+            map.push_str(&format!("… and {} more files\n", ranked.len() - index));
+            break;
+        }
+        map.push_str(&block);
+    }
+    map
+}
+
+/// The first line of a definition, which usually says what it is
+fn signature(text: &str) -> String {
+    let line = text.lines().next().unwrap_or_default().trim();
+    match line.char_indices().nth(MAX_SIGNATURE_LENGTH) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use indoc::indoc;
+
+    fn source(path: &str, content: &str) -> SourceFile {
+        SourceFile {
+            path: path.into(),
+            language: CodeLanguage::from_path(path).unwrap(),
+            content: content.into(),
+            hash: hash(content),
+        }
+    }
+
+    fn names(symbols: &[ParsedSymbol], definition: bool) -> Vec<(&str, SymbolKind)> {
+        symbols
+            .iter()
+            .filter(|symbol| symbol.definition == definition)
+            .map(|symbol| (symbol.name.as_str(), symbol.kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_rust() {
+        let symbols = source(
+            "src/lib.rs",
+            indoc! {"
+                pub struct Config {
+                    name: String,
+                }
+
+                impl Config {
+                    pub fn load(path: &str) -> Config {
+                        Config { name: read(path) }
+                    }
+                }
+
+                const LIMIT: usize = 10;
+
+                fn read(path: &str) -> String {
+                    String::from(path)
+                }
+            "},
+        )
+        .parse()
+        .unwrap();
+
+        assert_eq!(
+            names(&symbols, true),
+            vec![
+                ("Config", SymbolKind::Class),
+                ("load", SymbolKind::Method),
+                ("LIMIT", SymbolKind::Constant),
+                ("read", SymbolKind::Function),
+            ]
+        );
+        let references = names(&symbols, false);
+        assert!(references.contains(&("Config", SymbolKind::Implementation)));
+        assert!(references.contains(&("read", SymbolKind::Call)));
+        assert!(references.contains(&("from", SymbolKind::Call)));
+        assert!(!references.contains(&("Config", SymbolKind::Class)));
+
+        let load = symbols.iter().find(|symbol| symbol.name == "load").unwrap();
+        assert_eq!((load.line, load.column, load.end_line), (6, 12, 8));
+        assert_eq!(
+            load.signature.as_deref(),
+            Some("pub fn load(path: &str) -> Config {")
+        );
+    }
+
+    #[test]
+    fn test_parse_other_languages() {
+        let python = source(
+            "app.py",
+            "class Store:\n    def get(self):\n        return load()\n",
+        );
+        assert_eq!(
+            names(&python.parse().unwrap(), true),
+            vec![("Store", SymbolKind::Class), ("get", SymbolKind::Function)]
+        );
+
+        let typescript = source(
+            "web/api.ts",
+            "interface Client { get(): string }\nexport function connect(): Client { return open(); }\n",
+        );
+        let symbols = typescript.parse().unwrap();
+        assert_eq!(
+            names(&symbols, true),
+            vec![
+                ("Client", SymbolKind::Interface),
+                ("get", SymbolKind::Method),
+                ("connect", SymbolKind::Function)
+            ]
+        );
+        assert!(names(&symbols, false).contains(&("open", SymbolKind::Call)));
+
+        let go = source("main.go", "package main\n\nfunc main() {\n\tserve()\n}\n");
+        assert_eq!(
+            names(&go.parse().unwrap(), true),
+            vec![("main", SymbolKind::Function)]
+        );
+    }
+
+    #[test]
+    fn test_repo_map_ranks_referenced_files_first() {
+        let definition = |name: &str, line: i32| CodeSymbol {
+            id: name.into(),
+            file_id: String::new(),
+            worktree_id: String::new(),
+            name: name.into(),
+            kind: SymbolKind::Function,
+            definition: true,
+            line,
+            column: 1,
+            end_line: line,
+            signature: Some(format!("fn {name}()")),
+        };
+        let files = vec![
+            ("src/main.rs".to_string(), vec![definition("main", 1)]),
+            ("src/util.rs".to_string(), vec![definition("helper", 3)]),
+            ("src/empty.rs".to_string(), vec![]),
+        ];
+        let references = HashMap::from([("helper".to_string(), 4)]);
+
+        let map = repo_map(&files, &references, 1000);
+        assert_eq!(
+            map,
+            "src/util.rs:\n  3: fn helper()\nsrc/main.rs:\n  1: fn main()\n"
+        );
+        assert_eq!(
+            repo_map(&files, &references, 30),
+            "src/util.rs:\n  3: fn helper()\n… and 1 more files\n"
+        );
+    }
+
+    #[test]
+    fn test_walk_skips_ignored_files() {
+        let root = std::env::temp_dir().join("mirabel-code-index-walk");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::create_dir_all(root.join("target")).unwrap();
+        std::fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        std::fs::write(root.join("src/main.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(root.join("target/build.rs"), "fn build() {}\n").unwrap();
+        std::fs::write(root.join("README.md"), "# Readme\n").unwrap();
+
+        assert_eq!(walk(&root), vec!["src/main.rs".to_string()]);
+        assert!(SourceFile::read(&root, "README.md").unwrap().is_none());
+        assert!(SourceFile::read(&root, "src/gone.rs").unwrap().is_none());
+        let file = SourceFile::read(&root, "src/main.rs").unwrap().unwrap();
+        assert_eq!(file.hash, hash("fn main() {}\n"));
+    }
+}
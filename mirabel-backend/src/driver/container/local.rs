@@ -0,0 +1,103 @@
+use std::io;
+use std::path::Path;
+
+use portable_pty::CommandBuilder;
+use tokio::process::Child;
+use tokio::process::Command;
+
+use crate::prelude::*;
+use crate::driver::container::Limits;
+use crate::driver::container::find_executable;
+
+// Commands get a clean environment, the backend's secrets must not leak
+const PATH: &str = "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+
+/// The type `setrlimit` takes resources as differs between C libraries
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type Resource = libc::__rlimit_resource_t;
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+type Resource = libc::c_int;
+
+pub(super) fn command(workdir: &Path, limits: &Limits, command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c")
+        .arg(command)
+        .current_dir(workdir)
+        .env_clear()
+        .env("PATH", PATH)
+        .env("HOME", workdir)
+        .env("LANG", "C.UTF-8");
+
+    let cpu = limits.cpu_secs as libc::rlim_t;
+    let memory = (limits.memory_mb * 1024 * 1024) as libc::rlim_t;
+    let isolate = !limits.network;
+    // SAFETY: Only async-signal-safe calls happen between fork and exec
+    unsafe {
+        cmd.pre_exec(move || {
+            // Its own process group, so a timeout kills everything it started
+            if libc::setpgid(0, 0) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            set_limit(libc::RLIMIT_CPU, cpu)?;
+            set_limit(libc::RLIMIT_AS, memory)?;
+            // A fresh network namespace only has a loopback device, which is
+            // down. The user namespace makes this work without privileges.
+            if isolate && libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNET) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    cmd
+}
+
+/// An interactive shell for a pseudo-terminal. Limits are set by the shell
+/// itself, and network isolation needs `unshare` from util-linux. Without it
+/// no shell is started rather than one that can reach the network.
+pub(super) fn shell(workdir: &Path, limits: &Limits) -> Result<CommandBuilder> {
+    let script = format!(
+        "ulimit -t {}; ulimit -v {}; exec sh -i",
+        limits.cpu_secs,
+        limits.memory_mb * 1024
+    );
+    let mut cmd = match limits.network {
+        true => CommandBuilder::new("sh"),
+        false => {
+            let unshare = find_executable("unshare").ok_or(Error::Generic(
+                "unshare is not installed, the shell could reach the network.".into(),
+            ))?;
+            let mut cmd = CommandBuilder::new(unshare);
+            cmd.args(["--user", "--net", "--", "sh"]);
+            cmd
+        }
+    };
+    cmd.args(["-c", &script]);
+    cmd.cwd(workdir);
+    cmd.env_clear();
+    cmd.env("PATH", PATH);
+    cmd.env("HOME", workdir);
+    cmd.env("LANG", "C.UTF-8");
+    cmd.env("TERM", "xterm-256color");
+    Ok(cmd)
+}
+
+pub(super) fn kill(child: &Child) {
+    if let Some(pid) = child.id() {
+        // SAFETY: Sending a signal to the process group created in `command`
+        unsafe {
+            libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+        }
+    }
+}
+
+fn set_limit(resource: Resource, limit: libc::rlim_t) -> io::Result<()> {
+    let limit = libc::rlimit {
+        rlim_cur: limit,
+        rlim_max: limit,
+    };
+    // SAFETY: `limit` is a valid rlimit for the duration of the call
+    if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
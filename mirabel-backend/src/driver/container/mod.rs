@@ -0,0 +1,418 @@
+use std::env;
+use std::os::unix::process::ExitStatusExt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+use std::time::Instant;
+
+use async_trait::async_trait;
+use tokio::io::AsyncBufReadExt;
+use tokio::io::AsyncRead;
+use tokio::io::BufReader;
+use tokio::process::Child;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::prelude::*;
+use crate::driver::container::pty::PtyShell;
+use crate::session::tools::CommandOutput;
+use crate::session::tools::ShellTool;
+use crate::utils::env::parse_env;
+use mirabel_core::id;
+
+mod local;
+mod oci;
+pub(crate) mod pty;
+
+const SANDBOX_ROOT_ENV: &str = "SANDBOX_ROOT";
+const SANDBOX_BACKEND_ENV: &str = "SANDBOX_BACKEND";
+const SANDBOX_IMAGE_ENV: &str = "SANDBOX_IMAGE";
+const SANDBOX_NETWORK_ENV: &str = "SANDBOX_NETWORK";
+const SANDBOX_CPU_SECS_ENV: &str = "SANDBOX_CPU_SECS";
+const SANDBOX_MEMORY_MB_ENV: &str = "SANDBOX_MEMORY_MB";
+const SANDBOX_TIMEOUT_SECS_ENV: &str = "SANDBOX_TIMEOUT_SECS";
+const SANDBOX_ALLOW_LOCAL_ENV: &str = "SANDBOX_ALLOW_LOCAL";
+const DEFAULT_IMAGE: &str = "debian:bookworm-slim";
+// Only the end of long outputs is kept, that is where errors usually are
+const OUTPUT_LIMIT: usize = 64 * 1024;
+// Exit code `timeout(1)` uses when a command ran out of time
+const TIMEOUT_EXIT_CODE: i32 = 124;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Limits {
+    pub cpu_secs: u64,
+    pub memory_mb: u64,
+    pub timeout: Duration,
+    /// Whether commands may reach the network
+    pub network: bool,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            cpu_secs: 120,
+            memory_mb: 2048,
+            timeout: Duration::from_secs(300),
+            network: false,
+        }
+    }
+}
+
+impl Limits {
+    pub fn from_env() -> Result<Self> {
+        let default = Self::default();
+        Ok(Self {
+            cpu_secs: parse_env(SANDBOX_CPU_SECS_ENV)?.unwrap_or(default.cpu_secs),
+            memory_mb: parse_env(SANDBOX_MEMORY_MB_ENV)?.unwrap_or(default.memory_mb),
+            timeout: parse_env(SANDBOX_TIMEOUT_SECS_ENV)?
+                .map(Duration::from_secs)
+                .unwrap_or(default.timeout),
+            network: env::var(SANDBOX_NETWORK_ENV)
+                .map(|value| value == "true")
+                .unwrap_or(default.network),
+        })
+    }
+}
+
+/// Where commands are run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Backend {
+    /// A child process with rlimits, isolated from the network by a user and
+    /// network namespace. It is not isolated from the host: commands run as
+    /// the user of the backend and see its files and processes, which is why
+    /// it has to be allowed with `SANDBOX_ALLOW_LOCAL`.
+    Local,
+    /// A throwaway container of `image`, run by podman or docker
+    Oci { runtime: PathBuf, image: String },
+}
+
+impl Backend {
+    /// Prefers podman, then docker. Local processes are only used when they
+    /// are allowed.
+    pub fn detect(image: String, allow_local: bool) -> Result<Self> {
+        match ["podman", "docker"]
+            .iter()
+            .find_map(|name| find_executable(name))
+        {
+            Some(runtime) => Ok(Backend::Oci { runtime, image }),
+            None if allow_local => Ok(Backend::Local),
+            None => Err(Error::Generic(format!(
+                "Neither podman nor docker is installed. Set {SANDBOX_ALLOW_LOCAL_ENV}=true to \
+                 run commands as local processes, which are not isolated from the host."
+            ))),
+        }
+    }
+
+    pub fn from_env() -> Result<Self> {
+        let image = env::var(SANDBOX_IMAGE_ENV).unwrap_or(DEFAULT_IMAGE.into());
+        let allow_local = env::var(SANDBOX_ALLOW_LOCAL_ENV).is_ok_and(|value| value == "true");
+        let name = env::var(SANDBOX_BACKEND_ENV).ok();
+        Self::parse(name.as_deref(), image, allow_local)
+    }
+
+    fn parse(name: Option<&str>, image: String, allow_local: bool) -> Result<Self> {
+        match name {
+            None | Some("auto") => Self::detect(image, allow_local),
+            Some("local") if allow_local => Ok(Backend::Local),
+            Some("local") => Err(Error::Generic(format!(
+                "The local sandbox backend is not isolated from the host, set \
+                 {SANDBOX_ALLOW_LOCAL_ENV}=true to use it anyway."
+            ))),
+            Some(name @ ("podman" | "docker")) => {
+                let runtime = find_executable(name)
+                    .ok_or(Error::Generic(format!("{name} is not installed.")))?;
+                Ok(Backend::Oci { runtime, image })
+            }
+            Some(other) => Err(Error::Generic(format!(
+                "Unknown sandbox backend '{other}', use auto, local, podman or docker."
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecOutput {
+    pub exit_code: i32,
+    pub stdout: String,
+    pub stderr: String,
+    pub timed_out: bool,
+    pub duration: Duration,
+}
+
+/// An isolated working directory commands are run in
+#[derive(Debug, Clone)]
+pub struct Sandbox {
+    workdir: PathBuf,
+    backend: Backend,
+    limits: Limits,
+}
+
+impl Sandbox {
+    pub fn new(workdir: PathBuf, backend: Backend, limits: Limits) -> Result<Self> {
+        std::fs::create_dir_all(&workdir)?;
+        Ok(Self {
+            workdir: workdir.canonicalize()?,
+            backend,
+            limits,
+        })
+    }
+
+    /// The sandbox of a session, below `SANDBOX_ROOT`
+    pub fn from_env(session_id: &str) -> Result<Self> {
+        Self::new(
+            Self::workdir_of(session_id),
+            Backend::from_env()?,
+            Limits::from_env()?,
+        )
+    }
+
+    /// Where the sandbox of a session keeps its files, whichever backend
+    /// runs its commands
+    pub fn workdir_of(session_id: &str) -> PathBuf {
+        env::var(SANDBOX_ROOT_ENV)
+            .map(PathBuf::from)
+            .unwrap_or(env::temp_dir().join("mirabel"))
+            .join(session_id)
+    }
+
+    pub fn workdir(&self) -> &Path {
+        &self.workdir
+    }
+
+    /// Runs `command` with `sh -c`. Every line of output is sent to `output`
+    /// as it arrives, stdout and stderr interleaved.
+    pub async fn run(
+        &self,
+        command: &str,
+        output: Option<UnboundedSender<String>>,
+    ) -> Result<ExecOutput> {
+        let name = format!("mirabel-{}", id!());
+        let mut child = match &self.backend {
+            Backend::Local => local::command(&self.workdir, &self.limits, command),
+            Backend::Oci { runtime, image } => {
+                oci::command(runtime, image, &name, &self.workdir, &self.limits, command)
+            }
+        }
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+        let started = Instant::now();
+        let stdout = child.stdout.take().ok_or(Error::InternalServer)?;
+        let stderr = child.stderr.take().ok_or(Error::InternalServer)?;
+        let reading = tokio::spawn(async move {
+            tokio::join!(collect(stdout, output.clone()), collect(stderr, output))
+        });
+
+        let status = match tokio::time::timeout(self.limits.timeout, child.wait()).await {
+            Ok(status) => Some(status?),
+            Err(_) => {
+                self.kill(&mut child, &name).await;
+                None
+            }
+        };
+        // Killing the command closes its output, so this does not block
+        let (stdout, stderr) = reading
+            .await
+            .map_err(|err| Error::Generic(err.to_string()))?;
+        let exit_code = match status {
+            // Killed by a signal, reported the way shells do
+            Some(status) => status
+                .code()
+                .unwrap_or_else(|| 128 + status.signal().unwrap_or(0)),
+            None => TIMEOUT_EXIT_CODE,
+        };
+        Ok(ExecOutput {
+            exit_code,
+            stdout: stdout?,
+            stderr: stderr?,
+            timed_out: status.is_none(),
+            duration: started.elapsed(),
+        })
+    }
+
+    /// Starts an interactive shell in the sandbox, see [`PtyShell::spawn`]
+    pub fn shell(&self, rows: u16, cols: u16) -> Result<(PtyShell, UnboundedReceiver<String>)> {
+        let name = format!("mirabel-{}", id!());
+        let command = match &self.backend {
+            Backend::Local => local::shell(&self.workdir, &self.limits)?,
+            Backend::Oci { runtime, image } => {
+                oci::shell(runtime, image, &name, &self.workdir, &self.limits)
+            }
+        };
+        let (shell, output) = PtyShell::spawn(command, rows, cols)?;
+        Ok(match &self.backend {
+            Backend::Local => (shell, output),
+            Backend::Oci { runtime, .. } => (shell.in_container(runtime.clone(), name), output),
+        })
+    }
+
+    async fn kill(&self, child: &mut Child, name: &str) {
+        match &self.backend {
+            Backend::Local => local::kill(child),
+            Backend::Oci { runtime, .. } => oci::kill(runtime, name).await,
+        }
+        let _ = child.kill().await;
+    }
+}
+
+#[async_trait]
+impl ShellTool for Sandbox {
+    async fn run(&self, command: &str, output: UnboundedSender<String>) -> Result<CommandOutput> {
+        let result = Sandbox::run(self, command, Some(output)).await?;
+        let mut combined = result.stdout;
+        combined.push_str(&result.stderr);
+        if result.timed_out {
+            combined.push_str(&format!(
+                "Timed out after {} seconds\n",
+                self.limits.timeout.as_secs()
+            ));
+        }
+        Ok(CommandOutput {
+            exit_code: result.exit_code as i64,
+            output: combined,
+        })
+    }
+}
+
+async fn collect(
+    reader: impl AsyncRead + Unpin,
+    output: Option<UnboundedSender<String>>,
+) -> Result<String> {
+    let mut reader = BufReader::new(reader);
+    let mut collected = String::new();
+    let mut buffer = Vec::new();
+    loop {
+        buffer.clear();
+        if reader.read_until(b'\n', &mut buffer).await? == 0 {
+            break;
+        }
+        let line = String::from_utf8_lossy(&buffer);
+        if let Some(output) = &output {
+            let _ = output.send(line.trim_end_matches(['\n', '\r']).to_string());
+        }
+        collected.push_str(&line);
+        if collected.len() > OUTPUT_LIMIT {
+            let mut start = collected.len() - OUTPUT_LIMIT;
+            while !collected.is_char_boundary(start) {
+                start += 1;
+            }
+            collected.drain(..start);
+        }
+    }
+    Ok(collected)
+}
+
+pub(super) fn find_executable(name: &str) -> Option<PathBuf> {
+    let paths = env::var_os("PATH")?;
+    env::split_paths(&paths)
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::sync::mpsc::unbounded_channel;
+
+    fn sandbox(name: &str, limits: Limits) -> Sandbox {
+        let workdir = env::temp_dir().join("mirabel-sandbox-tests").join(name);
+        Sandbox::new(workdir, Backend::Local, limits).unwrap()
+    }
+
+    fn with_network() -> Limits {
+        Limits {
+            network: true,
+            ..Limits::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_captures_output() {
+        let sandbox = sandbox("output", with_network());
+        let (sender, mut receiver) = unbounded_channel();
+        let output = sandbox
+            .run("echo out; echo err >&2; pwd; exit 3", Some(sender))
+            .await
+            .unwrap();
+        assert_eq!(output.exit_code, 3);
+        assert_eq!(output.stderr, "err\n");
+        assert_eq!(
+            output.stdout,
+            format!("out\n{}\n", sandbox.workdir().display())
+        );
+        let mut lines = Vec::new();
+        while let Some(line) = receiver.recv().await {
+            lines.push(line);
+        }
+        assert_eq!(lines.len(), 3);
+        assert!(lines.contains(&"err".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_run_times_out() {
+        let sandbox = sandbox(
+            "timeout",
+            Limits {
+                timeout: Duration::from_millis(500),
+                ..with_network()
+            },
+        );
+        let output = sandbox.run("echo started; sleep 10", None).await.unwrap();
+        assert!(output.timed_out);
+        assert_eq!(output.exit_code, TIMEOUT_EXIT_CODE);
+        assert_eq!(output.stdout, "started\n");
+        assert!(output.duration < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_run_hides_environment() {
+        // What the sandbox sets and what the shell adds itself
+        let own = ["PATH", "HOME", "LANG", "PWD", "OLDPWD", "SHLVL", "_"];
+        let sandbox = sandbox("environment", with_network());
+        let output = sandbox.run("env", None).await.unwrap();
+        let names: Vec<&str> = output
+            .stdout
+            .lines()
+            .filter_map(|line| line.split_once('=').map(|(name, _)| name))
+            .collect();
+        assert!(names.contains(&"HOME"), "{names:?}");
+        for name in names {
+            assert!(own.contains(&name), "{name} leaked into the sandbox");
+        }
+        // Whatever the tests run with, none of it gets through
+        assert!(env::vars().any(|(name, _)| !own.contains(&name.as_str())));
+    }
+
+    #[test]
+    fn test_local_backend_is_opt_in() {
+        let image = || DEFAULT_IMAGE.to_string();
+        assert!(Backend::parse(Some("local"), image(), false).is_err());
+        assert_eq!(
+            Backend::parse(Some("local"), image(), true).unwrap(),
+            Backend::Local
+        );
+        assert!(Backend::parse(Some("chroot"), image(), true).is_err());
+        // Without podman or docker nothing is left to run commands in
+        if find_executable("podman").is_none() && find_executable("docker").is_none() {
+            assert!(Backend::parse(None, image(), false).is_err());
+            assert_eq!(Backend::parse(None, image(), true).unwrap(), Backend::Local);
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "Depends on unprivileged user namespaces"]
+    async fn test_run_without_network() {
+        let sandbox = sandbox("network", Limits::default());
+        let output = sandbox
+            .run("tail -n +3 /proc/net/dev | cut -d: -f1", None)
+            .await
+            .unwrap();
+        // Only the loopback device exists in the new network namespace
+        assert_eq!(output.stdout.trim(), "lo");
+    }
+}
@@ -0,0 +1,75 @@
+use std::path::Path;
+
+use portable_pty::CommandBuilder;
+use tokio::process::Command;
+
+use crate::driver::container::Limits;
+
+const CONTAINER_WORKDIR: &str = "/workspace";
+const PIDS_LIMIT: u32 = 512;
+
+/// A `run` of a throwaway container with the working directory mounted
+pub(super) fn command(
+    runtime: &Path,
+    image: &str,
+    name: &str,
+    workdir: &Path,
+    limits: &Limits,
+    command: &str,
+) -> Command {
+    let mut cmd = Command::new(runtime);
+    cmd.args(run_args(image, name, workdir, limits, false))
+        .args(["sh", "-c", command]);
+    cmd
+}
+
+/// An interactive shell in a throwaway container, for a pseudo-terminal
+pub(super) fn shell(
+    runtime: &Path,
+    image: &str,
+    name: &str,
+    workdir: &Path,
+    limits: &Limits,
+) -> CommandBuilder {
+    let mut cmd = CommandBuilder::new(runtime);
+    cmd.args(run_args(image, name, workdir, limits, true));
+    cmd.args(["sh", "-i"]);
+    cmd
+}
+
+/// Stopping the client is not enough, the container keeps running without it
+pub(super) async fn kill(runtime: &Path, name: &str) {
+    let _ = Command::new(runtime).args(["kill", name]).output().await;
+}
+
+fn run_args(
+    image: &str,
+    name: &str,
+    workdir: &Path,
+    limits: &Limits,
+    interactive: bool,
+) -> Vec<String> {
+    let mut args: Vec<String> = vec!["run".into(), "--rm".into(), "--name".into(), name.into()];
+    if interactive {
+        args.push("--interactive".into());
+        args.push("--tty".into());
+    }
+    args.extend([
+        "--network".into(),
+        if limits.network { "bridge" } else { "none" }.into(),
+        "--memory".into(),
+        format!("{}m", limits.memory_mb),
+        "--ulimit".into(),
+        format!("cpu={}", limits.cpu_secs),
+        "--pids-limit".into(),
+        PIDS_LIMIT.to_string(),
+        "--cpus".into(),
+        "1".into(),
+        "--volume".into(),
+        format!("{}:{CONTAINER_WORKDIR}", workdir.display()),
+        "--workdir".into(),
+        CONTAINER_WORKDIR.into(),
+        image.into(),
+    ]);
+    args
+}
@@ -0,0 +1,182 @@
+use std::io::Read;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use log::warn;
+use portable_pty::Child;
+use portable_pty::CommandBuilder;
+use portable_pty::MasterPty;
+use portable_pty::PtySize;
+use portable_pty::native_pty_system;
+use tokio::runtime::Handle;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::mpsc::unbounded_channel;
+
+use crate::prelude::*;
+
+use super::oci;
+
+const READ_BUFFER_SIZE: usize = 4096;
+
+/// An interactive shell on a pseudo-terminal, it lives until it exits or is
+/// dropped
+pub struct PtyShell {
+    master: Mutex<Box<dyn MasterPty + Send>>,
+    writer: Mutex<Box<dyn Write + Send>>,
+    child: Mutex<Box<dyn Child + Send + Sync>>,
+    /// The runtime and name of the container the shell runs in
+    container: Option<(PathBuf, String)>,
+}
+
+impl PtyShell {
+    /// Starts `command` on a new terminal. Its output arrives on the returned
+    /// receiver, which closes once the shell exited.
+    pub fn spawn(
+        command: CommandBuilder,
+        rows: u16,
+        cols: u16,
+    ) -> Result<(Self, UnboundedReceiver<String>)> {
+        let pair = native_pty_system()
+            .openpty(size(rows, cols))
+            .map_err(pty_error)?;
+        let child = pair.slave.spawn_command(command).map_err(pty_error)?;
+        // Without closing our end of the slave, reading never sees the exit
+        drop(pair.slave);
+        let reader = pair.master.try_clone_reader().map_err(pty_error)?;
+        let writer = pair.master.take_writer().map_err(pty_error)?;
+
+        let (sender, receiver) = unbounded_channel();
+        std::thread::spawn(move || read_output(reader, sender));
+        Ok((
+            Self {
+                master: Mutex::new(pair.master),
+                writer: Mutex::new(writer),
+                child: Mutex::new(child),
+                container: None,
+            },
+            receiver,
+        ))
+    }
+
+    /// The shell runs in the container `name`, which is stopped with it
+    pub(super) fn in_container(mut self, runtime: PathBuf, name: String) -> Self {
+        self.container = Some((runtime, name));
+        self
+    }
+
+    pub fn write(&self, data: &str) -> Result<()> {
+        let mut writer = self.writer.lock().map_err(poisoned)?;
+        writer.write_all(data.as_bytes())?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn resize(&self, rows: u16, cols: u16) -> Result<()> {
+        self.master
+            .lock()
+            .map_err(poisoned)?
+            .resize(size(rows, cols))
+            .map_err(pty_error)
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.child
+            .lock()
+            .map(|mut child| matches!(child.try_wait(), Ok(None)))
+            .unwrap_or(false)
+    }
+}
+
+impl Drop for PtyShell {
+    fn drop(&mut self) {
+        if let Ok(child) = self.child.get_mut() {
+            let _ = child.kill();
+        }
+        if let Some((runtime, name)) = self.container.take() {
+            match Handle::try_current() {
+                Ok(handle) => {
+                    handle.spawn(async move { oci::kill(&runtime, &name).await });
+                }
+                Err(_) => warn!("The container {name} of a shell was left running"),
+            }
+        }
+    }
+}
+
+/// Forwards the output, a character split over two reads is held back until
+/// it is complete
+fn read_output(mut reader: Box<dyn Read + Send>, sender: UnboundedSender<String>) {
+    let mut buffer = [0u8; READ_BUFFER_SIZE];
+    let mut pending = Vec::new();
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(read) => read,
+        };
+        pending.extend_from_slice(&buffer[..read]);
+        let valid = match std::str::from_utf8(&pending) {
+            Ok(_) => pending.len(),
+            Err(err) if err.error_len().is_none() => err.valid_up_to(),
+            // Not UTF-8 at all, it is passed on replaced
+            Err(_) => pending.len(),
+        };
+        let chunk: Vec<u8> = pending.drain(..valid).collect();
+        if sender
+            .send(String::from_utf8_lossy(&chunk).into_owned())
+            .is_err()
+        {
+            break;
+        }
+    }
+}
+
+fn size(rows: u16, cols: u16) -> PtySize {
+    PtySize {
+        rows,
+        cols,
+        pixel_width: 0,
+        pixel_height: 0,
+    }
+}
+
+fn pty_error(err: impl std::fmt::Display) -> Error {
+    Error::Pty(err.to_string())
+}
+
+fn poisoned<T>(err: std::sync::PoisonError<T>) -> Error {
+    Error::PoisonedLock(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_pty_shell() {
+        let mut command = CommandBuilder::new("sh");
+        command.env("PS1", "$ ");
+        let (shell, mut output) = PtyShell::spawn(command, 24, 80).unwrap();
+        shell.write("echo $((20 + 22))\n").unwrap();
+        shell.write("exit\n").unwrap();
+
+        let mut received = String::new();
+        while let Ok(Some(chunk)) =
+            tokio::time::timeout(Duration::from_secs(5), output.recv()).await
+        {
+            received.push_str(&chunk);
+        }
+        assert!(received.contains("42"));
+        // The output can close a moment before the exit is noticed
+        for _ in 0..50 {
+            if !shell.is_running() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert!(!shell.is_running());
+    }
+}
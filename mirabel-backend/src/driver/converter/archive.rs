@@ -0,0 +1,43 @@
+use std::io::Cursor;
+use std::io::Read;
+
+use zip::ZipArchive;
+use zip::result::ZipError;
+
+use crate::prelude::*;
+
+/// Files bigger than this when unpacked are not read, so a tiny archive
+/// cannot fill the memory
+const MAX_FILE_BYTES: u64 = 64 * 1024 * 1024;
+
+/// The ZIP archive DOCX and EPUB documents are
+pub(super) struct Archive<'a>(ZipArchive<Cursor<&'a [u8]>>);
+
+impl<'a> Archive<'a> {
+    pub fn new(data: &'a [u8]) -> Result<Self> {
+        ZipArchive::new(Cursor::new(data))
+            .map(Self)
+            .map_err(|err| Error::InvalidDocument(format!("it is no ZIP archive: {err}")))
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.index_for_name(name).is_some()
+    }
+
+    /// The file as text, `None` when the archive has no such file
+    pub fn read_string(&mut self, name: &str) -> Result<Option<String>> {
+        let file = match self.0.by_name(name) {
+            Ok(file) => file,
+            Err(ZipError::FileNotFound) => return Ok(None),
+            Err(err) => return Err(Error::InvalidDocument(format!("{name}: {err}"))),
+        };
+        if file.size() > MAX_FILE_BYTES {
+            return Err(Error::InvalidDocument(format!(
+                "{name} is bigger than {MAX_FILE_BYTES} bytes"
+            )));
+        }
+        let mut bytes = Vec::new();
+        file.take(MAX_FILE_BYTES).read_to_end(&mut bytes)?;
+        Ok(Some(String::from_utf8_lossy(&bytes).into_owned()))
+    }
+}
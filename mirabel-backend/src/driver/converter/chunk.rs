@@ -0,0 +1,365 @@
+use std::collections::HashMap;
+
+use sha2::Digest;
+use sha2::Sha256;
+
+use super::Converter;
+use super::markdown::Markdown;
+use super::pdf::page_at;
+use super::pdf::page_starts;
+
+/// How markdown is cut into chunks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkOptions {
+    /// Most tokens of a chunk. Blocks larger than this are split at lines,
+    /// and lines at words.
+    pub max_tokens: usize,
+    /// Tokens at the end of a chunk that the next one starts with, so text
+    /// cut in two is found in either. Only chunks cut for their size
+    /// overlap, a heading starts afresh.
+    pub overlap_tokens: usize,
+}
+
+impl Default for ChunkOptions {
+    fn default() -> Self {
+        Self {
+            max_tokens: 512,
+            overlap_tokens: 64,
+        }
+    }
+}
+
+/// A part of a markdown document small enough to hand to a model
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    /// Taken from the text, so chunking the same document again gives the
+    /// same ids, even when other parts of it changed
+    pub id: String,
+    /// Byte offsets of the text in the markdown
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+    /// The headings the text is under, the outermost first
+    pub headings: Vec<String>,
+    /// The page the text starts on, for documents with page anchors
+    pub page: Option<u32>,
+    pub tokens: usize,
+}
+
+/// Roughly the tokens a model reads the text as, one for every four
+/// characters of a word and at least one a word
+pub fn tokens(text: &str) -> usize {
+    text.split_whitespace()
+        .map(|word| word.chars().count().div_ceil(4))
+        .sum()
+}
+
+impl Converter<Markdown> {
+    /// Cuts the markdown where its structure allows: before headings, then
+    /// between paragraphs, lists, tables and code blocks, which are only cut
+    /// when they do not fit into a chunk on their own
+    pub fn chunks(&self, options: ChunkOptions) -> Vec<Chunk> {
+        let markdown = self.data.0.as_str();
+        let max_tokens = options.max_tokens.max(1);
+        let overlap_tokens = options.overlap_tokens.min(max_tokens / 2);
+
+        let mut chunks = Vec::new();
+        let mut headings: Vec<(usize, String)> = Vec::new();
+        // The range of the chunk being filled, its tokens and headings
+        let mut current: Option<(usize, usize, usize, Vec<String>)> = None;
+        for piece in pieces(markdown, max_tokens) {
+            if let Some((level, title)) = &piece.heading {
+                if let Some((start, end, _, path)) = current.take() {
+                    chunks.push((start, end, path));
+                }
+                headings.retain(|(outer, _)| outer < level);
+                headings.push((*level, title.clone()));
+            }
+            let path = || headings.iter().map(|(_, title)| title.clone()).collect();
+            current = match current.take() {
+                None => Some((piece.start, piece.end, piece.tokens, path())),
+                Some((start, _, count, held)) if count + piece.tokens <= max_tokens => {
+                    Some((start, piece.end, count + piece.tokens, held))
+                }
+                Some((start, end, _, previous)) => {
+                    chunks.push((start, end, previous));
+                    let overlap =
+                        overlap_start(markdown, start, end, overlap_tokens).filter(|overlap| {
+                            tokens(&markdown[*overlap..end]) + piece.tokens <= max_tokens
+                        });
+                    match overlap {
+                        Some(overlap) => Some((
+                            overlap,
+                            piece.end,
+                            tokens(&markdown[overlap..end]) + piece.tokens,
+                            path(),
+                        )),
+                        None => Some((piece.start, piece.end, piece.tokens, path())),
+                    }
+                }
+            };
+        }
+        if let Some((start, end, _, path)) = current {
+            chunks.push((start, end, path));
+        }
+
+        let pages = page_starts(markdown);
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        chunks
+            .into_iter()
+            .map(|(start, end, headings)| {
+                let text = markdown[start..end].to_string();
+                // The same text twice in a document is told apart by its order
+                let count = seen.entry(text.clone()).or_default();
+                *count += 1;
+                let id = match *count {
+                    1 => hash(&text),
+                    count => hash(&format!("{text}\0{count}")),
+                };
+                Chunk {
+                    id,
+                    start,
+                    end,
+                    tokens: tokens(&text),
+                    headings,
+                    page: page_at(&pages, start),
+                    text,
+                }
+            })
+            .collect()
+    }
+}
+
+fn hash(text: &str) -> String {
+    Sha256::digest(text.as_bytes())
+        .iter()
+        .take(8)
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// A part of the markdown that is not cut any further
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Piece {
+    start: usize,
+    end: usize,
+    tokens: usize,
+    /// The level and title when the piece is a heading
+    heading: Option<(usize, String)>,
+}
+
+/// The blocks of the markdown, the ones larger than `max_tokens` split into
+/// lines or words. Page anchors are left out, they stay between blocks.
+fn pieces(markdown: &str, max_tokens: usize) -> Vec<Piece> {
+    let mut pieces = Vec::new();
+    for (start, end) in blocks(markdown) {
+        let text = &markdown[start..end];
+        if text.starts_with(r#"<a id="page-"#) && text.ends_with("</a>") {
+            continue;
+        }
+        let heading = heading(text);
+        let count = tokens(text);
+        if count <= max_tokens || heading.is_some() {
+            pieces.push(Piece {
+                start,
+                end,
+                tokens: count,
+                heading,
+            });
+            continue;
+        }
+        for (line_start, line_end) in lines(markdown, start, end) {
+            let line = &markdown[line_start..line_end];
+            match tokens(line) {
+                0 => {}
+                count if count <= max_tokens => pieces.push(Piece {
+                    start: line_start,
+                    end: line_end,
+                    tokens: count,
+                    heading: None,
+                }),
+                _ => pieces.extend(words(markdown, line_start, line_end, max_tokens)),
+            }
+        }
+    }
+    pieces
+}
+
+/// Byte ranges of headings, code blocks and runs of lines between blank
+/// lines, which are paragraphs, lists or tables
+fn blocks(markdown: &str) -> Vec<(usize, usize)> {
+    let mut blocks = Vec::new();
+    let mut block: Option<(usize, usize)> = None;
+    let mut fence: Option<String> = None;
+    for (start, end) in lines(markdown, 0, markdown.len()) {
+        let line = &markdown[start..end];
+        let trimmed = line.trim_start();
+        if let Some(open) = &fence {
+            block = block.map(|(block_start, _)| (block_start, end));
+            let trimmed = trimmed.trim_end();
+            if trimmed.starts_with(open.as_str()) && trimmed.chars().all(|c| open.starts_with(c)) {
+                fence = None;
+                blocks.extend(block.take());
+            }
+            continue;
+        }
+        let marker = fence_marker(trimmed);
+        if marker.is_some() || heading(line).is_some() || line.trim().is_empty() {
+            blocks.extend(block.take());
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        match (marker, heading(line)) {
+            (Some(marker), _) => {
+                fence = Some(marker);
+                block = Some((start, end));
+            }
+            (None, Some(_)) => blocks.push((start, end)),
+            (None, None) => {
+                block = Some(block.map_or((start, end), |(block_start, _)| (block_start, end)))
+            }
+        }
+    }
+    blocks.extend(block);
+    blocks
+}
+
+/// The backticks or tildes that open a code block
+fn fence_marker(line: &str) -> Option<String> {
+    let marker: String = line.chars().take_while(|c| *c == '`').collect();
+    let marker = match marker.len() {
+        0 => line.chars().take_while(|c| *c == '~').collect(),
+        _ => marker,
+    };
+    (marker.len() >= 3).then_some(marker)
+}
+
+/// The level and title of an ATX heading, `## Usage`
+fn heading(line: &str) -> Option<(usize, String)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let title = line[level..].strip_prefix(' ')?;
+    (1..=6)
+        .contains(&level)
+        .then(|| (level, title.trim().trim_end_matches('#').trim().to_string()))
+}
+
+/// Byte ranges of the lines between `start` and `end`, without line breaks
+fn lines(markdown: &str, start: usize, end: usize) -> Vec<(usize, usize)> {
+    let mut lines = Vec::new();
+    let mut line_start = start;
+    for line in markdown[start..end].split_inclusive('\n') {
+        let line_end = line_start + line.trim_end_matches(['\n', '\r']).len();
+        lines.push((line_start, line_end));
+        line_start += line.len();
+    }
+    lines
+}
+
+/// A line too long for a chunk, cut between words
+fn words(markdown: &str, start: usize, end: usize, max_tokens: usize) -> Vec<Piece> {
+    let mut pieces: Vec<Piece> = Vec::new();
+    let line = &markdown[start..end];
+    for (offset, word) in line
+        .split_whitespace()
+        .map(|word| (word.as_ptr() as usize - line.as_ptr() as usize, word))
+    {
+        let (word_start, word_end) = (start + offset, start + offset + word.len());
+        let count = tokens(word);
+        match pieces.last_mut() {
+            Some(piece) if piece.tokens + count <= max_tokens => {
+                piece.end = word_end;
+                piece.tokens += count;
+            }
+            _ => pieces.push(Piece {
+                start: word_start,
+                end: word_end,
+                tokens: count,
+                heading: None,
+            }),
+        }
+    }
+    pieces
+}
+
+/// Where the last `overlap_tokens` of a chunk start, at a word
+fn overlap_start(markdown: &str, start: usize, end: usize, overlap_tokens: usize) -> Option<usize> {
+    let text = &markdown[start..end];
+    let mut count = 0;
+    let mut overlap = None;
+    for word in text.split_whitespace().rev() {
+        count += tokens(word);
+        if count > overlap_tokens {
+            break;
+        }
+        overlap = Some(start + (word.as_ptr() as usize - text.as_ptr() as usize));
+    }
+    overlap
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(markdown: &str, max_tokens: usize, overlap_tokens: usize) -> Vec<Chunk> {
+        Converter::<Markdown>::from_md(markdown.into()).chunks(ChunkOptions {
+            max_tokens,
+            overlap_tokens,
+        })
+    }
+
+    #[test]
+    fn test_chunks_follow_structure() {
+        let markdown = "# Guide\n\nIntro text.\n\n## Install\n\nRun the installer.\n\n```sh\n# not a heading\n\nmake\n```\n\n## Use\n\nStart it.";
+        let chunks = split(markdown, 100, 10);
+        let texts: Vec<&str> = chunks.iter().map(|chunk| chunk.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            [
+                "# Guide\n\nIntro text.",
+                "## Install\n\nRun the installer.\n\n```sh\n# not a heading\n\nmake\n```",
+                "## Use\n\nStart it."
+            ]
+        );
+        assert_eq!(chunks[1].headings, ["Guide", "Install"]);
+        assert_eq!(chunks[2].headings, ["Guide", "Use"]);
+        for chunk in &chunks {
+            assert_eq!(&markdown[chunk.start..chunk.end], chunk.text);
+        }
+    }
+
+    #[test]
+    fn test_chunks_overlap_when_too_large() {
+        let markdown = "one two three four\n\nfive six seven eight\n\nnine ten";
+        let chunks = split(markdown, 10, 3);
+        let texts: Vec<&str> = chunks.iter().map(|chunk| chunk.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            [
+                "one two three four",
+                "three four\n\nfive six seven eight",
+                "eight\n\nnine ten"
+            ]
+        );
+        assert!(chunks.iter().all(|chunk| chunk.tokens <= 10));
+
+        // A line longer than a chunk is cut at words
+        let long = split(&"word ".repeat(10), 4, 0);
+        assert_eq!(long.len(), 3);
+        assert_eq!(long[2].text, "word word");
+    }
+
+    #[test]
+    fn test_chunk_ids_and_pages() {
+        let markdown = "<a id=\"page-1\"></a>\n\nSame.\n\n<a id=\"page-2\"></a>\n\nSame.";
+        let chunks = split(markdown, 1, 0);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].page, Some(1));
+        assert_eq!(chunks[1].page, Some(2));
+        assert_ne!(chunks[0].id, chunks[1].id);
+
+        // Ids do not depend on what comes before
+        let moved = split(&format!("# Other\n\nText.\n\n{markdown}"), 1, 0);
+        assert_eq!(moved[2].id, chunks[0].id);
+    }
+}
@@ -0,0 +1,114 @@
+use std::fmt::Display;
+
+use super::Converter;
+use super::markdown::Markdown;
+
+/// Source code, with the name of its language as markdown fences know it
+pub struct Code {
+    pub(super) text: String,
+    pub(super) language: String,
+}
+
+impl Converter<Code> {
+    /// A fenced code block, fenced with more backticks than the code has in
+    /// a row
+    pub fn to_md(&self) -> Converter<Markdown> {
+        let code = self.data.text.trim_start_matches('\u{feff}').trim_end();
+        let longest = code
+            .split(|c| c != '`')
+            .map(str::len)
+            .max()
+            .unwrap_or_default();
+        let fence = "`".repeat(longest.max(2) + 1);
+        Converter {
+            data: Markdown(format!("{fence}{}\n{code}\n{fence}", self.data.language)),
+        }
+    }
+}
+
+impl Display for Converter<Code> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.data.text)
+    }
+}
+
+/// The language of a source file by its extension or name, `None` when it is
+/// no source file
+pub fn language(name: &str) -> Option<&'static str> {
+    let file = name.rsplit(['/', '\\']).next().unwrap_or(name);
+    match file.to_lowercase().as_str() {
+        "dockerfile" | "containerfile" => return Some("dockerfile"),
+        "makefile" | "gnumakefile" => return Some("makefile"),
+        _ => {}
+    }
+    let (_, extension) = file.rsplit_once('.')?;
+    let language = match extension.to_lowercase().as_str() {
+        "rs" => "rust",
+        "py" | "pyi" => "python",
+        "js" | "mjs" | "cjs" => "javascript",
+        "jsx" => "jsx",
+        "ts" | "mts" | "cts" => "typescript",
+        "tsx" => "tsx",
+        "go" => "go",
+        "java" => "java",
+        "kt" | "kts" => "kotlin",
+        "scala" => "scala",
+        "c" | "h" => "c",
+        "cc" | "cpp" | "cxx" | "hh" | "hpp" | "hxx" => "cpp",
+        "cs" => "csharp",
+        "swift" => "swift",
+        "rb" => "ruby",
+        "php" => "php",
+        "lua" => "lua",
+        "dart" => "dart",
+        "zig" => "zig",
+        "hs" => "haskell",
+        "ex" | "exs" => "elixir",
+        "erl" => "erlang",
+        "ml" | "mli" => "ocaml",
+        "r" => "r",
+        "sh" | "bash" | "zsh" => "bash",
+        "ps1" => "powershell",
+        "sql" => "sql",
+        "css" => "css",
+        "scss" => "scss",
+        "vue" => "vue",
+        "svelte" => "svelte",
+        "json" => "json",
+        "toml" => "toml",
+        "yaml" | "yml" => "yaml",
+        "xml" => "xml",
+        "proto" => "protobuf",
+        _ => return None,
+    };
+    Some(language)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_md() {
+        let code = |text: &str| {
+            Converter::<Code>::from_code(text.into(), "rust".into())
+                .to_md()
+                .to_string()
+        };
+        assert_eq!(code("fn main() {}\n"), "```rust\nfn main() {}\n```");
+        // Fences in the code do not end the block
+        assert_eq!(
+            code("/// ```\n/// x\n/// ```"),
+            "````rust\n/// ```\n/// x\n/// ```\n````"
+        );
+    }
+
+    #[test]
+    fn test_language() {
+        assert_eq!(language("src/main.rs"), Some("rust"));
+        assert_eq!(language("App.TSX"), Some("tsx"));
+        assert_eq!(language("build/Dockerfile"), Some("dockerfile"));
+        assert_eq!(language("notes.txt"), None);
+        assert_eq!(language("README"), None);
+    }
+}
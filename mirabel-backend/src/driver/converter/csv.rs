@@ -0,0 +1,128 @@
+use std::fmt::Display;
+
+use super::Converter;
+use super::markdown::Markdown;
+
+/// Separators tried when a file does not say which one it uses
+const DELIMITERS: [char; 4] = [',', ';', '\t', '|'];
+
+/// Comma separated values, or separated by whatever the header line is
+/// separated with
+pub struct Csv(pub(super) String);
+
+impl Converter<Csv> {
+    /// A markdown table with the first record as its header
+    pub fn to_md(&self) -> Converter<Markdown> {
+        let text = self.data.0.trim_start_matches('\u{feff}');
+        let header = text.lines().next().unwrap_or_default();
+        let delimiter = DELIMITERS
+            .into_iter()
+            .max_by_key(|delimiter| header.matches(*delimiter).count())
+            .unwrap_or(',');
+        Converter {
+            data: Markdown(table(&records(text, delimiter))),
+        }
+    }
+}
+
+impl Display for Converter<Csv> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.data.0)
+    }
+}
+
+/// The records of the text as RFC 4180 has them: fields in double quotes
+/// may hold separators, line breaks and doubled quotes
+fn records(text: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            c if quoted => field.push(c),
+            c if c == delimiter => record.push(std::mem::take(&mut field)),
+            '\r' => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    // Blank lines are no records
+    records.retain(|record| record.iter().any(|field| !field.trim().is_empty()));
+    records
+}
+
+/// A markdown table with the first row as its header. Rows are padded to
+/// the widest one, and what would break the table out of a cell is escaped.
+pub(super) fn table(rows: &[Vec<String>]) -> String {
+    let Some((header, body)) = rows.split_first() else {
+        return String::new();
+    };
+    let width = rows.iter().map(Vec::len).max().unwrap_or_default().max(1);
+    let line = |row: &[String]| {
+        let cells: Vec<String> = (0..width)
+            .map(|i| cell(row.get(i).map(String::as_str).unwrap_or_default()))
+            .collect();
+        format!("| {} |", cells.join(" | "))
+    };
+    let mut lines = vec![line(header), format!("|{}", " --- |".repeat(width))];
+    lines.extend(body.iter().map(|row| line(row)));
+    lines.join("\n")
+}
+
+fn cell(text: &str) -> String {
+    text.trim()
+        .replace('|', "\\|")
+        .replace("\r\n", "\n")
+        .replace('\n', "<br>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PEOPLE: &str = include_str!("fixtures/people.csv");
+
+    #[test]
+    fn test_to_md() {
+        let md = Converter::<Csv>::from_csv(PEOPLE.into())
+            .to_md()
+            .to_string();
+        assert_eq!(
+            md,
+            "| name | role | notes |\n\
+             | --- | --- | --- |\n\
+             | Ada Lovelace | analyst | wrote \"the first program\" |\n\
+             | Grace Hopper | admiral | a \\| b<br>compilers |\n\
+             | Alan Turing | logician |  |"
+        );
+    }
+
+    #[test]
+    fn test_delimiter() {
+        let md = Converter::<Csv>::from_csv("a;b\n1,5;2\n".into())
+            .to_md()
+            .to_string();
+        assert_eq!(md, "| a | b |\n| --- | --- |\n| 1,5 | 2 |");
+        assert_eq!(
+            Converter::<Csv>::from_csv(String::new())
+                .to_md()
+                .to_string(),
+            ""
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use crate::prelude::*;
+
+use super::archive::Archive;
+use super::code::language;
+
+/// The formats documents are converted from, each with its own pipeline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Format {
+    Pdf,
+    Docx,
+    Epub,
+    Html,
+    Markdown,
+    Csv,
+    /// Source code in a language markdown fences know
+    Code {
+        language: String,
+    },
+    Text,
+}
+
+/// Tells the format of a document. Binary formats are told by their first
+/// bytes, text by the MIME type it came with, then by the extension of its
+/// name and last by what it starts with. MIME types that say nothing, like
+/// `text/plain` for a file named `notes.md`, give way to the name.
+pub fn detect(data: &[u8], name: Option<&str>, mime: Option<&str>) -> Result<Format> {
+    if data.starts_with(b"%PDF-") {
+        return Ok(Format::Pdf);
+    }
+    if data.starts_with(b"PK\x03\x04") {
+        let archive = Archive::new(data)?;
+        if archive.contains("META-INF/container.xml") {
+            return Ok(Format::Epub);
+        }
+        if archive.contains("word/document.xml") {
+            return Ok(Format::Docx);
+        }
+        return Err(Error::UnsupportedFormat(
+            "a ZIP archive that is neither DOCX nor EPUB".into(),
+        ));
+    }
+
+    let mime = mime
+        .and_then(|mime| mime.split(';').next())
+        .map(|mime| mime.trim().to_lowercase());
+    let declared = match mime.as_deref() {
+        Some("text/html" | "application/xhtml+xml") => Some(Format::Html),
+        Some("text/markdown" | "text/x-markdown") => Some(Format::Markdown),
+        Some("text/csv" | "text/tab-separated-values") => Some(Format::Csv),
+        _ => None,
+    };
+    let named = name.and_then(|name| {
+        let extension = name.rsplit_once('.')?.1.to_lowercase();
+        match extension.as_str() {
+            "pdf" | "docx" | "epub" => None,
+            "html" | "htm" | "xhtml" => Some(Format::Html),
+            "md" | "markdown" => Some(Format::Markdown),
+            "csv" | "tsv" => Some(Format::Csv),
+            "txt" | "text" | "log" => Some(Format::Text),
+            _ => None,
+        }
+        .or_else(|| {
+            language(name).map(|language| Format::Code {
+                language: language.to_string(),
+            })
+        })
+    });
+    if let Some(format) = declared.or(named) {
+        return text_only(data, format);
+    }
+
+    let start = String::from_utf8_lossy(&data[..data.len().min(512)])
+        .trim_start_matches('\u{feff}')
+        .trim_start()
+        .to_lowercase();
+    if start.starts_with("<!doctype html") || start.starts_with("<html") {
+        return text_only(data, Format::Html);
+    }
+    match std::str::from_utf8(data) {
+        Ok(_) => Ok(Format::Text),
+        Err(_) => Err(Error::UnsupportedFormat(format!(
+            "{}, which is binary",
+            mime.or(name.map(str::to_string))
+                .unwrap_or("the document".into())
+        ))),
+    }
+}
+
+/// Text formats do not hold NUL bytes, which binary files always do
+fn text_only(data: &[u8], format: Format) -> Result<Format> {
+    match data.contains(&0) {
+        true => Err(Error::UnsupportedFormat(format!(
+            "the document, which claims to be {format:?} but is binary"
+        ))),
+        false => Ok(format),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        let detect = |data: &[u8], name, mime| detect(data, name, mime).unwrap();
+        assert_eq!(detect(b"%PDF-1.7\n", Some("x.txt"), None), Format::Pdf);
+        assert_eq!(
+            detect(include_bytes!("fixtures/book.epub"), None, None),
+            Format::Epub
+        );
+        assert_eq!(
+            detect(include_bytes!("fixtures/report.docx"), None, None),
+            Format::Docx
+        );
+        assert_eq!(
+            detect(b"<p>hi</p>", Some("page"), Some("text/html; charset=utf-8")),
+            Format::Html
+        );
+        // A name says more than text/plain
+        assert_eq!(
+            detect(b"# Notes", Some("notes.md"), Some("text/plain")),
+            Format::Markdown
+        );
+        assert_eq!(detect(b"a,b\n1,2", Some("data.CSV"), None), Format::Csv);
+        assert_eq!(
+            detect(b"print(1)", Some("main.py"), None),
+            Format::Code {
+                language: "python".into()
+            }
+        );
+        assert_eq!(
+            detect(b"\n<!DOCTYPE html><html></html>", None, None),
+            Format::Html
+        );
+        assert_eq!(detect(b"Just text", None, None), Format::Text);
+    }
+
+    #[test]
+    fn test_detect_unsupported() {
+        let unsupported = |data: &[u8], name| {
+            matches!(detect(data, name, None), Err(Error::UnsupportedFormat(_)))
+        };
+        assert!(unsupported(b"\x89PNG\r\n\x1a\n\0\0", Some("image.png")));
+        assert!(unsupported(b"\0\x01\x02", Some("data.csv")));
+        assert!(matches!(
+            detect(b"PK\x03\x04 broken", None, None),
+            Err(Error::InvalidDocument(_))
+        ));
+    }
+}
@@ -0,0 +1,371 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use crate::prelude::*;
+
+use super::Converter;
+use super::Metadata;
+use super::archive::Archive;
+use super::csv::table;
+use super::markdown::Markdown;
+use super::xml::Event;
+use super::xml::events;
+use super::xml::text_of;
+
+pub struct Docx(pub(super) Vec<u8>);
+
+impl Converter<Docx> {
+    /// Headings, lists, tables, links and bold or italic text are kept,
+    /// everything else becomes plain paragraphs
+    pub fn to_md(&self) -> Result<Converter<Markdown>> {
+        let mut archive = Archive::new(&self.data.0)?;
+        let document = part(&mut archive, "word/document.xml")?.ok_or(Error::InvalidDocument(
+            "the DOCX has no word/document.xml".into(),
+        ))?;
+        let styles = style_names(&part(&mut archive, "word/styles.xml")?.unwrap_or_default());
+        let ordered = ordered_lists(&part(&mut archive, "word/numbering.xml")?.unwrap_or_default());
+        let links =
+            link_targets(&part(&mut archive, "word/_rels/document.xml.rels")?.unwrap_or_default());
+        let mut writer = Writer {
+            styles,
+            ordered,
+            links,
+            ..Writer::default()
+        };
+        for event in &document {
+            writer.handle(event);
+        }
+        Ok(Converter {
+            data: Markdown(writer.finish()),
+        })
+    }
+
+    /// The title and author from the document properties, and the pages as
+    /// Word counted them when it last saved the document
+    pub fn metadata(&self) -> Result<Metadata> {
+        let mut archive = Archive::new(&self.data.0)?;
+        let core = part(&mut archive, "docProps/core.xml")?.unwrap_or_default();
+        let app = part(&mut archive, "docProps/app.xml")?.unwrap_or_default();
+        Ok(Metadata {
+            title: text_of(&core, "title"),
+            author: text_of(&core, "creator"),
+            pages: text_of(&app, "Pages").and_then(|pages| pages.parse().ok()),
+        })
+    }
+}
+
+/// The XML of a part of the document, `None` when the document has no such
+/// part
+fn part(archive: &mut Archive, name: &str) -> Result<Option<Vec<Event>>> {
+    archive
+        .read_string(name)?
+        .map(|xml| events(&xml))
+        .transpose()
+}
+
+/// Style ids with the lowercase names they are shown with, which unlike the
+/// ids are not translated
+fn style_names(events: &[Event]) -> HashMap<String, String> {
+    let mut names = HashMap::new();
+    let mut id = None;
+    for event in events
+        .iter()
+        .filter(|event| matches!(event, Event::Start { .. }))
+    {
+        match event.local_name().unwrap_or_default() {
+            "style" => id = event.attr("styleId").map(str::to_string),
+            "name" => {
+                if let (Some(id), Some(name)) = (id.take(), event.attr("val")) {
+                    names.insert(id, name.to_lowercase());
+                }
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+/// The numbering ids and levels of numbered lists, the others have bullets
+fn ordered_lists(events: &[Event]) -> HashSet<(String, String)> {
+    let mut numbered_levels: HashMap<String, Vec<String>> = HashMap::new();
+    let mut lists = Vec::new();
+    let (mut abstract_id, mut level, mut num_id) = (None, None, None);
+    for event in events
+        .iter()
+        .filter(|event| matches!(event, Event::Start { .. }))
+    {
+        match event.local_name().unwrap_or_default() {
+            "abstractNum" => abstract_id = event.attr("abstractNumId").map(str::to_string),
+            "lvl" => level = event.attr("ilvl").map(str::to_string),
+            "numFmt" => {
+                let numbered = event
+                    .attr("val")
+                    .is_some_and(|format| !matches!(format, "bullet" | "none"));
+                if let (Some(abstract_id), Some(level), true) = (&abstract_id, &level, numbered) {
+                    numbered_levels
+                        .entry(abstract_id.clone())
+                        .or_default()
+                        .push(level.clone());
+                }
+            }
+            "num" => num_id = event.attr("numId").map(str::to_string),
+            "abstractNumId" => {
+                if let (Some(num_id), Some(abstract_id)) = (&num_id, event.attr("val")) {
+                    lists.push((num_id.clone(), abstract_id.to_string()));
+                }
+            }
+            _ => {}
+        }
+    }
+    lists
+        .into_iter()
+        .flat_map(|(num_id, abstract_id)| {
+            numbered_levels
+                .get(&abstract_id)
+                .into_iter()
+                .flatten()
+                .map(move |level| (num_id.clone(), level.clone()))
+        })
+        .collect()
+}
+
+/// Relationship ids with the URLs of the links they stand for
+fn link_targets(events: &[Event]) -> HashMap<String, String> {
+    events
+        .iter()
+        .filter(|event| event.is_start("Relationship"))
+        .filter(|event| event.attr("TargetMode") == Some("External"))
+        .filter_map(|event| Some((event.attr("Id")?.into(), event.attr("Target")?.into())))
+        .collect()
+}
+
+#[derive(Debug, Default)]
+struct Paragraph {
+    style: Option<String>,
+    /// Numbering id and level
+    list: (Option<String>, usize),
+    text: String,
+    /// Runs in a row with the same formatting, merged so the markers are not
+    /// repeated for every run
+    span: (bool, bool, String),
+    /// Where the text of the current link starts, and where it points
+    link: Option<(usize, Option<String>)>,
+}
+
+impl Paragraph {
+    fn flush_span(&mut self) {
+        let (bold, italic, text) = std::mem::take(&mut self.span);
+        let marker = match (bold, italic) {
+            (true, true) => "***",
+            (true, false) => "**",
+            (false, true) => "_",
+            (false, false) => "",
+        };
+        let core = text.trim();
+        if marker.is_empty() || core.is_empty() {
+            self.text.push_str(&text);
+            return;
+        }
+        let leading = &text[..text.len() - text.trim_start().len()];
+        let trailing = &text[text.trim_end().len()..];
+        self.text
+            .push_str(&format!("{leading}{marker}{core}{marker}{trailing}"));
+    }
+}
+
+#[derive(Debug, Default)]
+struct Writer {
+    styles: HashMap<String, String>,
+    ordered: HashSet<(String, String)>,
+    links: HashMap<String, String>,
+    /// Blocks, and whether each is a list item
+    blocks: Vec<(String, bool)>,
+    paragraph: Paragraph,
+    run: (bool, bool),
+    in_text: bool,
+    /// Tables in tables are flattened into the cells of the outermost one
+    table_depth: usize,
+    rows: Vec<Vec<String>>,
+}
+
+impl Writer {
+    fn handle(&mut self, event: &Event) {
+        match event {
+            Event::Text(text) if self.in_text => self.paragraph.span.2.push_str(text),
+            Event::Text(_) => {}
+            Event::Start { .. } => self.start(event),
+            Event::End { .. } => self.end(event),
+        }
+    }
+
+    fn start(&mut self, event: &Event) {
+        let on = || !matches!(event.attr("val"), Some("0" | "false" | "none"));
+        match event.local_name().unwrap_or_default() {
+            "p" => self.paragraph = Paragraph::default(),
+            "pStyle" => self.paragraph.style = event.attr("val").map(str::to_string),
+            "numId" => {
+                self.paragraph.list.0 = event.attr("val").filter(|id| *id != "0").map(Into::into)
+            }
+            "ilvl" => {
+                self.paragraph.list.1 = event
+                    .attr("val")
+                    .and_then(|level| level.parse().ok())
+                    .unwrap_or(0)
+            }
+            "r" => self.run = (false, false),
+            "b" => self.run.0 = on(),
+            "i" => self.run.1 = on(),
+            "t" => {
+                let (bold, italic, _) = &self.paragraph.span;
+                if (*bold, *italic) != self.run {
+                    self.paragraph.flush_span();
+                    self.paragraph.span = (self.run.0, self.run.1, String::new());
+                }
+                self.in_text = true;
+            }
+            // Tab stops of the paragraph have a position, tabs in the text do not
+            "tab" if event.attr("pos").is_none() => self.paragraph.span.2.push(' '),
+            "br" | "cr" => self.paragraph.span.2.push('\n'),
+            "hyperlink" => {
+                self.paragraph.flush_span();
+                let target = event.attr("id").and_then(|id| self.links.get(id)).cloned();
+                self.paragraph.link = Some((self.paragraph.text.len(), target));
+            }
+            "tbl" => {
+                self.table_depth += 1;
+                if self.table_depth == 1 {
+                    self.rows.clear();
+                }
+            }
+            "tr" if self.table_depth == 1 => self.rows.push(Vec::new()),
+            "tc" if self.table_depth == 1 => {
+                if let Some(row) = self.rows.last_mut() {
+                    row.push(String::new());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, event: &Event) {
+        match event.local_name().unwrap_or_default() {
+            "t" => self.in_text = false,
+            "hyperlink" => {
+                self.paragraph.flush_span();
+                if let Some((start, target)) = self.paragraph.link.take() {
+                    let text = self.paragraph.text.split_off(start);
+                    match target {
+                        Some(target) if !text.trim().is_empty() => self
+                            .paragraph
+                            .text
+                            .push_str(&format!("[{}]({target})", text.trim())),
+                        _ => self.paragraph.text.push_str(&text),
+                    }
+                }
+            }
+            "p" => self.end_paragraph(),
+            "tbl" => {
+                self.table_depth = self.table_depth.saturating_sub(1);
+                if self.table_depth == 0 {
+                    let rows = std::mem::take(&mut self.rows);
+                    self.blocks.push((table(&rows), false));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn end_paragraph(&mut self) {
+        let mut paragraph = std::mem::take(&mut self.paragraph);
+        paragraph.flush_span();
+        let text = paragraph.text.trim();
+        if text.is_empty() {
+            return;
+        }
+        if self.table_depth > 0 {
+            if let Some(cell) = self.rows.last_mut().and_then(|row| row.last_mut()) {
+                if !cell.is_empty() {
+                    cell.push('\n');
+                }
+                cell.push_str(text);
+            }
+            return;
+        }
+        let style = paragraph
+            .style
+            .map(|id| self.styles.get(&id).cloned().unwrap_or(id.to_lowercase()))
+            .unwrap_or_default();
+        let heading = match style.as_str() {
+            "title" => Some(1),
+            style => style
+                .strip_prefix("heading")
+                .and_then(|level| level.trim().parse::<usize>().ok()),
+        };
+        let block = match (heading, paragraph.list) {
+            (Some(level), _) => (format!("{} {text}", "#".repeat(level.clamp(1, 6))), false),
+            (None, (Some(num_id), level)) => {
+                let marker = match self.ordered.contains(&(num_id, level.to_string())) {
+                    true => "1.",
+                    false => "-",
+                };
+                (format!("{}{marker} {text}", "   ".repeat(level)), true)
+            }
+            (None, _) if style.contains("quote") => (format!("> {text}"), false),
+            (None, _) => (text.to_string(), false),
+        };
+        self.blocks.push(block);
+    }
+
+    fn finish(self) -> String {
+        let mut markdown = String::new();
+        let mut previous_item = false;
+        for (block, item) in self.blocks {
+            if !markdown.is_empty() {
+                markdown.push_str(if item && previous_item { "\n" } else { "\n\n" });
+            }
+            markdown.push_str(&block);
+            previous_item = item;
+        }
+        markdown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPORT: &[u8] = include_bytes!("fixtures/report.docx");
+
+    #[test]
+    fn test_to_md() {
+        let md = Converter::<Docx>::from_docx(REPORT.to_vec())
+            .to_md()
+            .unwrap()
+            .to_string();
+        assert_eq!(
+            md,
+            "# Quarterly report\n\n\
+             ## Summary\n\n\
+             Revenue grew **by 12 percent**, see _the appendix_ and [the dashboard](https://example.com/dash).\n\n\
+             - Hiring\n   - Two engineers\n1. First step\n1. Second step\n\n\
+             > Numbers are preliminary.\n\n\
+             | Region | Revenue |\n| --- | --- |\n| North | 1.2M |\n| South | 0.8M<br>estimated |"
+        );
+    }
+
+    #[test]
+    fn test_metadata() {
+        let metadata = Converter::<Docx>::from_docx(REPORT.to_vec())
+            .metadata()
+            .unwrap();
+        assert_eq!(metadata.title.as_deref(), Some("Q3 Report"));
+        assert_eq!(metadata.author.as_deref(), Some("Ada Lovelace"));
+        assert_eq!(metadata.pages, Some(2));
+    }
+
+    #[test]
+    fn test_not_a_docx() {
+        let result = Converter::<Docx>::from_docx(b"PK\x03\x04 truncated".to_vec()).to_md();
+        assert!(matches!(result, Err(Error::InvalidDocument(_))));
+    }
+}
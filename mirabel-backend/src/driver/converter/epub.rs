@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+
+use scraper::Selector;
+
+use crate::prelude::*;
+
+use super::Converter;
+use super::Metadata;
+use super::archive::Archive;
+use super::html::Html;
+use super::xml::Event;
+use super::xml::events;
+use super::xml::text_of;
+
+pub struct Epub(pub(super) Vec<u8>);
+
+impl Converter<Epub> {
+    /// The chapters in reading order, each with the body of its page
+    pub fn to_html(&self) -> Result<Converter<Html>> {
+        let mut archive = Archive::new(&self.data.0)?;
+        let (path, package) = package(&mut archive)?;
+        let items: HashMap<&str, (&str, &str)> = package
+            .iter()
+            .filter(|event| event.is_start("item"))
+            .filter_map(|event| {
+                Some((
+                    event.attr("id")?,
+                    (event.attr("href")?, event.attr("media-type")?),
+                ))
+            })
+            .collect();
+        let body = Selector::parse("body").unwrap();
+        let mut chapters = Vec::new();
+        for idref in package
+            .iter()
+            .filter(|event| event.is_start("itemref"))
+            .filter_map(|event| event.attr("idref"))
+        {
+            let Some((href, media_type)) = items.get(idref) else {
+                continue;
+            };
+            if !matches!(*media_type, "application/xhtml+xml" | "text/html") {
+                continue;
+            }
+            let Some(page) = archive.read_string(&resolve(&path, href))? else {
+                continue;
+            };
+            let page = scraper::Html::parse_document(&page);
+            if let Some(body) = page.select(&body).next() {
+                chapters.push(body.inner_html().trim().to_string());
+            }
+        }
+        Ok(Converter {
+            data: Html(chapters.join("\n")),
+        })
+    }
+
+    pub fn metadata(&self) -> Result<Metadata> {
+        let mut archive = Archive::new(&self.data.0)?;
+        let (_, package) = package(&mut archive)?;
+        Ok(Metadata {
+            title: text_of(&package, "title"),
+            author: text_of(&package, "creator"),
+            pages: None,
+        })
+    }
+}
+
+/// The path of the package document, which lists the chapters, and its
+/// content
+fn package(archive: &mut Archive) -> Result<(String, Vec<Event>)> {
+    let container =
+        archive
+            .read_string("META-INF/container.xml")?
+            .ok_or(Error::InvalidDocument(
+                "the EPUB has no META-INF/container.xml".into(),
+            ))?;
+    let path = events(&container)?
+        .iter()
+        .find(|event| event.is_start("rootfile"))
+        .and_then(|event| event.attr("full-path"))
+        .map(str::to_string)
+        .ok_or(Error::InvalidDocument(
+            "the EPUB does not say where its package is".into(),
+        ))?;
+    let package = archive
+        .read_string(&path)?
+        .ok_or(Error::InvalidDocument(format!("the EPUB has no {path}")))?;
+    Ok((path, events(&package)?))
+}
+
+/// The path in the archive a link of the package points to
+fn resolve(package: &str, href: &str) -> String {
+    let href = percent_decode(href.split('#').next().unwrap_or_default());
+    let mut parts: Vec<&str> = package.split('/').collect();
+    parts.pop();
+    for part in href.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = text
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match hex {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOOK: &[u8] = include_bytes!("fixtures/book.epub");
+
+    #[test]
+    fn test_to_html() {
+        let html = Converter::<Epub>::from_epub(BOOK.to_vec())
+            .to_html()
+            .unwrap()
+            .to_string();
+        assert_eq!(
+            html,
+            "<h1>Ownership</h1><p>Every value has an <em>owner</em>.</p>\n\
+             <h1>Borrowing</h1><p>References borrow values.</p><ul><li>Shared</li><li>Mutable</li></ul>"
+        );
+    }
+
+    #[test]
+    fn test_metadata() {
+        let metadata = Converter::<Epub>::from_epub(BOOK.to_vec())
+            .metadata()
+            .unwrap();
+        assert_eq!(
+            metadata.title.as_deref(),
+            Some("The Little Book of Ownership")
+        );
+        assert_eq!(metadata.author.as_deref(), Some("Ferris Crab"));
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(
+            resolve("OEBPS/content.opf", "text/chapter%201.xhtml#start"),
+            "OEBPS/text/chapter 1.xhtml"
+        );
+        assert_eq!(resolve("content.opf", "../x/./y.xhtml"), "x/y.xhtml");
+    }
+}
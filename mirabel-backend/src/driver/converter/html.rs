@@ -2,7 +2,10 @@ use crate::prelude::*;
 
 use std::fmt::Display;
 
+use scraper::Selector;
+
 use super::Converter;
+use super::Metadata;
 use super::markdown::Markdown;
 
 pub struct Html(pub(super) String);
@@ -13,6 +16,27 @@ impl Converter<Html> {
             data: Markdown(html2md::rewrite_html(&self.data.0, false)),
         })
     }
+
+    /// The title and author the page names in its head
+    pub fn metadata(&self) -> Metadata {
+        let document = scraper::Html::parse_document(&self.data.0);
+        let first = |selector: &str, attr: Option<&str>| {
+            let selector = Selector::parse(selector).unwrap();
+            document
+                .select(&selector)
+                .filter_map(|element| match attr {
+                    Some(attr) => element.attr(attr).map(str::to_string),
+                    None => Some(element.text().collect()),
+                })
+                .map(|text| text.split_whitespace().collect::<Vec<_>>().join(" "))
+                .find(|text| !text.is_empty())
+        };
+        Metadata {
+            title: first("title", None).or_else(|| first("h1", None)),
+            author: first(r#"meta[name="author"]"#, Some("content")),
+            pages: None,
+        }
+    }
 }
 
 impl Display for Converter<Html> {
@@ -20,3 +44,20 @@ impl Display for Converter<Html> {
         write!(f, "{}", self.data.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metadata() {
+        let html = Converter::<Html>::from_html(
+            r#"<html><head><title> Pinning
+            futures </title><meta name="author" content="Ferris"></head></html>"#
+                .into(),
+        );
+        let metadata = html.metadata();
+        assert_eq!(metadata.title.as_deref(), Some("Pinning futures"));
+        assert_eq!(metadata.author.as_deref(), Some("Ferris"));
+    }
+}
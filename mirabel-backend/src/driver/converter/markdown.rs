@@ -1,9 +1,153 @@
 use std::fmt::Display;
 
+use pulldown_cmark::CowStr;
+use pulldown_cmark::Event;
+use pulldown_cmark::Options;
+use pulldown_cmark::Parser;
+use pulldown_cmark::Tag;
+
+use super::Converter;
+use super::Metadata;
+use super::html::Html;
+use super::pdf::opens_page_anchor;
+
 pub struct Markdown(pub(super) String);
 
+impl Converter<Markdown> {
+    /// Renders the markdown, with tables, strikethrough and task lists as
+    /// GitHub has them. The markdown is not trusted, so raw HTML is shown as
+    /// text and links cannot run scripts. Only the page anchors of PDFs are
+    /// kept, so pages can still be linked to.
+    pub fn to_html(&self) -> Converter<Html> {
+        let options =
+            Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+        let mut anchor = false;
+        let events = Parser::new_ext(&self.data.0, options).map(move |event| match event {
+            Event::InlineHtml(html) if opens_page_anchor(&html) => {
+                anchor = true;
+                Event::InlineHtml(html)
+            }
+            Event::InlineHtml(html) if anchor && &*html == "</a>" => {
+                anchor = false;
+                Event::InlineHtml(html)
+            }
+            Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+            Event::Start(Tag::Link {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) => Event::Start(Tag::Link {
+                link_type,
+                dest_url: safe_url(dest_url),
+                title,
+                id,
+            }),
+            Event::Start(Tag::Image {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) => Event::Start(Tag::Image {
+                link_type,
+                dest_url: safe_url(dest_url),
+                title,
+                id,
+            }),
+            event => event,
+        });
+        let mut html = String::new();
+        pulldown_cmark::html::push_html(&mut html, events);
+        Converter { data: Html(html) }
+    }
+
+    /// The first top level heading is the title, headings in code blocks
+    /// do not count
+    pub fn metadata(&self) -> Metadata {
+        let mut fenced = false;
+        let title = self
+            .data
+            .0
+            .lines()
+            .filter(|line| {
+                if line.trim_start().starts_with("```") || line.trim_start().starts_with("~~~") {
+                    fenced = !fenced;
+                    return false;
+                }
+                !fenced
+            })
+            .find_map(|line| line.strip_prefix("# "))
+            .map(|title| title.trim().to_string());
+        Metadata {
+            title,
+            ..Metadata::default()
+        }
+    }
+}
+
+/// Drops URLs whose scheme runs code when followed
+fn safe_url(url: CowStr<'_>) -> CowStr<'_> {
+    let scheme: String = url
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace() && !c.is_ascii_control())
+        .take_while(|c| *c != ':')
+        .collect::<String>()
+        .to_ascii_lowercase();
+    match scheme.as_str() {
+        "javascript" | "vbscript" | "data" if url.contains(':') => CowStr::Borrowed(""),
+        _ => url,
+    }
+}
+
 impl Display for Markdown {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.0)
     }
 }
+
+impl Display for Converter<Markdown> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.data.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MARKDOWN: &str = "# Guide\n\nSome `code` and ~~old~~ text.\n\n```md\n# Not a title\n```\n\n| a | b |\n| --- | --- |\n| 1 | 2 |\n";
+
+    #[test]
+    fn test_to_html() {
+        let html = Converter::<Markdown>::from_md(MARKDOWN.into())
+            .to_html()
+            .to_string();
+        assert!(
+            html.starts_with(
+                "<h1>Guide</h1>\n<p>Some <code>code</code> and <del>old</del> text.</p>"
+            )
+        );
+        assert!(html.contains("<code class=\"language-md\"># Not a title\n</code>"));
+        assert!(html.contains("<td>1</td>"));
+    }
+
+    #[test]
+    fn test_to_html_escapes_html() {
+        let markdown = "<script>alert(1)</script>\n\nHi <img src=x onerror=alert(1)> \
+                        [link](javascript:alert(1)) [ok](https://example.com)\n";
+        let html = Converter::<Markdown>::from_md(markdown.into())
+            .to_html()
+            .to_string();
+        assert!(html.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+        assert!(html.contains("&lt;img src=x onerror=alert(1)&gt;"));
+        assert!(!html.contains("<script") && !html.contains("<img"));
+        assert!(html.contains("<a href=\"\">link</a>"));
+        assert!(html.contains("<a href=\"https://example.com\">ok</a>"));
+    }
+
+    #[test]
+    fn test_metadata() {
+        let markdown = Converter::<Markdown>::from_md("```\n# Not a title\n```\n# Guide\n".into());
+        assert_eq!(markdown.metadata().title.as_deref(), Some("Guide"));
+    }
+}
@@ -1,10 +1,27 @@
+use crate::prelude::*;
+
+use code::Code;
+use csv::Csv;
+use detect::Format;
+use docx::Docx;
+use epub::Epub;
 use html::Html;
 use markdown::Markdown;
 use pdf::Pdf;
+use text::Text;
 
+mod archive;
+pub mod chunk;
+pub mod code;
+pub mod csv;
+pub mod detect;
+pub mod docx;
+pub mod epub;
 pub mod html;
 pub mod markdown;
 pub mod pdf;
+pub mod text;
+mod xml;
 
 pub struct Converter<T> {
     data: T,
@@ -22,4 +39,122 @@ impl<T> Converter<T> {
     pub fn from_md(md: String) -> Converter<Markdown> {
         Converter { data: Markdown(md) }
     }
+
+    pub fn from_text(text: String) -> Converter<Text> {
+        Converter { data: Text(text) }
+    }
+
+    pub fn from_csv(csv: String) -> Converter<Csv> {
+        Converter { data: Csv(csv) }
+    }
+
+    pub fn from_code(text: String, language: String) -> Converter<Code> {
+        Converter {
+            data: Code { text, language },
+        }
+    }
+
+    pub fn from_docx(docx: Vec<u8>) -> Converter<Docx> {
+        Converter { data: Docx(docx) }
+    }
+
+    pub fn from_epub(epub: Vec<u8>) -> Converter<Epub> {
+        Converter { data: Epub(epub) }
+    }
+}
+
+/// What a document says about itself, as far as its format tells
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub pages: Option<usize>,
+}
+
+/// A document of any supported format, converted to markdown
+pub struct Document {
+    pub format: Format,
+    pub metadata: Metadata,
+    pub markdown: Converter<Markdown>,
+}
+
+/// Converts a document to markdown along the pipeline of its format. The
+/// format is told by the content first, then by the MIME type and the file
+/// name it came with.
+pub fn convert(data: Vec<u8>, name: Option<&str>, mime: Option<&str>) -> Result<Document> {
+    let format = detect::detect(&data, name, mime)?;
+    let (metadata, markdown) = match &format {
+        Format::Pdf => {
+            let pdf = Converter::<Pdf>::from_pdf(data);
+            (pdf.metadata()?, pdf.to_md()?)
+        }
+        Format::Docx => {
+            let docx = Converter::<Docx>::from_docx(data);
+            (docx.metadata()?, docx.to_md()?)
+        }
+        Format::Epub => {
+            let epub = Converter::<Epub>::from_epub(data);
+            (epub.metadata()?, epub.to_html()?.to_md()?)
+        }
+        Format::Html => {
+            let html = Converter::<Html>::from_html(text(data));
+            (html.metadata(), html.to_md()?)
+        }
+        Format::Markdown => {
+            let markdown = Converter::<Markdown>::from_md(text(data));
+            (markdown.metadata(), markdown)
+        }
+        Format::Csv => (
+            Metadata::default(),
+            Converter::<Csv>::from_csv(text(data)).to_md(),
+        ),
+        Format::Code { language } => (
+            Metadata::default(),
+            Converter::<Code>::from_code(text(data), language.clone()).to_md(),
+        ),
+        Format::Text => (
+            Metadata::default(),
+            Converter::<Text>::from_text(text(data)).to_md(),
+        ),
+    };
+    Ok(Document {
+        format,
+        metadata,
+        markdown,
+    })
+}
+
+fn text(data: Vec<u8>) -> String {
+    String::from_utf8(data)
+        .unwrap_or_else(|err| String::from_utf8_lossy(err.as_bytes()).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert() {
+        let document = convert(
+            include_bytes!("fixtures/report.docx").to_vec(),
+            Some("report.bin"),
+            Some("application/octet-stream"),
+        )
+        .unwrap();
+        assert_eq!(document.format, Format::Docx);
+        assert_eq!(document.metadata.pages, Some(2));
+        assert!(
+            document
+                .markdown
+                .to_string()
+                .starts_with("# Quarterly report")
+        );
+
+        let document = convert(b"# Notes\n\nText".to_vec(), Some("notes.md"), None).unwrap();
+        assert_eq!(document.format, Format::Markdown);
+        assert_eq!(document.metadata.title.as_deref(), Some("Notes"));
+
+        let document = convert(b"fn main() {}".to_vec(), Some("main.rs"), None).unwrap();
+        assert_eq!(document.markdown.to_string(), "```rust\nfn main() {}\n```");
+    }
 }
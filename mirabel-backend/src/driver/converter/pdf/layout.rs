@@ -0,0 +1,349 @@
+use std::collections::HashMap;
+
+use super::super::csv::table;
+use super::text::Span;
+
+/// Spans this far apart, in font sizes, are separate words
+pub(super) const WORD_GAP: f32 = 0.15;
+/// Spans this far apart, in font sizes, are cells of a table
+const CELL_GAP: f32 = 1.5;
+/// Lines further apart than this, in font sizes, are separate paragraphs
+const PARAGRAPH_GAP: f32 = 1.7;
+/// Text this much larger than the body text is a heading
+const HEADING_RATIO: f32 = 1.15;
+/// Bold lines longer than this are emphasis rather than headings
+const MAX_HEADING_CHARS: usize = 80;
+/// Markers of unordered list items
+const BULLETS: [char; 9] = ['•', '◦', '▪', '‣', '●', '○', '■', '·', '❖'];
+
+/// The spans on one baseline, cut into cells where they are far apart
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Line {
+    pub x: f32,
+    pub y: f32,
+    pub size: f32,
+    pub cells: Vec<String>,
+    pub bold: bool,
+}
+
+impl Line {
+    fn text(&self) -> String {
+        self.cells.join(" ")
+    }
+
+    fn chars(&self) -> usize {
+        self.cells.iter().map(|cell| cell.chars().count()).sum()
+    }
+}
+
+/// Groups spans in the order they are drawn into lines, a span leaving the
+/// baseline starts the next line
+pub(super) fn lines(spans: Vec<Span>) -> Vec<Line> {
+    let mut groups: Vec<Vec<Span>> = Vec::new();
+    for span in spans {
+        match groups.last_mut() {
+            Some(group) if (group[0].y - span.y).abs() < 0.4 * group[0].size.min(span.size) => {
+                group.push(span)
+            }
+            _ => groups.push(vec![span]),
+        }
+    }
+    groups.into_iter().filter_map(line).collect()
+}
+
+fn line(mut spans: Vec<Span>) -> Option<Line> {
+    spans.sort_by(|a, b| a.x.total_cmp(&b.x));
+    let size = spans.iter().map(|span| span.size).fold(0.0, f32::max);
+    let mut cells: Vec<String> = Vec::new();
+    let mut end = f32::MIN;
+    for span in &spans {
+        let gap = span.x - end;
+        let text: String = span
+            .text
+            .chars()
+            .map(|c| if c.is_control() { ' ' } else { c })
+            .collect();
+        match cells.last_mut() {
+            Some(cell) if gap < CELL_GAP * size => {
+                if gap >= WORD_GAP * size {
+                    cell.push(' ');
+                }
+                cell.push_str(&text);
+            }
+            _ => cells.push(text),
+        }
+        end = end.max(span.end());
+    }
+    let cells: Vec<String> = cells
+        .iter()
+        .map(|cell| cell.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|cell| !cell.is_empty())
+        .collect();
+    let first = spans.first()?;
+    (!cells.is_empty()).then(|| Line {
+        x: first.x,
+        y: first.y,
+        size,
+        cells,
+        bold: spans.iter().all(|span| span.bold),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Block {
+    Heading(usize, String),
+    /// A list item and where its marker is
+    Item(f32, String),
+    Paragraph(String),
+    Table(Vec<Vec<String>>),
+}
+
+/// What the text of a whole document tells about how its pages are laid out
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Layout {
+    /// The size most of the text is set in, in half points
+    body: i32,
+    /// Sizes of headings in half points, the largest first
+    headings: Vec<i32>,
+}
+
+/// Sizes are compared in half points, which differences in rounding do not
+/// tell apart
+fn half_points(size: f32) -> i32 {
+    (size * 2.0).round() as i32
+}
+
+impl Layout {
+    pub fn new<'a>(lines: impl IntoIterator<Item = &'a Line>) -> Layout {
+        let mut sizes: HashMap<i32, usize> = HashMap::new();
+        for line in lines {
+            *sizes.entry(half_points(line.size)).or_default() += line.chars();
+        }
+        let body = sizes
+            .iter()
+            .max_by_key(|(size, chars)| (**chars, -**size))
+            .map(|(size, _)| *size)
+            .unwrap_or_default();
+        let mut headings: Vec<i32> = sizes
+            .into_keys()
+            .filter(|size| *size as f32 >= body as f32 * HEADING_RATIO)
+            .collect();
+        headings.sort_unstable_by(|a, b| b.cmp(a));
+        Layout { body, headings }
+    }
+
+    /// The level of a heading, set larger than the body text or on its own
+    /// line in bold
+    fn heading(&self, line: &Line) -> Option<usize> {
+        let text = line.text();
+        if !text.chars().any(char::is_alphabetic) {
+            return None;
+        }
+        let size = half_points(line.size);
+        if let Some(level) = self.headings.iter().position(|heading| *heading == size) {
+            return Some((level + 1).min(6));
+        }
+        let bold = line.bold
+            && size == self.body
+            && line.cells.len() == 1
+            && text.chars().count() <= MAX_HEADING_CHARS
+            && !text.ends_with(['.', ',', ':', ';'])
+            && item(line).is_none();
+        bold.then(|| (self.headings.len() + 1).min(6))
+    }
+
+    /// The markdown of a page. Page numbers at the top or bottom are left
+    /// out.
+    pub fn markdown(&self, lines: &[Line]) -> String {
+        let top = lines.iter().map(|line| line.y).fold(f32::MIN, f32::max);
+        let bottom = lines.iter().map(|line| line.y).fold(f32::MAX, f32::min);
+        let lines: Vec<&Line> = lines
+            .iter()
+            .filter(|line| !(page_number(line) && (line.y == top || line.y == bottom)))
+            .collect();
+
+        let mut blocks: Vec<Block> = Vec::new();
+        let mut previous: Option<&Line> = None;
+        let mut i = 0;
+        while i < lines.len() {
+            let line = lines[i];
+            // Lines in a row with as many cells are rows of a table
+            let plain = |row: &Line| item(row).is_none() && self.heading(row).is_none();
+            let width = line.cells.len();
+            if width >= 2 && plain(line) {
+                let rows: Vec<Vec<String>> = lines[i..]
+                    .iter()
+                    .take_while(|row| row.cells.len() == width && plain(row))
+                    .map(|row| row.cells.clone())
+                    .collect();
+                if rows.len() >= 2 {
+                    i += rows.len();
+                    previous = Some(lines[i - 1]);
+                    blocks.push(Block::Table(rows));
+                    continue;
+                }
+            }
+
+            let near = previous.is_some_and(|previous| {
+                let gap = previous.y - line.y;
+                gap > 0.0
+                    && gap < PARAGRAPH_GAP * previous.size.max(line.size)
+                    && half_points(previous.size) == half_points(line.size)
+            });
+            match (self.heading(line), item(line), blocks.last_mut()) {
+                (Some(level), _, Some(Block::Heading(previous, text)))
+                    if near && *previous == level =>
+                {
+                    join(text, &line.text())
+                }
+                (Some(level), _, _) => blocks.push(Block::Heading(level, line.text())),
+                (None, Some(item), _) => blocks.push(Block::Item(line.x, item)),
+                // Lines indented past the marker go on with the item
+                (None, None, Some(Block::Item(x, text)))
+                    if near && line.x > *x + WORD_GAP * line.size =>
+                {
+                    join(text, &line.text())
+                }
+                (None, None, Some(Block::Paragraph(text))) if near => join(text, &line.text()),
+                (None, None, _) => blocks.push(Block::Paragraph(line.text())),
+            }
+            previous = Some(line);
+            i += 1;
+        }
+
+        let mut markdown = String::new();
+        let mut previous_item = false;
+        for block in blocks {
+            let item = matches!(block, Block::Item(..));
+            if !markdown.is_empty() {
+                markdown.push_str(if item && previous_item { "\n" } else { "\n\n" });
+            }
+            match block {
+                Block::Heading(level, text) => {
+                    markdown.push_str(&format!("{} {text}", "#".repeat(level)))
+                }
+                Block::Item(_, text) | Block::Paragraph(text) => markdown.push_str(&text),
+                Block::Table(rows) => markdown.push_str(&table(&rows)),
+            }
+            previous_item = item;
+        }
+        markdown
+    }
+}
+
+/// The line as a markdown list item, when it starts with a bullet or a
+/// number
+fn item(line: &Line) -> Option<String> {
+    let text = line.text();
+    if let Some(rest) = text.strip_prefix(BULLETS) {
+        return Some(format!("- {}", rest.trim_start()));
+    }
+    let (marker, rest) = text.split_once(' ')?;
+    match marker {
+        "-" | "–" | "*" => Some(format!("- {rest}")),
+        marker => {
+            let number = marker.strip_suffix(['.', ')'])?;
+            let numbered =
+                (1..=3).contains(&number.len()) && number.chars().all(|c| c.is_ascii_digit());
+            numbered.then(|| format!("{number}. {rest}"))
+        }
+    }
+}
+
+/// Words broken with a hyphen at the end of a line are joined again
+fn join(text: &mut String, line: &str) {
+    let broken = text
+        .strip_suffix('-')
+        .and_then(|text| text.chars().last())
+        .is_some_and(char::is_alphabetic)
+        && line.starts_with(char::is_lowercase);
+    match broken {
+        true => {
+            text.pop();
+        }
+        false => text.push(' '),
+    }
+    text.push_str(line);
+}
+
+/// `12`, `Page 12` or `12 of 30`
+fn page_number(line: &Line) -> bool {
+    let text = line.text().to_lowercase();
+    let mut words = text
+        .trim_start_matches("page")
+        .split([' ', '/', '-', '–'])
+        .filter(|word| !word.is_empty() && *word != "of")
+        .peekable();
+    words.peek().is_some() && words.all(|word| word.chars().all(|c| c.is_ascii_digit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(y: f32, size: f32, cells: &[&str]) -> Line {
+        Line {
+            x: 72.0,
+            y,
+            size,
+            cells: cells.iter().map(|cell| cell.to_string()).collect(),
+            bold: false,
+        }
+    }
+
+    #[test]
+    fn test_lines() {
+        let span = |x: f32, y: f32, width: f32, text: &str| Span {
+            x,
+            y,
+            size: 10.0,
+            width,
+            text: text.into(),
+            bold: false,
+        };
+        let lines = lines(vec![
+            span(72.0, 700.0, 20.0, "Hello"),
+            span(94.0, 700.5, 20.0, "world"),
+            span(200.0, 700.0, 10.0, "42"),
+            span(72.0, 688.0, 30.0, "Next \u{0}line"),
+        ]);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].cells, ["Hello world", "42"]);
+        assert_eq!(lines[1].cells, ["Next line"]);
+    }
+
+    #[test]
+    fn test_markdown() {
+        let mut lines = vec![
+            line(750.0, 10.0, &["3"]),
+            line(700.0, 20.0, &["Results"]),
+            line(670.0, 10.0, &["Sales went up, as every-"]),
+            line(658.0, 10.0, &["one expected."]),
+            line(630.0, 10.0, &["Details"]),
+            line(610.0, 10.0, &["Quarter", "Sales"]),
+            line(598.0, 10.0, &["Q1", "10"]),
+            line(586.0, 10.0, &["Q2", "12"]),
+            line(560.0, 10.0, &["• First"]),
+            line(548.0, 10.0, &["2) Second"]),
+        ];
+        lines[4].bold = true;
+        let layout = Layout::new(&lines);
+        assert_eq!(
+            layout.markdown(&lines),
+            "# Results\n\n\
+             Sales went up, as everyone expected.\n\n\
+             ## Details\n\n\
+             | Quarter | Sales |\n| --- | --- |\n| Q1 | 10 |\n| Q2 | 12 |\n\n\
+             - First\n2. Second"
+        );
+    }
+
+    #[test]
+    fn test_page_number() {
+        assert!(page_number(&line(0.0, 10.0, &["12"])));
+        assert!(page_number(&line(0.0, 10.0, &["Page 3 of 10"])));
+        assert!(page_number(&line(0.0, 10.0, &["- 4 -"])));
+        assert!(!page_number(&line(0.0, 10.0, &["Page three"])));
+        assert!(!page_number(&line(0.0, 10.0, &["2024 results"])));
+    }
+}
@@ -0,0 +1,240 @@
+use crate::prelude::*;
+
+use std::io::BufWriter;
+use std::io::Write;
+
+use log::warn;
+use lopdf::Object;
+use pdf_extract::Document;
+use pdf_extract::HTMLOutput;
+use thiserror::Error;
+
+use super::Converter;
+use super::Metadata;
+use super::html::Html;
+use super::markdown::Markdown;
+use layout::Layout;
+use layout::Line;
+
+mod layout;
+mod text;
+
+/// Why no text can be taken from a PDF
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum PdfProblem {
+    #[error("it is locked with a password")]
+    Encrypted,
+    #[error("its pages are images, the text would have to be recognized")]
+    Scanned,
+    #[error("it has no text")]
+    NoText,
+}
+
+pub struct Pdf(pub(super) Vec<u8>);
+
+impl Converter<Pdf> {
+    pub fn to_html(&self) -> Result<Converter<Html>> {
+        let doc = Document::load_mem(&self.data.0)?;
+        let mut buffer = Vec::new();
+        {
+            let mut writer = BufWriter::new(&mut buffer);
+            let mut output = HTMLOutput::new(&mut writer);
+            pdf_extract::output_doc(&doc, &mut output)?;
+            writer.flush()?;
+        }
+        Ok(Converter {
+            data: Html(String::from_utf8(buffer)?),
+        })
+    }
+
+    /// Headings, lists and tables as the layout of the text shows them. Each
+    /// page starts with its [`page_anchor`], so any part of the markdown can
+    /// be traced back to its page with [`page_starts`] and [`page_at`].
+    pub fn to_md(&self) -> Result<Converter<Markdown>> {
+        let mut doc = Document::load_mem(&self.data.0)?;
+        let mut pages = read(&doc);
+        // Documents that open without a password are encrypted all the same
+        if no_text(&pages) && doc.is_encrypted() {
+            doc.decrypt("")
+                .map_err(|_| Error::UnreadablePdf(PdfProblem::Encrypted))?;
+            pages = read(&doc);
+        }
+        if no_text(&pages) {
+            let scanned = doc.get_pages().into_values().any(|page_id| {
+                doc.get_page_images(page_id)
+                    .is_ok_and(|images| !images.is_empty())
+            });
+            return Err(Error::UnreadablePdf(match scanned {
+                true => PdfProblem::Scanned,
+                false => PdfProblem::NoText,
+            }));
+        }
+
+        let layout = Layout::new(pages.iter().flat_map(|(_, lines)| lines));
+        let markdown: Vec<String> = pages
+            .iter()
+            .map(|(number, lines)| {
+                let text = layout.markdown(lines);
+                match text.is_empty() {
+                    true => page_anchor(*number),
+                    false => format!("{}\n\n{text}", page_anchor(*number)),
+                }
+            })
+            .collect();
+        Ok(Converter {
+            data: Markdown(markdown.join("\n\n")),
+        })
+    }
+
+    /// The pages, and the title and author from the document information
+    pub fn metadata(&self) -> Result<Metadata> {
+        let doc = Document::load_mem(&self.data.0)?;
+        let info = doc
+            .trailer
+            .get(b"Info")
+            .and_then(Object::as_reference)
+            .and_then(|id| doc.get_object(id))
+            .and_then(Object::as_dict)
+            .ok();
+        let entry = |key: &[u8]| {
+            let value = info?.get(key).ok()?;
+            let text = lopdf::decode_text_string(value).ok()?;
+            let text = text.trim();
+            (!text.is_empty()).then(|| text.to_string())
+        };
+        Ok(Metadata {
+            title: entry(b"Title"),
+            author: entry(b"Author"),
+            pages: Some(doc.get_pages().len()),
+        })
+    }
+}
+
+/// The lines of every page with its number. Pages that cannot be read are
+/// left out, so one broken page does not cost the rest of the document.
+fn read(doc: &Document) -> Vec<(u32, Vec<Line>)> {
+    doc.get_pages()
+        .into_iter()
+        .filter_map(|(number, page_id)| match text::spans(doc, page_id) {
+            Ok(spans) => Some((number, layout::lines(spans))),
+            Err(err) => {
+                warn!("Could not read page {number} of a PDF: {err}");
+                None
+            }
+        })
+        .collect()
+}
+
+fn no_text(pages: &[(u32, Vec<Line>)]) -> bool {
+    pages.iter().all(|(_, lines)| lines.is_empty())
+}
+
+/// Marks where a page starts in the markdown of a PDF, linkable as
+/// `#page-{number}`
+pub fn page_anchor(number: u32) -> String {
+    format!(r#"<a id="page-{number}"></a>"#)
+}
+
+/// Whether raw HTML is the opening tag of a [`page_anchor`]
+pub(crate) fn opens_page_anchor(html: &str) -> bool {
+    html.strip_prefix(r#"<a id="page-"#)
+        .and_then(|rest| rest.strip_suffix(r#"">"#))
+        .is_some_and(|number| number.parse::<u32>().is_ok())
+}
+
+/// Where the pages start in the markdown of a PDF: the byte offset of each
+/// [`page_anchor`] and its page, in order
+pub fn page_starts(markdown: &str) -> Vec<(usize, u32)> {
+    let prefix = r#"<a id="page-"#;
+    markdown
+        .match_indices(prefix)
+        .filter_map(|(offset, _)| {
+            let digits = markdown[offset + prefix.len()..].split('"').next()?;
+            match digits.chars().all(|c| c.is_ascii_digit()) {
+                true => Some((offset, digits.parse().ok()?)),
+                false => None,
+            }
+        })
+        .collect()
+}
+
+/// The page the text at a byte offset is on, the one that started last
+/// before it
+pub fn page_at(pages: &[(usize, u32)], offset: usize) -> Option<u32> {
+    let started = pages.partition_point(|(start, _)| *start < offset);
+    started.checked_sub(1).map(|last| pages[last].1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAPER: &[u8] = include_bytes!("../fixtures/paper.pdf");
+
+    #[test]
+    fn test_to_md() {
+        let md = Converter::<Pdf>::from_pdf(PAPER.to_vec())
+            .to_md()
+            .unwrap()
+            .to_string();
+        assert_eq!(
+            md,
+            "<a id=\"page-1\"></a>\n\n\
+             # Annual Report\n\n\
+             ## 1 Introduction\n\n\
+             The company grew steadily over the last year and hired many new engineers across all of its offices.\n\n\
+             ### Highlights\n\n\
+             - Revenue rose\n- Costs fell\n1. Plan\n2. Build\n\n\
+             <a id=\"page-2\"></a>\n\n\
+             | Region | Revenue | Growth |\n| --- | --- | --- |\n| North | 1.2M | 12% |\n| South | 0.8M | 5% |\n\n\
+             Kerned words are read as words."
+        );
+        let pages = page_starts(&md);
+        assert_eq!(pages, [(0, 1), (md.find("<a id=\"page-2").unwrap(), 2)]);
+        assert_eq!(page_at(&pages, md.find("Plan").unwrap()), Some(1));
+        assert_eq!(page_at(&pages, md.find("North").unwrap()), Some(2));
+        assert_eq!(page_at(&pages, 0), None);
+    }
+
+    #[test]
+    fn test_page_anchors_in_html() {
+        let html = Converter::<Pdf>::from_pdf(PAPER.to_vec())
+            .to_md()
+            .unwrap()
+            .to_html()
+            .to_string();
+        assert!(html.starts_with("<p><a id=\"page-1\"></a></p>\n<h1>Annual Report</h1>"));
+        assert!(html.contains("<p><a id=\"page-2\"></a></p>"));
+        assert!(!html.contains("&lt;"));
+
+        assert!(opens_page_anchor(r#"<a id="page-12">"#));
+        assert!(!opens_page_anchor(r#"<a id="page-1" onclick="alert(1)">"#));
+        assert!(!opens_page_anchor(r#"<a id="page-">"#));
+    }
+
+    #[test]
+    fn test_metadata() {
+        let metadata = Converter::<Pdf>::from_pdf(PAPER.to_vec())
+            .metadata()
+            .unwrap();
+        assert_eq!(metadata.title.as_deref(), Some("Annual Report"));
+        assert_eq!(metadata.author.as_deref(), Some("Ada Lovelace"));
+        assert_eq!(metadata.pages, Some(2));
+    }
+
+    #[test]
+    fn test_unreadable() {
+        let problem = |pdf: &[u8]| match Converter::<Pdf>::from_pdf(pdf.to_vec()).to_md() {
+            Err(Error::UnreadablePdf(problem)) => Some(problem),
+            _ => None,
+        };
+        assert_eq!(
+            problem(include_bytes!("../fixtures/locked.pdf")),
+            Some(PdfProblem::Encrypted)
+        );
+        assert_eq!(
+            problem(include_bytes!("../fixtures/scanned.pdf")),
+            Some(PdfProblem::Scanned)
+        );
+    }
+}
@@ -0,0 +1,487 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+
+use lopdf::Dictionary;
+use lopdf::Document;
+use lopdf::Encoding;
+use lopdf::Object;
+use lopdf::ObjectId;
+use lopdf::content::Content;
+use lopdf::content::Operation;
+
+use crate::prelude::*;
+
+use super::layout::WORD_GAP;
+
+/// Forms drawn by forms are followed this deep
+const MAX_FORM_DEPTH: usize = 4;
+
+/// Glyph width when a font does not tell, in thousandths of its size
+const DEFAULT_WIDTH: f32 = 500.0;
+
+/// Text shown in one go, in the coordinates of the page
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Span {
+    pub x: f32,
+    /// The baseline, counted from the bottom of the page
+    pub y: f32,
+    pub size: f32,
+    pub width: f32,
+    pub text: String,
+    pub bold: bool,
+}
+
+impl Span {
+    pub fn end(&self) -> f32 {
+        self.x + self.width
+    }
+}
+
+/// The text of a page in the order it is drawn, with runs that touch merged
+pub(super) fn spans(doc: &Document, page_id: ObjectId) -> Result<Vec<Span>> {
+    let content = doc.get_and_decode_page_content(page_id)?;
+    let fonts = doc
+        .get_page_fonts(page_id)?
+        .into_iter()
+        .map(|(name, font)| (name, Font::new(doc, font)))
+        .collect();
+    let (resources, inherited) = doc.get_page_resources(page_id)?;
+    let xobjects = resources
+        .into_iter()
+        .chain(
+            inherited
+                .iter()
+                .filter_map(|id| doc.get_dictionary(*id).ok()),
+        )
+        .find_map(|resources| {
+            resources
+                .get_deref(b"XObject", doc)
+                .and_then(Object::as_dict)
+                .ok()
+        });
+    let resources = Resources {
+        fonts,
+        xobjects,
+        parent: None,
+    };
+    let mut spans = Vec::new();
+    run(
+        doc,
+        &content.operations,
+        &resources,
+        State::default(),
+        0,
+        &mut spans,
+    );
+    Ok(spans)
+}
+
+/// `[a b c d e f]`, mapping `(x, y)` to `(ax + cy + e, bx + dy + f)`
+type Matrix = [f32; 6];
+
+const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// The matrix applying `m` first and `n` second
+fn multiply(m: &Matrix, n: &Matrix) -> Matrix {
+    [
+        m[0] * n[0] + m[1] * n[2],
+        m[0] * n[1] + m[1] * n[3],
+        m[2] * n[0] + m[3] * n[2],
+        m[2] * n[1] + m[3] * n[3],
+        m[4] * n[0] + m[5] * n[2] + n[4],
+        m[4] * n[1] + m[5] * n[3] + n[5],
+    ]
+}
+
+fn translate(x: f32, y: f32) -> Matrix {
+    [1.0, 0.0, 0.0, 1.0, x, y]
+}
+
+fn matrix(operands: &[Object]) -> Matrix {
+    let mut matrix = IDENTITY;
+    if operands.len() == 6 {
+        for (value, operand) in matrix.iter_mut().zip(operands) {
+            *value = operand.as_float().unwrap_or_default();
+        }
+    }
+    matrix
+}
+
+struct Font<'a> {
+    encoding: Option<Encoding<'a>>,
+    /// Glyph widths by code, in thousandths of the size
+    widths: HashMap<u32, f32>,
+    default_width: f32,
+    /// Composite fonts take two bytes for a glyph, simple fonts one
+    composite: bool,
+    bold: bool,
+}
+
+impl<'a> Font<'a> {
+    fn new(doc: &'a Document, font: &'a Dictionary) -> Font<'a> {
+        let number = |object: &Object| doc.dereference(object).ok()?.1.as_float().ok();
+        let array = |dict: &'a Dictionary, key: &[u8]| {
+            dict.get_deref(key, doc)
+                .and_then(Object::as_array)
+                .map(Vec::as_slice)
+                .unwrap_or_default()
+        };
+        let composite = font
+            .get(b"Subtype")
+            .and_then(Object::as_name)
+            .is_ok_and(|subtype| subtype == b"Type0");
+        let mut widths = HashMap::new();
+        let mut default_width = DEFAULT_WIDTH;
+        match composite {
+            false => {
+                let first = font
+                    .get(b"FirstChar")
+                    .and_then(Object::as_i64)
+                    .unwrap_or_default();
+                for (code, width) in array(font, b"Widths").iter().enumerate() {
+                    if let Some(width) = number(width) {
+                        widths.insert((first + code as i64) as u32, width);
+                    }
+                }
+            }
+            true => {
+                let descendant = array(font, b"DescendantFonts")
+                    .first()
+                    .and_then(|descendant| doc.dereference(descendant).ok())
+                    .and_then(|(_, descendant)| descendant.as_dict().ok());
+                if let Some(descendant) = descendant {
+                    default_width = descendant
+                        .get(b"DW")
+                        .ok()
+                        .and_then(number)
+                        .unwrap_or(1000.0);
+                    // Either `first [w1 w2 ...]` or `first last w`
+                    let mut entries = array(descendant, b"W").iter();
+                    while let Some(first) = entries.next().and_then(number) {
+                        let first = first as u32;
+                        match entries.next().map(|next| doc.dereference(next)) {
+                            Some(Ok((_, Object::Array(list)))) => {
+                                for (code, width) in list.iter().enumerate() {
+                                    if let Some(width) = number(width) {
+                                        widths.insert(first + code as u32, width);
+                                    }
+                                }
+                            }
+                            Some(Ok((_, last))) => {
+                                let last = last.as_float().unwrap_or_default() as u32;
+                                let width =
+                                    entries.next().and_then(number).unwrap_or(default_width);
+                                // A broken range should not fill the memory
+                                for code in first..=last.min(first + 0xffff) {
+                                    widths.insert(code, width);
+                                }
+                            }
+                            _ => break,
+                        }
+                    }
+                }
+            }
+        }
+        let name = font
+            .get_deref(b"BaseFont", doc)
+            .and_then(Object::as_name)
+            .map(|name| String::from_utf8_lossy(name).to_lowercase())
+            .unwrap_or_default();
+        Font {
+            encoding: font.get_font_encoding(doc).ok(),
+            widths,
+            default_width,
+            composite,
+            bold: ["bold", "black", "heavy"]
+                .iter()
+                .any(|weight| name.contains(weight)),
+        }
+    }
+
+    fn codes<'b>(&self, bytes: &'b [u8]) -> impl Iterator<Item = u32> + 'b {
+        let size = if self.composite { 2 } else { 1 };
+        bytes
+            .chunks(size)
+            .map(|code| code.iter().fold(0, |code, byte| code << 8 | *byte as u32))
+    }
+
+    /// Simple fonts without a known encoding are read as Latin-1, composite
+    /// fonts without a map to unicode cannot be read at all
+    fn decode(&self, bytes: &[u8]) -> String {
+        let decoded = self
+            .encoding
+            .as_ref()
+            .and_then(|encoding| Document::decode_text(encoding, bytes).ok());
+        match (decoded, self.composite) {
+            (Some(text), _) => text,
+            (None, true) => String::new(),
+            (None, false) => bytes.iter().map(|byte| *byte as char).collect(),
+        }
+    }
+
+    /// How far showing the bytes moves the text, in unscaled text space
+    fn advance(&self, bytes: &[u8], state: &State) -> f32 {
+        self.codes(bytes)
+            .map(|code| {
+                let width = self
+                    .widths
+                    .get(&code)
+                    .copied()
+                    .unwrap_or(self.default_width);
+                let mut advance = width / 1000.0 * state.size + state.char_spacing;
+                if !self.composite && code == 32 {
+                    advance += state.word_spacing;
+                }
+                advance * state.scale
+            })
+            .sum()
+    }
+}
+
+/// The fonts and forms a content stream can use, a form falls back to the
+/// resources of what draws it
+struct Resources<'r, 'a> {
+    fonts: BTreeMap<Vec<u8>, Font<'a>>,
+    xobjects: Option<&'a Dictionary>,
+    parent: Option<&'r Resources<'r, 'a>>,
+}
+
+impl<'a> Resources<'_, 'a> {
+    fn font(&self, name: &[u8]) -> Option<&Font<'a>> {
+        self.fonts.get(name).or_else(|| self.parent?.font(name))
+    }
+
+    fn xobject(&self, doc: &'a Document, name: &[u8]) -> Option<&'a Object> {
+        self.xobjects
+            .and_then(|xobjects| xobjects.get_deref(name, doc).ok())
+            .or_else(|| self.parent?.xobject(doc, name))
+    }
+}
+
+/// The part of the graphics state that places text
+#[derive(Debug, Clone)]
+struct State {
+    ctm: Matrix,
+    font: Vec<u8>,
+    size: f32,
+    char_spacing: f32,
+    word_spacing: f32,
+    scale: f32,
+    leading: f32,
+    rise: f32,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        State {
+            ctm: IDENTITY,
+            font: Vec::new(),
+            size: 0.0,
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            scale: 1.0,
+            leading: 0.0,
+            rise: 0.0,
+        }
+    }
+}
+
+fn run(
+    doc: &Document,
+    operations: &[Operation],
+    resources: &Resources,
+    mut state: State,
+    depth: usize,
+    spans: &mut Vec<Span>,
+) {
+    let mut stack = Vec::new();
+    let (mut tm, mut tlm) = (IDENTITY, IDENTITY);
+    for operation in operations {
+        let operands = operation.operands.as_slice();
+        let number = |i: usize| {
+            operands
+                .get(i)
+                .and_then(|operand| operand.as_float().ok())
+                .unwrap_or_default()
+        };
+        let bytes = |i: usize| match operands.get(i) {
+            Some(Object::String(bytes, _)) => bytes.as_slice(),
+            _ => &[],
+        };
+        match operation.operator.as_str() {
+            "q" => stack.push(state.clone()),
+            "Q" => state = stack.pop().unwrap_or(state),
+            "cm" => state.ctm = multiply(&matrix(operands), &state.ctm),
+            "BT" => (tm, tlm) = (IDENTITY, IDENTITY),
+            "Tf" => {
+                state.font = operands
+                    .first()
+                    .and_then(|name| name.as_name().ok())
+                    .unwrap_or_default()
+                    .to_vec();
+                state.size = number(1);
+            }
+            "Tc" => state.char_spacing = number(0),
+            "Tw" => state.word_spacing = number(0),
+            "Tz" => state.scale = number(0) / 100.0,
+            "TL" => state.leading = number(0),
+            "Ts" => state.rise = number(0),
+            "Td" | "TD" => {
+                if operation.operator == "TD" {
+                    state.leading = -number(1);
+                }
+                tlm = multiply(&translate(number(0), number(1)), &tlm);
+                tm = tlm;
+            }
+            "Tm" => (tm, tlm) = (matrix(operands), matrix(operands)),
+            "T*" | "'" | "\"" => {
+                if operation.operator == "\"" {
+                    state.word_spacing = number(0);
+                    state.char_spacing = number(1);
+                }
+                tlm = multiply(&translate(0.0, -state.leading), &tlm);
+                tm = tlm;
+                let shown = match operation.operator.as_str() {
+                    "'" => bytes(0),
+                    "\"" => bytes(2),
+                    _ => &[],
+                };
+                show(resources, &state, &mut tm, shown, spans);
+            }
+            "Tj" => show(resources, &state, &mut tm, bytes(0), spans),
+            "TJ" => {
+                let elements = operands
+                    .first()
+                    .and_then(|elements| elements.as_array().ok())
+                    .map(Vec::as_slice)
+                    .unwrap_or_default();
+                for element in elements {
+                    match element {
+                        Object::String(bytes, _) => show(resources, &state, &mut tm, bytes, spans),
+                        element => {
+                            let adjustment = element.as_float().unwrap_or_default();
+                            let shift = -adjustment / 1000.0 * state.size * state.scale;
+                            tm = multiply(&translate(shift, 0.0), &tm);
+                        }
+                    }
+                }
+            }
+            "Do" if depth < MAX_FORM_DEPTH => {
+                let form = operands
+                    .first()
+                    .and_then(|name| name.as_name().ok())
+                    .and_then(|name| resources.xobject(doc, name))
+                    .and_then(|form| form.as_stream().ok())
+                    .filter(|form| {
+                        form.dict
+                            .get(b"Subtype")
+                            .and_then(Object::as_name)
+                            .is_ok_and(|subtype| subtype == b"Form")
+                    });
+                let Some(form) = form else {
+                    continue;
+                };
+                let Ok(content) = form
+                    .get_plain_content()
+                    .and_then(|content| Content::decode(&content))
+                else {
+                    continue;
+                };
+                let fonts = form
+                    .dict
+                    .get_deref(b"Resources", doc)
+                    .and_then(Object::as_dict)
+                    .and_then(|resources| resources.get_deref(b"Font", doc))
+                    .and_then(Object::as_dict)
+                    .map(|fonts| {
+                        fonts
+                            .iter()
+                            .filter_map(|(name, font)| {
+                                let font = doc.dereference(font).ok()?.1.as_dict().ok()?;
+                                Some((name.clone(), Font::new(doc, font)))
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                let xobjects = form
+                    .dict
+                    .get_deref(b"Resources", doc)
+                    .and_then(Object::as_dict)
+                    .and_then(|resources| resources.get_deref(b"XObject", doc))
+                    .and_then(Object::as_dict)
+                    .ok();
+                let resources = Resources {
+                    fonts,
+                    xobjects,
+                    parent: Some(resources),
+                };
+                let mut form_state = state.clone();
+                let form_matrix = form.dict.get(b"Matrix").and_then(Object::as_array);
+                if let Ok(form_matrix) = form_matrix {
+                    form_state.ctm = multiply(&matrix(form_matrix), &state.ctm);
+                }
+                run(
+                    doc,
+                    &content.operations,
+                    &resources,
+                    form_state,
+                    depth + 1,
+                    spans,
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Shows the bytes at the text matrix and moves it past them
+fn show(
+    resources: &Resources,
+    state: &State,
+    tm: &mut Matrix,
+    bytes: &[u8],
+    spans: &mut Vec<Span>,
+) {
+    let Some(font) = resources.font(&state.font) else {
+        return;
+    };
+    if bytes.is_empty() {
+        return;
+    }
+    let advance = font.advance(bytes, state);
+    let placed = multiply(tm, &state.ctm);
+    let (x, y) = (
+        placed[2] * state.rise + placed[4],
+        placed[3] * state.rise + placed[5],
+    );
+    let size = state.size * placed[2].hypot(placed[3]);
+    let width = advance * placed[0].hypot(placed[1]);
+    *tm = multiply(&translate(advance, 0.0), tm);
+
+    let text = font.decode(bytes);
+    // Runs that touch, like the parts of a kerned word, are one span
+    if let Some(last) = spans.last_mut() {
+        let gap = x - last.end();
+        if last.bold == font.bold
+            && (last.y - y).abs() < 0.1 * size
+            && (last.size - size).abs() < 0.1 * size
+            && gap > -WORD_GAP * size
+            && gap < WORD_GAP * size
+        {
+            last.text.push_str(&text);
+            last.width = x + width - last.x;
+            return;
+        }
+    }
+    if text.trim().is_empty() || size <= 0.0 {
+        return;
+    }
+    spans.push(Span {
+        x,
+        y,
+        size,
+        width,
+        text,
+        bold: font.bold,
+    });
+}
@@ -0,0 +1,23 @@
+use std::fmt::Display;
+
+use super::Converter;
+use super::markdown::Markdown;
+
+pub struct Text(pub(super) String);
+
+impl Converter<Text> {
+    /// Plain text is kept as it is, only line endings are unified
+    pub fn to_md(&self) -> Converter<Markdown> {
+        let text = self.data.0.trim_start_matches('\u{feff}');
+        let lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+        Converter {
+            data: Markdown(lines.join("\n").trim_matches('\n').to_string()),
+        }
+    }
+}
+
+impl Display for Converter<Text> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.data.0)
+    }
+}
@@ -0,0 +1,170 @@
+use quick_xml::Reader;
+use quick_xml::events::BytesStart;
+use quick_xml::events::Event as XmlEvent;
+
+use crate::prelude::*;
+
+/// A piece of an XML document. Empty elements are a start right followed by
+/// their end.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum Event {
+    Start {
+        name: String,
+        attributes: Vec<(String, String)>,
+    },
+    End {
+        name: String,
+    },
+    Text(String),
+}
+
+impl Event {
+    /// The name of the element without its namespace prefix, `p` for `w:p`
+    pub fn local_name(&self) -> Option<&str> {
+        match self {
+            Event::Start { name, .. } | Event::End { name } => Some(local(name)),
+            Event::Text(_) => None,
+        }
+    }
+
+    pub fn is_start(&self, local_name: &str) -> bool {
+        matches!(self, Event::Start { name, .. } if local(name) == local_name)
+    }
+
+    pub fn is_end(&self, local_name: &str) -> bool {
+        matches!(self, Event::End { name } if local(name) == local_name)
+    }
+
+    /// An attribute of a start, looked up without its namespace prefix
+    pub fn attr(&self, local_name: &str) -> Option<&str> {
+        match self {
+            Event::Start { attributes, .. } => attributes
+                .iter()
+                .find(|(name, _)| local(name) == local_name)
+                .map(|(_, value)| value.as_str()),
+            _ => None,
+        }
+    }
+}
+
+/// Reads the elements and text of a document. Entities that XML does not
+/// know, like `&nbsp;` in XHTML, are kept as they are.
+pub(super) fn events(xml: &str) -> Result<Vec<Event>> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().check_end_names = false;
+    let mut events = Vec::new();
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|err| Error::InvalidDocument(format!("broken XML: {err}")))?;
+        match event {
+            XmlEvent::Start(start) => events.push(self::start(&start)),
+            XmlEvent::Empty(start) => {
+                let start = self::start(&start);
+                if let Event::Start { name, .. } = &start {
+                    let name = name.clone();
+                    events.push(start);
+                    events.push(Event::End { name });
+                }
+            }
+            XmlEvent::End(end) => events.push(Event::End {
+                name: String::from_utf8_lossy(end.name().as_ref()).into_owned(),
+            }),
+            XmlEvent::Text(text) => {
+                let text = match text.unescape() {
+                    Ok(text) => text.into_owned(),
+                    Err(_) => String::from_utf8_lossy(&text).into_owned(),
+                };
+                events.push(Event::Text(text));
+            }
+            XmlEvent::CData(text) => {
+                events.push(Event::Text(
+                    String::from_utf8_lossy(&text.into_inner()).into_owned(),
+                ));
+            }
+            XmlEvent::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(events)
+}
+
+/// The text of the first element named `local_name`, when it has any
+pub(super) fn text_of(events: &[Event], local_name: &str) -> Option<String> {
+    let start = events.iter().position(|event| event.is_start(local_name))?;
+    let text: String = events[start + 1..]
+        .iter()
+        .take_while(|event| !event.is_end(local_name))
+        .filter_map(|event| match event {
+            Event::Text(text) => Some(text.as_str()),
+            _ => None,
+        })
+        .collect();
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+fn local(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+fn start(start: &BytesStart) -> Event {
+    Event::Start {
+        name: String::from_utf8_lossy(start.name().as_ref()).into_owned(),
+        attributes: start
+            .attributes()
+            .flatten()
+            .map(|attribute| {
+                let value = match attribute.unescape_value() {
+                    Ok(value) => value.into_owned(),
+                    Err(_) => String::from_utf8_lossy(&attribute.value).into_owned(),
+                };
+                (
+                    String::from_utf8_lossy(attribute.key.as_ref()).into_owned(),
+                    value,
+                )
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events() {
+        let events = events(
+            r#"<?xml version="1.0"?><!-- note --><w:p w:rsid='1'><w:t xml:space="preserve">a &lt; b &amp;&#x41;</w:t><w:br/><![CDATA[<raw>]]></w:p>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            events,
+            vec![
+                Event::Start {
+                    name: "w:p".into(),
+                    attributes: vec![("w:rsid".into(), "1".into())],
+                },
+                Event::Start {
+                    name: "w:t".into(),
+                    attributes: vec![("xml:space".into(), "preserve".into())],
+                },
+                Event::Text("a < b &A".into()),
+                Event::End { name: "w:t".into() },
+                Event::Start {
+                    name: "w:br".into(),
+                    attributes: vec![],
+                },
+                Event::End {
+                    name: "w:br".into(),
+                },
+                Event::Text("<raw>".into()),
+                Event::End { name: "w:p".into() },
+            ]
+        );
+        assert_eq!(events[0].attr("rsid"), Some("1"));
+        assert!(events[1].is_start("t"));
+        assert_eq!(text_of(&events, "t"), Some("a < b &A".into()));
+        assert!(super::events("<p>a &nbsp; b</p>").is_ok());
+    }
+}
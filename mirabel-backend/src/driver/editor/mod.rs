@@ -0,0 +1,319 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::prelude::*;
+use mirabel_core::id;
+use mirabel_core::models::file_action::FileAction;
+
+mod operation;
+
+pub use operation::EditOperation;
+
+/// A change to a file an agent asked for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileEdit {
+    pub path: String,
+    pub operation: EditOperation,
+    /// The content the edit was written against. When the file changed in
+    /// the meantime, both changes are merged.
+    pub base: Option<String>,
+}
+
+/// What an applied edit did to a file, `None` stands for a missing file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChange {
+    pub path: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+impl FileChange {
+    pub fn diff(&self) -> String {
+        diffy::create_patch(
+            self.before.as_deref().unwrap_or_default(),
+            self.after.as_deref().unwrap_or_default(),
+        )
+        .to_string()
+    }
+}
+
+/// Applies edits to the files below a root directory. Every write replaces
+/// the file at once and is refused when the file changed since it was read.
+#[derive(Debug)]
+pub struct FileEditor {
+    root: PathBuf,
+    // Edits of the same editor are applied one after another
+    lock: Mutex<()>,
+}
+
+impl FileEditor {
+    pub fn new(root: &Path) -> Result<Self> {
+        Ok(Self {
+            root: root.canonicalize()?,
+            lock: Mutex::new(()),
+        })
+    }
+
+    /// The content of the file at `path`, `None` if it does not exist
+    pub fn read(&self, path: &str) -> Result<Option<String>> {
+        read(&self.resolve(path)?)
+    }
+
+    pub fn apply(&self, edit: &FileEdit) -> Result<FileChange> {
+        let _guard = self.lock.lock().map_err(poisoned)?;
+        let file = self.resolve(&edit.path)?;
+        let current = read(&file)?;
+        let after = match &edit.base {
+            Some(base) if Some(base) != current.as_ref() => {
+                let edited = edit.operation.apply(Some(base))?;
+                let current = current.as_deref().ok_or(Error::Conflict(format!(
+                    "{} was deleted while it was being edited.",
+                    edit.path
+                )))?;
+                diffy::merge(base, current, &edited).map_err(|_| {
+                    Error::Conflict(format!(
+                        "{} was changed while it was being edited and the changes overlap.",
+                        edit.path
+                    ))
+                })?
+            }
+            _ => edit.operation.apply(current.as_deref())?,
+        };
+        self.write(&file, &edit.path, current.as_deref(), Some(&after))?;
+        Ok(FileChange {
+            path: edit.path.clone(),
+            before: current,
+            after: Some(after),
+        })
+    }
+
+    /// Undoes `action`. Changes made to the file since are kept, unless they
+    /// touch the same lines.
+    pub fn revert(&self, action: &FileAction) -> Result<FileChange> {
+        let _guard = self.lock.lock().map_err(poisoned)?;
+        let file = self.resolve(&action.path)?;
+        let current = read(&file)?;
+        let conflict = || {
+            Error::Conflict(format!(
+                "{} was changed since, the change cannot be reverted.",
+                action.path
+            ))
+        };
+        let after = if current == action.after {
+            action.before.clone()
+        } else {
+            match (&action.before, &action.after, &current) {
+                (Some(before), Some(after), Some(current)) => {
+                    Some(diffy::merge(after, current, before).map_err(|_| conflict())?)
+                }
+                _ => return Err(conflict()),
+            }
+        };
+        self.write(&file, &action.path, current.as_deref(), after.as_deref())?;
+        Ok(FileChange {
+            path: action.path.clone(),
+            before: current,
+            after,
+        })
+    }
+
+    /// Replaces `expected` by `content` through a temporary file, a missing
+    /// `content` removes the file
+    fn write(
+        &self,
+        file: &Path,
+        path: &str,
+        expected: Option<&str>,
+        content: Option<&str>,
+    ) -> Result<()> {
+        let Some(content) = content else {
+            self.check_unchanged(file, path, expected)?;
+            return Ok(fs::remove_file(file)?);
+        };
+        let dir = file.parent().ok_or(Error::BadRequest(format!(
+            "{path} is not a file in the sandbox."
+        )))?;
+        fs::create_dir_all(dir)?;
+        let temporary = dir.join(format!(".mirabel-edit-{}", id!()));
+        fs::write(&temporary, content)?;
+        let replaced = match fs::metadata(file) {
+            Ok(metadata) => fs::set_permissions(&temporary, metadata.permissions()),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err),
+        }
+        .map_err(Error::from)
+        .and_then(|_| self.check_unchanged(file, path, expected))
+        .and_then(|_| Ok(fs::rename(&temporary, file)?));
+        if replaced.is_err() {
+            let _ = fs::remove_file(&temporary);
+        }
+        replaced
+    }
+
+    /// Someone else, e.g. a user in the shell, can write to the file at any
+    /// time
+    fn check_unchanged(&self, file: &Path, path: &str, expected: Option<&str>) -> Result<()> {
+        if read(file)?.as_deref() != expected {
+            return Err(Error::Conflict(format!(
+                "{path} was changed while it was being edited."
+            )));
+        }
+        Ok(())
+    }
+
+    /// The file at `path` below the root. Paths leaving the root, directly
+    /// or through a symbolic link, are refused.
+    fn resolve(&self, path: &str) -> Result<PathBuf> {
+        let outside = || Error::BadRequest(format!("{path} is not a file in the sandbox."));
+        let relative = Path::new(path);
+        if path.is_empty()
+            || !relative
+                .components()
+                .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(outside());
+        }
+        let file = self.root.join(relative);
+        // The closest existing ancestor tells where the file really ends up
+        let mut existing = file.as_path();
+        while fs::symlink_metadata(existing).is_err() {
+            existing = existing.parent().ok_or_else(outside)?;
+        }
+        if !existing.canonicalize()?.starts_with(&self.root) {
+            return Err(outside());
+        }
+        if file.is_dir() {
+            return Err(Error::BadRequest(format!("{path} is a directory.")));
+        }
+        Ok(file)
+    }
+}
+
+fn read(file: &Path) -> Result<Option<String>> {
+    match fs::read_to_string(file) {
+        Ok(content) => Ok(Some(content)),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn poisoned<T>(err: std::sync::PoisonError<T>) -> Error {
+    Error::PoisonedLock(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editor(name: &str) -> FileEditor {
+        let root = std::env::temp_dir().join("mirabel-editor-tests").join(name);
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        FileEditor::new(&root).unwrap()
+    }
+
+    fn edit(path: &str, text: &str, base: Option<&str>) -> FileEdit {
+        FileEdit {
+            path: path.into(),
+            operation: EditOperation::parse(text).unwrap(),
+            base: base.map(String::from),
+        }
+    }
+
+    fn action(change: &FileChange) -> FileAction {
+        FileAction::new(
+            "session".into(),
+            change.path.clone(),
+            change.before.clone(),
+            change.after.clone(),
+            change.diff(),
+            None,
+        )
+    }
+
+    #[test]
+    fn test_apply_and_revert() {
+        let editor = editor("revert");
+        let created = editor
+            .apply(&edit("src/lib.rs", "pub fn a() {}\n", None))
+            .unwrap();
+        assert_eq!(created.before, None);
+        let edited = editor
+            .apply(&edit(
+                "src/lib.rs",
+                "<<<<<<< SEARCH\npub fn a() {}\n=======\npub fn a() {}\npub fn b() {}\n>>>>>>> REPLACE\n",
+                None,
+            ))
+            .unwrap();
+        assert!(edited.diff().contains("+pub fn b() {}"));
+
+        // A user adds a line elsewhere, reverting keeps it
+        fs::write(
+            editor.root.join("src/lib.rs"),
+            "// Library\npub fn a() {}\npub fn b() {}\n",
+        )
+        .unwrap();
+        editor.revert(&action(&edited)).unwrap();
+        assert_eq!(
+            editor.read("src/lib.rs").unwrap().unwrap(),
+            "// Library\npub fn a() {}\n"
+        );
+
+        // The file no longer is what was created, so it is not removed
+        assert!(matches!(
+            editor.revert(&action(&created)),
+            Err(Error::Conflict(_))
+        ));
+    }
+
+    #[test]
+    fn test_apply_merges_concurrent_changes() {
+        let editor = editor("merge");
+        let base = "one\ntwo\nthree\nfour\nfive\n";
+        fs::write(
+            editor.root.join("list.txt"),
+            "zero\none\ntwo\nthree\nfour\nfive\n",
+        )
+        .unwrap();
+
+        let change = editor
+            .apply(&edit(
+                "list.txt",
+                "<<<<<<< SEARCH\nfive\n=======\n5\n>>>>>>> REPLACE\n",
+                Some(base),
+            ))
+            .unwrap();
+        assert_eq!(change.after.unwrap(), "zero\none\ntwo\nthree\nfour\n5\n");
+
+        fs::write(editor.root.join("list.txt"), "one\n2\nthree\n").unwrap();
+        let overlapping = edit(
+            "list.txt",
+            "<<<<<<< SEARCH\ntwo\n=======\nTWO\n>>>>>>> REPLACE\n",
+            Some("one\ntwo\nthree\n"),
+        );
+        assert!(matches!(
+            editor.apply(&overlapping),
+            Err(Error::Conflict(_))
+        ));
+    }
+
+    #[test]
+    fn test_paths_stay_in_root() {
+        let editor = editor("paths");
+        for path in ["", "../escape.txt", "/etc/passwd", "src/../../escape.txt"] {
+            assert!(
+                matches!(editor.read(path), Err(Error::BadRequest(_))),
+                "{path} was accepted"
+            );
+        }
+        std::os::unix::fs::symlink(std::env::temp_dir(), editor.root.join("tmp")).unwrap();
+        assert!(matches!(
+            editor.apply(&edit("tmp/escape.txt", "escaped\n", None)),
+            Err(Error::BadRequest(_))
+        ));
+    }
+}
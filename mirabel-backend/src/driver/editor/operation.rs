@@ -0,0 +1,236 @@
+use diffy::Patch;
+
+use crate::prelude::*;
+
+use crate::agent::unfence;
+
+const SEARCH_MARKER: &str = "<<<<<<< SEARCH";
+const DIVIDER_MARKER: &str = "=======";
+const REPLACE_MARKER: &str = ">>>>>>> REPLACE";
+
+/// How an agent describes a change to a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditOperation {
+    /// The whole new content of the file
+    Write { content: String },
+    /// Blocks of which each `search` has to match exactly once
+    Replace { blocks: Vec<SearchReplace> },
+    /// A unified diff
+    Patch { diff: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchReplace {
+    pub search: String,
+    pub replace: String,
+}
+
+impl EditOperation {
+    /// Reads an edit as agents write it: search/replace blocks, a unified
+    /// diff, or otherwise the whole content of the file.
+    pub fn parse(text: &str) -> Result<Self> {
+        let text = unfence(text);
+        if text.contains(SEARCH_MARKER) {
+            return Ok(EditOperation::Replace {
+                blocks: parse_blocks(text)?,
+            });
+        }
+        if text.starts_with("--- ") || text.starts_with("@@ ") {
+            Patch::from_str(text)
+                .map_err(|err| invalid(format!("The diff is malformed: {err}")))?;
+            return Ok(EditOperation::Patch {
+                diff: ensure_newline(text),
+            });
+        }
+        Ok(EditOperation::Write {
+            content: ensure_newline(text),
+        })
+    }
+
+    /// The content after the edit, `current` is absent for files that do not
+    /// exist yet
+    pub fn apply(&self, current: Option<&str>) -> Result<String> {
+        let current = current.unwrap_or_default();
+        match self {
+            EditOperation::Write { content } => Ok(content.clone()),
+            EditOperation::Replace { blocks } => {
+                let mut content = current.to_string();
+                for block in blocks {
+                    content = block.apply(&content)?;
+                }
+                Ok(content)
+            }
+            EditOperation::Patch { diff } => {
+                let patch = Patch::from_str(diff)
+                    .map_err(|err| invalid(format!("The diff is malformed: {err}")))?;
+                diffy::apply(current, &patch)
+                    .map_err(|err| Error::Conflict(format!("The diff does not apply: {err}")))
+            }
+        }
+    }
+}
+
+impl SearchReplace {
+    fn apply(&self, content: &str) -> Result<String> {
+        // An empty search can only start an empty file
+        if self.search.is_empty() {
+            if !content.is_empty() {
+                return Err(invalid(
+                    "An empty search block only works on empty files.".into(),
+                ));
+            }
+            return Ok(self.replace.clone());
+        }
+        match content.matches(&self.search).count() {
+            1 => Ok(content.replacen(&self.search, &self.replace, 1)),
+            0 => Err(Error::Conflict(format!(
+                "The search block was not found:\n{}",
+                self.search
+            ))),
+            count => Err(invalid(format!(
+                "The search block matches {count} times, it has to be unique:\n{}",
+                self.search
+            ))),
+        }
+    }
+}
+
+fn parse_blocks(text: &str) -> Result<Vec<SearchReplace>> {
+    enum Part {
+        Outside,
+        Search,
+        Replace,
+    }
+    let mut blocks = Vec::new();
+    let mut part = Part::Outside;
+    let mut search = String::new();
+    let mut replace = String::new();
+    for line in text.split_inclusive('\n') {
+        let marker = line.trim_end();
+        part = match part {
+            Part::Outside if marker == SEARCH_MARKER => Part::Search,
+            // Anything between blocks, like the file name, is ignored
+            Part::Outside => Part::Outside,
+            Part::Search if marker == DIVIDER_MARKER => Part::Replace,
+            Part::Search => {
+                search.push_str(line);
+                Part::Search
+            }
+            Part::Replace if marker == REPLACE_MARKER => {
+                blocks.push(SearchReplace {
+                    search: ensure_newline_if_any(std::mem::take(&mut search)),
+                    replace: ensure_newline_if_any(std::mem::take(&mut replace)),
+                });
+                Part::Outside
+            }
+            Part::Replace => {
+                replace.push_str(line);
+                Part::Replace
+            }
+        };
+    }
+    if !matches!(part, Part::Outside) {
+        return Err(invalid("A search/replace block is not closed.".into()));
+    }
+    Ok(blocks)
+}
+
+fn ensure_newline(text: &str) -> String {
+    ensure_newline_if_any(text.to_string())
+}
+
+fn ensure_newline_if_any(mut text: String) -> String {
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+    text
+}
+
+fn invalid(message: String) -> Error {
+    Error::BadRequest(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use indoc::indoc;
+
+    #[test]
+    fn test_parse_and_apply_blocks() {
+        let operation = EditOperation::parse(indoc! {"
+            ```rust
+            src/main.rs
+            <<<<<<< SEARCH
+            fn main() {
+                println!(\"Hello\");
+            =======
+            fn main() {
+                println!(\"Hello, world\");
+            >>>>>>> REPLACE
+            <<<<<<< SEARCH
+            }
+            =======
+            }
+
+            fn unused() {}
+            >>>>>>> REPLACE
+            ```
+        "})
+        .unwrap();
+        let EditOperation::Replace { blocks } = &operation else {
+            panic!("Expected search/replace blocks, got {operation:?}");
+        };
+        assert_eq!(blocks.len(), 2);
+
+        let content = "fn main() {\n    println!(\"Hello\");\n}\n";
+        assert_eq!(
+            operation.apply(Some(content)).unwrap(),
+            "fn main() {\n    println!(\"Hello, world\");\n}\n\nfn unused() {}\n"
+        );
+        // Not found means the file changed under the edit, ambiguous
+        // matches are a bad edit
+        assert!(matches!(
+            operation.apply(Some("fn other() {}\n")),
+            Err(Error::Conflict(_))
+        ));
+        assert!(matches!(
+            operation.apply(Some("}\n}\nfn main() {\n    println!(\"Hello\");\n")),
+            Err(Error::BadRequest(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_and_apply_patch() {
+        let operation = EditOperation::parse(indoc! {"
+            --- a/notes.txt
+            +++ b/notes.txt
+            @@ -1,2 +1,2 @@
+             first
+            -second
+            +2nd
+        "})
+        .unwrap();
+        assert!(matches!(operation, EditOperation::Patch { .. }));
+        assert_eq!(
+            operation.apply(Some("first\nsecond\n")).unwrap(),
+            "first\n2nd\n"
+        );
+        assert!(matches!(
+            operation.apply(Some("first\nthird\n")),
+            Err(Error::Conflict(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_whole_file() {
+        let operation = EditOperation::parse("```\n# Title\n```").unwrap();
+        assert_eq!(
+            operation,
+            EditOperation::Write {
+                content: "# Title\n".into()
+            }
+        );
+        assert!(EditOperation::parse("<<<<<<< SEARCH\na\n=======\n").is_err());
+    }
+}
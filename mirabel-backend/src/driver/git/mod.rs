@@ -0,0 +1,487 @@
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Stdio;
+
+use tokio::process::Command;
+
+use crate::prelude::*;
+use mirabel_core::id;
+
+/// Who commits are attributed to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub name: String,
+    pub email: String,
+}
+
+/// A bare clone of a repository, agents work in worktrees of it so the
+/// clone itself is never checked out
+#[derive(Debug, Clone)]
+pub struct GitRepository {
+    path: PathBuf,
+}
+
+impl GitRepository {
+    pub fn open(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Clones `source`, a local path or a URL, to `path`
+    pub async fn clone(source: &str, path: PathBuf) -> Result<Self> {
+        let parent = path.parent().ok_or(Error::InternalServer)?;
+        tokio::fs::create_dir_all(parent).await?;
+        git(parent, ["clone", "--bare", "--", source])
+            .arg(&path)
+            .output_checked()
+            .await?;
+        Ok(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The branch the source had checked out when it was cloned
+    pub async fn default_branch(&self) -> Result<String> {
+        self.run(["symbolic-ref", "--short", "HEAD"]).await
+    }
+
+    /// Checks out a new branch `branch` starting at `start` to `path`,
+    /// returns the commit it starts at
+    pub async fn add_worktree(&self, path: &Path, branch: &str, start: &str) -> Result<String> {
+        let base = self.run(["rev-parse", "--verify", start]).await?;
+        git(&self.path, ["worktree", "add", "-b", branch])
+            .arg(path)
+            .arg(&base)
+            .output_checked()
+            .await?;
+        Ok(base)
+    }
+
+    /// Removes the worktree at `path` with its uncommitted changes, and the
+    /// branch it was on
+    pub async fn remove_worktree(&self, path: &Path, branch: &str) -> Result<()> {
+        if path.exists() {
+            git(&self.path, ["worktree", "remove", "--force"])
+                .arg(path)
+                .output_checked()
+                .await?;
+        }
+        // The worktree could have been deleted by hand
+        self.run(["worktree", "prune"]).await?;
+        self.run(["branch", "-D", branch]).await?;
+        Ok(())
+    }
+
+    /// Merges `branch` into `into` of the source and returns the merge
+    /// commit. The source is updated first, nothing changes when the merge
+    /// conflicts or the source refuses the push. Merging without a checkout
+    /// needs git 2.38 or newer.
+    pub async fn merge(
+        &self,
+        branch: &str,
+        into: &str,
+        message: &str,
+        author: &Signature,
+    ) -> Result<String> {
+        let target = format!("refs/heads/{into}");
+        self.run(["fetch", "origin", &format!("+{target}:{target}")])
+            .await?;
+        let current = self.run(["rev-parse", "--verify", &target]).await?;
+        let head = self.run(["rev-parse", "--verify", branch]).await?;
+        if self
+            .succeeds(["merge-base", "--is-ancestor", &head, &current])
+            .await?
+        {
+            return Err(Error::BadRequest(format!(
+                "{branch} has nothing that is not in {into} yet."
+            )));
+        }
+
+        let tree = git(&self.path, ["merge-tree", "--write-tree", &current, &head])
+            .output()
+            .await?;
+        if !tree.status.success() {
+            return Err(Error::Conflict(format!(
+                "{branch} conflicts with {into}:\n{}",
+                String::from_utf8_lossy(&tree.stdout)
+            )));
+        }
+        let tree = String::from_utf8_lossy(&tree.stdout)
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        let commit = git(
+            &self.path,
+            [
+                "commit-tree",
+                &tree,
+                "-p",
+                &current,
+                "-p",
+                &head,
+                "-m",
+                message,
+            ],
+        )
+        .signed(author)
+        .output_checked()
+        .await?;
+
+        self.run(["push", "origin", &format!("{commit}:{target}")])
+            .await?;
+        self.run(["update-ref", &target, &commit, &current]).await?;
+        Ok(commit)
+    }
+
+    /// Pushes `branch` to the source under the same name, to be reviewed and
+    /// merged there. Returns the commit it points to.
+    pub async fn push(&self, branch: &str, into: &str) -> Result<String> {
+        let source = format!("refs/heads/{branch}");
+        let head = self.run(["rev-parse", "--verify", &source]).await?;
+        let target = self.run(["rev-parse", "--verify", into]).await?;
+        if self
+            .succeeds(["merge-base", "--is-ancestor", &head, &target])
+            .await?
+        {
+            return Err(Error::BadRequest(format!(
+                "{branch} has nothing that is not in {into} yet."
+            )));
+        }
+        self.run(["push", "origin", &format!("{source}:{source}")])
+            .await?;
+        Ok(head)
+    }
+
+    async fn run<const N: usize>(&self, args: [&str; N]) -> Result<String> {
+        git(&self.path, args).output_checked().await
+    }
+
+    async fn succeeds<const N: usize>(&self, args: [&str; N]) -> Result<bool> {
+        Ok(git(&self.path, args).output().await?.status.success())
+    }
+}
+
+/// A checkout of a branch that agents and users change files in
+#[derive(Debug, Clone)]
+pub struct GitWorktree {
+    path: PathBuf,
+}
+
+impl GitWorktree {
+    pub fn open(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub async fn has_changes(&self) -> Result<bool> {
+        let status = git(&self.path, ["status", "--porcelain"])
+            .output_checked()
+            .await?;
+        Ok(!status.is_empty())
+    }
+
+    /// Commits every change, new files included. Returns the commit, or
+    /// `None` when there was nothing to commit.
+    pub async fn commit(&self, message: &str, author: &Signature) -> Result<Option<String>> {
+        if !self.has_changes().await? {
+            return Ok(None);
+        }
+        git(&self.path, ["add", "--all"]).output_checked().await?;
+        git(
+            &self.path,
+            ["commit", "--quiet", "--no-verify", "-m", message],
+        )
+        .signed(author)
+        .output_checked()
+        .await?;
+        Ok(Some(
+            git(&self.path, ["rev-parse", "HEAD"])
+                .output_checked()
+                .await?,
+        ))
+    }
+
+    /// Unified diff of the worktree against `base`, committed or not and
+    /// new files included. The index is left as it is.
+    pub async fn diff(&self, base: &str) -> Result<String> {
+        // New files only show up in diffs once git knows about them, they
+        // are added to a copy of the index
+        let index = self.path.join(
+            git(&self.path, ["rev-parse", "--git-path", "index"])
+                .output_checked()
+                .await?,
+        );
+        let copy = index.with_file_name(format!("index.mirabel-diff-{}", id!()));
+        match tokio::fs::copy(&index, &copy).await {
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            copied => {
+                copied?;
+            }
+        }
+        let diff = async {
+            git(&self.path, ["add", "--all", "--intent-to-add"])
+                .env("GIT_INDEX_FILE", &copy)
+                .output_checked()
+                .await?;
+            git(&self.path, ["diff", "--no-color", "--no-ext-diff", base])
+                .env("GIT_INDEX_FILE", &copy)
+                .output_text()
+                .await
+        }
+        .await;
+        let _ = tokio::fs::remove_file(&copy).await;
+        diff
+    }
+}
+
+fn git<const N: usize>(dir: &Path, args: [&str; N]) -> Command {
+    let mut command = Command::new("git");
+    command
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .stdin(Stdio::null())
+        // Never wait for credentials nobody is going to type
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .kill_on_drop(true);
+    command
+}
+
+trait GitCommand {
+    fn signed(&mut self, author: &Signature) -> &mut Self;
+    async fn output_text(&mut self) -> Result<String>;
+    async fn output_checked(&mut self) -> Result<String>;
+}
+
+impl GitCommand for Command {
+    fn signed(&mut self, author: &Signature) -> &mut Self {
+        self.env("GIT_AUTHOR_NAME", &author.name)
+            .env("GIT_AUTHOR_EMAIL", &author.email)
+            .env("GIT_COMMITTER_NAME", &author.name)
+            .env("GIT_COMMITTER_EMAIL", &author.email)
+    }
+
+    /// Stdout, a failed command is an error carrying its stderr
+    async fn output_text(&mut self) -> Result<String> {
+        let output = self.output().await?;
+        if !output.status.success() {
+            return Err(Error::Git(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+        Ok(String::from_utf8(output.stdout)?)
+    }
+
+    /// Stdout without the trailing newline, for commands that print a name
+    async fn output_checked(&mut self) -> Result<String> {
+        let mut stdout = self.output_text().await?;
+        stdout.truncate(stdout.trim_end().len());
+        Ok(stdout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signature() -> Signature {
+        Signature {
+            name: "Mirabel".into(),
+            email: "mirabel@example.com".into(),
+        }
+    }
+
+    /// A bare repository with one commit on `main`, like one users would
+    /// point to
+    async fn origin(name: &str) -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join("mirabel-git-tests").join(name);
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let origin = dir.join("origin.git");
+        git(&dir, ["init", "--quiet", "--bare", "--initial-branch=main"])
+            .arg(&origin)
+            .output_checked()
+            .await
+            .unwrap();
+
+        let seed = dir.join("seed");
+        git(&dir, ["clone", "--quiet"])
+            .arg(&origin)
+            .arg(&seed)
+            .output_checked()
+            .await
+            .unwrap();
+        tokio::fs::write(seed.join("README.md"), "# Project\n")
+            .await
+            .unwrap();
+        GitWorktree::open(seed.clone())
+            .commit("Initial commit", &signature())
+            .await
+            .unwrap();
+        git(&seed, ["push", "--quiet", "origin", "HEAD:main"])
+            .output_checked()
+            .await
+            .unwrap();
+        (dir, origin)
+    }
+
+    #[tokio::test]
+    async fn test_worktree_commit_and_merge() {
+        let (dir, origin) = origin("merge").await;
+        let repository = GitRepository::clone(origin.to_str().unwrap(), dir.join("clone.git"))
+            .await
+            .unwrap();
+        assert_eq!(repository.default_branch().await.unwrap(), "main");
+
+        let path = dir.join("session");
+        let base = repository
+            .add_worktree(&path, "mirabel/session", "main")
+            .await
+            .unwrap();
+        let worktree = GitWorktree::open(path.clone());
+        tokio::fs::write(path.join("notes.txt"), "Agent notes\n")
+            .await
+            .unwrap();
+        let diff = worktree.diff(&base).await.unwrap();
+        assert!(diff.contains("+Agent notes"), "{diff}");
+        // Reading the diff does not stage anything
+        let status = git(&path, ["status", "--porcelain"])
+            .output_checked()
+            .await
+            .unwrap();
+        assert_eq!(status, "?? notes.txt");
+
+        assert!(
+            worktree
+                .commit("Add notes", &signature())
+                .await
+                .unwrap()
+                .is_some()
+        );
+        assert_eq!(worktree.commit("Again", &signature()).await.unwrap(), None);
+        let commit = repository
+            .merge("mirabel/session", "main", "Merge session", &signature())
+            .await
+            .unwrap();
+
+        let pushed = git(&origin, ["rev-parse", "main"])
+            .output_checked()
+            .await
+            .unwrap();
+        assert_eq!(pushed, commit);
+        let notes = git(&origin, ["show", "main:notes.txt"])
+            .output_checked()
+            .await
+            .unwrap();
+        assert_eq!(notes, "Agent notes");
+
+        // Everything is in main now, merging again has no point
+        assert!(matches!(
+            repository
+                .merge("mirabel/session", "main", "Merge session", &signature())
+                .await,
+            Err(Error::BadRequest(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_merge_conflict_changes_nothing() {
+        let (dir, origin) = origin("conflict").await;
+        let repository = GitRepository::clone(origin.to_str().unwrap(), dir.join("clone.git"))
+            .await
+            .unwrap();
+        let path = dir.join("session");
+        repository
+            .add_worktree(&path, "mirabel/session", "main")
+            .await
+            .unwrap();
+        tokio::fs::write(path.join("README.md"), "# Agent\n")
+            .await
+            .unwrap();
+        GitWorktree::open(path.clone())
+            .commit("Rename", &signature())
+            .await
+            .unwrap();
+
+        // Someone else changed the same line upstream in the meantime
+        let seed = dir.join("seed");
+        tokio::fs::write(seed.join("README.md"), "# Upstream\n")
+            .await
+            .unwrap();
+        GitWorktree::open(seed.clone())
+            .commit("Rename upstream", &signature())
+            .await
+            .unwrap();
+        git(&seed, ["push", "--quiet", "origin", "HEAD:main"])
+            .output_checked()
+            .await
+            .unwrap();
+        let upstream = git(&origin, ["rev-parse", "main"])
+            .output_checked()
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            repository
+                .merge("mirabel/session", "main", "Merge session", &signature())
+                .await,
+            Err(Error::Conflict(_))
+        ));
+        let after = git(&origin, ["rev-parse", "main"])
+            .output_checked()
+            .await
+            .unwrap();
+        assert_eq!(after, upstream);
+
+        repository
+            .remove_worktree(&path, "mirabel/session")
+            .await
+            .unwrap();
+        assert!(!path.exists());
+        assert!(
+            !repository
+                .succeeds(["rev-parse", "--verify", "mirabel/session"])
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_push_leaves_main_alone() {
+        let (dir, origin) = origin("push").await;
+        let repository = GitRepository::clone(origin.to_str().unwrap(), dir.join("clone.git"))
+            .await
+            .unwrap();
+        let path = dir.join("session");
+        let base = repository
+            .add_worktree(&path, "mirabel/session", "main")
+            .await
+            .unwrap();
+        assert!(matches!(
+            repository.push("mirabel/session", "main").await,
+            Err(Error::BadRequest(_))
+        ));
+
+        tokio::fs::write(path.join("notes.txt"), "Agent notes\n")
+            .await
+            .unwrap();
+        let commit = GitWorktree::open(path)
+            .commit("Add notes", &signature())
+            .await
+            .unwrap();
+        let pushed = repository.push("mirabel/session", "main").await.unwrap();
+        assert_eq!(Some(pushed.clone()), commit);
+
+        let branch = git(&origin, ["rev-parse", "mirabel/session"])
+            .output_checked()
+            .await
+            .unwrap();
+        assert_eq!(branch, pushed);
+        let main = git(&origin, ["rev-parse", "main"])
+            .output_checked()
+            .await
+            .unwrap();
+        assert_eq!(main, base);
+    }
+}
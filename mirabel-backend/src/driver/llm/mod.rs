@@ -11,6 +11,13 @@ pub trait Llm {
     async fn generate(&self, parameters: Option<Parameters>, prompt: &str) -> Result<LlmResponse>;
 }
 
+/// Turns texts into vectors that are close when the texts mean similar things
+#[async_trait]
+pub trait Embedder {
+    /// One embedding per input, in the same order
+    async fn embed(&self, inputs: Vec<String>) -> Result<Vec<Vec<f32>>>;
+}
+
 #[derive(Debug, Default, Clone, Eq, PartialEq)]
 pub struct Parameters;
 
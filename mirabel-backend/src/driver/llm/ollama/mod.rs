@@ -1,5 +1,9 @@
+use async_trait::async_trait;
+use chrono::Utc;
 use futures::Stream;
 use futures::TryStreamExt;
+use models::EmbedRequest;
+use models::EmbedResponse;
 use models::GenerateRequest;
 use models::GenerateRequestInternal;
 use models::GenerateResponse;
@@ -10,14 +14,29 @@ use reqwest_streams::JsonStreamResponse;
 use serde::Serialize;
 use serde::de::DeserializeOwned;
 
+use crate::driver::llm::Embedder;
+use crate::driver::llm::Llm;
+use crate::driver::llm::LlmResponse;
+use crate::driver::llm::LlmResponseMetadata;
+use crate::driver::llm::Parameters;
 use crate::prelude::*;
 
 pub(crate) mod models;
 
+const OLLAMA_HOST_ENV: &str = "OLLAMA_HOST";
+const OLLAMA_MODEL_ENV: &str = "OLLAMA_MODEL";
+const OLLAMA_EMBEDDING_MODEL_ENV: &str = "OLLAMA_EMBEDDING_MODEL";
+const DEFAULT_MODEL: &str = "llama3.2";
+const DEFAULT_EMBEDDING_MODEL: &str = "nomic-embed-text";
+
 #[derive(Debug)]
 pub struct Ollama {
     base_url: String,
     client: Client,
+    // Model used when generating through the `Llm` trait
+    model: String,
+    // Model used when embedding through the `Embedder` trait
+    embedding_model: String,
 }
 
 impl Default for Ollama {
@@ -25,11 +44,24 @@ impl Default for Ollama {
         Self {
             base_url: "http://localhost:11434".into(),
             client: Client::new(),
+            model: DEFAULT_MODEL.into(),
+            embedding_model: DEFAULT_EMBEDDING_MODEL.into(),
         }
     }
 }
 
 impl Ollama {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        Self {
+            base_url: std::env::var(OLLAMA_HOST_ENV).unwrap_or(default.base_url),
+            model: std::env::var(OLLAMA_MODEL_ENV).unwrap_or(default.model),
+            embedding_model: std::env::var(OLLAMA_EMBEDDING_MODEL_ENV)
+                .unwrap_or(default.embedding_model),
+            ..default
+        }
+    }
+
     async fn request<T, U>(&self, method: Method, route: &str, body: T) -> Result<U>
     where
         T: Serialize,
@@ -80,12 +112,57 @@ impl Ollama {
     pub async fn delete() {}
     pub async fn pull() {}
     pub async fn push() {}
-    pub async fn generate_embeddings() {}
+    pub async fn generate_embeddings(&self, request: EmbedRequest) -> Result<EmbedResponse> {
+        self.request::<EmbedRequest, EmbedResponse>(Method::POST, "api/embed", request)
+            .await
+    }
+
     pub async fn ps() {}
     pub async fn generate_embedding() {}
     pub async fn version() {}
 }
 
+#[async_trait]
+impl Llm for Ollama {
+    async fn generate(&self, _parameters: Option<Parameters>, prompt: &str) -> Result<LlmResponse> {
+        let start_time = Utc::now();
+        let response = Ollama::generate(
+            self,
+            GenerateRequest::new(self.model.as_str().into(), prompt.into()),
+        )
+        .await?;
+        Ok(LlmResponse {
+            generation: response.response,
+            metadata: LlmResponseMetadata {
+                start_time,
+                end_time: Utc::now(),
+                prompt_token_count: response.prompt_eval_count.unwrap_or_default(),
+                response_token_count: response.eval_count.unwrap_or_default(),
+            },
+        })
+    }
+}
+
+#[async_trait]
+impl Embedder for Ollama {
+    async fn embed(&self, inputs: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        let count = inputs.len();
+        let response = self
+            .generate_embeddings(EmbedRequest {
+                model: self.embedding_model.clone(),
+                input: inputs,
+            })
+            .await?;
+        if response.embeddings.len() != count {
+            return Err(Error::Generic(format!(
+                "Expected {count} embeddings, got {}",
+                response.embeddings.len()
+            )));
+        }
+        Ok(response.embeddings)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use serial_test::serial;
@@ -181,7 +258,14 @@ mod tests {
     #[tokio::test]
     #[serial]
     #[ignore = "Depends on external service"]
-    async fn test_generate_embeddings() {}
+    async fn test_generate_embeddings() {
+        let embeddings = Ollama::default()
+            .embed(vec!["Hello".into(), "World".into()])
+            .await
+            .unwrap();
+        assert_eq!(embeddings.len(), 2);
+        assert_eq!(embeddings[0].len(), embeddings[1].len());
+    }
 
     #[tokio::test]
     #[serial]
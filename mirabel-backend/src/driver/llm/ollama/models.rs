@@ -236,3 +236,16 @@ pub enum DoneReason {
     #[serde(rename = "load")]
     Load,
 }
+
+#[derive(Debug, Serialize)]
+pub struct EmbedRequest {
+    pub model: String,
+    pub input: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EmbedResponse {
+    pub model: String,
+    /// One per input, in the same order
+    pub embeddings: Vec<Vec<f32>>,
+}
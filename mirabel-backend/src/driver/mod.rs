@@ -1,7 +1,13 @@
 pub(crate) mod browser;
+pub(crate) mod code_index;
 pub(crate) mod container;
 pub(crate) mod converter;
+pub(crate) mod editor;
 pub(crate) mod email;
+pub(crate) mod git;
 pub(crate) mod llm;
+pub(crate) mod policy;
+pub(crate) mod research;
+pub(crate) mod retrieval;
 pub(crate) mod scraper;
 pub(crate) mod search;
@@ -0,0 +1,443 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::prelude::*;
+use mirabel_core::models::domain_rule::DomainAccess;
+use mirabel_core::models::domain_rule::DomainRule;
+
+use deadpool_diesel::postgres::Pool;
+use diesel::prelude::*;
+use log::debug;
+use log::warn;
+use reqwest::Client;
+use reqwest::Url;
+use thiserror::Error;
+use tokio::sync::Mutex;
+use tokio::sync::OwnedSemaphorePermit;
+use tokio::sync::Semaphore;
+use tokio::time;
+use tokio::time::Instant;
+
+use robots::Robots;
+
+use crate::utils::env::parse_env;
+
+pub mod robots;
+
+pub const USER_AGENT_ENV: &str = "FETCH_USER_AGENT";
+const DOMAIN_INTERVAL_MS_ENV: &str = "FETCH_DOMAIN_INTERVAL_MS";
+const DOMAIN_CONCURRENCY_ENV: &str = "FETCH_DOMAIN_CONCURRENCY";
+const RESPECT_ROBOTS_ENV: &str = "FETCH_RESPECT_ROBOTS";
+const ROBOTS_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a robots.txt is trusted
+const ROBOTS_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// How long a site whose robots.txt could not be read is left alone
+const ROBOTS_RETRY: Duration = Duration::from_secs(10 * 60);
+/// Crawl delays asked for by sites are followed up to this
+const MAX_CRAWL_DELAY: Duration = Duration::from_secs(30);
+/// Bigger robots.txt files are cut, as RFC 9309 allows
+const MAX_ROBOTS_BYTES: usize = 500 * 1024;
+
+/// Why a page is not fetched
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum FetchDenial {
+    #[error("only http and https pages are fetched, not {0}")]
+    Scheme(String),
+    #[error("{0} is on the deny list of the workspace")]
+    DenyList(String),
+    #[error("{0} is not on the allow list of the workspace")]
+    NotAllowed(String),
+    #[error("the robots.txt of {0} disallows it")]
+    Robots(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchConfig {
+    /// Sent with every request, and looked up in robots.txt files by the
+    /// name before the first `/`
+    pub user_agent: String,
+    /// Time between two requests to the same domain, unless its robots.txt
+    /// asks for more
+    pub domain_interval: Duration,
+    /// Requests to the same domain at a time
+    pub domain_concurrency: usize,
+    pub respect_robots: bool,
+}
+
+impl Default for FetchConfig {
+    fn default() -> Self {
+        Self {
+            user_agent: default_user_agent(),
+            domain_interval: Duration::from_secs(1),
+            domain_concurrency: 2,
+            respect_robots: true,
+        }
+    }
+}
+
+impl FetchConfig {
+    pub fn from_env() -> Result<Self> {
+        let default = Self::default();
+        Ok(Self {
+            user_agent: env::var(USER_AGENT_ENV).unwrap_or(default.user_agent),
+            domain_interval: parse_env(DOMAIN_INTERVAL_MS_ENV)?
+                .map(Duration::from_millis)
+                .unwrap_or(default.domain_interval),
+            domain_concurrency: parse_env(DOMAIN_CONCURRENCY_ENV)?
+                .map(|concurrency: usize| concurrency.max(1))
+                .unwrap_or(default.domain_concurrency),
+            respect_robots: env::var(RESPECT_ROBOTS_ENV)
+                .map(|value| value != "false")
+                .unwrap_or(default.respect_robots),
+        })
+    }
+
+    /// The name crawlers go by in robots.txt files
+    pub fn product(&self) -> &str {
+        self.user_agent.split(['/', ' ']).next().unwrap_or_default()
+    }
+}
+
+pub fn default_user_agent() -> String {
+    format!("Mirabel/{}", env!("CARGO_PKG_VERSION"))
+}
+
+/// Pacing of the requests to a single domain
+struct DomainLimit {
+    slots: Arc<Semaphore>,
+    next_at: Mutex<Instant>,
+}
+
+impl DomainLimit {
+    fn new(concurrency: usize) -> Self {
+        Self {
+            slots: Arc::new(Semaphore::new(concurrency)),
+            next_at: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Waits for a slot, and then for `interval` to pass since the request
+    /// before
+    async fn take(&self, interval: Duration) -> Result<OwnedSemaphorePermit> {
+        let slot = self
+            .slots
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|err| Error::Generic(err.to_string()))?;
+        let at = {
+            let mut next_at = self.next_at.lock().await;
+            let at = (*next_at).max(Instant::now());
+            *next_at = at + interval;
+            at
+        };
+        time::sleep_until(at).await;
+        Ok(slot)
+    }
+}
+
+struct CachedRobots {
+    robots: Arc<Robots>,
+    expires_at: Instant,
+}
+
+/// Permission to fetch a page, the next request to the domain waits until it
+/// is dropped when the domain is busy
+pub struct FetchPermit {
+    _slot: OwnedSemaphorePermit,
+}
+
+/// Decides which pages may be fetched and paces the requests: the allow and
+/// deny lists of the workspace and robots.txt are followed, and every domain
+/// gets a limited number of requests at a time, spaced out.
+pub struct FetchPolicy {
+    pool: Pool,
+    config: FetchConfig,
+    client: Client,
+    robots: Mutex<HashMap<String, CachedRobots>>,
+    domains: Mutex<HashMap<String, Arc<DomainLimit>>>,
+}
+
+impl FetchPolicy {
+    pub fn new(pool: Pool, config: FetchConfig) -> Result<Self> {
+        let client = Client::builder()
+            .user_agent(config.user_agent.clone())
+            .timeout(ROBOTS_TIMEOUT)
+            .build()?;
+        Ok(Self {
+            pool,
+            config,
+            client,
+            robots: Mutex::new(HashMap::new()),
+            domains: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Waits until `url` may be fetched for the workspace, or tells why it
+    /// may not
+    pub async fn permit(&self, workspace_id: &str, url: &str) -> Result<FetchPermit> {
+        let (host, interval) = self
+            .allowed(workspace_id, url, self.config.respect_robots)
+            .await?;
+        let limit = self
+            .domains
+            .lock()
+            .await
+            .entry(host)
+            .or_insert_with(|| Arc::new(DomainLimit::new(self.config.domain_concurrency)))
+            .clone();
+        Ok(FetchPermit {
+            _slot: limit.take(interval).await?,
+        })
+    }
+
+    /// Tells why a page that was already loaded may not be used, e.g. the
+    /// one a fetch was redirected to. Nothing is waited for.
+    pub async fn check(&self, workspace_id: &str, url: &str) -> Result<()> {
+        self.allowed(workspace_id, url, self.config.respect_robots)
+            .await
+            .map(|_| ())
+    }
+
+    /// Tells why a page read before, e.g. one from a cache, may no longer be
+    /// used by the workspace. Only its lists are asked, nothing is fetched.
+    pub async fn check_lists(&self, workspace_id: &str, url: &str) -> Result<()> {
+        self.allowed(workspace_id, url, false).await.map(|_| ())
+    }
+
+    /// The host of `url` and how long to wait between two requests to it,
+    /// when the workspace may fetch it
+    async fn allowed(
+        &self,
+        workspace_id: &str,
+        url: &str,
+        respect_robots: bool,
+    ) -> Result<(String, Duration)> {
+        let parsed = Url::parse(url).map_err(|err| Error::BadRequest(format!("{url}: {err}")))?;
+        let denied = |reason| Error::FetchDenied {
+            url: url.to_string(),
+            reason,
+        };
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(denied(FetchDenial::Scheme(parsed.scheme().to_string())));
+        }
+        let host = parsed.host_str().unwrap_or_default().to_lowercase();
+        if let Some(reason) = check_rules(&self.rules(workspace_id).await?, &host) {
+            return Err(denied(reason));
+        }
+
+        let mut interval = self.config.domain_interval;
+        if respect_robots {
+            let robots = self.robots(&parsed).await;
+            let path = match parsed.query() {
+                Some(query) => format!("{}?{query}", parsed.path()),
+                None => parsed.path().to_string(),
+            };
+            if !robots.allows(self.config.product(), &path) {
+                return Err(denied(FetchDenial::Robots(host)));
+            }
+            if let Some(delay) = robots.crawl_delay(self.config.product()) {
+                interval = interval.max(delay.min(MAX_CRAWL_DELAY));
+            }
+        }
+        Ok((host, interval))
+    }
+
+    async fn rules(&self, workspace_id: &str) -> Result<Vec<DomainRule>> {
+        use mirabel_core::schema::domain_rules::dsl as dr;
+
+        let workspace_id = workspace_id.to_string();
+        let conn = self.pool.get().await?;
+        Ok(conn
+            .interact(move |conn| {
+                dr::domain_rules
+                    .filter(dr::workspace_id.eq(&workspace_id))
+                    .load::<DomainRule>(conn)
+            })
+            .await??)
+    }
+
+    /// The robots.txt of the site of `url`, fetched once a day
+    async fn robots(&self, url: &Url) -> Arc<Robots> {
+        let origin = url.origin().ascii_serialization();
+        let cached = self
+            .robots
+            .lock()
+            .await
+            .get(&origin)
+            .filter(|cached| cached.expires_at > Instant::now())
+            .map(|cached| cached.robots.clone());
+        if let Some(robots) = cached {
+            return robots;
+        }
+        let (robots, ttl) = match self.fetch_robots(&origin).await {
+            Ok(robots) => (robots, ROBOTS_TTL),
+            Err(err) => {
+                warn!("Could not read the robots.txt of {origin}, leaving it alone: {err}");
+                (Robots::disallow_all(), ROBOTS_RETRY)
+            }
+        };
+        let robots = Arc::new(robots);
+        self.robots.lock().await.insert(
+            origin,
+            CachedRobots {
+                robots: robots.clone(),
+                expires_at: Instant::now() + ttl,
+            },
+        );
+        robots
+    }
+
+    /// Sites without a robots.txt allow everything, sites that fail to
+    /// answer allow nothing
+    async fn fetch_robots(&self, origin: &str) -> Result<Robots> {
+        let response = self
+            .client
+            .get(format!("{origin}/robots.txt"))
+            .send()
+            .await?;
+        let status = response.status();
+        if status.is_client_error() {
+            debug!("{origin} has no robots.txt ({status})");
+            return Ok(Robots::allow_all());
+        }
+        let body = response.error_for_status()?.bytes().await?;
+        let body = &body[..body.len().min(MAX_ROBOTS_BYTES)];
+        Ok(Robots::parse(&String::from_utf8_lossy(body)))
+    }
+}
+
+/// Denied domains are never fetched, and once a domain is allowed only
+/// allowed domains are
+fn check_rules(rules: &[DomainRule], host: &str) -> Option<FetchDenial> {
+    let covering = |access| {
+        rules
+            .iter()
+            .filter(move |rule| rule.access == access)
+            .find(|rule| rule.covers(host))
+    };
+    if let Some(rule) = covering(DomainAccess::Deny) {
+        return Some(FetchDenial::DenyList(rule.domain.clone()));
+    }
+    let has_allow_list = rules.iter().any(|rule| rule.access == DomainAccess::Allow);
+    if has_allow_list && covering(DomainAccess::Allow).is_none() {
+        return Some(FetchDenial::NotAllowed(host.to_string()));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use deadpool_diesel::postgres::Manager;
+    use deadpool_diesel::postgres::Runtime;
+
+    use crate::driver::search::fixtures::serve;
+
+    /// The database is never reached by these tests
+    fn policy() -> FetchPolicy {
+        let manager = Manager::new("postgres://localhost/unused", Runtime::Tokio1);
+        let pool = Pool::builder(manager).build().unwrap();
+        FetchPolicy::new(pool, FetchConfig::default()).unwrap()
+    }
+
+    fn rule(domain: &str, access: DomainAccess) -> DomainRule {
+        DomainRule::new("workspace".into(), domain.into(), access)
+    }
+
+    #[test]
+    fn test_check_rules() {
+        assert_eq!(check_rules(&[], "example.com"), None);
+
+        let rules = [
+            rule("example.com", DomainAccess::Deny),
+            rule("docs.rs", DomainAccess::Allow),
+            rule("rust-lang.org", DomainAccess::Allow),
+        ];
+        assert_eq!(check_rules(&rules, "docs.rs"), None);
+        assert_eq!(check_rules(&rules, "doc.rust-lang.org"), None);
+        assert_eq!(
+            check_rules(&rules, "www.example.com"),
+            Some(FetchDenial::DenyList("example.com".into()))
+        );
+        assert_eq!(
+            check_rules(&rules, "crates.io"),
+            Some(FetchDenial::NotAllowed("crates.io".into()))
+        );
+
+        // Without an allow list everything but the denied domains is fetched
+        let rules = [rule("example.com", DomainAccess::Deny)];
+        assert_eq!(check_rules(&rules, "crates.io"), None);
+        // Denying wins over allowing
+        let rules = [
+            rule("example.com", DomainAccess::Allow),
+            rule("ads.example.com", DomainAccess::Deny),
+        ];
+        assert!(check_rules(&rules, "ads.example.com").is_some());
+        assert_eq!(check_rules(&rules, "www.example.com"), None);
+    }
+
+    #[test]
+    fn test_product() {
+        let config = |user_agent: &str| FetchConfig {
+            user_agent: user_agent.into(),
+            ..FetchConfig::default()
+        };
+        assert_eq!(
+            config("Mirabel/0.1 (+https://example.com)").product(),
+            "Mirabel"
+        );
+        assert_eq!(config("ResearchBot").product(), "ResearchBot");
+        assert!(FetchConfig::default().user_agent.starts_with("Mirabel/"));
+    }
+
+    #[tokio::test]
+    async fn test_robots() {
+        let (base_url, request) = serve("text/plain", "User-agent: *\nDisallow: /private\n").await;
+        let policy = policy();
+        let url = Url::parse(&format!("{base_url}/private/notes")).unwrap();
+        assert!(!policy.robots(&url).await.allows("Mirabel", url.path()));
+        let head = request.await.unwrap();
+        assert!(head.starts_with("GET /robots.txt "));
+        assert!(head.contains("user-agent: mirabel/"));
+
+        // Cached, the server only answers once
+        assert!(policy.robots(&url).await.allows("Mirabel", "/public"));
+    }
+
+    #[tokio::test]
+    async fn test_robots_unreachable() {
+        let policy = policy();
+        // Nothing listens on the discard port
+        let url = Url::parse("http://127.0.0.1:9/page").unwrap();
+        assert!(!policy.robots(&url).await.allows("Mirabel", "/page"));
+    }
+
+    #[tokio::test]
+    async fn test_domain_limit() {
+        let limit = DomainLimit::new(1);
+        let interval = Duration::from_millis(50);
+        let start = Instant::now();
+        let first = limit.take(interval).await.unwrap();
+        assert!(start.elapsed() < interval);
+
+        // The second request waits for the first to finish and its interval
+        let release = tokio::spawn(async move {
+            time::sleep(Duration::from_millis(100)).await;
+            drop(first);
+        });
+        let _second = limit.take(interval).await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        release.await.unwrap();
+
+        let spaced = DomainLimit::new(3);
+        let start = Instant::now();
+        for _ in 0..3 {
+            let _ = spaced.take(interval).await.unwrap();
+        }
+        assert!(start.elapsed() >= interval * 2);
+    }
+}
@@ -0,0 +1,209 @@
+use std::time::Duration;
+
+/// The rules of a robots.txt as in RFC 9309, plus the common `Crawl-delay`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Robots {
+    groups: Vec<Group>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Group {
+    /// Lowercase product tokens, `*` for every crawler
+    agents: Vec<String>,
+    rules: Vec<Rule>,
+    crawl_delay: Option<Duration>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    allow: bool,
+    pattern: String,
+}
+
+impl Robots {
+    /// Allows everything, for sites without a robots.txt
+    pub fn allow_all() -> Self {
+        Self::default()
+    }
+
+    /// Disallows everything, for sites whose robots.txt could not be read
+    pub fn disallow_all() -> Self {
+        Self {
+            groups: vec![Group {
+                agents: vec!["*".into()],
+                rules: vec![Rule {
+                    allow: false,
+                    pattern: "/".into(),
+                }],
+                crawl_delay: None,
+            }],
+        }
+    }
+
+    /// Lines that cannot be read are skipped, like crawlers do
+    pub fn parse(text: &str) -> Self {
+        let mut groups: Vec<Group> = Vec::new();
+        // Consecutive user agent lines start a single group
+        let mut in_agents = false;
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim().to_lowercase().as_str() {
+                "user-agent" => {
+                    if !in_agents {
+                        groups.push(Group::default());
+                    }
+                    in_agents = true;
+                    if let Some(group) = groups.last_mut() {
+                        group.agents.push(value.to_lowercase());
+                    }
+                }
+                key @ ("allow" | "disallow") => {
+                    in_agents = false;
+                    // An empty disallow allows everything, which is the default
+                    if value.is_empty() {
+                        continue;
+                    }
+                    if let Some(group) = groups.last_mut() {
+                        group.rules.push(Rule {
+                            allow: key == "allow",
+                            pattern: value.to_string(),
+                        });
+                    }
+                }
+                "crawl-delay" => {
+                    in_agents = false;
+                    let delay = value.parse::<f64>().ok().filter(|secs| *secs >= 0.0);
+                    if let (Some(group), Some(delay)) = (groups.last_mut(), delay) {
+                        group.crawl_delay = Some(Duration::from_secs_f64(delay));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Self { groups }
+    }
+
+    /// Whether `agent` may fetch `path`, which includes the query. The
+    /// longest matching rule wins, allowing on a tie.
+    pub fn allows(&self, agent: &str, path: &str) -> bool {
+        if path == "/robots.txt" {
+            return true;
+        }
+        self.groups_for(agent)
+            .flat_map(|group| &group.rules)
+            .filter(|rule| matches(&rule.pattern, path))
+            .max_by_key(|rule| (rule.pattern.len(), rule.allow))
+            .is_none_or(|rule| rule.allow)
+    }
+
+    pub fn crawl_delay(&self, agent: &str) -> Option<Duration> {
+        self.groups_for(agent).find_map(|group| group.crawl_delay)
+    }
+
+    /// The groups naming the agent, or the ones for every crawler when none
+    /// does
+    fn groups_for(&self, agent: &str) -> impl Iterator<Item = &Group> {
+        let agent = agent.to_lowercase();
+        let named = self
+            .groups
+            .iter()
+            .any(|group| group.agents.contains(&agent));
+        let agent = if named { agent } else { "*".to_string() };
+        self.groups
+            .iter()
+            .filter(move |group| group.agents.contains(&agent))
+    }
+}
+
+/// Matches a path against a pattern, where `*` stands for any characters and
+/// a trailing `$` anchors the pattern at the end of the path
+fn matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let Some(rest) = path.strip_prefix(parts[0]) else {
+        return false;
+    };
+    let mut rest = rest;
+    for (i, part) in parts.iter().enumerate().skip(1) {
+        let last = i == parts.len() - 1;
+        if last && anchored {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    !anchored || rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROBOTS: &str = "
+        # Everybody
+        User-agent: *
+        Disallow: /private/
+        Allow: /private/public-*.html$
+        Disallow: /*.pdf$
+        Crawl-delay: 2
+
+        User-Agent: Mirabel
+        user-agent: OtherBot
+        Disallow: /search
+        Allow: /search/about
+        Disallow:
+
+        Sitemap: https://example.com/sitemap.xml
+    ";
+
+    #[test]
+    fn test_parse() {
+        let robots = Robots::parse(ROBOTS);
+        assert_eq!(robots.groups.len(), 2);
+        assert_eq!(robots.groups[1].agents, vec!["mirabel", "otherbot"]);
+        assert_eq!(robots.groups[1].rules.len(), 2);
+        assert_eq!(robots.crawl_delay("SomeBot"), Some(Duration::from_secs(2)));
+        assert_eq!(robots.crawl_delay("mirabel"), None);
+    }
+
+    #[test]
+    fn test_allows() {
+        let robots = Robots::parse(ROBOTS);
+        assert!(robots.allows("SomeBot", "/"));
+        assert!(!robots.allows("SomeBot", "/private/notes.html"));
+        assert!(robots.allows("SomeBot", "/private/public-2024.html"));
+        assert!(!robots.allows("SomeBot", "/private/public-2024.html?print=1"));
+        assert!(!robots.allows("SomeBot", "/papers/rfc.pdf"));
+        assert!(robots.allows("SomeBot", "/papers/rfc.pdf.html"));
+        assert!(robots.allows("SomeBot", "/robots.txt"));
+
+        // A crawler named by a group only follows that group
+        assert!(robots.allows("Mirabel", "/private/notes.html"));
+        assert!(!robots.allows("Mirabel", "/search?q=rust"));
+        assert!(robots.allows("Mirabel", "/search/about"));
+
+        assert!(Robots::allow_all().allows("Mirabel", "/private/"));
+        assert!(!Robots::disallow_all().allows("Mirabel", "/"));
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("/", "/anything"));
+        assert!(matches("/fish*", "/fish.html"));
+        assert!(matches("/*.php$", "/index.php"));
+        assert!(!matches("/*.php$", "/index.php?q=1"));
+        assert!(matches("/a*b*c", "/axxbyyczz"));
+        assert!(!matches("/a*b*c", "/axxcyyb"));
+        assert!(matches("/exact$", "/exact"));
+        assert!(!matches("/exact$", "/exactly"));
+    }
+}
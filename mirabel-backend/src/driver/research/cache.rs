@@ -0,0 +1,220 @@
+use crate::prelude::*;
+use mirabel_core::models::web_cache::CachedPage;
+use mirabel_core::models::web_cache::CachedSearch;
+use mirabel_core::models::web_cache::Freshness;
+
+use chrono::DateTime;
+use chrono::TimeDelta;
+use chrono::Utc;
+use deadpool_diesel::postgres::Pool;
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+
+use crate::driver::search::models::SearchResult;
+use crate::driver::search::normalize_url;
+use crate::utils::env::parse_env;
+
+const SEARCH_TTL_SECS_ENV: &str = "WEB_CACHE_SEARCH_TTL_SECS";
+const PAGE_TTL_SECS_ENV: &str = "WEB_CACHE_PAGE_TTL_SECS";
+const STALE_SECS_ENV: &str = "WEB_CACHE_STALE_SECS";
+
+/// How long answers from the web are used
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheTtls {
+    pub search: TimeDelta,
+    pub page: TimeDelta,
+    /// How long answers are still served after their time to live, while
+    /// they are fetched again in the background
+    pub stale: TimeDelta,
+}
+
+impl Default for CacheTtls {
+    fn default() -> Self {
+        Self {
+            search: TimeDelta::days(1),
+            page: TimeDelta::days(7),
+            stale: TimeDelta::days(7),
+        }
+    }
+}
+
+impl CacheTtls {
+    pub fn from_env() -> Result<Self> {
+        let default = Self::default();
+        Ok(Self {
+            search: parse_env(SEARCH_TTL_SECS_ENV)?
+                .map(TimeDelta::seconds)
+                .unwrap_or(default.search),
+            page: parse_env(PAGE_TTL_SECS_ENV)?
+                .map(TimeDelta::seconds)
+                .unwrap_or(default.page),
+            stale: parse_env(STALE_SECS_ENV)?
+                .map(TimeDelta::seconds)
+                .unwrap_or(default.stale),
+        })
+    }
+}
+
+/// Search results and scraped pages, kept per workspace so its sessions do
+/// not go out to the web for what another one already looked up
+#[derive(Clone)]
+pub struct WebCache {
+    pool: Pool,
+    ttls: CacheTtls,
+}
+
+impl WebCache {
+    pub fn new(pool: Pool, ttls: CacheTtls) -> Self {
+        Self { pool, ttls }
+    }
+
+    pub async fn search(
+        &self,
+        workspace_id: &str,
+        query: &str,
+        page: i32,
+    ) -> Result<Option<(Freshness, Vec<SearchResult>)>> {
+        use mirabel_core::schema::search_cache::dsl as sc;
+
+        let workspace_id = workspace_id.to_string();
+        let key = query_key(query);
+        let conn = self.pool.get().await?;
+        let cached = conn
+            .interact(move |conn| {
+                sc::search_cache
+                    .filter(sc::workspace_id.eq(&workspace_id))
+                    .filter(sc::query_key.eq(&key))
+                    .filter(sc::page.eq(page))
+                    .first::<CachedSearch>(conn)
+                    .optional()
+            })
+            .await??;
+        let Some(cached) = cached else {
+            return Ok(None);
+        };
+        let freshness = self.freshness(cached.fetched_at, self.ttls.search);
+        Ok(Some((freshness, serde_json::from_value(cached.results)?)))
+    }
+
+    pub async fn store_search(
+        &self,
+        workspace_id: &str,
+        query: &str,
+        page: i32,
+        results: &[SearchResult],
+    ) -> Result<()> {
+        use mirabel_core::schema::search_cache::dsl as sc;
+
+        let cached = CachedSearch {
+            workspace_id: workspace_id.to_string(),
+            query_key: query_key(query),
+            page,
+            query: query.to_string(),
+            results: serde_json::to_value(results)?,
+            fetched_at: Utc::now(),
+        };
+        let conn = self.pool.get().await?;
+        conn.interact(move |conn| {
+            diesel::insert_into(sc::search_cache)
+                .values(&cached)
+                .on_conflict((sc::workspace_id, sc::query_key, sc::page))
+                .do_update()
+                .set((
+                    sc::query.eq(excluded(sc::query)),
+                    sc::results.eq(excluded(sc::results)),
+                    sc::fetched_at.eq(excluded(sc::fetched_at)),
+                ))
+                .execute(conn)
+        })
+        .await??;
+        Ok(())
+    }
+
+    pub async fn page(
+        &self,
+        workspace_id: &str,
+        url: &str,
+    ) -> Result<Option<(Freshness, CachedPage)>> {
+        use mirabel_core::schema::page_cache::dsl as pc;
+
+        let workspace_id = workspace_id.to_string();
+        let key = normalize_url(url);
+        let conn = self.pool.get().await?;
+        let cached = conn
+            .interact(move |conn| {
+                pc::page_cache
+                    .filter(pc::workspace_id.eq(&workspace_id))
+                    .filter(pc::url_key.eq(&key))
+                    .first::<CachedPage>(conn)
+                    .optional()
+            })
+            .await??;
+        Ok(cached.map(|page| (self.freshness(page.fetched_at, self.ttls.page), page)))
+    }
+
+    pub async fn store_page(
+        &self,
+        workspace_id: &str,
+        url: &str,
+        title: &str,
+        html: String,
+        markdown: String,
+    ) -> Result<()> {
+        use mirabel_core::schema::page_cache::dsl as pc;
+
+        let cached = CachedPage {
+            workspace_id: workspace_id.to_string(),
+            url_key: normalize_url(url),
+            url: url.to_string(),
+            title: title.to_string(),
+            html,
+            markdown,
+            fetched_at: Utc::now(),
+        };
+        let conn = self.pool.get().await?;
+        conn.interact(move |conn| {
+            diesel::insert_into(pc::page_cache)
+                .values(&cached)
+                .on_conflict((pc::workspace_id, pc::url_key))
+                .do_update()
+                .set((
+                    pc::url.eq(excluded(pc::url)),
+                    pc::title.eq(excluded(pc::title)),
+                    pc::html.eq(excluded(pc::html)),
+                    pc::markdown.eq(excluded(pc::markdown)),
+                    pc::fetched_at.eq(excluded(pc::fetched_at)),
+                ))
+                .execute(conn)
+        })
+        .await??;
+        Ok(())
+    }
+
+    fn freshness(&self, fetched_at: DateTime<Utc>, ttl: TimeDelta) -> Freshness {
+        Freshness::of(fetched_at, Utc::now(), ttl, self.ttls.stale)
+    }
+}
+
+/// Queries that only differ in case and spacing are the same search
+fn query_key(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_key() {
+        assert_eq!(query_key("  Rust  Tokio\truntime "), "rust tokio runtime");
+        assert_eq!(
+            query_key("rust tokio runtime"),
+            query_key("RUST tokio Runtime")
+        );
+        assert_ne!(query_key("rust tokio"), query_key("tokio rust"));
+    }
+}
@@ -0,0 +1,281 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use fantoccini::Client;
+use futures::future::join_all;
+use log::debug;
+use log::warn;
+use tokio::sync::Mutex;
+
+use crate::prelude::*;
+use mirabel_core::models::web_cache::Freshness;
+
+use crate::driver::browser::Browsers;
+use crate::driver::converter::Converter;
+use crate::driver::converter::html::Html;
+use crate::driver::policy::FetchPolicy;
+use crate::driver::scraper::Page;
+use crate::driver::scraper::ScrapeOptions;
+use crate::driver::scraper::Scraper;
+use crate::driver::scraper::WaitFor;
+use crate::driver::search::SearchEngines;
+use crate::driver::search::local::LocalIndex;
+use crate::driver::search::models::SearchResult;
+use cache::WebCache;
+
+pub mod cache;
+
+/// How long a page may take to load, and how long to wait for a browser
+/// while the others are busy
+const PAGE_TIMEOUT: Duration = Duration::from_secs(20);
+const BROWSER_TIMEOUT: Duration = Duration::from_secs(60);
+/// How long a page has to fetch nothing more to count as rendered
+const NETWORK_IDLE: Duration = Duration::from_millis(500);
+
+/// Looks things up on the web: searches and reads the pages found with the
+/// browsers. Answers from the web are cached per workspace, stale ones are
+/// served while they are fetched again in the background. Pages are only
+/// read when the fetch policy lets them.
+#[derive(Clone)]
+pub struct WebResearch {
+    engines: Arc<SearchEngines>,
+    browsers: Arc<Browsers>,
+    policy: Arc<FetchPolicy>,
+    index: LocalIndex,
+    cache: WebCache,
+    /// Cache keys fetched again in the background right now
+    refreshing: Arc<Mutex<HashSet<String>>>,
+}
+
+/// A page read for research. When the page could not be scraped, the
+/// summary of the search result stands in for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Source {
+    pub title: String,
+    pub url: String,
+    pub markdown: String,
+}
+
+impl WebResearch {
+    pub fn new(
+        engines: SearchEngines,
+        browsers: Browsers,
+        policy: FetchPolicy,
+        index: LocalIndex,
+        cache: WebCache,
+    ) -> Self {
+        Self {
+            engines: Arc::new(engines),
+            browsers: Arc::new(browsers),
+            policy: Arc::new(policy),
+            index,
+            cache,
+            refreshing: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    pub fn engines(&self) -> &SearchEngines {
+        &self.engines
+    }
+
+    pub fn browsers(&self) -> &Browsers {
+        &self.browsers
+    }
+
+    /// The top `count` results of the first page the workspace may read,
+    /// every page only once, and why the results before them may not be read
+    pub async fn search(
+        &self,
+        workspace_id: &str,
+        query: &str,
+        count: usize,
+    ) -> Result<(Vec<SearchResult>, Vec<String>)> {
+        let results = match self.cache.search(workspace_id, query, 1).await {
+            Ok(Some((Freshness::Fresh, results))) => results,
+            Ok(Some((Freshness::Stale, results))) => {
+                let this = self.clone();
+                let (workspace_id, query) = (workspace_id.to_string(), query.to_string());
+                self.revalidate(format!("search:{workspace_id}:{query}"), async move {
+                    this.fetch_search(&workspace_id, &query).await.map(|_| ())
+                })
+                .await;
+                results
+            }
+            Ok(_) => self.fetch_search(workspace_id, query).await?,
+            Err(err) => {
+                warn!("Could not look up '{query}' in the cache: {err}");
+                self.fetch_search(workspace_id, query).await?
+            }
+        };
+        let mut seen = HashSet::new();
+        let (mut allowed, mut denied) = (Vec::new(), Vec::new());
+        for result in results {
+            if allowed.len() == count {
+                break;
+            }
+            if !seen.insert(result.url.clone()) {
+                continue;
+            }
+            // Not even the summary of a page the workspace denies is used
+            match self.policy.check_lists(workspace_id, &result.url).await {
+                Ok(()) => allowed.push(result),
+                Err(err @ Error::FetchDenied { .. }) => denied.push(err.to_string()),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok((allowed, denied))
+    }
+
+    /// Reads the pages of the results in parallel, as many at once as there
+    /// are browsers in the pool. Pages that may not be fetched are left out,
+    /// with why they may not.
+    pub async fn read(
+        &self,
+        workspace_id: &str,
+        results: Vec<SearchResult>,
+    ) -> (Vec<Source>, Vec<String>) {
+        let read = join_all(results.into_iter().map(|result| async move {
+            let markdown = match self.page(workspace_id, &result).await {
+                Ok(markdown) if !markdown.trim().is_empty() => markdown,
+                Ok(_) => result.summary,
+                Err(err @ Error::FetchDenied { .. }) => return Err(err.to_string()),
+                Err(err) => {
+                    warn!("Could not read {}, using its summary: {err}", result.url);
+                    result.summary
+                }
+            };
+            Ok(Source {
+                title: result.title,
+                url: result.url,
+                markdown,
+            })
+        }))
+        .await;
+        let (mut sources, mut denied) = (Vec::new(), Vec::new());
+        for source in read {
+            match source {
+                Ok(source) => sources.push(source),
+                Err(reason) => denied.push(reason),
+            }
+        }
+        (sources, denied)
+    }
+
+    /// Takes a screenshot of a page, it is not cached
+    pub async fn capture(&self, workspace_id: &str, url: &str) -> Result<Page> {
+        self.scrape(workspace_id, url, true).await
+    }
+
+    /// The markdown of the page of a result, from the cache when it is there.
+    /// Cached pages of domains the workspace denied since are not served.
+    async fn page(&self, workspace_id: &str, result: &SearchResult) -> Result<String> {
+        self.policy.check_lists(workspace_id, &result.url).await?;
+        match self.cache.page(workspace_id, &result.url).await {
+            Ok(Some((Freshness::Fresh, page))) => return Ok(page.markdown),
+            Ok(Some((Freshness::Stale, page))) => {
+                let this = self.clone();
+                let (workspace_id, result) = (workspace_id.to_string(), result.clone());
+                self.revalidate(
+                    format!("page:{workspace_id}:{}", page.url_key),
+                    async move { this.fetch_page(&workspace_id, &result).await.map(|_| ()) },
+                )
+                .await;
+                return Ok(page.markdown);
+            }
+            Ok(_) => {}
+            Err(err) => warn!("Could not look up {} in the cache: {err}", result.url),
+        }
+        self.fetch_page(workspace_id, result).await
+    }
+
+    /// Searches the engines and caches what they found
+    async fn fetch_search(&self, workspace_id: &str, query: &str) -> Result<Vec<SearchResult>> {
+        let page = self
+            .engines
+            .search_with(&self.index.workspace(workspace_id), query.to_string(), 1)
+            .await?;
+        // Nothing found may be a hiccup, the next search tries again
+        if !page.results.is_empty() {
+            self.cache
+                .store_search(workspace_id, query, 1, &page.results)
+                .await
+                .unwrap_or_else(|err| warn!("Could not cache the search '{query}': {err}"));
+        }
+        Ok(page.results)
+    }
+
+    /// Scrapes a page, caches it and adds it to the local index. Only the
+    /// main content of the page is kept as markdown.
+    async fn fetch_page(&self, workspace_id: &str, result: &SearchResult) -> Result<String> {
+        let page = self.scrape(workspace_id, &result.url, false).await?;
+        let markdown = Converter::<Html>::from_html(page.article().html)
+            .to_md()?
+            .to_string();
+        if markdown.trim().is_empty() {
+            return Ok(markdown);
+        }
+        self.cache
+            .store_page(
+                workspace_id,
+                &result.url,
+                &result.title,
+                page.html,
+                markdown.clone(),
+            )
+            .await
+            .unwrap_or_else(|err| warn!("Could not cache {}: {err}", result.url));
+        self.index
+            .add(
+                workspace_id.to_string(),
+                result.url.clone(),
+                result.title.clone(),
+                markdown.clone(),
+            )
+            .await
+            .unwrap_or_else(|err| warn!("Could not index {}: {err}", result.url));
+        Ok(markdown)
+    }
+
+    /// Runs `refresh` in the background, unless the same key is already
+    /// being refreshed
+    async fn revalidate<F>(&self, key: String, refresh: F)
+    where
+        F: Future<Output = Result<()>> + Send + 'static,
+    {
+        if !self.refreshing.lock().await.insert(key.clone()) {
+            return;
+        }
+        let refreshing = self.refreshing.clone();
+        tokio::spawn(async move {
+            match refresh.await {
+                Ok(()) => debug!("Refreshed {key} in the cache"),
+                Err(err) => warn!("Could not refresh {key} in the cache: {err}"),
+            }
+            refreshing.lock().await.remove(&key);
+        });
+    }
+
+    /// Scrapes a page once the fetch policy lets it, holding a browser only
+    /// while the page loads. Pages redirected to must be let by the policy
+    /// too.
+    async fn scrape(&self, workspace_id: &str, url: &str, screenshot: bool) -> Result<Page> {
+        let _permit = self.policy.permit(workspace_id, url).await?;
+        let browser = self.browsers.acquire_timeout(BROWSER_TIMEOUT).await?;
+        let options = ScrapeOptions {
+            timeout: PAGE_TIMEOUT,
+            wait: vec![WaitFor::NetworkIdle { idle: NETWORK_IDLE }],
+            screenshot,
+            ..ScrapeOptions::default()
+        };
+        let page = Scraper::new(Client::clone(&browser))
+            .scrape(url, &options)
+            .await?;
+        // The browser follows redirects to wherever they lead
+        if page.url != url {
+            self.policy.check(workspace_id, &page.url).await?;
+        }
+        debug!("Read {} characters of HTML from {url}", page.html.len());
+        Ok(page)
+    }
+}
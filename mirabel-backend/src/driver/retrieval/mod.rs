@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use mirabel_core::utils::vector::cosine_similarity;
+use tokio::sync::OnceCell;
+
+use crate::prelude::*;
+
+use crate::driver::converter::chunk::Chunk;
+use crate::driver::llm::Embedder;
+
+/// How quickly more of the same word stops making a chunk match better
+const K1: f32 = 1.2;
+/// How much long chunks are held back for matching words by chance
+const B: f32 = 0.75;
+/// Keeps chunks about something else out of the meaning ranking
+const MIN_SIMILARITY: f32 = 0.5;
+/// Dampens the lead of top ranks when word and meaning rankings are fused
+const RANK_OFFSET: f32 = 60.0;
+
+/// Picks the chunks of a document that answer a query, so a model gets the
+/// parts of a long document it needs rather than all of it. Words are ranked
+/// with BM25, meaning by the embeddings of an [`Embedder`].
+pub struct Retriever {
+    chunks: Vec<Chunk>,
+    /// How often every term is in each chunk
+    frequencies: Vec<HashMap<String, usize>>,
+    lengths: Vec<usize>,
+    /// The chunks every term is in
+    chunk_counts: HashMap<String, usize>,
+    average_length: f32,
+    /// Made when the chunks are first searched by meaning
+    embeddings: OnceCell<Vec<Vec<f32>>>,
+}
+
+impl Retriever {
+    pub fn new(chunks: Vec<Chunk>) -> Self {
+        let mut frequencies = Vec::with_capacity(chunks.len());
+        let mut lengths = Vec::with_capacity(chunks.len());
+        let mut chunk_counts: HashMap<String, usize> = HashMap::new();
+        for chunk in &chunks {
+            let terms = terms(&chunk.text);
+            let mut frequency: HashMap<String, usize> = HashMap::new();
+            lengths.push(terms.len());
+            for term in terms {
+                *frequency.entry(term).or_default() += 1;
+            }
+            for term in frequency.keys() {
+                *chunk_counts.entry(term.clone()).or_default() += 1;
+            }
+            frequencies.push(frequency);
+        }
+        let average_length = match lengths.is_empty() {
+            true => 0.0,
+            false => lengths.iter().sum::<usize>() as f32 / lengths.len() as f32,
+        };
+        Self {
+            chunks,
+            frequencies,
+            lengths,
+            chunk_counts,
+            average_length,
+            embeddings: OnceCell::new(),
+        }
+    }
+
+    /// A retriever of chunks embedded before, one embedding for every chunk
+    /// in the same order. Searching by meaning then only embeds the query.
+    pub fn with_embeddings(chunks: Vec<Chunk>, embeddings: Vec<Vec<f32>>) -> Self {
+        Self {
+            embeddings: OnceCell::new_with(Some(embeddings)),
+            ..Self::new(chunks)
+        }
+    }
+
+    pub fn chunks(&self) -> &[Chunk] {
+        &self.chunks
+    }
+
+    /// The `k` chunks whose words match the query best, the best first.
+    /// Chunks without any word of the query are left out.
+    pub fn top(&self, query: &str, k: usize) -> Vec<(&Chunk, f32)> {
+        self.keyword_ranking(query)
+            .into_iter()
+            .take(k)
+            .map(|(index, score)| (&self.chunks[index], score))
+            .collect()
+    }
+
+    /// Like [`Retriever::top`], but also finds chunks that say what the query
+    /// asks in other words. Both rankings are fused by their ranks. Chunks
+    /// neither sharing a word with the query nor close to it in meaning are
+    /// left out. The chunks are embedded on the first search.
+    pub async fn top_semantic<E: Embedder + Sync>(
+        &self,
+        embedder: &E,
+        query: &str,
+        k: usize,
+    ) -> Result<Vec<(&Chunk, f32)>> {
+        if self.chunks.is_empty() {
+            return Ok(Vec::new());
+        }
+        let embeddings = self
+            .embeddings
+            .get_or_try_init(|| {
+                embedder.embed(self.chunks.iter().map(|chunk| chunk.text.clone()).collect())
+            })
+            .await?;
+        let embedding = embedder
+            .embed(vec![query.to_string()])
+            .await?
+            .pop()
+            .unwrap_or_default();
+        let mut semantic: Vec<(usize, f32)> = embeddings
+            .iter()
+            .map(|chunk| cosine_similarity(&embedding, chunk))
+            .enumerate()
+            .filter(|(_, similarity)| *similarity >= MIN_SIMILARITY)
+            .collect();
+        semantic.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        let keyword = self.keyword_ranking(query);
+
+        let mut scores: HashMap<usize, f32> = HashMap::new();
+        for ranking in [keyword, semantic] {
+            for (rank, (index, _)) in ranking.into_iter().enumerate() {
+                *scores.entry(index).or_default() += 1.0 / (RANK_OFFSET + rank as f32 + 1.0);
+            }
+        }
+        let mut ranked: Vec<(usize, f32)> = scores.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        Ok(ranked
+            .into_iter()
+            .take(k)
+            .map(|(index, score)| (&self.chunks[index], score))
+            .collect())
+    }
+
+    /// Indexes of the chunks with any word of the query and their BM25
+    /// scores, the best first
+    fn keyword_ranking(&self, query: &str) -> Vec<(usize, f32)> {
+        let query: HashSet<String> = terms(query).into_iter().collect();
+        let count = self.chunks.len() as f32;
+        let mut ranking: Vec<(usize, f32)> = self
+            .frequencies
+            .iter()
+            .zip(&self.lengths)
+            .enumerate()
+            .filter_map(|(index, (frequency, length))| {
+                let score: f32 = query
+                    .iter()
+                    .filter_map(|term| {
+                        let found = *frequency.get(term)? as f32;
+                        let chunks = self.chunk_counts[term] as f32;
+                        let rarity = ((count - chunks + 0.5) / (chunks + 0.5) + 1.0).ln();
+                        let norm = 1.0 - B + B * *length as f32 / self.average_length;
+                        Some(rarity * found * (K1 + 1.0) / (found + K1 * norm))
+                    })
+                    .sum();
+                (score > 0.0).then_some((index, score))
+            })
+            .collect();
+        ranking.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        ranking
+    }
+}
+
+/// Lowercase words and numbers. Inline HTML, like the page anchors of PDFs,
+/// holds no words.
+fn terms(text: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    let mut term = String::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let (c, length) = match tag_end(rest) {
+            Some(end) => (' ', end),
+            None => (c, c.len_utf8()),
+        };
+        match c.is_alphanumeric() {
+            true => term.extend(c.to_lowercase()),
+            false if !term.is_empty() => terms.push(std::mem::take(&mut term)),
+            false => {}
+        }
+        rest = &rest[length..];
+    }
+    if !term.is_empty() {
+        terms.push(term);
+    }
+    terms
+}
+
+/// Where a tag at the start of the text ends, `<a id="page-2">` or `</a>`
+fn tag_end(text: &str) -> Option<usize> {
+    let name = text.strip_prefix('<')?.chars().next()?;
+    if !name.is_ascii_alphabetic() && name != '/' {
+        return None;
+    }
+    let end = text.find('>')?;
+    (!text[1..end].contains(['\n', '<'])).then_some(end + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+
+    use super::*;
+
+    use crate::driver::converter::Converter;
+    use crate::driver::converter::chunk::ChunkOptions;
+    use crate::driver::converter::markdown::Markdown;
+
+    const GUIDE: &str = "# Install\n\nDownload the installer and run it.\n\n\
+        # Configure\n\nSet the port in the config file. The port defaults to 8080.\n\n\
+        # Vehicles\n\nThe car is parked in the garage.";
+
+    fn retriever() -> Retriever {
+        Retriever::new(Converter::<Markdown>::from_md(GUIDE.into()).chunks(ChunkOptions::default()))
+    }
+
+    /// Texts about the same thing get the same vector
+    struct Topics;
+
+    #[async_trait]
+    impl Embedder for Topics {
+        async fn embed(&self, inputs: Vec<String>) -> Result<Vec<Vec<f32>>> {
+            Ok(inputs
+                .iter()
+                .map(|input| {
+                    let input = input.to_lowercase();
+                    let vehicle = ["car", "automobile"]
+                        .iter()
+                        .any(|word| input.contains(word));
+                    vec![vehicle as u8 as f32, !vehicle as u8 as f32]
+                })
+                .collect())
+        }
+    }
+
+    #[test]
+    fn test_top() {
+        let retriever = retriever();
+        // "it" is in the install section too, which ranks lower
+        let top = retriever.top("Which PORT does it use?", 3);
+        let headings: Vec<&str> = top
+            .iter()
+            .map(|(chunk, _)| chunk.headings[0].as_str())
+            .collect();
+        assert_eq!(headings, ["Configure", "Install"]);
+        assert!(retriever.top("automobile", 3).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_top_semantic() {
+        let retriever = retriever();
+        let top = retriever
+            .top_semantic(&Topics, "Where is my automobile?", 3)
+            .await
+            .unwrap();
+        // The other sections are about something else
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].0.headings, ["Vehicles"]);
+    }
+
+    #[test]
+    fn test_terms() {
+        assert_eq!(
+            terms("<a id=\"page-2\"></a>\n\nThe Ports, 8080 & 1 < 2 > 0"),
+            ["the", "ports", "8080", "1", "2", "0"]
+        );
+    }
+}
@@ -1,11 +1,91 @@
 use log::debug;
 use log::warn;
+use reqwest::Url;
+use scraper::Html;
+use scraper::Selector;
+use std::collections::HashSet;
 use std::time::Duration;
+use std::time::Instant;
 use tokio::time;
 
 use crate::prelude::*;
 
 use fantoccini::Client;
+use fantoccini::Locator;
+use readability::Article;
+
+pub mod readability;
+
+/// How often the page is asked whether it is done loading
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How long content shown lazily gets to load after scrolling
+const SCROLL_PAUSE: Duration = Duration::from_millis(500);
+const SCROLL_TO_BOTTOM: &str =
+    "window.scrollTo(0, document.body.scrollHeight); return document.body.scrollHeight;";
+const LOAD_STATE: &str =
+    "return [document.readyState, performance.getEntriesByType('resource').length];";
+
+/// What to wait for before a page counts as loaded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WaitFor {
+    /// An element matching the CSS selector is on the page
+    Selector(String),
+    /// The page loaded and fetched nothing more for `idle`
+    NetworkIdle { idle: Duration },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScrapeOptions {
+    /// How long loading and each wait condition may take. When it runs out,
+    /// the page is scraped as far as it got.
+    pub timeout: Duration,
+    pub wait: Vec<WaitFor>,
+    /// Times the page is scrolled to the bottom, to load content shown
+    /// lazily. Stops early once the page stops growing.
+    pub scrolls: u32,
+    /// Whether to take a PNG screenshot of the top of the page
+    pub screenshot: bool,
+}
+
+impl Default for ScrapeOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(20),
+            wait: Vec::new(),
+            scrolls: 0,
+            screenshot: false,
+        }
+    }
+}
+
+/// A scraped page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Page {
+    /// Where the page ended up, after redirects
+    pub url: String,
+    pub html: String,
+    pub screenshot: Option<Vec<u8>>,
+}
+
+impl Page {
+    pub fn article(&self) -> Article {
+        readability::extract(&self.html)
+    }
+
+    pub fn links(&self) -> Vec<Link> {
+        match Url::parse(&self.url) {
+            Ok(base) => links(&self.html, &base),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+/// A link to another page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    pub url: String,
+    pub text: String,
+}
 
 pub struct Scraper {
     client: Client,
@@ -16,8 +96,9 @@ impl Scraper {
         Self { client }
     }
 
-    pub async fn scrape(&self, url: &str, timeout: Duration) -> Result<String> {
+    pub async fn scrape(&self, url: &str, options: &ScrapeOptions) -> Result<Page> {
         debug!("Scraping URL: {url}");
+        let timeout = options.timeout;
         match time::timeout(timeout, self.client.goto(url)).await {
             Ok(result) => {
                 result?;
@@ -26,6 +107,216 @@ impl Scraper {
                 warn!("Navigation timeout after {timeout:?} - proceeding with partial page");
             }
         }
-        Ok(self.client.source().await?)
+        for condition in &options.wait {
+            if let Err(err) = self.wait_for(condition, timeout).await {
+                warn!("Waited for {condition:?} in vain - proceeding with partial page: {err}");
+            }
+        }
+        if options.scrolls > 0 {
+            self.scroll(options.scrolls).await?;
+        }
+        let html = self.client.source().await?;
+        let screenshot = match options.screenshot {
+            true => Some(self.client.screenshot().await?),
+            false => None,
+        };
+        let url = match self.client.current_url().await {
+            Ok(current) => current.to_string(),
+            Err(_) => url.to_string(),
+        };
+        Ok(Page {
+            url,
+            html,
+            screenshot,
+        })
+    }
+
+    async fn wait_for(&self, condition: &WaitFor, timeout: Duration) -> Result<()> {
+        match condition {
+            WaitFor::Selector(selector) => {
+                self.client
+                    .wait()
+                    .at_most(timeout)
+                    .every(POLL_INTERVAL)
+                    .for_element(Locator::Css(selector))
+                    .await?;
+            }
+            WaitFor::NetworkIdle { idle } => self.wait_for_idle(*idle, timeout).await?,
+        }
+        Ok(())
+    }
+
+    /// Waits until the page is loaded and no resource was fetched for `idle`
+    async fn wait_for_idle(&self, idle: Duration, timeout: Duration) -> Result<()> {
+        let start = Instant::now();
+        let mut last = (String::new(), 0);
+        let mut since = Instant::now();
+        while start.elapsed() < timeout {
+            let state = self.client.execute(LOAD_STATE, Vec::new()).await?;
+            let current = (
+                state[0].as_str().unwrap_or_default().to_string(),
+                state[1].as_u64().unwrap_or_default(),
+            );
+            if current != last {
+                last = current;
+                since = Instant::now();
+            } else if last.0 == "complete" && since.elapsed() >= idle {
+                return Ok(());
+            }
+            time::sleep(POLL_INTERVAL).await;
+        }
+        Err(Error::Generic(format!(
+            "The page was still loading after {timeout:?}"
+        )))
+    }
+
+    /// Scrolls to the bottom until the page stops growing, then back up
+    async fn scroll(&self, scrolls: u32) -> Result<()> {
+        let mut height = self.client.execute(SCROLL_TO_BOTTOM, Vec::new()).await?;
+        for _ in 1..scrolls {
+            time::sleep(SCROLL_PAUSE).await;
+            let grown = self.client.execute(SCROLL_TO_BOTTOM, Vec::new()).await?;
+            if grown == height {
+                break;
+            }
+            height = grown;
+        }
+        time::sleep(SCROLL_PAUSE).await;
+        self.client
+            .execute("window.scrollTo(0, 0);", Vec::new())
+            .await?;
+        Ok(())
+    }
+}
+
+/// The links of a page to other pages, resolved against `base` or the base
+/// the page sets itself. Every page is listed once, with the first text it
+/// is linked with.
+pub fn links(html: &str, base: &Url) -> Vec<Link> {
+    let document = Html::parse_document(html);
+    let base_href = Selector::parse("base[href]").unwrap();
+    let anchors = Selector::parse("a[href]").unwrap();
+    let base = document
+        .select(&base_href)
+        .filter_map(|element| base.join(element.attr("href")?).ok())
+        .next()
+        .unwrap_or(base.clone());
+
+    let mut seen = HashSet::new();
+    document
+        .select(&anchors)
+        .filter_map(|anchor| {
+            let href = anchor.attr("href")?.trim();
+            // Only points somewhere else on the same page
+            if href.starts_with('#') {
+                return None;
+            }
+            let mut url = base.join(href).ok()?;
+            if !matches!(url.scheme(), "http" | "https") {
+                return None;
+            }
+            url.set_fragment(None);
+            let text = anchor
+                .text()
+                .flat_map(str::split_whitespace)
+                .collect::<Vec<_>>()
+                .join(" ");
+            Some(Link {
+                url: url.to_string(),
+                text,
+            })
+        })
+        .filter(|link| seen.insert(link.url.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use fantoccini::ClientBuilder;
+
+    use crate::driver::search::fixtures::serve;
+
+    const ARTICLE: &str = include_str!("fixtures/article.html");
+    const LAZY: &str = include_str!("fixtures/lazy.html");
+
+    #[test]
+    fn test_links() {
+        let base = Url::parse("https://elsewhere.example.com/feed").unwrap();
+        let found: Vec<(String, String)> = links(ARTICLE, &base)
+            .into_iter()
+            .map(|link| (link.url, link.text))
+            .collect();
+        let expected = [
+            ("https://blog.example.com/", "Home"),
+            ("https://blog.example.com/archive", "Archive"),
+            ("https://blog.example.com/about", "About"),
+            (
+                "https://blog.example.com/posts/waker-internals",
+                "How wakers work, in detail and with examples",
+            ),
+            (
+                "https://other.example.org/executors",
+                "Writing a tiny executor, from scratch",
+            ),
+            ("https://doc.rust-lang.org/std/pin/macro.pin.html", "pin!"),
+        ];
+        assert_eq!(
+            found,
+            expected
+                .iter()
+                .map(|(url, text)| (url.to_string(), text.to_string()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    async fn connect() -> Client {
+        let host = std::env::var("WEBDRIVER_HOST").unwrap_or("http://localhost:4444".into());
+        ClientBuilder::native().connect(&host).await.unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "Depends on external service"]
+    async fn test_scrape_article() {
+        let (base_url, _) = serve("text/html", ARTICLE).await;
+        let client = connect().await;
+        let options = ScrapeOptions {
+            screenshot: true,
+            ..ScrapeOptions::default()
+        };
+        let page = Scraper::new(client.clone())
+            .scrape(&base_url, &options)
+            .await
+            .unwrap();
+        client.close().await.unwrap();
+        let article = page.article();
+        assert_eq!(article.title, "Pinning futures without fear");
+        assert!(!article.text.contains("Related posts"));
+        assert!(page.screenshot.unwrap().starts_with(b"\x89PNG"));
+    }
+
+    #[tokio::test]
+    #[ignore = "Depends on external service"]
+    async fn test_scrape_waits_and_scrolls() {
+        let (base_url, _) = serve("text/html", LAZY).await;
+        let client = connect().await;
+        let options = ScrapeOptions {
+            wait: vec![
+                WaitFor::Selector("#rendered".into()),
+                WaitFor::NetworkIdle {
+                    idle: Duration::from_millis(300),
+                },
+            ],
+            scrolls: 5,
+            ..ScrapeOptions::default()
+        };
+        let page = Scraper::new(client.clone())
+            .scrape(&base_url, &options)
+            .await
+            .unwrap();
+        client.close().await.unwrap();
+        assert!(page.html.contains("Rendered after a while"));
+        assert!(page.html.contains("Loaded on scroll 3"));
     }
 }
@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+
+use scraper::ElementRef;
+use scraper::Html;
+use scraper::Selector;
+
+/// Never part of the main content
+const BOILERPLATE_TAGS: [&str; 12] = [
+    "aside", "button", "footer", "form", "header", "iframe", "nav", "noscript", "script", "style",
+    "svg", "template",
+];
+/// Classes and ids of blocks around the main content
+const UNLIKELY: [&str; 16] = [
+    "banner",
+    "breadcrumb",
+    "comment",
+    "cookie",
+    "footer",
+    "masthead",
+    "menu",
+    "modal",
+    "nav",
+    "popup",
+    "promo",
+    "related",
+    "share",
+    "sidebar",
+    "social",
+    "sponsor",
+];
+/// Classes and ids of the main content
+const LIKELY: [&str; 8] = [
+    "article", "body", "content", "entry", "main", "post", "story", "text",
+];
+/// Paragraphs shorter than this are not worth scoring
+const MIN_PARAGRAPH_CHARS: usize = 25;
+
+/// The main content of a page, without navigation, sidebars, comments and
+/// the like
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Article {
+    pub title: String,
+    pub html: String,
+    pub text: String,
+}
+
+/// Finds the main content the way readability does: boilerplate is cut, every
+/// paragraph adds to the score of its parent and half of it to its
+/// grandparent, and the block with the best score that is not mostly links
+/// wins. Falls back to the whole body when nothing scores.
+pub fn extract(html: &str) -> Article {
+    let mut document = Html::parse_document(html);
+    let title = title(&document);
+
+    // Cut before scoring, so boilerplate cannot win
+    let cut: Vec<_> = document
+        .root_element()
+        .descendent_elements()
+        .filter(|element| is_boilerplate(element))
+        .map(|element| element.id())
+        .collect();
+    for id in cut {
+        if let Some(mut node) = document.tree.get_mut(id) {
+            node.detach();
+        }
+    }
+
+    let paragraphs = Selector::parse("p, pre, td, blockquote").unwrap();
+    let mut scores = HashMap::new();
+    for paragraph in document.select(&paragraphs) {
+        let text = collapse(paragraph.text());
+        let chars = text.chars().count();
+        if chars < MIN_PARAGRAPH_CHARS {
+            continue;
+        }
+        let score = 1.0 + text.matches(',').count() as f32 + (chars as f32 / 100.0).min(3.0);
+        let ancestors = paragraph.ancestors().filter_map(ElementRef::wrap).take(2);
+        for (level, ancestor) in ancestors.enumerate() {
+            *scores
+                .entry(ancestor.id())
+                .or_insert_with(|| initial_score(&ancestor)) += score / (level + 1) as f32;
+        }
+    }
+
+    // In document order, so ties go the same way every time
+    let best = document
+        .root_element()
+        .descendent_elements()
+        .filter_map(|element| {
+            let score = scores.get(&element.id())?;
+            Some((element, score * (1.0 - link_density(&element))))
+        })
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(element, _)| element);
+    let body = Selector::parse("body").unwrap();
+    let content = best
+        .or_else(|| document.select(&body).next())
+        .unwrap_or(document.root_element());
+
+    Article {
+        title,
+        html: content.html(),
+        text: content
+            .text()
+            .map(str::trim)
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+/// The title the page gives itself, preferring what it shares with others
+fn title(document: &Html) -> String {
+    let og_title = Selector::parse(r#"meta[property="og:title"]"#).unwrap();
+    let title = Selector::parse("title, h1").unwrap();
+    document
+        .select(&og_title)
+        .filter_map(|meta| meta.attr("content"))
+        .map(str::to_string)
+        .chain(
+            document
+                .select(&title)
+                .map(|element| collapse(element.text())),
+        )
+        .map(|title| title.trim().to_string())
+        .find(|title| !title.is_empty())
+        .unwrap_or_default()
+}
+
+fn is_boilerplate(element: &ElementRef) -> bool {
+    let name = element.value().name();
+    if BOILERPLATE_TAGS.contains(&name) {
+        return true;
+    }
+    if matches!(name, "html" | "body" | "main" | "article") {
+        return false;
+    }
+    let hints = hints(element);
+    UNLIKELY.iter().any(|hint| hints.contains(hint))
+        && !LIKELY.iter().any(|hint| hints.contains(hint))
+}
+
+fn initial_score(element: &ElementRef) -> f32 {
+    let tag = match element.value().name() {
+        "article" | "main" => 10.0,
+        "div" | "section" => 5.0,
+        "pre" | "td" | "blockquote" => 3.0,
+        "ol" | "ul" | "li" | "form" => -3.0,
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => -5.0,
+        _ => 0.0,
+    };
+    let hints = hints(element);
+    let class = if LIKELY.iter().any(|hint| hints.contains(hint)) {
+        25.0
+    } else if UNLIKELY.iter().any(|hint| hints.contains(hint)) {
+        -25.0
+    } else {
+        0.0
+    };
+    tag + class
+}
+
+/// The class and id of an element, lowercased
+fn hints(element: &ElementRef) -> String {
+    let value = element.value();
+    format!(
+        "{} {}",
+        value.attr("class").unwrap_or_default(),
+        value.id().unwrap_or_default()
+    )
+    .to_lowercase()
+}
+
+/// How much of the text of an element is in links
+fn link_density(element: &ElementRef) -> f32 {
+    let chars = collapse(element.text()).chars().count();
+    if chars == 0 {
+        return 0.0;
+    }
+    let links = Selector::parse("a").unwrap();
+    let linked: usize = element
+        .select(&links)
+        .map(|link| collapse(link.text()).chars().count())
+        .sum();
+    linked as f32 / chars as f32
+}
+
+fn collapse<'a>(text: impl Iterator<Item = &'a str>) -> String {
+    text.flat_map(str::split_whitespace)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARTICLE: &str = include_str!("fixtures/article.html");
+
+    #[test]
+    fn test_extract() {
+        let article = extract(ARTICLE);
+        assert_eq!(article.title, "Pinning futures without fear");
+        assert!(article.text.contains("A future that borrows from itself"));
+        assert!(article.text.contains("Box::pin"));
+        for boilerplate in [
+            "Subscribe to the newsletter",
+            "Home",
+            "Related posts",
+            "Great post, thanks",
+            "All rights reserved",
+            "tracking",
+        ] {
+            assert!(
+                !article.text.contains(boilerplate),
+                "{boilerplate} was extracted"
+            );
+        }
+    }
+
+    #[test]
+    fn test_extract_without_paragraphs() {
+        let article = extract("<html><body><div>Only a line</div></body></html>");
+        assert_eq!(article.title, "");
+        assert_eq!(article.text, "Only a line");
+    }
+}
@@ -0,0 +1,131 @@
+use crate::prelude::*;
+
+use async_trait::async_trait;
+use log::debug;
+use log::info;
+use reqwest::Client;
+use serde::Deserialize;
+
+use super::SearchEngine;
+use super::SearchPage;
+use super::models::SearchResult;
+
+const BASE_URL: &str = "https://api.bing.microsoft.com";
+const PAGE_SIZE: i32 = 20;
+
+/// The Bing Web Search API, needs a subscription key
+pub struct Bing {
+    base_url: String,
+    key: String,
+    client: Client,
+}
+
+impl Bing {
+    pub fn new(key: String) -> Self {
+        Self::with_base_url(BASE_URL.into(), key)
+    }
+
+    pub fn with_base_url(base_url: String, key: String) -> Self {
+        info!("Starting Bing search engine with base URL: {base_url}");
+        Bing {
+            base_url,
+            key,
+            client: Client::new(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BingSearchPage {
+    web_pages: Option<BingWebPages>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BingWebPages {
+    value: Vec<BingSearchResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BingSearchResult {
+    url: String,
+    name: String,
+    #[serde(default)]
+    snippet: String,
+}
+
+impl From<BingSearchResult> for SearchResult {
+    fn from(result: BingSearchResult) -> Self {
+        SearchResult {
+            url: result.url,
+            title: result.name,
+            summary: result.snippet,
+            source: "bing".into(),
+        }
+    }
+}
+
+#[async_trait]
+impl SearchEngine for Bing {
+    async fn search(&self, query: String, page: i32) -> Result<SearchPage> {
+        debug!("Searching Bing for '{query}' on page {page}");
+        let offset = (page.max(1) - 1) * PAGE_SIZE;
+        let search_page: BingSearchPage = self
+            .client
+            .get(format!("{}/v7.0/search", self.base_url))
+            .query(&[
+                ("q", query.as_str()),
+                ("count", &PAGE_SIZE.to_string()),
+                ("offset", &offset.to_string()),
+                ("textFormat", "Raw"),
+            ])
+            .header("Ocp-Apim-Subscription-Key", &self.key)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(SearchPage {
+            page,
+            query,
+            results: search_page
+                .web_pages
+                .map(|pages| pages.value.into_iter().map(|r| r.into()).collect())
+                .unwrap_or_default(),
+        })
+    }
+
+    async fn available(&self) -> bool {
+        !self.key.is_empty()
+    }
+
+    fn name(&self) -> String {
+        "Bing".into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::driver::search::fixtures;
+
+    #[tokio::test]
+    async fn test_search() {
+        let (base_url, request) =
+            fixtures::serve("application/json", include_str!("fixtures/bing.json")).await;
+        let engine = Bing::with_base_url(base_url, "secret".into());
+        let page = engine.search("rust & tokio".into(), 2).await.unwrap();
+        let request = request.await.unwrap();
+        assert!(
+            request.starts_with(
+                "GET /v7.0/search?q=rust+%26+tokio&count=20&offset=20&textFormat=Raw "
+            )
+        );
+        assert!(request.contains("ocp-apim-subscription-key: secret"));
+
+        assert_eq!(page.results.len(), 2);
+        assert_eq!(page.results[0].title, "tokio - Rust");
+        assert_eq!(page.results[1].source, "bing");
+    }
+}
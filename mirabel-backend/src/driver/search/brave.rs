@@ -0,0 +1,132 @@
+use crate::prelude::*;
+
+use async_trait::async_trait;
+use log::debug;
+use log::info;
+use reqwest::Client;
+use serde::Deserialize;
+
+use super::SearchEngine;
+use super::SearchPage;
+use super::models::SearchResult;
+use super::strip_tags;
+
+const BASE_URL: &str = "https://api.search.brave.com";
+const PAGE_SIZE: i32 = 20;
+
+/// The Brave Search API, needs a subscription key
+pub struct Brave {
+    base_url: String,
+    key: String,
+    client: Client,
+}
+
+impl Brave {
+    pub fn new(key: String) -> Self {
+        Self::with_base_url(BASE_URL.into(), key)
+    }
+
+    pub fn with_base_url(base_url: String, key: String) -> Self {
+        info!("Starting Brave search engine with base URL: {base_url}");
+        Brave {
+            base_url,
+            key,
+            client: Client::new(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct BraveSearchPage {
+    web: Option<BraveWebResults>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BraveWebResults {
+    results: Vec<BraveSearchResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BraveSearchResult {
+    url: String,
+    title: String,
+    #[serde(default)]
+    description: String,
+}
+
+impl From<BraveSearchResult> for SearchResult {
+    fn from(result: BraveSearchResult) -> Self {
+        SearchResult {
+            url: result.url,
+            title: strip_tags(&result.title),
+            summary: strip_tags(&result.description),
+            source: "brave".into(),
+        }
+    }
+}
+
+#[async_trait]
+impl SearchEngine for Brave {
+    async fn search(&self, query: String, page: i32) -> Result<SearchPage> {
+        debug!("Searching Brave for '{query}' on page {page}");
+        // The offset counts pages, not results
+        let offset = page.max(1) - 1;
+        let search_page: BraveSearchPage = self
+            .client
+            .get(format!("{}/res/v1/web/search", self.base_url))
+            .query(&[
+                ("q", query.as_str()),
+                ("count", &PAGE_SIZE.to_string()),
+                ("offset", &offset.to_string()),
+            ])
+            .header("Accept", "application/json")
+            .header("X-Subscription-Token", &self.key)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(SearchPage {
+            page,
+            query,
+            results: search_page
+                .web
+                .map(|web| web.results.into_iter().map(|r| r.into()).collect())
+                .unwrap_or_default(),
+        })
+    }
+
+    async fn available(&self) -> bool {
+        !self.key.is_empty()
+    }
+
+    fn name(&self) -> String {
+        "Brave".into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::driver::search::fixtures;
+
+    #[tokio::test]
+    async fn test_search() {
+        let (base_url, request) =
+            fixtures::serve("application/json", include_str!("fixtures/brave.json")).await;
+        let engine = Brave::with_base_url(base_url, "secret".into());
+        let page = engine.search("rust & tokio".into(), 1).await.unwrap();
+        let request = request.await.unwrap();
+        assert!(request.starts_with("GET /res/v1/web/search?q=rust+%26+tokio&count=20&offset=0 "));
+        assert!(request.contains("x-subscription-token: secret"));
+
+        assert_eq!(page.results.len(), 2);
+        assert_eq!(page.results[0].url, "https://tokio.rs/");
+        assert_eq!(
+            page.results[0].summary,
+            "Tokio is an asynchronous runtime for the Rust programming language & more."
+        );
+        assert_eq!(page.results[1].source, "brave");
+    }
+}
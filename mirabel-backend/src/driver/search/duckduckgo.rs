@@ -0,0 +1,162 @@
+use crate::prelude::*;
+
+use async_trait::async_trait;
+use log::debug;
+use log::info;
+use reqwest::Client;
+use reqwest::Url;
+use scraper::ElementRef;
+use scraper::Html;
+use scraper::Selector;
+
+use super::SearchEngine;
+use super::SearchPage;
+use super::USER_AGENT;
+use super::models::SearchResult;
+
+const BASE_URL: &str = "https://html.duckduckgo.com";
+/// Results on a page of the HTML version
+const PAGE_SIZE: i32 = 30;
+
+/// Scrapes the HTML version of DuckDuckGo, which needs no key
+pub struct DuckDuckGo {
+    base_url: String,
+    client: Client,
+}
+
+impl DuckDuckGo {
+    pub fn new() -> Result<Self> {
+        Self::with_base_url(BASE_URL.into())
+    }
+
+    pub fn with_base_url(base_url: String) -> Result<Self> {
+        info!("Starting DuckDuckGo search engine with base URL: {base_url}");
+        Ok(DuckDuckGo {
+            base_url,
+            client: Client::builder().user_agent(USER_AGENT).build()?,
+        })
+    }
+}
+
+#[async_trait]
+impl SearchEngine for DuckDuckGo {
+    async fn search(&self, query: String, page: i32) -> Result<SearchPage> {
+        debug!("Searching DuckDuckGo for '{query}' on page {page}");
+        let offset = (page.max(1) - 1) * PAGE_SIZE;
+        let html = self
+            .client
+            .get(format!("{}/html/", self.base_url))
+            .query(&[("q", query.as_str()), ("s", &offset.to_string())])
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        Ok(SearchPage {
+            page,
+            query,
+            results: parse_results(&html),
+        })
+    }
+
+    async fn available(&self) -> bool {
+        true
+    }
+
+    async fn probe(&self) -> Result<()> {
+        self.client
+            .head(format!("{}/html/", self.base_url))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    fn name(&self) -> String {
+        "DuckDuckGo".into()
+    }
+}
+
+fn parse_results(html: &str) -> Vec<SearchResult> {
+    let document = Html::parse_document(html);
+    let result = selector("div.result:not(.result--ad)");
+    let link = selector("a.result__a");
+    let snippet = selector(".result__snippet");
+    document
+        .select(&result)
+        .filter_map(|result| {
+            let link = result.select(&link).next()?;
+            let url = target(link.value().attr("href")?)?;
+            Some(SearchResult {
+                url,
+                title: text(link),
+                summary: result.select(&snippet).next().map(text).unwrap_or_default(),
+                source: "duckduckgo".into(),
+            })
+        })
+        .collect()
+}
+
+/// Links go through a redirect of DuckDuckGo, the page is in `uddg`
+fn target(href: &str) -> Option<String> {
+    let absolute = match href.strip_prefix("//") {
+        Some(rest) => format!("https://{rest}"),
+        None => href.to_string(),
+    };
+    let url = Url::parse(&absolute).ok()?;
+    if !url.host_str()?.ends_with("duckduckgo.com") {
+        return Some(url.into());
+    }
+    url.query_pairs()
+        .find(|(key, _)| key == "uddg")
+        .map(|(_, target)| target.into_owned())
+}
+
+fn text(element: ElementRef) -> String {
+    element
+        .text()
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn selector(selectors: &str) -> Selector {
+    Selector::parse(selectors).expect("selectors are valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::driver::search::fixtures;
+
+    #[tokio::test]
+    async fn test_search() {
+        let (base_url, request) =
+            fixtures::serve("text/html", include_str!("fixtures/duckduckgo.html")).await;
+        let engine = DuckDuckGo::with_base_url(base_url).unwrap();
+        let page = engine.search("rust & tokio".into(), 2).await.unwrap();
+        let request = request.await.unwrap();
+        assert!(request.starts_with("GET /html/?q=rust+%26+tokio&s=30 "));
+
+        assert_eq!(page.results.len(), 2);
+        assert_eq!(page.results[0].url, "https://tokio.rs/tokio/tutorial");
+        assert_eq!(page.results[0].title, "Tutorial | Tokio");
+        assert_eq!(
+            page.results[0].summary,
+            "Tokio is an asynchronous runtime for the Rust programming language."
+        );
+        assert_eq!(page.results[1].url, "https://docs.rs/tokio/latest/tokio/");
+        assert_eq!(page.results[1].source, "duckduckgo");
+    }
+
+    #[tokio::test]
+    #[ignore = "Depends on external service"]
+    async fn test_live_search() {
+        let engine = DuckDuckGo::new().unwrap();
+        let page = engine.search("rust".into(), 1).await.unwrap();
+        assert!(!page.results.is_empty());
+        dbg!(&page);
+    }
+}
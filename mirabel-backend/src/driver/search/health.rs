@@ -0,0 +1,278 @@
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::prelude::*;
+use mirabel_core::dto::search::CircuitState;
+use mirabel_core::dto::search::EngineStatus;
+
+use async_trait::async_trait;
+use chrono::DateTime;
+use chrono::Utc;
+use log::info;
+use log::warn;
+use thiserror::Error;
+use tokio::sync::Mutex;
+use tokio::time;
+
+use super::SearchEngine;
+use super::SearchPage;
+
+/// Failures in a row after which an engine is left alone
+const FAILURE_THRESHOLD: u32 = 3;
+/// How long an engine is left alone before it is tried again
+const COOLDOWN: Duration = Duration::from_secs(60);
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(15);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Why a search engine did not answer
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum EngineFailure {
+    #[error("timed out after {0:?}")]
+    Timeout(Duration),
+    #[error("left alone after {FAILURE_THRESHOLD} failures in a row")]
+    CircuitOpen,
+    #[error("answered with status {0}")]
+    Status(u16),
+    #[error("{0}")]
+    Failed(String),
+}
+
+impl From<&Error> for EngineFailure {
+    fn from(err: &Error) -> Self {
+        match err {
+            Error::Reqwest(err) if err.is_timeout() => EngineFailure::Timeout(SEARCH_TIMEOUT),
+            Error::Reqwest(err) => match err.status() {
+                Some(status) => EngineFailure::Status(status.as_u16()),
+                None => EngineFailure::Failed(err.to_string()),
+            },
+            Error::SearchEngine(err) => err.failure.clone(),
+            err => EngineFailure::Failed(err.to_string()),
+        }
+    }
+}
+
+/// A failure of a single search engine
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{engine} {failure}")]
+pub struct EngineError {
+    pub engine: String,
+    pub failure: EngineFailure,
+}
+
+/// Counts failures in a row and opens once there are too many, so a broken
+/// engine is not asked until the cooldown is over
+#[derive(Debug, Default)]
+struct Breaker {
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+}
+
+impl Breaker {
+    fn allows(&mut self, now: Instant) -> bool {
+        match self.state {
+            CircuitState::Closed | CircuitState::HalfOpen => true,
+            CircuitState::Open => {
+                let cooled_down = self
+                    .opened_at
+                    .is_none_or(|opened_at| now.duration_since(opened_at) >= COOLDOWN);
+                if cooled_down {
+                    self.state = CircuitState::HalfOpen;
+                }
+                cooled_down
+            }
+        }
+    }
+
+    fn succeeded(&mut self) {
+        self.state = CircuitState::Closed;
+        self.consecutive_failures = 0;
+        self.opened_at = None;
+    }
+
+    /// Whether the failure opened the breaker
+    fn failed(&mut self, now: Instant) -> bool {
+        self.consecutive_failures += 1;
+        let opens = self.state == CircuitState::HalfOpen
+            || (self.state == CircuitState::Closed
+                && self.consecutive_failures >= FAILURE_THRESHOLD);
+        if opens {
+            self.state = CircuitState::Open;
+            self.opened_at = Some(now);
+        }
+        opens
+    }
+}
+
+#[derive(Debug, Default)]
+struct Health {
+    breaker: Breaker,
+    searches: u64,
+    failures: u64,
+    timeouts: u64,
+    total_latency: Duration,
+    last_latency: Option<Duration>,
+    last_error: Option<String>,
+    last_success_at: Option<DateTime<Utc>>,
+    last_failure_at: Option<DateTime<Utc>>,
+    last_probe_at: Option<DateTime<Utc>>,
+}
+
+/// A search engine behind a circuit breaker. Keeps track of how it does, and
+/// turns its failures into [`EngineError`]s.
+pub struct MonitoredEngine {
+    engine: Box<dyn SearchEngine>,
+    health: Mutex<Health>,
+}
+
+impl MonitoredEngine {
+    pub fn new(engine: Box<dyn SearchEngine>) -> Self {
+        Self {
+            engine,
+            health: Mutex::new(Health::default()),
+        }
+    }
+
+    /// Checks the engine answers, an answer closes the breaker again
+    pub async fn check(&self) {
+        let probed = match time::timeout(PROBE_TIMEOUT, self.engine.probe()).await {
+            Ok(probed) => probed.map_err(|err| EngineFailure::from(&err)),
+            Err(_) => Err(EngineFailure::Timeout(PROBE_TIMEOUT)),
+        };
+        let mut health = self.health.lock().await;
+        health.last_probe_at = Some(Utc::now());
+        match probed {
+            Ok(()) => {
+                if health.breaker.state != CircuitState::Closed {
+                    info!("{} answers again", self.engine.name());
+                }
+                health.breaker.succeeded();
+            }
+            Err(failure) => {
+                if health.breaker.failed(Instant::now()) {
+                    warn!(
+                        "{} failed its probe and is left alone: {failure}",
+                        self.engine.name()
+                    );
+                }
+                health.last_error = Some(failure.to_string());
+            }
+        }
+    }
+
+    pub async fn status(&self) -> EngineStatus {
+        let health = self.health.lock().await;
+        let answered = health.searches - health.failures;
+        EngineStatus {
+            name: self.engine.name(),
+            state: health.breaker.state,
+            consecutive_failures: health.breaker.consecutive_failures,
+            searches: health.searches,
+            failures: health.failures,
+            timeouts: health.timeouts,
+            average_latency_ms: (answered > 0)
+                .then(|| health.total_latency.as_millis() as u64 / answered),
+            last_latency_ms: health
+                .last_latency
+                .map(|latency| latency.as_millis() as u64),
+            last_error: health.last_error.clone(),
+            last_success_at: health.last_success_at,
+            last_failure_at: health.last_failure_at,
+            last_probe_at: health.last_probe_at,
+        }
+    }
+
+    fn error(&self, failure: EngineFailure) -> Error {
+        Error::SearchEngine(EngineError {
+            engine: self.engine.name(),
+            failure,
+        })
+    }
+}
+
+#[async_trait]
+impl SearchEngine for MonitoredEngine {
+    async fn search(&self, query: String, page: i32) -> Result<SearchPage> {
+        if !self.health.lock().await.breaker.allows(Instant::now()) {
+            return Err(self.error(EngineFailure::CircuitOpen));
+        }
+        let start = Instant::now();
+        let searched = match time::timeout(SEARCH_TIMEOUT, self.engine.search(query, page)).await {
+            Ok(searched) => searched.map_err(|err| EngineFailure::from(&err)),
+            Err(_) => Err(EngineFailure::Timeout(SEARCH_TIMEOUT)),
+        };
+        let latency = start.elapsed();
+
+        let mut health = self.health.lock().await;
+        health.searches += 1;
+        health.last_latency = Some(latency);
+        match searched {
+            Ok(found) => {
+                info!(
+                    "{} answered '{}' with {} results in {latency:?}",
+                    self.engine.name(),
+                    found.query,
+                    found.results.len()
+                );
+                health.breaker.succeeded();
+                health.total_latency += latency;
+                health.last_success_at = Some(Utc::now());
+                Ok(found)
+            }
+            Err(failure) => {
+                health.failures += 1;
+                if matches!(failure, EngineFailure::Timeout(_)) {
+                    health.timeouts += 1;
+                }
+                if health.breaker.failed(Instant::now()) {
+                    warn!("{} is left alone for {COOLDOWN:?}", self.engine.name());
+                }
+                health.last_error = Some(failure.to_string());
+                health.last_failure_at = Some(Utc::now());
+                Err(self.error(failure))
+            }
+        }
+    }
+
+    async fn available(&self) -> bool {
+        self.engine.available().await && self.health.lock().await.breaker.allows(Instant::now())
+    }
+
+    async fn probe(&self) -> Result<()> {
+        self.engine.probe().await
+    }
+
+    fn name(&self) -> String {
+        self.engine.name()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breaker() {
+        let start = Instant::now();
+        let mut breaker = Breaker::default();
+        assert!(!breaker.failed(start));
+        assert!(!breaker.failed(start));
+        breaker.succeeded();
+        assert!(!breaker.failed(start));
+        assert!(!breaker.failed(start));
+        assert!(breaker.failed(start));
+        assert_eq!(breaker.state, CircuitState::Open);
+        assert!(!breaker.allows(start + COOLDOWN / 2));
+
+        // One more failure after the cooldown opens it right away
+        assert!(breaker.allows(start + COOLDOWN));
+        assert_eq!(breaker.state, CircuitState::HalfOpen);
+        assert!(breaker.failed(start + COOLDOWN));
+        assert!(!breaker.allows(start + COOLDOWN));
+
+        assert!(breaker.allows(start + COOLDOWN * 2));
+        breaker.succeeded();
+        assert_eq!(breaker.state, CircuitState::Closed);
+        assert_eq!(breaker.consecutive_failures, 0);
+    }
+}
@@ -0,0 +1,141 @@
+use crate::prelude::*;
+
+use async_trait::async_trait;
+use deadpool_diesel::postgres::Pool;
+use diesel::prelude::*;
+use diesel::sql_types::BigInt;
+use diesel::sql_types::Text;
+use log::debug;
+
+use super::SearchEngine;
+use super::SearchPage;
+use super::models::SearchResult;
+
+const PAGE_SIZE: i64 = 10;
+
+const SEARCH_QUERY: &str = r#"
+    SELECT url, title,
+        ts_headline('english', markdown, websearch_to_tsquery('english', $1),
+            'StartSel="", StopSel="", MaxWords=35, MinWords=15') AS summary
+    FROM web_pages
+    WHERE workspace_id = $2
+        AND to_tsvector('english', title || ' ' || markdown) @@ websearch_to_tsquery('english', $1)
+    ORDER BY ts_rank(to_tsvector('english', title || ' ' || markdown),
+        websearch_to_tsquery('english', $1)) DESC, scraped_at DESC
+    LIMIT $3 OFFSET $4
+"#;
+
+const UPSERT_QUERY: &str = r#"
+    INSERT INTO web_pages (workspace_id, url, title, markdown, scraped_at)
+    VALUES ($1, $2, $3, $4, NOW())
+    ON CONFLICT (workspace_id, url) DO UPDATE
+        SET title = EXCLUDED.title, markdown = EXCLUDED.markdown, scraped_at = EXCLUDED.scraped_at
+"#;
+
+/// Pages scraped before, searched by their words. Finds what was read once
+/// again without going out to the web. Every workspace only finds the pages
+/// it read itself, what its fetch policy let through, so the index is
+/// searched through [`LocalIndex::workspace`].
+#[derive(Clone)]
+pub struct LocalIndex {
+    pool: Pool,
+}
+
+/// The pages of the local index one workspace read
+pub struct WorkspaceIndex {
+    pool: Pool,
+    workspace_id: String,
+}
+
+#[derive(QueryableByName)]
+struct IndexedPage {
+    #[diesel(sql_type = Text)]
+    url: String,
+    #[diesel(sql_type = Text)]
+    title: String,
+    #[diesel(sql_type = Text)]
+    summary: String,
+}
+
+impl From<IndexedPage> for SearchResult {
+    fn from(page: IndexedPage) -> Self {
+        SearchResult {
+            url: page.url,
+            title: page.title,
+            summary: page
+                .summary
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" "),
+            source: "local".into(),
+        }
+    }
+}
+
+impl LocalIndex {
+    pub fn new(pool: Pool) -> Self {
+        Self { pool }
+    }
+
+    /// Adds a page scraped for the workspace, or replaces what was scraped
+    /// from it before
+    pub async fn add(
+        &self,
+        workspace_id: String,
+        url: String,
+        title: String,
+        markdown: String,
+    ) -> Result<()> {
+        let conn = self.pool.get().await?;
+        conn.interact(move |conn| {
+            diesel::sql_query(UPSERT_QUERY)
+                .bind::<Text, _>(workspace_id)
+                .bind::<Text, _>(url)
+                .bind::<Text, _>(title)
+                .bind::<Text, _>(markdown)
+                .execute(conn)
+        })
+        .await??;
+        Ok(())
+    }
+
+    pub fn workspace(&self, workspace_id: &str) -> WorkspaceIndex {
+        WorkspaceIndex {
+            pool: self.pool.clone(),
+            workspace_id: workspace_id.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl SearchEngine for WorkspaceIndex {
+    async fn search(&self, query: String, page: i32) -> Result<SearchPage> {
+        debug!("Searching the local index for '{query}' on page {page}");
+        let offset = i64::from(page.max(1) - 1) * PAGE_SIZE;
+        let conn = self.pool.get().await?;
+        let (workspace_id, terms) = (self.workspace_id.clone(), query.clone());
+        let pages = conn
+            .interact(move |conn| {
+                diesel::sql_query(SEARCH_QUERY)
+                    .bind::<Text, _>(terms)
+                    .bind::<Text, _>(workspace_id)
+                    .bind::<BigInt, _>(PAGE_SIZE)
+                    .bind::<BigInt, _>(offset)
+                    .load::<IndexedPage>(conn)
+            })
+            .await??;
+        Ok(SearchPage {
+            page,
+            query,
+            results: pages.into_iter().map(|p| p.into()).collect(),
+        })
+    }
+
+    async fn available(&self) -> bool {
+        self.pool.get().await.is_ok()
+    }
+
+    fn name(&self) -> String {
+        "Local index".into()
+    }
+}
@@ -1,47 +1,180 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
 use crate::prelude::*;
+use mirabel_core::dto::search::EngineStatus;
 
 use async_trait::async_trait;
+use bing::Bing;
+use brave::Brave;
+use duckduckgo::DuckDuckGo;
+use futures::future::join_all;
+use health::EngineError;
+use health::EngineFailure;
+use health::MonitoredEngine;
+use local::WorkspaceIndex;
 use log::debug;
+use log::warn;
 use models::SearchPage;
+use models::SearchResult;
+use reqwest::Url;
 use searxng::SearxNG;
+use tokio::time;
 use traits::SearchEngine;
 
+pub mod health;
+pub mod local;
 pub mod models;
 pub mod traits;
 
+mod bing;
+mod brave;
+mod duckduckgo;
 mod searxng;
 
 const SEARXNG_HOST_ENV: &str = "SEARXNG_HOST";
+const BRAVE_API_KEY_ENV: &str = "BRAVE_API_KEY";
+const BING_API_KEY_ENV: &str = "BING_API_KEY";
+const DUCKDUCKGO_ENV: &str = "SEARCH_DUCKDUCKGO";
+const SEARCH_MODE_ENV: &str = "SEARCH_MODE";
+
+/// Sent by engines that scrape pages meant for browsers
+const USER_AGENT: &str = "Mozilla/5.0 (compatible; Mirabel/0.1)";
+const PROBE_INTERVAL: Duration = Duration::from_secs(60);
+/// Dampens the lead of top ranks when the results of engines are merged
+const RANK_OFFSET: f32 = 60.0;
+
+/// How the results of several engines are used
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SearchMode {
+    /// Only the first available engine is asked
+    #[default]
+    First,
+    /// All available engines are asked at once, their results are merged
+    Aggregate,
+}
+
+impl SearchMode {
+    fn from_env() -> Self {
+        match std::env::var(SEARCH_MODE_ENV).as_deref() {
+            Ok("aggregate") => SearchMode::Aggregate,
+            Ok("first") | Err(_) => SearchMode::First,
+            Ok(other) => {
+                warn!("Unknown search mode '{other}', using the first available engine");
+                SearchMode::First
+            }
+        }
+    }
+}
 
 pub struct SearchEngines {
-    engines: Vec<Box<dyn SearchEngine>>,
+    engines: Vec<MonitoredEngine>,
+    mode: SearchMode,
 }
 
 impl SearchEngines {
+    pub fn new(engines: Vec<Box<dyn SearchEngine>>, mode: SearchMode) -> Self {
+        let engines = engines.into_iter().map(MonitoredEngine::new).collect();
+        Self { engines, mode }
+    }
+
+    /// Engines of the web in order of priority
     pub fn from_env() -> Self {
         debug!("Creating search engines from environment variables");
         let mut engines: Vec<Box<dyn SearchEngine>> = Vec::new();
         if let Ok(host) = std::env::var(SEARXNG_HOST_ENV) {
             engines.push(Box::new(SearxNG::new(host)));
         }
-        debug!("Search engines created ({}):", engines.len());
+        if let Ok(key) = std::env::var(BRAVE_API_KEY_ENV) {
+            engines.push(Box::new(Brave::new(key)));
+        }
+        if let Ok(key) = std::env::var(BING_API_KEY_ENV) {
+            engines.push(Box::new(Bing::new(key)));
+        }
+        if std::env::var(DUCKDUCKGO_ENV).is_ok_and(|enabled| enabled == "true") {
+            match DuckDuckGo::new() {
+                Ok(engine) => engines.push(Box::new(engine)),
+                Err(err) => warn!("Could not start DuckDuckGo: {err}"),
+            }
+        }
+        let mode = SearchMode::from_env();
+        debug!("Search engines created ({}, {mode:?}):", engines.len());
         for engine in &engines {
             debug!("    - Engine: {}", engine.name());
         }
-        Self { engines }
+        Self::new(engines, mode)
+    }
+
+    /// How each engine is doing, in order of priority
+    pub async fn statuses(&self) -> Vec<EngineStatus> {
+        join_all(self.engines.iter().map(MonitoredEngine::status)).await
+    }
+
+    /// Probes every engine once in a while, so broken engines are noticed
+    /// before a search runs into them and recovered ones are asked again
+    pub async fn run_probes(&self) {
+        loop {
+            join_all(self.engines.iter().map(MonitoredEngine::check)).await;
+            time::sleep(PROBE_INTERVAL).await;
+        }
+    }
+
+    /// Like [`SearchEngine::search`], the pages the workspace read before
+    /// are searched as the last engine so the web is preferred
+    pub async fn search_with(
+        &self,
+        index: &WorkspaceIndex,
+        query: String,
+        page: i32,
+    ) -> Result<SearchPage> {
+        let mut engines = self.monitored();
+        engines.push(index);
+        self.search_in(engines, query, page).await
+    }
+
+    fn monitored(&self) -> Vec<&dyn SearchEngine> {
+        self.engines
+            .iter()
+            .map(|engine| engine as &dyn SearchEngine)
+            .collect()
+    }
+
+    async fn search_in(
+        &self,
+        engines: Vec<&dyn SearchEngine>,
+        query: String,
+        page: i32,
+    ) -> Result<SearchPage> {
+        if self.mode == SearchMode::Aggregate {
+            return aggregate(engines, query, page).await;
+        }
+        // Priority is given to the first available engine, the next one is
+        // asked when it fails
+        let mut failures = Vec::new();
+        for engine in engines {
+            if !engine.available().await {
+                continue;
+            }
+            match engine.search(query.clone(), page).await {
+                Ok(found) => return Ok(found),
+                Err(err) => {
+                    let failure = engine_error(engine, err);
+                    warn!("Search failed on {failure}, trying the next engine");
+                    failures.push(failure);
+                }
+            }
+        }
+        match failures.is_empty() {
+            true => Err(Error::NoAvailableEngine),
+            false => Err(Error::SearchEnginesFailed(failures)),
+        }
     }
 }
 
 #[async_trait]
 impl SearchEngine for SearchEngines {
     async fn search(&self, query: String, page: i32) -> Result<SearchPage> {
-        // Priority is given to the first available engine
-        for engine in &self.engines {
-            if engine.available().await {
-                return engine.search(query, page).await;
-            }
-        }
-        Err(Error::NoAvailableEngine)
+        self.search_in(self.monitored(), query, page).await
     }
 
     async fn available(&self) -> bool {
@@ -61,3 +194,288 @@ impl SearchEngine for SearchEngines {
         names.join(", ")
     }
 }
+
+/// Asks every available engine at once. Engines that fail are left out,
+/// the search only fails when all of them do.
+async fn aggregate(
+    engines: Vec<&dyn SearchEngine>,
+    query: String,
+    page: i32,
+) -> Result<SearchPage> {
+    let mut available = Vec::new();
+    for engine in engines {
+        if engine.available().await {
+            available.push(engine);
+        }
+    }
+    if available.is_empty() {
+        return Err(Error::NoAvailableEngine);
+    }
+    let searches = available
+        .iter()
+        .map(|engine| engine.search(query.clone(), page));
+    let mut pages = Vec::new();
+    let mut failures = Vec::new();
+    for (engine, result) in available.iter().zip(join_all(searches).await) {
+        match result {
+            Ok(found) => pages.push(found.results),
+            Err(err) => failures.push(engine_error(*engine, err)),
+        }
+    }
+    if pages.is_empty() {
+        return Err(Error::SearchEnginesFailed(failures));
+    }
+    for failure in &failures {
+        warn!("Search failed on {failure}");
+    }
+    Ok(SearchPage {
+        page,
+        query,
+        results: merge(pages),
+    })
+}
+
+/// Monitored engines fail with engine errors, the errors of others are
+/// told apart by the name of the engine
+fn engine_error(engine: &dyn SearchEngine, err: Error) -> EngineError {
+    match err {
+        Error::SearchEngine(err) => err,
+        err => EngineError {
+            engine: engine.name(),
+            failure: EngineFailure::from(&err),
+        },
+    }
+}
+
+/// Merges the ranked results of several engines into one ranking. A page is
+/// kept once, the more engines rank it high the higher it gets.
+fn merge(rankings: Vec<Vec<SearchResult>>) -> Vec<SearchResult> {
+    let mut merged: Vec<(f32, SearchResult)> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for ranking in rankings {
+        for (rank, result) in ranking.into_iter().enumerate() {
+            let score = 1.0 / (RANK_OFFSET + rank as f32 + 1.0);
+            let key = normalize_url(&result.url);
+            match positions.get(&key) {
+                Some(&position) => {
+                    let (known_score, known) = &mut merged[position];
+                    *known_score += score;
+                    if !known
+                        .source
+                        .split(", ")
+                        .any(|source| source == result.source)
+                    {
+                        known.source = format!("{}, {}", known.source, result.source);
+                    }
+                    if known.summary.len() < result.summary.len() {
+                        known.summary = result.summary;
+                    }
+                }
+                None => {
+                    positions.insert(key, merged.len());
+                    merged.push((score, result));
+                }
+            }
+        }
+    }
+    // The sort is stable, equally ranked pages keep the order of the engines
+    merged.sort_by(|a, b| b.0.total_cmp(&a.0));
+    merged.into_iter().map(|(_, result)| result).collect()
+}
+
+/// The same page under different URLs, e.g. with `www.`, a trailing slash,
+/// a fragment or tracking parameters, normalizes to the same key
+pub(crate) fn normalize_url(url: &str) -> String {
+    let Ok(parsed) = Url::parse(url.trim()) else {
+        return url.trim().to_lowercase();
+    };
+    let host = parsed.host_str().unwrap_or_default();
+    let host = host.strip_prefix("www.").unwrap_or(host);
+    let path = parsed.path().trim_end_matches('/');
+    let mut params: Vec<(String, String)> = parsed
+        .query_pairs()
+        .filter(|(key, _)| !key.starts_with("utm_"))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    params.sort();
+    let query = params
+        .iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect::<Vec<_>>()
+        .join("&");
+    match query.is_empty() {
+        true => format!("{host}{path}"),
+        false => format!("{host}{path}?{query}"),
+    }
+}
+
+/// The text of an HTML snippet, e.g. a summary with highlighted words
+fn strip_tags(html: &str) -> String {
+    scraper::Html::parse_fragment(html)
+        .root_element()
+        .text()
+        .collect::<String>()
+}
+
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use tokio::io::AsyncReadExt;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+    use tokio::task::JoinHandle;
+
+    /// Answers a single request with a canned body. Returns the base URL to
+    /// send it to, and the head of the request once it was answered.
+    pub async fn serve(
+        content_type: &'static str,
+        body: &'static str,
+    ) -> (String, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let request = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut head = Vec::new();
+            let mut buffer = [0; 1024];
+            while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+                let read = stream.read(&mut buffer).await.unwrap();
+                if read == 0 {
+                    break;
+                }
+                head.extend_from_slice(&buffer[..read]);
+            }
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+            stream.shutdown().await.unwrap();
+            lowercase_headers(&String::from_utf8_lossy(&head))
+        });
+        (base_url, request)
+    }
+
+    /// Keeps the request line as sent, header names are case insensitive
+    fn lowercase_headers(head: &str) -> String {
+        let mut lines = head.lines();
+        let request_line = lines.next().unwrap_or_default().to_string();
+        lines.fold(request_line, |head, line| {
+            format!("{head}\n{}", line.to_lowercase())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(url: &str, summary: &str, source: &str) -> SearchResult {
+        SearchResult {
+            url: url.into(),
+            title: url.into(),
+            summary: summary.into(),
+            source: source.into(),
+        }
+    }
+
+    #[test]
+    fn test_normalize_url() {
+        assert_eq!(
+            normalize_url("https://www.Example.com/docs/?utm_source=x&b=2&a=1#intro"),
+            "example.com/docs?a=1&b=2"
+        );
+        assert_eq!(
+            normalize_url("http://example.com/docs"),
+            normalize_url("https://example.com/docs/")
+        );
+        assert_ne!(
+            normalize_url("https://example.com/docs?page=1"),
+            normalize_url("https://example.com/docs?page=2")
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let merged = merge(vec![
+            vec![
+                result("https://a.com", "A", "brave"),
+                result("https://b.com", "B", "brave"),
+            ],
+            vec![
+                result("https://www.b.com/", "B, but longer", "bing"),
+                result("https://c.com", "C", "bing"),
+            ],
+        ]);
+        let urls: Vec<&str> = merged.iter().map(|r| r.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            vec!["https://b.com", "https://a.com", "https://c.com"]
+        );
+        assert_eq!(merged[0].source, "brave, bing");
+        assert_eq!(merged[0].summary, "B, but longer");
+    }
+
+    #[tokio::test]
+    async fn test_aggregate() {
+        let (brave_url, _) =
+            fixtures::serve("application/json", include_str!("fixtures/brave.json")).await;
+        let (bing_url, _) =
+            fixtures::serve("application/json", include_str!("fixtures/bing.json")).await;
+        let engines = SearchEngines::new(
+            vec![
+                Box::new(Brave::with_base_url(brave_url, "secret".into())),
+                Box::new(Bing::with_base_url(bing_url, "secret".into())),
+                // Fails, the others still answer
+                Box::new(Bing::with_base_url(
+                    "http://127.0.0.1:9".into(),
+                    "secret".into(),
+                )),
+            ],
+            SearchMode::Aggregate,
+        );
+        let page = engines.search("rust & tokio".into(), 1).await.unwrap();
+        let urls: Vec<&str> = page.results.iter().map(|r| r.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            vec![
+                "https://docs.rs/tokio/latest/tokio/",
+                "https://tokio.rs/",
+                "https://github.com/tokio-rs/tokio?utm_source=bing",
+            ]
+        );
+        assert_eq!(page.results[0].source, "brave, bing");
+
+        let statuses = engines.statuses().await;
+        assert_eq!(statuses[0].searches, 1);
+        assert_eq!(statuses[0].failures, 0);
+        assert!(statuses[0].last_latency_ms.is_some());
+        assert_eq!(statuses[2].failures, 1);
+        assert!(statuses[2].last_error.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_fall_through() {
+        let (brave_url, _) =
+            fixtures::serve("application/json", include_str!("fixtures/brave.json")).await;
+        let engines = SearchEngines::new(
+            vec![
+                Box::new(Bing::with_base_url(
+                    "http://127.0.0.1:9".into(),
+                    "secret".into(),
+                )),
+                Box::new(Brave::with_base_url(brave_url, "secret".into())),
+            ],
+            SearchMode::First,
+        );
+        let page = engines.search("rust".into(), 1).await.unwrap();
+        assert_eq!(page.results[0].source, "brave");
+
+        // The fixture only answers once, now every engine fails
+        let err = engines.search("rust".into(), 1).await.unwrap_err();
+        let Error::SearchEnginesFailed(failures) = err else {
+            panic!("Expected the failures of the engines, got {err}");
+        };
+        assert_eq!(failures.len(), 2);
+        assert_eq!(failures[0].engine, "Bing");
+        assert!(matches!(failures[0].failure, EngineFailure::Failed(_)));
+    }
+}
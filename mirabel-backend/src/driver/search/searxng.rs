@@ -54,12 +54,19 @@ impl From<SearxNGSearchResult> for SearchResult {
 impl SearchEngine for SearxNG {
     async fn search(&self, query: String, page: i32) -> Result<SearchPage> {
         debug!("Searching for '{}' on page {}", query, page);
-        let url = format!(
-            "{}/search?q={}&format=json&pageno={}",
-            self.base_url, &query, page
-        );
-
-        let search_page: SearxNGSearchPage = self.client.get(&url).send().await?.json().await?;
+        let search_page: SearxNGSearchPage = self
+            .client
+            .get(format!("{}/search", self.base_url))
+            .query(&[
+                ("q", query.as_str()),
+                ("format", "json"),
+                ("pageno", &page.to_string()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
 
         Ok(SearchPage {
             page,
@@ -69,7 +76,16 @@ impl SearchEngine for SearxNG {
     }
 
     async fn available(&self) -> bool {
-        true
+        !self.base_url.is_empty()
+    }
+
+    async fn probe(&self) -> Result<()> {
+        self.client
+            .get(format!("{}/healthz", self.base_url))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
     }
 
     fn name(&self) -> String {
@@ -81,6 +97,30 @@ impl SearchEngine for SearxNG {
 mod tests {
     use super::*;
 
+    use crate::driver::search::fixtures;
+
+    #[tokio::test]
+    async fn test_encoded_search() {
+        let (base_url, request) =
+            fixtures::serve("application/json", include_str!("fixtures/searxng.json")).await;
+        let searxng = SearxNG::new(base_url);
+        let search_page = searxng.search("c++ & rust?".into(), 2).await.unwrap();
+        let request = request.await.unwrap();
+        assert!(request.starts_with("GET /search?q=c%2B%2B+%26+rust%3F&format=json&pageno=2 "));
+        assert_eq!(search_page.results.len(), 1);
+        assert_eq!(search_page.results[0].source, "duckduckgo");
+    }
+
+    #[tokio::test]
+    async fn test_probe() {
+        let (base_url, request) = fixtures::serve("text/plain", "OK").await;
+        SearxNG::new(base_url).probe().await.unwrap();
+        assert!(request.await.unwrap().starts_with("GET /healthz "));
+
+        let unreachable = SearxNG::new("http://127.0.0.1:9".into());
+        assert!(unreachable.probe().await.is_err());
+    }
+
     #[tokio::test]
     #[ignore = "Depends on external service"]
     async fn test_search() {
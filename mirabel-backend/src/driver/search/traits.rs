@@ -6,7 +6,16 @@ use super::models::SearchPage;
 
 #[async_trait]
 pub trait SearchEngine: Send + Sync {
+    /// Whether the engine is set up to be asked, e.g. has a key. Asked before
+    /// every search, so it does not go out to the network.
     async fn available(&self) -> bool;
     async fn search(&self, query: String, page: i32) -> Result<SearchPage>;
+    /// Checks the engine answers, run periodically in the background
+    async fn probe(&self) -> Result<()> {
+        match self.available().await {
+            true => Ok(()),
+            false => Err(Error::NoAvailableEngine),
+        }
+    }
     fn name(&self) -> String;
 }
@@ -8,6 +8,10 @@ use scraper::error::SelectorErrorKind;
 use std::sync::MutexGuard;
 use thiserror::Error;
 
+use crate::driver::converter::pdf::PdfProblem;
+use crate::driver::policy::FetchDenial;
+use crate::driver::search::health::EngineError;
+
 // Import for the ResponseError implementation
 use mirabel_core::dto::api_response::ApiResponse;
 
@@ -25,6 +29,21 @@ pub enum Error {
     // Search engine error types
     #[error("No available search engine")]
     NoAvailableEngine,
+    #[error("Search engine {0}")]
+    SearchEngine(EngineError),
+    #[error("Every search engine failed: {}", list(.0))]
+    SearchEnginesFailed(Vec<EngineError>),
+    #[error("No available browser")]
+    NoAvailableBrowser,
+    #[error("Fetching {url} is not allowed: {reason}")]
+    FetchDenied { url: String, reason: FetchDenial },
+    // Converter error types
+    #[error("Cannot convert {0}")]
+    UnsupportedFormat(String),
+    #[error("The document cannot be read, {0}")]
+    InvalidDocument(String),
+    #[error("The PDF cannot be read, {0}")]
+    UnreadablePdf(PdfProblem),
 
     // 400.. HTTP error types
     #[error("{0}")]
@@ -85,7 +104,7 @@ pub enum Error {
     #[error("A fantoccini error occurred: {0}")]
     FantocciniCmd(Box<fantoccini::error::CmdError>),
     #[error("A deadpool error occurred: {0}")]
-    Deadpool(#[from] deadpool::unmanaged::PoolError),
+    Deadpool(String),
     #[error("A regex error occurred: {0}")]
     Regex(#[from] regex::Error),
     #[error("A lopdf error occurred: {0}")]
@@ -98,6 +117,14 @@ pub enum Error {
     Scraper(String), // TODO: Find a way to keep more information
     #[error("A Tera error occurred: {0}")]
     Tera(#[from] tera::Error),
+    #[error("A pty error occurred: {0}")]
+    Pty(String),
+    #[error("A git error occurred: {0}")]
+    Git(String),
+    #[error("A tree-sitter error occurred: {0}")]
+    TreeSitter(String),
+    #[error("A blocking task failed: {0}")]
+    Join(#[from] tokio::task::JoinError),
 
     // `std`-error types
     #[error("An IO error occurred: {0}")]
@@ -113,11 +140,23 @@ pub enum Error {
     DoubleSubscription,
     #[error("A socket was closed unexpectedly")]
     SocketClosed,
+    #[error("The prompt {0} was not answered in time")]
+    PromptTimeout(String),
+    #[error("The step failed: {0}")]
+    StepFailed(String),
 }
 
 unsafe impl Send for Error {}
 unsafe impl Sync for Error {}
 
+fn list(errors: &[EngineError]) -> String {
+    errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
 impl From<Box<dyn AStdError + std::marker::Send + std::marker::Sync>> for Error {
     fn from(error: Box<dyn AStdError + std::marker::Send + std::marker::Sync>) -> Self {
         Error::Generic(format!("{error:?}"))
@@ -154,6 +193,18 @@ impl From<fantoccini::error::CmdError> for Error {
     }
 }
 
+impl From<deadpool::managed::PoolError<Error>> for Error {
+    fn from(error: deadpool::managed::PoolError<Error>) -> Self {
+        match error {
+            deadpool::managed::PoolError::Backend(error) => error,
+            deadpool::managed::PoolError::Timeout(_) | deadpool::managed::PoolError::Closed => {
+                Error::NoAvailableBrowser
+            }
+            error => Error::Deadpool(error.to_string()),
+        }
+    }
+}
+
 impl From<&str> for Error {
     fn from(s: &str) -> Self {
         Error::Generic(s.to_string())
@@ -182,10 +233,19 @@ impl ResponseError for Error {
         match self {
             Error::BadRequest(_) => StatusCode::BAD_REQUEST,
             Error::Unauthorized(_) => StatusCode::UNAUTHORIZED,
-            Error::Forbidden(_) => StatusCode::FORBIDDEN,
+            Error::Forbidden(_) | Error::FetchDenied { .. } => StatusCode::FORBIDDEN,
             Error::NotFound | Error::NotFoundRecentUpdate(_) => StatusCode::NOT_FOUND,
             Error::AlreadyExists(_) | Error::Conflict(_) => StatusCode::CONFLICT,
             Error::DoubleSubscription => StatusCode::CONFLICT,
+            Error::UnsupportedFormat(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Error::InvalidDocument(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Error::UnreadablePdf(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Error::NoAvailableEngine
+            | Error::SearchEngine(_)
+            | Error::SearchEnginesFailed(_)
+            | Error::NoAvailableBrowser => StatusCode::SERVICE_UNAVAILABLE,
+            Error::MirabelCore(mirabel_core::Error::BadRequest(_)) => StatusCode::BAD_REQUEST,
+            Error::MirabelCore(mirabel_core::Error::NotFound) => StatusCode::NOT_FOUND,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
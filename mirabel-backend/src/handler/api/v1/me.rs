@@ -10,6 +10,7 @@ use crate::handler::extractors::W;
 use crate::handler::middleware::auth_middleware::Auth;
 use mirabel_core::dto::api_response::ApiResponse;
 
+pub mod user_settings;
 pub mod user_workspaces;
 
 pub fn scope(cfg: &mut web::ServiceConfig) {
@@ -19,6 +20,7 @@ pub fn scope(cfg: &mut web::ServiceConfig) {
             .service(get_me)
             // .service(update_me)
             // .service(delete_me)
+            .configure(user_settings::scope)
             .configure(user_workspaces::scope),
     );
 }
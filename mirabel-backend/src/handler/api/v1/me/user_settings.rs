@@ -0,0 +1,42 @@
+use crate::prelude::*;
+use mirabel_core::dto::api_response::ApiResponse;
+use mirabel_core::dto::updated_user_settings::UpdatedUserSettings;
+
+use actix_web::Responder;
+use actix_web::Scope;
+use actix_web::get;
+use actix_web::patch;
+use actix_web::web;
+use actix_web::web::Data;
+use actix_web::web::Json;
+
+use crate::handler::extractors::W;
+use crate::service::users::UserService;
+
+pub fn scope(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        Scope::new("/settings")
+            .service(get_user_settings)
+            .service(update_user_settings),
+    );
+}
+
+#[get("")]
+pub async fn get_user_settings(user_service: Data<UserService>, user: W) -> Result<impl Responder> {
+    Ok(ApiResponse::ok(
+        user_service.get_settings(user.into_inner().id).await?,
+    ))
+}
+
+#[patch("")]
+pub async fn update_user_settings(
+    user_service: Data<UserService>,
+    user: W,
+    updated_settings: Json<UpdatedUserSettings>,
+) -> Result<impl Responder> {
+    Ok(ApiResponse::ok(
+        user_service
+            .update_settings(user.into_inner().id, updated_settings.into_inner())
+            .await?,
+    ))
+}
@@ -4,6 +4,7 @@ use actix_web::web;
 pub mod auth;
 pub mod me;
 // pub mod users;
+pub mod search;
 pub mod sessions;
 pub mod workspaces;
 
@@ -13,6 +14,7 @@ pub fn scope(cfg: &mut web::ServiceConfig) {
             .configure(auth::scope)
             .configure(me::scope)
             // .configure(users::scope),
+            .configure(search::scope)
             .configure(workspaces::scope),
     );
 }
@@ -0,0 +1,21 @@
+use crate::prelude::*;
+use mirabel_core::dto::api_response::ApiResponse;
+
+use actix_web::Responder;
+use actix_web::Scope;
+use actix_web::get;
+use actix_web::web;
+use actix_web::web::Data;
+
+use crate::driver::research::WebResearch;
+use crate::handler::middleware::auth_middleware::Auth;
+
+pub fn scope(cfg: &mut web::ServiceConfig) {
+    cfg.service(Scope::new("/search").wrap(Auth).service(get_search_engines));
+}
+
+/// The health of the search engines and how they did, in order of priority
+#[get("/engines")]
+pub async fn get_search_engines(web: Data<WebResearch>) -> Result<impl Responder> {
+    Ok(ApiResponse::ok(web.engines().statuses().await))
+}
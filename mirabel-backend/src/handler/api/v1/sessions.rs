@@ -1,15 +1,44 @@
+use chrono::DateTime;
+use chrono::Utc;
 use mirabel_core::dto::session::FullSession;
+use std::collections::HashSet;
 use std::rc::Rc;
 use std::sync::Arc;
 use std::time::Duration;
 
 use crate::prelude::*;
 use mirabel_core::dto::api_response::ApiResponse;
+use mirabel_core::dto::document::ChunkSearch;
 use mirabel_core::dto::page::CursorPageRequest;
+use mirabel_core::dto::session::event::ClientEvent;
+use mirabel_core::dto::session::event::ClientMessage;
+use mirabel_core::dto::session::event::ErrorCode;
+use mirabel_core::dto::session::event::PROTOCOL_VERSION;
+use mirabel_core::dto::session::event::ServerMessage;
 use mirabel_core::dto::updated_session::UpdatedSession;
+use mirabel_core::models::attachment::Attachment;
+use mirabel_core::models::attachment::PNG;
+use mirabel_core::models::plan::Plan;
+use mirabel_core::models::plan::PlanAction;
+use mirabel_core::models::plan::PlanEdit;
+use mirabel_core::models::timeline::TimelineEntry;
+use mirabel_core::models::user::User;
+use mirabel_core::models::workspace::WorkspaceRole;
 
 use crate::handler::extractors::W;
+use crate::service::attachments::AttachmentService;
+use crate::service::code_index::CodeIndexService;
+use crate::service::file_actions::FileActionService;
+use crate::service::repositories::RepositoryService;
 use crate::service::sessions::SessionService;
+use crate::service::specs::SpecService;
+use crate::session::code::REPO_MAP_BUDGET;
+use crate::session::models::Interaction;
+use crate::session::models::LastSeen;
+use crate::session::models::Participant;
+use crate::session::models::ServerEvent;
+use crate::session::models::SessionWorker;
+use crate::session::models::ShellInput;
 use crate::session::models::UserInteraction;
 use crate::session::models::WorkerEvent;
 
@@ -19,8 +48,13 @@ use actix_web::Responder;
 use actix_web::Scope;
 use actix_web::delete;
 use actix_web::get;
+use actix_web::http::header::ContentDisposition;
+use actix_web::http::header::DispositionParam;
+use actix_web::http::header::DispositionType;
 use actix_web::patch;
+use actix_web::post;
 use actix_web::web;
+use actix_web::web::Bytes;
 use actix_web::web::Data;
 use actix_web::web::Json;
 use actix_web::web::Path;
@@ -31,22 +65,51 @@ use actix_ws::Session;
 use futures::StreamExt;
 use log::debug;
 use log::warn;
+use serde::Deserialize;
 use tokio::sync::Mutex;
 use tokio::sync::mpsc;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::mpsc::UnboundedSender;
 use tokio::time::Instant;
 
 // Constants
 const PING_INTERVAL_SECS: u64 = 5;
 const INACTIVE_TIMEOUT_SECS: u64 = 10;
+const SSE_RETRY_MILLIS: u64 = 3000;
+const MAX_DOCUMENT_BYTES: usize = 20 * 1024 * 1024;
 
 pub fn scope(cfg: &mut web::ServiceConfig) {
     cfg.service(
         Scope::new("/session/{session_id}")
+            .app_data(web::PayloadConfig::new(MAX_DOCUMENT_BYTES))
             .service(get_workspace_session)
             .service(get_session_timeline)
+            .service(get_session_specs)
+            .service(get_session_plan)
+            .service(approve_session_plan)
+            .service(reject_session_plan)
+            .service(edit_session_plan)
             .service(archive_user_session)
             .service(update_user_session)
-            .service(session_socket),
+            .service(session_socket)
+            .service(session_events)
+            .service(post_session_interaction)
+            .service(post_session_shell)
+            .service(get_session_actions)
+            .service(revert_session_action)
+            .service(get_session_worktrees)
+            .service(create_session_worktree)
+            .service(get_session_worktree_diff)
+            .service(commit_session_worktree)
+            .service(merge_session_worktree)
+            .service(discard_session_worktree)
+            .service(index_session_worktree)
+            .service(get_session_repo_map)
+            .service(get_session_symbols)
+            .service(get_session_references)
+            .service(upload_session_attachment)
+            .service(get_session_attachment)
+            .service(search_session_attachment),
     );
 }
 
@@ -102,6 +165,536 @@ pub async fn get_session_timeline(
     Ok(ApiResponse::ok(timeline))
 }
 
+/// Every version of the session spec, oldest first, with the diff to its
+/// predecessor
+#[get("/spec")]
+pub async fn get_session_specs(
+    session_service: Data<SessionService>,
+    spec_service: Data<SpecService>,
+    user: W,
+    ids: Path<(String, String)>,
+) -> Result<impl Responder> {
+    let (workspace_id, session_id) = ids.into_inner();
+    session_service
+        .get_user_session_by_id(user.into_inner(), workspace_id, session_id.clone())
+        .await?
+        .ok_or(Error::NotFound)?;
+    Ok(ApiResponse::ok(
+        spec_service.get_versions(session_id).await?,
+    ))
+}
+
+/// The latest plan of the session, whatever its status
+#[get("/plan")]
+pub async fn get_session_plan(
+    session_service: Data<SessionService>,
+    user: W,
+    ids: Path<(String, String)>,
+) -> Result<impl Responder> {
+    let (workspace_id, session_id) = ids.into_inner();
+    session_service
+        .get_user_session_by_id(user.into_inner(), workspace_id, session_id.clone())
+        .await?
+        .ok_or(Error::NotFound)?;
+    Ok(ApiResponse::ok(
+        session_service.get_latest_plan(session_id).await?,
+    ))
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RejectPlan {
+    reason: Option<String>,
+}
+
+#[post("/plan/{plan_id}/approve")]
+pub async fn approve_session_plan(
+    session_service: Data<SessionService>,
+    user: W,
+    ids: Path<(String, String, String)>,
+) -> Result<impl Responder> {
+    let plan = plan_action(session_service, user, ids, PlanAction::Approve).await?;
+    Ok(ApiResponse::ok(plan))
+}
+
+/// Rejects the plan, a revised draft follows on the timeline
+#[post("/plan/{plan_id}/reject")]
+pub async fn reject_session_plan(
+    session_service: Data<SessionService>,
+    user: W,
+    ids: Path<(String, String, String)>,
+    body: Option<Json<RejectPlan>>,
+) -> Result<impl Responder> {
+    let reason = body.and_then(|body| body.into_inner().reason);
+    let plan = plan_action(session_service, user, ids, PlanAction::Reject { reason }).await?;
+    Ok(ApiResponse::ok(plan))
+}
+
+#[patch("/plan/{plan_id}")]
+pub async fn edit_session_plan(
+    session_service: Data<SessionService>,
+    user: W,
+    ids: Path<(String, String, String)>,
+    edit: Json<PlanEdit>,
+) -> Result<impl Responder> {
+    let action = PlanAction::Edit {
+        edit: edit.into_inner(),
+    };
+    let plan = plan_action(session_service, user, ids, action).await?;
+    Ok(ApiResponse::ok(plan))
+}
+
+async fn plan_action(
+    session_service: Data<SessionService>,
+    user: W,
+    ids: Path<(String, String, String)>,
+    action: PlanAction,
+) -> Result<Plan> {
+    let (workspace_id, session_id, plan_id) = ids.into_inner();
+    writable_handler(
+        &session_service,
+        user.into_inner(),
+        workspace_id,
+        session_id,
+    )
+    .await?
+    .plan_action(plan_id, action)
+    .await
+}
+
+/// Every file action of the session, oldest first, with its diff
+#[get("/actions")]
+pub async fn get_session_actions(
+    session_service: Data<SessionService>,
+    file_action_service: Data<FileActionService>,
+    user: W,
+    ids: Path<(String, String)>,
+) -> Result<impl Responder> {
+    let (workspace_id, session_id) = ids.into_inner();
+    session_service
+        .get_user_session_by_id(user.into_inner(), workspace_id, session_id.clone())
+        .await?
+        .ok_or(Error::NotFound)?;
+    Ok(ApiResponse::ok(
+        file_action_service.get_all(session_id).await?,
+    ))
+}
+
+/// Undoes a file action, the revert is recorded as an action of its own
+#[post("/actions/{action_id}/revert")]
+pub async fn revert_session_action(
+    session_service: Data<SessionService>,
+    user: W,
+    ids: Path<(String, String, String)>,
+) -> Result<impl Responder> {
+    let (workspace_id, session_id, action_id) = ids.into_inner();
+    let handler = writable_handler(
+        &session_service,
+        user.into_inner(),
+        workspace_id,
+        session_id,
+    )
+    .await?;
+    Ok(ApiResponse::ok(handler.revert_action(action_id).await?))
+}
+
+/// The checkouts of workspace repositories the session has or had
+#[get("/repository")]
+pub async fn get_session_worktrees(
+    session_service: Data<SessionService>,
+    repository_service: Data<RepositoryService>,
+    user: W,
+    ids: Path<(String, String)>,
+) -> Result<impl Responder> {
+    let (workspace_id, session_id) = ids.into_inner();
+    session_service
+        .get_user_session_by_id(user.into_inner(), workspace_id, session_id.clone())
+        .await?
+        .ok_or(Error::NotFound)?;
+    Ok(ApiResponse::ok(
+        repository_service.get_worktrees(session_id).await?,
+    ))
+}
+
+/// Checks a repository out into the session sandbox, on a branch of the
+/// session. The code of the checkout is indexed in the background.
+#[post("/repository/{repository_id}")]
+pub async fn create_session_worktree(
+    session_service: Data<SessionService>,
+    repository_service: Data<RepositoryService>,
+    code_index_service: Data<CodeIndexService>,
+    user: W,
+    ids: Path<(String, String, String)>,
+) -> Result<impl Responder> {
+    let (workspace_id, session_id, repository_id) = ids.into_inner();
+    let session = writable_session(
+        &session_service,
+        user.into_inner(),
+        workspace_id,
+        session_id.clone(),
+    )
+    .await?;
+    let worktree = repository_service
+        .create_worktree(&session, repository_id.clone())
+        .await?;
+    let (_, root) = repository_service
+        .get_checkout(session_id, repository_id)
+        .await?;
+    let worktree_id = worktree.id.clone();
+    actix_web::rt::spawn(async move {
+        if let Err(err) = code_index_service.index(worktree_id.clone(), root).await {
+            warn!("Failed to index worktree {worktree_id}: {err}");
+        }
+    });
+    Ok(ApiResponse::ok(worktree))
+}
+
+/// The changes of the session to the repository as a unified diff
+#[get("/repository/{repository_id}/diff")]
+pub async fn get_session_worktree_diff(
+    session_service: Data<SessionService>,
+    repository_service: Data<RepositoryService>,
+    user: W,
+    ids: Path<(String, String, String)>,
+) -> Result<impl Responder> {
+    let (workspace_id, session_id, repository_id) = ids.into_inner();
+    session_service
+        .get_user_session_by_id(user.into_inner(), workspace_id, session_id.clone())
+        .await?
+        .ok_or(Error::NotFound)?;
+    Ok(ApiResponse::ok(
+        repository_service.diff(session_id, repository_id).await?,
+    ))
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CommitInput {
+    message: String,
+}
+
+/// Commits the changes of the session, returns the commit or nothing when
+/// there were no changes
+#[post("/repository/{repository_id}/commit")]
+pub async fn commit_session_worktree(
+    session_service: Data<SessionService>,
+    repository_service: Data<RepositoryService>,
+    user: W,
+    ids: Path<(String, String, String)>,
+    input: Json<CommitInput>,
+) -> Result<impl Responder> {
+    let (workspace_id, session_id, repository_id) = ids.into_inner();
+    let user = user.into_inner();
+    writable_session(
+        &session_service,
+        user.clone(),
+        workspace_id,
+        session_id.clone(),
+    )
+    .await?;
+    Ok(ApiResponse::ok(
+        repository_service
+            .commit(session_id, repository_id, input.into_inner().message, &user)
+            .await?,
+    ))
+}
+
+/// Merges the session branch into the default branch of the repository, or
+/// pushes it for review when the repository does not merge upstream
+#[post("/repository/{repository_id}/merge")]
+pub async fn merge_session_worktree(
+    session_service: Data<SessionService>,
+    repository_service: Data<RepositoryService>,
+    code_index_service: Data<CodeIndexService>,
+    user: W,
+    ids: Path<(String, String, String)>,
+) -> Result<impl Responder> {
+    let (workspace_id, session_id, repository_id) = ids.into_inner();
+    let user = user.into_inner();
+    writable_session(
+        &session_service,
+        user.clone(),
+        workspace_id,
+        session_id.clone(),
+    )
+    .await?;
+    let worktree = repository_service
+        .merge(session_id, repository_id, &user)
+        .await?;
+    code_index_service.clear(worktree.id.clone()).await?;
+    Ok(ApiResponse::ok(worktree))
+}
+
+#[post("/repository/{repository_id}/discard")]
+pub async fn discard_session_worktree(
+    session_service: Data<SessionService>,
+    repository_service: Data<RepositoryService>,
+    code_index_service: Data<CodeIndexService>,
+    user: W,
+    ids: Path<(String, String, String)>,
+) -> Result<impl Responder> {
+    let (workspace_id, session_id, repository_id) = ids.into_inner();
+    writable_session(
+        &session_service,
+        user.into_inner(),
+        workspace_id,
+        session_id.clone(),
+    )
+    .await?;
+    let worktree = repository_service
+        .discard(session_id, repository_id)
+        .await?;
+    code_index_service.clear(worktree.id.clone()).await?;
+    Ok(ApiResponse::ok(worktree))
+}
+
+/// Brings the code index of the checkout up to date with changes made
+/// outside of the editor, e.g. through the shell
+#[post("/repository/{repository_id}/index")]
+pub async fn index_session_worktree(
+    session_service: Data<SessionService>,
+    repository_service: Data<RepositoryService>,
+    code_index_service: Data<CodeIndexService>,
+    user: W,
+    ids: Path<(String, String, String)>,
+) -> Result<impl Responder> {
+    let (workspace_id, session_id, repository_id) = ids.into_inner();
+    writable_session(
+        &session_service,
+        user.into_inner(),
+        workspace_id,
+        session_id.clone(),
+    )
+    .await?;
+    let (worktree, root) = repository_service
+        .get_checkout(session_id, repository_id)
+        .await?;
+    Ok(ApiResponse::ok(
+        code_index_service.index(worktree.id, root).await?,
+    ))
+}
+
+/// An outline of the definitions in the checkout, the most used first
+#[get("/repository/{repository_id}/map")]
+pub async fn get_session_repo_map(
+    session_service: Data<SessionService>,
+    repository_service: Data<RepositoryService>,
+    code_index_service: Data<CodeIndexService>,
+    user: W,
+    ids: Path<(String, String, String)>,
+) -> Result<impl Responder> {
+    let (workspace_id, session_id, repository_id) = ids.into_inner();
+    session_service
+        .get_user_session_by_id(user.into_inner(), workspace_id, session_id.clone())
+        .await?
+        .ok_or(Error::NotFound)?;
+    let (worktree, _) = repository_service
+        .get_checkout(session_id, repository_id)
+        .await?;
+    Ok(ApiResponse::ok(
+        code_index_service
+            .repo_map(worktree.id, String::new(), REPO_MAP_BUDGET)
+            .await?,
+    ))
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SymbolQuery {
+    name: String,
+}
+
+/// Where a symbol of the checkout is defined
+#[get("/repository/{repository_id}/symbols")]
+pub async fn get_session_symbols(
+    session_service: Data<SessionService>,
+    repository_service: Data<RepositoryService>,
+    code_index_service: Data<CodeIndexService>,
+    user: W,
+    ids: Path<(String, String, String)>,
+    query: Query<SymbolQuery>,
+) -> Result<impl Responder> {
+    let (workspace_id, session_id, repository_id) = ids.into_inner();
+    session_service
+        .get_user_session_by_id(user.into_inner(), workspace_id, session_id.clone())
+        .await?
+        .ok_or(Error::NotFound)?;
+    let (worktree, _) = repository_service
+        .get_checkout(session_id, repository_id)
+        .await?;
+    Ok(ApiResponse::ok(
+        code_index_service
+            .definitions(worktree.id, vec![query.into_inner().name])
+            .await?,
+    ))
+}
+
+/// Where a symbol of the checkout is used
+#[get("/repository/{repository_id}/references")]
+pub async fn get_session_references(
+    session_service: Data<SessionService>,
+    repository_service: Data<RepositoryService>,
+    code_index_service: Data<CodeIndexService>,
+    user: W,
+    ids: Path<(String, String, String)>,
+    query: Query<SymbolQuery>,
+) -> Result<impl Responder> {
+    let (workspace_id, session_id, repository_id) = ids.into_inner();
+    session_service
+        .get_user_session_by_id(user.into_inner(), workspace_id, session_id.clone())
+        .await?
+        .ok_or(Error::NotFound)?;
+    let (worktree, _) = repository_service
+        .get_checkout(session_id, repository_id)
+        .await?;
+    Ok(ApiResponse::ok(
+        code_index_service
+            .references(worktree.id, vec![query.into_inner().name])
+            .await?,
+    ))
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct UploadQuery {
+    name: Option<String>,
+}
+
+/// Attaches a document to the session for the agents to look things up in.
+/// The body is the file, its format is told by the content, the
+/// `Content-Type` and the `name` it is uploaded with. Returns the id of the
+/// attachment.
+#[post("/attachments")]
+pub async fn upload_session_attachment(
+    req: HttpRequest,
+    session_service: Data<SessionService>,
+    attachment_service: Data<AttachmentService>,
+    user: W,
+    ids: Path<(String, String)>,
+    query: Query<UploadQuery>,
+    body: Bytes,
+) -> Result<impl Responder> {
+    let (workspace_id, session_id) = ids.into_inner();
+    writable_session(
+        &session_service,
+        user.into_inner(),
+        workspace_id,
+        session_id.clone(),
+    )
+    .await?;
+    if body.is_empty() {
+        return Err(Error::BadRequest("The document is empty.".into()));
+    }
+    let media_type = req
+        .headers()
+        .get(actix_web::http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("application/octet-stream")
+        .to_string();
+    let document = Attachment::document(
+        session_id,
+        query.into_inner().name,
+        media_type,
+        body.to_vec(),
+    );
+    let attachment = attachment_service.upload(document).await?;
+    Ok(ApiResponse::ok(attachment.id))
+}
+
+/// A file of the session as is, e.g. a screenshot on its timeline. Files
+/// never change, so they may be cached. Documents are uploaded by users and
+/// only ever downloaded, a browser must not render them as pages of ours.
+#[get("/attachments/{attachment_id}")]
+pub async fn get_session_attachment(
+    session_service: Data<SessionService>,
+    attachment_service: Data<AttachmentService>,
+    user: W,
+    ids: Path<(String, String, String)>,
+) -> Result<impl Responder> {
+    let (workspace_id, session_id, attachment_id) = ids.into_inner();
+    session_service
+        .get_user_session_by_id(user.into_inner(), workspace_id, session_id.clone())
+        .await?
+        .ok_or(Error::NotFound)?;
+    let attachment = attachment_service
+        .get(session_id, attachment_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+    let mut response = HttpResponse::Ok();
+    response
+        .insert_header(("Cache-Control", "private, max-age=31536000, immutable"))
+        .insert_header(("X-Content-Type-Options", "nosniff"));
+    if attachment.media_type == PNG {
+        response.content_type(attachment.media_type);
+    } else {
+        response
+            .content_type("application/octet-stream")
+            .insert_header(ContentDisposition {
+                disposition: DispositionType::Attachment,
+                parameters: attachment
+                    .name
+                    .map(|name| vec![DispositionParam::Filename(name)])
+                    .unwrap_or_default(),
+            });
+    }
+    Ok(response.body(attachment.data))
+}
+
+/// The parts of a document of the session that answer a question, so agents
+/// can work with documents longer than their context
+#[get("/attachments/{attachment_id}/chunks")]
+pub async fn search_session_attachment(
+    session_service: Data<SessionService>,
+    attachment_service: Data<AttachmentService>,
+    user: W,
+    ids: Path<(String, String, String)>,
+    search: Query<ChunkSearch>,
+) -> Result<impl Responder> {
+    let (workspace_id, session_id, attachment_id) = ids.into_inner();
+    session_service
+        .get_user_session_by_id(user.into_inner(), workspace_id, session_id.clone())
+        .await?
+        .ok_or(Error::NotFound)?;
+    Ok(ApiResponse::ok(
+        attachment_service
+            .search(session_id, attachment_id, search.into_inner())
+            .await?
+            .ok_or(Error::NotFound)?,
+    ))
+}
+
+async fn writable_session(
+    session_service: &SessionService,
+    user: User,
+    workspace_id: String,
+    session_id: String,
+) -> Result<mirabel_core::models::session::Session> {
+    let (session, role) = session_service
+        .get_session_with_role(user, workspace_id, session_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+    writable(role)?;
+    Ok(session)
+}
+
+/// The worker of a session the user may change, like [`writable_session`]
+async fn writable_handler(
+    session_service: &SessionService,
+    user: User,
+    workspace_id: String,
+    session_id: String,
+) -> Result<Arc<SessionWorker>> {
+    let (handler, role) = session_service
+        .get_handler(user, workspace_id, session_id)
+        .await?;
+    writable(role)?;
+    Ok(handler)
+}
+
+/// Viewers may only read sessions, every change goes through here
+fn writable(role: WorkspaceRole) -> Result<()> {
+    match role.can_write() {
+        true => Ok(()),
+        false => Err(Error::Forbidden("You can only view this session.".into())),
+    }
+}
+
 #[get("")]
 pub async fn get_workspace_session(
     session_service: Data<SessionService>,
@@ -118,6 +711,23 @@ pub async fn get_workspace_session(
     Ok(ApiResponse::ok(session))
 }
 
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SocketQuery {
+    last_seen_id: Option<String>,
+    last_seen_at: Option<DateTime<Utc>>,
+}
+
+impl SocketQuery {
+    fn last_seen(self) -> Option<LastSeen> {
+        match (self.last_seen_id, self.last_seen_at) {
+            (Some(id), _) => Some(LastSeen::Entry(id)),
+            (None, Some(timestamp)) => Some(LastSeen::Timestamp(timestamp)),
+            (None, None) => None,
+        }
+    }
+}
+
 #[get("/socket")]
 pub async fn session_socket(
     req: HttpRequest,
@@ -125,16 +735,27 @@ pub async fn session_socket(
     session_service: Data<SessionService>,
     user: W,
     ids: Path<(String, String)>,
+    query: Query<SocketQuery>,
 ) -> Result<HttpResponse> {
     let (workspace_id, session_id) = ids.into_inner();
     debug!("WebSocket connection for session: {session_id}");
-    let (res, session, stream) = actix_ws::handle(&req, stream)?;
-    let handler = session_service
-        .get_handler(user.into_inner(), workspace_id, session_id)
+    let user = user.into_inner();
+    let participant = Participant::from(&user);
+    let (handler, role) = session_service
+        .get_handler(user, workspace_id, session_id)
         .await?;
 
     let (sender, receiver) = mpsc::unbounded_channel();
-    let (id, sender) = handler.subscribe(sender).await?;
+    let (id, sender, missed) = handler
+        .subscribe_from(sender, participant.clone(), query.into_inner().last_seen())
+        .await?;
+    let (res, session, stream) = match actix_ws::handle(&req, stream) {
+        Ok(handshake) => handshake,
+        Err(e) => {
+            let _ = sender.send(WorkerEvent::Unsubscribe(id));
+            return Err(e.into());
+        }
+    };
 
     let session = Arc::new(Mutex::new(session));
     let stream = Rc::new(Mutex::new(stream));
@@ -146,27 +767,23 @@ pub async fn session_socket(
     let open_clone = open.clone();
     let mut receiver = receiver;
     actix_web::rt::spawn(async move {
-        while let Some(event) = receiver.recv().await {
+        let mut replay = Replay::new(missed);
+        loop {
+            let event = match replay.next() {
+                Some(event) => event,
+                None => match receiver.recv().await {
+                    Some(event) if replay.replayed(&event) => continue,
+                    Some(event) => event,
+                    None => break,
+                },
+            };
             if !*open_clone.lock().await {
                 break;
             }
 
-            match serde_json::to_string(&event) {
-                Ok(serialized_event) => {
-                    if session_clone
-                        .lock()
-                        .await
-                        .text(serialized_event)
-                        .await
-                        .is_err()
-                    {
-                        *open_clone.lock().await = false;
-                        break;
-                    }
-                }
-                Err(e) => {
-                    warn!("Error serializing event: {e:?}");
-                }
+            if send_event(&session_clone, event).await.is_err() {
+                *open_clone.lock().await = false;
+                break;
             }
         }
         debug!("Outgoing event handler stopped");
@@ -177,7 +794,12 @@ pub async fn session_socket(
     let stream_clone = stream.clone();
     let open_clone = open.clone();
     let alive_clone = alive.clone();
-    let sender_clone = sender.clone();
+    let client = SocketClient {
+        subscriber_id: id,
+        user_id: participant.user_id,
+        can_write: role.can_write(),
+        sender,
+    };
     actix_web::rt::spawn(async move {
         while let Some(msg) = stream_clone.lock().await.next().await {
             match msg {
@@ -187,7 +809,7 @@ pub async fn session_socket(
                         msg,
                         open_clone.clone(),
                         alive_clone.clone(),
-                        sender_clone.clone(),
+                        &client,
                     )
                     .await
                     {
@@ -207,7 +829,9 @@ pub async fn session_socket(
         }
         debug!("Incoming message handler stopped");
 
-        let _ = sender_clone.send(WorkerEvent::Unsubscribe(id));
+        let _ = client
+            .sender
+            .send(WorkerEvent::Unsubscribe(client.subscriber_id));
     });
 
     // Start keep-alive handler
@@ -229,6 +853,16 @@ pub async fn session_socket(
                 break;
             }
 
+            // Browsers do not expose websocket pings, so clients get an
+            // application level heartbeat as well
+            if send_event(&session_clone, ServerEvent::heartbeat())
+                .await
+                .is_err()
+            {
+                *open_clone.lock().await = false;
+                break;
+            }
+
             let last_alive = *alive_clone.lock().await;
             if Instant::now().duration_since(last_alive)
                 > Duration::from_secs(INACTIVE_TIMEOUT_SECS)
@@ -245,25 +879,331 @@ pub async fn session_socket(
     Ok(res)
 }
 
+/// Server-Sent Events alternative to [`session_socket`] for clients whose
+/// network does not allow websockets. Every event is a [`ServerMessage`],
+/// timeline entries carry their id as the SSE event id so the browser resumes
+/// with `Last-Event-ID` on reconnect. The first event, named `subscribed`,
+/// holds the subscriber id to pass along to [`post_session_interaction`].
+#[get("/events")]
+pub async fn session_events(
+    req: HttpRequest,
+    session_service: Data<SessionService>,
+    user: W,
+    ids: Path<(String, String)>,
+    query: Query<SocketQuery>,
+) -> Result<HttpResponse> {
+    let (workspace_id, session_id) = ids.into_inner();
+    debug!("SSE connection for session: {session_id}");
+    let user = user.into_inner();
+    let participant = Participant::from(&user);
+    let (handler, _) = session_service
+        .get_handler(user, workspace_id, session_id)
+        .await?;
+
+    let last_seen = resume_from(&req, query.into_inner());
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let (id, sender, missed) = handler
+        .subscribe_from(sender, participant, last_seen)
+        .await?;
+    let stream = EventStream::new(Subscription { id, sender }, missed, receiver).into_stream();
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(stream))
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InteractionQuery {
+    subscriber_id: Option<String>,
+}
+
+/// Hands a [`UserInteraction`] to the session worker, the results are
+/// delivered to the subscribers like any other event. Passing the
+/// `subscriberId` of an event stream routes failures to that stream.
+#[post("/interaction")]
+pub async fn post_session_interaction(
+    session_service: Data<SessionService>,
+    user: W,
+    ids: Path<(String, String)>,
+    query: Query<InteractionQuery>,
+    interaction: Json<UserInteraction>,
+) -> Result<impl Responder> {
+    let (workspace_id, session_id) = ids.into_inner();
+    let user = user.into_inner();
+    let user_id = user.id.clone();
+    let handler = writable_handler(&session_service, user, workspace_id, session_id).await?;
+    handler
+        .sender
+        .send(WorkerEvent::UserInteraction(Interaction {
+            user_id,
+            subscriber_id: query.into_inner().subscriber_id,
+            correlation_id: None,
+            interaction: interaction.into_inner(),
+        }))
+        .map_err(|_| Error::InternalServer)?;
+    Ok(ApiResponse::ok(()))
+}
+
+/// Types into the session shell, for clients following an event stream
+#[post("/shell")]
+pub async fn post_session_shell(
+    session_service: Data<SessionService>,
+    user: W,
+    ids: Path<(String, String)>,
+    input: Json<ShellInput>,
+) -> Result<impl Responder> {
+    let (workspace_id, session_id) = ids.into_inner();
+    let handler = writable_handler(
+        &session_service,
+        user.into_inner(),
+        workspace_id,
+        session_id,
+    )
+    .await?;
+    handler.shell_input(input.into_inner()).await?;
+    Ok(ApiResponse::ok(()))
+}
+
+/// The entries a resuming subscriber missed. They are handed out before any
+/// live event, live entries that were already part of the replay are skipped.
+struct Replay {
+    missed: std::vec::IntoIter<TimelineEntry>,
+    ids: HashSet<String>,
+}
+
+impl Replay {
+    fn new(missed: Vec<TimelineEntry>) -> Self {
+        Self {
+            ids: missed.iter().map(|entry| entry.id.clone()).collect(),
+            missed: missed.into_iter(),
+        }
+    }
+
+    fn replayed(&self, event: &ServerEvent) -> bool {
+        matches!(event, ServerEvent::TimelineEntry(entry) if self.ids.contains(&entry.id))
+    }
+}
+
+impl Iterator for Replay {
+    type Item = ServerEvent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.missed.next().map(ServerEvent::TimelineEntry)
+    }
+}
+
+struct EventStream {
+    greeting: Option<Bytes>,
+    replay: Replay,
+    receiver: UnboundedReceiver<ServerEvent>,
+    _subscription: Subscription,
+}
+
+impl EventStream {
+    fn new(
+        subscription: Subscription,
+        missed: Vec<TimelineEntry>,
+        receiver: UnboundedReceiver<ServerEvent>,
+    ) -> Self {
+        let greeting = format!(
+            "retry: {SSE_RETRY_MILLIS}\nevent: subscribed\ndata: {}\n\n",
+            subscription.id
+        );
+        Self {
+            greeting: Some(Bytes::from(greeting)),
+            replay: Replay::new(missed),
+            receiver,
+            _subscription: subscription,
+        }
+    }
+
+    /// The greeting, then the replay, then live events until the worker goes
+    /// away
+    fn into_stream(self) -> impl futures::Stream<Item = Result<Bytes>> {
+        futures::stream::unfold(self, |mut state| async move {
+            if let Some(greeting) = state.greeting.take() {
+                return Some((Ok(greeting), state));
+            }
+            let event = match state.replay.next() {
+                Some(event) => event,
+                None => loop {
+                    // Idle streams get a heartbeat so proxies keep them open
+                    let next = tokio::time::timeout(
+                        Duration::from_secs(PING_INTERVAL_SECS),
+                        state.receiver.recv(),
+                    )
+                    .await;
+                    match next {
+                        Ok(Some(event)) if state.replay.replayed(&event) => continue,
+                        Ok(Some(event)) => break event,
+                        Ok(None) => return None,
+                        Err(_) => break ServerEvent::heartbeat(),
+                    }
+                },
+            };
+            Some((sse_frame(event), state))
+        })
+    }
+}
+
+/// Unsubscribes once the event stream is dropped, which is when the client
+/// goes away.
+struct Subscription {
+    id: String,
+    sender: UnboundedSender<WorkerEvent>,
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let _ = self.sender.send(WorkerEvent::Unsubscribe(self.id.clone()));
+    }
+}
+
+/// Where an event stream resumes. The `Last-Event-ID` header is set by the
+/// browser on reconnect and wins over the query.
+fn resume_from(req: &HttpRequest, query: SocketQuery) -> Option<LastSeen> {
+    req.headers()
+        .get("Last-Event-ID")
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty())
+        .map(|value| LastSeen::Entry(value.to_string()))
+        .or_else(|| query.last_seen())
+}
+
+fn sse_frame(event: ServerEvent) -> Result<Bytes> {
+    let id = match &event {
+        ServerEvent::TimelineEntry(entry) => Some(entry.id.clone()),
+        _ => None,
+    };
+    let data = serde_json::to_string(&ServerMessage::new(event))?;
+    let frame = match id {
+        Some(id) => format!("id: {id}\ndata: {data}\n\n"),
+        None => format!("data: {data}\n\n"),
+    };
+    Ok(Bytes::from(frame))
+}
+
+/// Everything the incoming side of a socket needs to know about who is on
+/// the other end.
+struct SocketClient {
+    subscriber_id: String,
+    user_id: String,
+    can_write: bool,
+    sender: UnboundedSender<WorkerEvent>,
+}
+
+async fn send_event(session: &Arc<Mutex<Session>>, event: ServerEvent) -> Result<()> {
+    let message = serde_json::to_string(&ServerMessage::new(event))?;
+    session
+        .lock()
+        .await
+        .text(message)
+        .await
+        .map_err(|_| Error::SocketClosed)
+}
+
+/// Best effort extraction of the correlation id from a message that could not
+/// be parsed, so the error can still be matched by the client.
+fn correlation_id_of(text: &str) -> Option<String> {
+    serde_json::from_str::<serde_json::Value>(text)
+        .ok()?
+        .get("correlationId")?
+        .as_str()
+        .map(str::to_string)
+}
+
+/// The error a well formed message is refused with, viewers can only send
+/// heartbeats
+fn rejection(message: &ClientMessage, can_write: bool) -> Option<ServerEvent> {
+    if message.version != PROTOCOL_VERSION {
+        return Some(ServerEvent::error(
+            ErrorCode::UnsupportedVersion,
+            format!(
+                "Protocol version {} is not supported, expected {PROTOCOL_VERSION}",
+                message.version
+            ),
+            message.correlation_id.clone(),
+        ));
+    }
+    let writes = matches!(
+        message.event,
+        ClientEvent::Interaction(_) | ClientEvent::Typing | ClientEvent::Shell(_)
+    );
+    (writes && !can_write).then(|| {
+        ServerEvent::error(
+            ErrorCode::Forbidden,
+            "You can only view this session",
+            message.correlation_id.clone(),
+        )
+    })
+}
+
 async fn handle_message(
     session: Arc<Mutex<Session>>,
     msg: Message,
     open: Arc<Mutex<bool>>,
     alive: Arc<Mutex<Instant>>,
-    sender: mpsc::UnboundedSender<WorkerEvent>,
+    client: &SocketClient,
 ) -> Result<()> {
     match msg {
-        Message::Text(text) => match serde_json::from_str::<UserInteraction>(&text) {
-            Ok(event) => {
-                if sender.send(WorkerEvent::UserInteraction(event)).is_err() {
-                    *open.lock().await = false;
-                    return Err(Error::SocketClosed);
+        Message::Text(text) => {
+            let message = match serde_json::from_str::<ClientMessage>(&text) {
+                Ok(message) => message,
+                Err(e) => {
+                    debug!("Received an invalid client message: {e:?}");
+                    let error = ServerEvent::error(
+                        ErrorCode::InvalidMessage,
+                        e.to_string(),
+                        correlation_id_of(&text),
+                    );
+                    return send_event(&session, error).await;
                 }
+            };
+            if let Some(error) = rejection(&message, client.can_write) {
+                return send_event(&session, error).await;
             }
-            Err(e) => {
-                warn!("Error parsing SessionEvent: {e:?}");
+            match message.event {
+                ClientEvent::Interaction(interaction) => {
+                    let event = WorkerEvent::UserInteraction(Interaction {
+                        user_id: client.user_id.clone(),
+                        subscriber_id: Some(client.subscriber_id.clone()),
+                        correlation_id: message.correlation_id.clone(),
+                        interaction,
+                    });
+                    if client.sender.send(event).is_err() {
+                        let error = ServerEvent::error(
+                            ErrorCode::SessionClosed,
+                            "The session is no longer running",
+                            message.correlation_id,
+                        );
+                        let _ = send_event(&session, error).await;
+                        *open.lock().await = false;
+                        return Err(Error::SocketClosed);
+                    }
+                    if let Some(correlation_id) = message.correlation_id {
+                        send_event(&session, ServerEvent::ack(correlation_id)).await?;
+                    }
+                }
+                ClientEvent::Heartbeat(_) => {
+                    *alive.lock().await = Instant::now();
+                }
+                ClientEvent::Typing => {
+                    let _ = client
+                        .sender
+                        .send(WorkerEvent::Typing(client.subscriber_id.clone()));
+                }
+                ClientEvent::Shell(input) => {
+                    let _ = client.sender.send(WorkerEvent::Shell {
+                        subscriber_id: client.subscriber_id.clone(),
+                        correlation_id: message.correlation_id,
+                        input,
+                    });
+                }
             }
-        },
+        }
         Message::Ping(bytes) => {
             if session.lock().await.pong(&bytes).await.is_err() {
                 *open.lock().await = false;
@@ -285,3 +1225,181 @@ async fn handle_message(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use actix_web::test::TestRequest;
+    use mirabel_core::dto::session::event::Heartbeat;
+
+    fn entry(content: &str) -> TimelineEntry {
+        TimelineEntry::agent_message("session".into(), content.into())
+    }
+
+    #[test]
+    fn test_socket_query_last_seen() {
+        let at: DateTime<Utc> = "2025-09-01T12:00:00Z".parse().unwrap();
+        let query = |id: Option<&str>, at: Option<DateTime<Utc>>| SocketQuery {
+            last_seen_id: id.map(str::to_string),
+            last_seen_at: at,
+        };
+        assert_eq!(query(None, None).last_seen(), None);
+        assert_eq!(
+            query(None, Some(at)).last_seen(),
+            Some(LastSeen::Timestamp(at))
+        );
+        // The entry is exact, it wins over the timestamp
+        assert_eq!(
+            query(Some("entry"), Some(at)).last_seen(),
+            Some(LastSeen::Entry("entry".into()))
+        );
+    }
+
+    #[test]
+    fn test_replay() {
+        let (first, second, live) = (entry("first"), entry("second"), entry("live"));
+        let mut replay = Replay::new(vec![first.clone(), second.clone()]);
+        assert_eq!(
+            replay.next(),
+            Some(ServerEvent::TimelineEntry(first.clone()))
+        );
+        assert_eq!(
+            replay.next(),
+            Some(ServerEvent::TimelineEntry(second.clone()))
+        );
+        assert_eq!(replay.next(), None);
+
+        // Entries written while the replay was queried also arrive live
+        assert!(replay.replayed(&ServerEvent::TimelineEntry(first)));
+        assert!(replay.replayed(&ServerEvent::TimelineEntry(second)));
+        assert!(!replay.replayed(&ServerEvent::TimelineEntry(live)));
+        assert!(!replay.replayed(&ServerEvent::heartbeat()));
+    }
+
+    #[test]
+    fn test_resume_from() {
+        let query = || SocketQuery {
+            last_seen_id: Some("query".into()),
+            last_seen_at: None,
+        };
+        let req = TestRequest::default().to_http_request();
+        assert_eq!(
+            resume_from(&req, query()),
+            Some(LastSeen::Entry("query".into()))
+        );
+        let req = TestRequest::default()
+            .insert_header(("Last-Event-ID", "header"))
+            .to_http_request();
+        assert_eq!(
+            resume_from(&req, query()),
+            Some(LastSeen::Entry("header".into()))
+        );
+        // Browsers send an empty id when no event carried one
+        let req = TestRequest::default()
+            .insert_header(("Last-Event-ID", ""))
+            .to_http_request();
+        assert_eq!(
+            resume_from(&req, query()),
+            Some(LastSeen::Entry("query".into()))
+        );
+    }
+
+    #[test]
+    fn test_sse_frame() {
+        let entry = entry("hello");
+        let frame = sse_frame(ServerEvent::TimelineEntry(entry.clone())).unwrap();
+        let frame = std::str::from_utf8(&frame).unwrap();
+        let data = serde_json::to_string(&ServerMessage::new(ServerEvent::TimelineEntry(
+            entry.clone(),
+        )))
+        .unwrap();
+        assert_eq!(frame, format!("id: {}\ndata: {data}\n\n", entry.id));
+
+        // Only timeline entries move the resume point
+        let frame = sse_frame(ServerEvent::ack("correlation".into())).unwrap();
+        assert!(frame.starts_with(b"data: "));
+    }
+
+    #[tokio::test]
+    async fn test_event_stream() {
+        let (missed, live) = (entry("missed"), entry("live"));
+        let (worker, mut unsubscribed) = mpsc::unbounded_channel();
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let subscription = Subscription {
+            id: "subscriber".into(),
+            sender: worker,
+        };
+        let stream = EventStream::new(subscription, vec![missed.clone()], receiver).into_stream();
+
+        // The missed entry also arrives live, it is only sent once
+        sender
+            .send(ServerEvent::TimelineEntry(missed.clone()))
+            .unwrap();
+        sender
+            .send(ServerEvent::TimelineEntry(live.clone()))
+            .unwrap();
+        drop(sender);
+        let frames: Vec<String> = stream
+            .map(|frame| String::from_utf8(frame.unwrap().to_vec()).unwrap())
+            .collect()
+            .await;
+        assert_eq!(frames.len(), 3, "{frames:?}");
+        assert_eq!(
+            frames[0],
+            format!("retry: {SSE_RETRY_MILLIS}\nevent: subscribed\ndata: subscriber\n\n")
+        );
+        assert!(frames[1].starts_with(&format!("id: {}\n", missed.id)));
+        assert!(frames[2].starts_with(&format!("id: {}\n", live.id)));
+
+        // Dropping the stream ends the subscription
+        assert!(matches!(
+            unsubscribed.recv().await,
+            Some(WorkerEvent::Unsubscribe(id)) if id == "subscriber"
+        ));
+    }
+
+    #[test]
+    fn test_rejection() {
+        let typing = ClientMessage::new(ClientEvent::Typing, Some("typing".into()));
+        let shell = ClientMessage::new(
+            ClientEvent::Shell(ShellInput::Data {
+                data: "ls\n".into(),
+            }),
+            None,
+        );
+        let heartbeat = ClientMessage::new(
+            ClientEvent::Heartbeat(Heartbeat {
+                timestamp: Utc::now(),
+            }),
+            None,
+        );
+
+        for message in [&typing, &shell, &heartbeat] {
+            assert_eq!(rejection(message, true), None);
+        }
+        assert_eq!(
+            rejection(&typing, false),
+            Some(ServerEvent::error(
+                ErrorCode::Forbidden,
+                "You can only view this session",
+                Some("typing".into())
+            ))
+        );
+        assert!(matches!(
+            rejection(&shell, false),
+            Some(ServerEvent::Error(error)) if error.code == ErrorCode::Forbidden
+        ));
+        // Viewers keep their connection alive
+        assert_eq!(rejection(&heartbeat, false), None);
+
+        let outdated = ClientMessage {
+            version: PROTOCOL_VERSION + 1,
+            ..heartbeat
+        };
+        assert!(matches!(
+            rejection(&outdated, true),
+            Some(ServerEvent::Error(error)) if error.code == ErrorCode::UnsupportedVersion
+        ));
+    }
+}
@@ -1,10 +1,18 @@
 use crate::prelude::*;
 use mirabel_core::dto::api_response::ApiResponse;
+use mirabel_core::dto::domain_rule::NewDomainRule;
+use mirabel_core::dto::memory::NewMemory;
+use mirabel_core::dto::memory::UpdatedMemory;
 use mirabel_core::dto::page::PageRequest;
+use mirabel_core::dto::repository::NewRepository;
+use mirabel_core::dto::search::TimelineSearch;
+use mirabel_core::models::user::User;
 
 use actix_web::Responder;
 use actix_web::Scope;
+use actix_web::delete;
 use actix_web::get;
+use actix_web::patch;
 use actix_web::post;
 use actix_web::web;
 use actix_web::web::Data;
@@ -16,6 +24,10 @@ use serde::Deserialize;
 
 use crate::handler::extractors::W;
 use crate::handler::middleware::auth_middleware::Auth;
+use crate::service::domain_rules::DomainRuleService;
+use crate::service::memories::MemoryService;
+use crate::service::repositories::RepositoryService;
+use crate::service::search::TimelineSearchService;
 use crate::service::sessions::SessionService;
 use crate::service::workspaces::WorkspaceService;
 
@@ -28,6 +40,17 @@ pub fn scope(cfg: &mut web::ServiceConfig) {
             .service(get_workspace_by_id)
             .service(get_user_workspace_sessions)
             .service(create_workspace_session)
+            .service(search_workspace_timelines)
+            .service(get_workspace_repositories)
+            .service(create_workspace_repository)
+            .service(delete_workspace_repository)
+            .service(get_workspace_memories)
+            .service(create_workspace_memory)
+            .service(update_workspace_memory)
+            .service(delete_workspace_memory)
+            .service(get_workspace_domain_rules)
+            .service(set_workspace_domain_rule)
+            .service(delete_workspace_domain_rule)
             .configure(sessions::scope),
     );
 }
@@ -45,12 +68,20 @@ pub async fn get_workspace_by_id(
     ))
 }
 
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SessionListQuery {
+    #[serde(default)]
+    include_shared: bool,
+}
+
 #[get("/session")]
 pub async fn get_user_workspace_sessions(
     session_service: Data<SessionService>,
     user: W,
     workspace_id: Path<String>,
     page: Query<PageRequest>,
+    filter: Query<SessionListQuery>,
 ) -> Result<impl Responder> {
     Ok(ApiResponse::ok(
         session_service
@@ -58,6 +89,7 @@ pub async fn get_user_workspace_sessions(
                 workspace_id.to_string(),
                 user.into_inner(),
                 page.into_inner(),
+                filter.include_shared,
             )
             .await?,
     ))
@@ -81,3 +113,261 @@ pub async fn create_workspace_session(
             .await?,
     ))
 }
+
+/// Finds where something was discussed in the sessions of the workspace
+#[get("/search")]
+pub async fn search_workspace_timelines(
+    search_service: Data<TimelineSearchService>,
+    user: W,
+    workspace_id: Path<String>,
+    search: Query<TimelineSearch>,
+) -> Result<impl Responder> {
+    Ok(ApiResponse::ok(
+        search_service
+            .search(
+                user.into_inner(),
+                workspace_id.to_string(),
+                search.into_inner(),
+            )
+            .await?,
+    ))
+}
+
+#[get("/repository")]
+pub async fn get_workspace_repositories(
+    workspace_service: Data<WorkspaceService>,
+    repository_service: Data<RepositoryService>,
+    user: W,
+    workspace_id: Path<String>,
+) -> Result<impl Responder> {
+    let workspace_id = workspace_id.into_inner();
+    workspace_service
+        .get_role(user.into_inner().id, workspace_id.clone())
+        .await?
+        .ok_or(Error::NotFound)?;
+    Ok(ApiResponse::ok(
+        repository_service.get_all(workspace_id).await?,
+    ))
+}
+
+/// Clones a repository into the workspace, which can take a while for big
+/// repositories
+#[post("/repository")]
+pub async fn create_workspace_repository(
+    workspace_service: Data<WorkspaceService>,
+    repository_service: Data<RepositoryService>,
+    user: W,
+    workspace_id: Path<String>,
+    new_repository: Json<NewRepository>,
+) -> Result<impl Responder> {
+    let workspace_id = workspace_id.into_inner();
+    require_admin(
+        &workspace_service,
+        user.into_inner(),
+        workspace_id.clone(),
+        "repositories",
+    )
+    .await?;
+    Ok(ApiResponse::ok(
+        repository_service
+            .create(workspace_id, new_repository.into_inner())
+            .await?,
+    ))
+}
+
+#[delete("/repository/{repository_id}")]
+pub async fn delete_workspace_repository(
+    workspace_service: Data<WorkspaceService>,
+    repository_service: Data<RepositoryService>,
+    user: W,
+    ids: Path<(String, String)>,
+) -> Result<impl Responder> {
+    let (workspace_id, repository_id) = ids.into_inner();
+    require_admin(
+        &workspace_service,
+        user.into_inner(),
+        workspace_id.clone(),
+        "repositories",
+    )
+    .await?;
+    repository_service
+        .delete(workspace_id, repository_id)
+        .await?;
+    Ok(ApiResponse::ok(()))
+}
+
+/// Memories the agents recall with most relevant ones for the query
+const MEMORY_RECALL_LIMIT: usize = 20;
+
+#[derive(Debug, Clone, Deserialize)]
+struct MemoryQuery {
+    query: Option<String>,
+}
+
+/// Lists what the workspace remembers, or only the memories relevant to
+/// `query` the way agents recall them
+#[get("/memory")]
+pub async fn get_workspace_memories(
+    workspace_service: Data<WorkspaceService>,
+    memory_service: Data<MemoryService>,
+    user: W,
+    workspace_id: Path<String>,
+    query: Query<MemoryQuery>,
+) -> Result<impl Responder> {
+    let workspace_id = workspace_id.into_inner();
+    workspace_service
+        .get_role(user.into_inner().id, workspace_id.clone())
+        .await?
+        .ok_or(Error::NotFound)?;
+    let memories = match query.into_inner().query {
+        Some(query) => {
+            memory_service
+                .recall(workspace_id, query, MEMORY_RECALL_LIMIT)
+                .await?
+        }
+        None => memory_service.get_all(workspace_id).await?,
+    };
+    Ok(ApiResponse::ok(memories))
+}
+
+#[post("/memory")]
+pub async fn create_workspace_memory(
+    workspace_service: Data<WorkspaceService>,
+    memory_service: Data<MemoryService>,
+    user: W,
+    workspace_id: Path<String>,
+    new_memory: Json<NewMemory>,
+) -> Result<impl Responder> {
+    let workspace_id = workspace_id.into_inner();
+    require_writer(&workspace_service, user.into_inner(), workspace_id.clone()).await?;
+    Ok(ApiResponse::ok(
+        memory_service
+            .create(workspace_id, new_memory.into_inner())
+            .await?,
+    ))
+}
+
+#[patch("/memory/{memory_id}")]
+pub async fn update_workspace_memory(
+    workspace_service: Data<WorkspaceService>,
+    memory_service: Data<MemoryService>,
+    user: W,
+    ids: Path<(String, String)>,
+    updated: Json<UpdatedMemory>,
+) -> Result<impl Responder> {
+    let (workspace_id, memory_id) = ids.into_inner();
+    require_writer(&workspace_service, user.into_inner(), workspace_id.clone()).await?;
+    Ok(ApiResponse::ok(
+        memory_service
+            .update(workspace_id, memory_id, updated.into_inner())
+            .await?,
+    ))
+}
+
+#[delete("/memory/{memory_id}")]
+pub async fn delete_workspace_memory(
+    workspace_service: Data<WorkspaceService>,
+    memory_service: Data<MemoryService>,
+    user: W,
+    ids: Path<(String, String)>,
+) -> Result<impl Responder> {
+    let (workspace_id, memory_id) = ids.into_inner();
+    require_writer(&workspace_service, user.into_inner(), workspace_id.clone()).await?;
+    memory_service.delete(workspace_id, memory_id).await?;
+    Ok(ApiResponse::ok(()))
+}
+
+/// Lists the domains pages are or are not fetched from for the workspace
+#[get("/domain")]
+pub async fn get_workspace_domain_rules(
+    workspace_service: Data<WorkspaceService>,
+    domain_rule_service: Data<DomainRuleService>,
+    user: W,
+    workspace_id: Path<String>,
+) -> Result<impl Responder> {
+    let workspace_id = workspace_id.into_inner();
+    workspace_service
+        .get_role(user.into_inner().id, workspace_id.clone())
+        .await?
+        .ok_or(Error::NotFound)?;
+    Ok(ApiResponse::ok(
+        domain_rule_service.get_all(workspace_id).await?,
+    ))
+}
+
+#[post("/domain")]
+pub async fn set_workspace_domain_rule(
+    workspace_service: Data<WorkspaceService>,
+    domain_rule_service: Data<DomainRuleService>,
+    user: W,
+    workspace_id: Path<String>,
+    new_rule: Json<NewDomainRule>,
+) -> Result<impl Responder> {
+    let workspace_id = workspace_id.into_inner();
+    require_admin(
+        &workspace_service,
+        user.into_inner(),
+        workspace_id.clone(),
+        "fetched domains",
+    )
+    .await?;
+    Ok(ApiResponse::ok(
+        domain_rule_service
+            .set(workspace_id, new_rule.into_inner())
+            .await?,
+    ))
+}
+
+#[delete("/domain/{rule_id}")]
+pub async fn delete_workspace_domain_rule(
+    workspace_service: Data<WorkspaceService>,
+    domain_rule_service: Data<DomainRuleService>,
+    user: W,
+    ids: Path<(String, String)>,
+) -> Result<impl Responder> {
+    let (workspace_id, rule_id) = ids.into_inner();
+    require_admin(
+        &workspace_service,
+        user.into_inner(),
+        workspace_id.clone(),
+        "fetched domains",
+    )
+    .await?;
+    domain_rule_service.delete(workspace_id, rule_id).await?;
+    Ok(ApiResponse::ok(()))
+}
+
+async fn require_admin(
+    workspace_service: &WorkspaceService,
+    user: User,
+    workspace_id: String,
+    what: &str,
+) -> Result<()> {
+    let role = workspace_service
+        .get_role(user.id, workspace_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+    if !role.is_at_least_admin() {
+        return Err(Error::Forbidden(format!(
+            "Only workspace admins can manage {what}."
+        )));
+    }
+    Ok(())
+}
+
+async fn require_writer(
+    workspace_service: &WorkspaceService,
+    user: User,
+    workspace_id: String,
+) -> Result<()> {
+    let role = workspace_service
+        .get_role(user.id, workspace_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+    if !role.can_write() {
+        return Err(Error::Forbidden(
+            "Viewers cannot change what the workspace remembers.".into(),
+        ));
+    }
+    Ok(())
+}
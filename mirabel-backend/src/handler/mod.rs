@@ -1,11 +1,21 @@
 use crate::prelude::*;
 
+use crate::service::attachments::AttachmentService;
 use crate::service::auth::AuthService;
+use crate::service::code_index::CodeIndexService;
+use crate::service::domain_rules::DomainRuleService;
+use crate::service::file_actions::FileActionService;
+use crate::service::memories::MemoryService;
+use crate::service::plans::PlanService;
+use crate::service::repositories::RepositoryService;
+use crate::service::search::TimelineSearchService;
 use crate::service::sessions::SessionService;
+use crate::service::specs::SpecService;
 use crate::service::users::UserService;
 use crate::service::workspaces::WorkspaceService;
 
 use crate::driver::llm::ollama::Ollama;
+use crate::driver::research::WebResearch;
 
 use std::env;
 
@@ -16,7 +26,7 @@ use actix_web::HttpServer;
 use actix_web::middleware::Logger;
 use actix_web::web;
 use actix_web::web::Data;
-use actix_web::{Result as ActixResult, HttpRequest};
+use actix_web::{HttpRequest, Result as ActixResult};
 
 use deadpool_diesel::postgres::Pool;
 use log::info;
@@ -31,14 +41,27 @@ async fn spa_fallback(_req: HttpRequest) -> ActixResult<NamedFile> {
     Ok(NamedFile::open(path)?)
 }
 
-pub async fn run(db: Data<Pool>, llm: Data<Ollama>) -> Result<()> {
+pub async fn run(db: Data<Pool>, llm: Data<Ollama>, web: Data<WebResearch>) -> Result<()> {
     let host = env::var("BACKEND_HOST")?;
     let port: u16 = env::var("BACKEND_PORT")?.parse()?;
 
     let auth_service = Data::new(AuthService::from(db.clone())?);
     let user_service = Data::new(UserService::from(db.clone())?);
     let workspace_service = Data::new(WorkspaceService::from(db.clone())?);
-    let session_service = Data::new(SessionService::from(db.clone(), llm.clone())?);
+    let session_service = Data::new(SessionService::from(db.clone(), llm.clone(), web.clone())?);
+    let spec_service = Data::new(SpecService::from(db.clone())?);
+    let plan_service = Data::new(PlanService::from(db.clone())?);
+    let file_action_service = Data::new(FileActionService::from(db.clone())?);
+    let attachment_service = Data::new(AttachmentService::from(db.clone())?);
+    let repository_service = Data::new(RepositoryService::from(db.clone())?);
+    let code_index_service = Data::new(CodeIndexService::from(db.clone())?);
+    let memory_service = Data::new(MemoryService::from(db.clone(), llm.clone())?);
+    let domain_rule_service = Data::new(DomainRuleService::from(db.clone())?);
+    let search_service = Data::new(TimelineSearchService::from(db.clone(), llm.clone())?);
+    if search_service.is_semantic() {
+        let embedder = search_service.clone();
+        tokio::spawn(async move { embedder.run_embedder().await });
+    }
 
     info!("Listening on {host}:{port}");
     HttpServer::new(move || {
@@ -54,9 +77,19 @@ pub async fn run(db: Data<Pool>, llm: Data<Ollama>) -> Result<()> {
         App::new()
             .app_data(db.clone())
             .app_data(llm.clone())
+            .app_data(web.clone())
             .app_data(auth_service.clone())
             .app_data(user_service.clone())
             .app_data(session_service.clone())
+            .app_data(spec_service.clone())
+            .app_data(plan_service.clone())
+            .app_data(file_action_service.clone())
+            .app_data(attachment_service.clone())
+            .app_data(repository_service.clone())
+            .app_data(code_index_service.clone())
+            .app_data(memory_service.clone())
+            .app_data(domain_rule_service.clone())
+            .app_data(search_service.clone())
             .app_data(workspace_service.clone())
             .wrap(cors)
             .wrap(logger)
@@ -65,7 +98,7 @@ pub async fn run(db: Data<Pool>, llm: Data<Ollama>) -> Result<()> {
             .service(
                 Files::new("/", "../mirabel-web/build/")
                     .index_file("200.html")
-                    .default_handler(web::get().to(spa_fallback))
+                    .default_handler(web::get().to(spa_fallback)),
             )
     })
     .bind((host, port))?
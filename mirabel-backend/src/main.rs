@@ -2,7 +2,13 @@
 
 use actix_web::web::Data;
 use driver::browser::Browsers;
+use driver::policy::FetchConfig;
+use driver::policy::FetchPolicy;
+use driver::research::WebResearch;
+use driver::research::cache::CacheTtls;
+use driver::research::cache::WebCache;
 use driver::search::SearchEngines;
+use driver::search::local::LocalIndex;
 use driver::search::traits::SearchEngine;
 
 use log::info;
@@ -25,6 +31,7 @@ pub(crate) mod session;
 pub(crate) mod db;
 pub(crate) mod security;
 pub(crate) mod service;
+pub(crate) mod utils;
 
 #[dotenvy::load]
 #[tokio::main]
@@ -47,15 +54,23 @@ async fn run() -> Result<()> {
     // let db = SurrealDB::from_env().await?;
     let db = db::connect().await?;
     // let repos = RepositoryProvider::new(db.into());
+    let index = LocalIndex::new(db.clone());
+    let cache = WebCache::new(db.clone(), CacheTtls::from_env()?);
     let engines = SearchEngines::from_env();
     if !engines.available().await {
         warn!("No search engines are available");
     }
-    let browsers = Browsers::new().await?;
-    let llm = Ollama::default();
+    let browsers = Browsers::from_env()?;
+    let policy = FetchPolicy::new(db.clone(), FetchConfig::from_env()?)?;
+    let llm = Ollama::from_env();
     info!("Running lifecycle tasks");
-    handler::run(Data::new(db), Data::new(llm)).await?;
+    let web = Data::new(WebResearch::new(engines, browsers, policy, index, cache));
+    let prober = web.clone();
+    tokio::spawn(async move { prober.engines().run_probes().await });
+    let discovery = web.clone();
+    tokio::spawn(async move { discovery.browsers().run_discovery().await });
+    handler::run(Data::new(db), Data::new(llm), web.clone()).await?;
     info!("Running cleanup tasks");
-    browsers.close().await?;
+    web.browsers().close().await?;
     Ok(())
 }
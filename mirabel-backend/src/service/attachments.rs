@@ -0,0 +1,463 @@
+use std::collections::HashMap;
+
+use crate::prelude::*;
+use mirabel_core::dto::document::ChunkSearch;
+use mirabel_core::dto::document::DocumentChunk;
+use mirabel_core::models::attachment::Attachment;
+use mirabel_core::models::attachment::AttachmentChunk;
+
+use actix_web::web::Data;
+use deadpool_diesel::postgres::Pool;
+use diesel::prelude::*;
+use log::warn;
+
+use crate::driver::converter::chunk::Chunk;
+use crate::driver::converter::chunk::ChunkOptions;
+use crate::driver::converter::convert;
+use crate::driver::llm::Embedder;
+use crate::driver::retrieval::Retriever;
+
+const DEFAULT_CHUNKS: i64 = 5;
+const MAX_CHUNKS: i64 = 50;
+/// Rows per insert, Postgres takes at most 65535 parameters per statement
+const INSERT_BATCH_SIZE: usize = 1000;
+
+pub struct AttachmentService {
+    repository: Data<Pool>,
+}
+
+impl AttachmentService {
+    pub fn from(repository: Data<Pool>) -> Result<Self> {
+        Ok(Self { repository })
+    }
+
+    pub async fn create(&self, attachment: Attachment) -> Result<Attachment> {
+        use mirabel_core::schema::attachments::dsl as a;
+
+        let conn = self.repository.get().await?;
+        Ok(conn
+            .interact(move |conn| {
+                diesel::insert_into(a::attachments)
+                    .values(&attachment)
+                    .get_result::<Attachment>(conn)
+            })
+            .await??)
+    }
+
+    /// Attaches a document to the session. Images and documents that cannot
+    /// be converted are refused. The document is cut into chunks right away,
+    /// so it is never converted again to be searched.
+    pub async fn upload(&self, document: Attachment) -> Result<Attachment> {
+        use mirabel_core::schema::attachment_chunks::dsl as ac;
+        use mirabel_core::schema::attachments::dsl as a;
+
+        if document.media_type.starts_with("image/") {
+            return Err(Error::UnsupportedFormat(format!(
+                "{}, which is an image",
+                document.media_type
+            )));
+        }
+        // Converting a long document keeps a thread busy for a while
+        let (document, chunks) = tokio::task::spawn_blocking(move || -> Result<_> {
+            let chunks = document_chunks(&document)?;
+            Ok((document, chunks))
+        })
+        .await??;
+
+        let conn = self.repository.get().await?;
+        Ok(conn
+            .interact(move |conn| {
+                conn.transaction::<Attachment, diesel::result::Error, _>(|t| {
+                    let document = diesel::insert_into(a::attachments)
+                        .values(&document)
+                        .get_result::<Attachment>(t)?;
+                    for batch in chunks.chunks(INSERT_BATCH_SIZE) {
+                        diesel::insert_into(ac::attachment_chunks)
+                            .values(batch)
+                            .execute(t)?;
+                    }
+                    Ok(document)
+                })
+            })
+            .await??)
+    }
+
+    /// An attachment of the session, `None` when it belongs to another one
+    pub async fn get(
+        &self,
+        session_id: String,
+        attachment_id: String,
+    ) -> Result<Option<Attachment>> {
+        use mirabel_core::schema::attachments::dsl as a;
+
+        let conn = self.repository.get().await?;
+        Ok(conn
+            .interact(move |conn| {
+                a::attachments
+                    .filter(a::id.eq(&attachment_id))
+                    .filter(a::session_id.eq(&session_id))
+                    .first::<Attachment>(conn)
+                    .optional()
+            })
+            .await??)
+    }
+
+    /// The parts of a document attached to the session that match the
+    /// search best, the best first. `None` when the attachment belongs to
+    /// another session.
+    pub async fn search(
+        &self,
+        session_id: String,
+        attachment_id: String,
+        search: ChunkSearch,
+    ) -> Result<Option<Vec<DocumentChunk>>> {
+        use mirabel_core::schema::attachment_chunks::dsl as ac;
+        use mirabel_core::schema::attachments::dsl as a;
+
+        let (query, limit) = parse_search(search)?;
+        let conn = self.repository.get().await?;
+        let chunks = conn
+            .interact(move |conn| {
+                let found = diesel::select(diesel::dsl::exists(
+                    a::attachments
+                        .filter(a::id.eq(&attachment_id))
+                        .filter(a::session_id.eq(&session_id)),
+                ))
+                .get_result::<bool>(conn)?;
+                if !found {
+                    return Ok(None);
+                }
+                ac::attachment_chunks
+                    .filter(ac::attachment_id.eq(&attachment_id))
+                    .order(ac::position.asc())
+                    .select(AttachmentChunk::as_select())
+                    .load::<AttachmentChunk>(conn)
+                    .map(Some)
+            })
+            .await??;
+        Ok(chunks.map(|chunks| DocumentIndex::new(chunks).top(&query, limit)))
+    }
+
+    /// Like [`AttachmentService::search`], across every document of the
+    /// session and also by meaning. Chunks are embedded the first time they
+    /// are searched, and only words are matched while the embedder is
+    /// unavailable.
+    pub async fn search_documents<E: Embedder + Sync>(
+        &self,
+        session_id: String,
+        search: ChunkSearch,
+        embedder: &E,
+    ) -> Result<Vec<DocumentChunk>> {
+        let (query, limit) = parse_search(search)?;
+        let mut chunks = self.session_chunks(session_id).await?;
+        match self.embed_missing(&mut chunks, embedder).await {
+            Ok(()) => {
+                let index = DocumentIndex::new(chunks);
+                Ok(index.top_semantic(embedder, &query, limit).await)
+            }
+            Err(err) => {
+                warn!("Could not search the documents by meaning: {err}");
+                Ok(DocumentIndex::new(chunks).top(&query, limit))
+            }
+        }
+    }
+
+    /// The chunks of all documents of the session, the oldest document first
+    async fn session_chunks(&self, session_id: String) -> Result<Vec<AttachmentChunk>> {
+        use mirabel_core::schema::attachment_chunks::dsl as ac;
+        use mirabel_core::schema::attachments::dsl as a;
+
+        let conn = self.repository.get().await?;
+        Ok(conn
+            .interact(move |conn| {
+                ac::attachment_chunks
+                    .inner_join(a::attachments)
+                    .filter(a::session_id.eq(&session_id))
+                    .order((a::created_at.asc(), ac::attachment_id, ac::position.asc()))
+                    .select(AttachmentChunk::as_select())
+                    .load::<AttachmentChunk>(conn)
+            })
+            .await??)
+    }
+
+    /// Embeds the chunks that are not yet and stores their embeddings
+    async fn embed_missing<E: Embedder + Sync>(
+        &self,
+        chunks: &mut [AttachmentChunk],
+        embedder: &E,
+    ) -> Result<()> {
+        use mirabel_core::schema::attachment_chunks::dsl as ac;
+
+        let embedded = embed(chunks, embedder).await?;
+        if embedded.is_empty() {
+            return Ok(());
+        }
+        let conn = self.repository.get().await?;
+        conn.interact(move |conn| {
+            conn.transaction::<(), Error, _>(|t| {
+                for chunk in embedded {
+                    diesel::update(
+                        ac::attachment_chunks
+                            .filter(ac::attachment_id.eq(chunk.attachment_id))
+                            .filter(ac::id.eq(chunk.id)),
+                    )
+                    .set(ac::embedding.eq(chunk.embedding))
+                    .execute(t)?;
+                }
+                Ok(())
+            })
+        })
+        .await??;
+        Ok(())
+    }
+}
+
+/// The query and how many chunks to return at most
+fn parse_search(search: ChunkSearch) -> Result<(String, usize)> {
+    let query = search.query.trim().to_string();
+    if query.is_empty() {
+        return Err(Error::BadRequest(
+            "The search query cannot be empty.".into(),
+        ));
+    }
+    let limit = search.limit.unwrap_or(DEFAULT_CHUNKS).clamp(1, MAX_CHUNKS) as usize;
+    Ok((query, limit))
+}
+
+/// The chunks of a document as they are stored
+fn document_chunks(attachment: &Attachment) -> Result<Vec<AttachmentChunk>> {
+    let document = convert(
+        attachment.data.clone(),
+        attachment.name.as_deref(),
+        Some(&attachment.media_type),
+    )?;
+    Ok(document
+        .markdown
+        .chunks(ChunkOptions::default())
+        .into_iter()
+        .enumerate()
+        .map(|(position, chunk)| AttachmentChunk {
+            attachment_id: attachment.id.clone(),
+            id: chunk.id,
+            position: position as i32,
+            start: chunk.start as i32,
+            end: chunk.end as i32,
+            text: chunk.text,
+            headings: chunk.headings,
+            page: chunk.page.map(|page| page as i32),
+            tokens: chunk.tokens as i32,
+            embedding: None,
+        })
+        .collect())
+}
+
+/// Embeds the chunks without an embedding, and returns them
+async fn embed<E: Embedder + Sync>(
+    chunks: &mut [AttachmentChunk],
+    embedder: &E,
+) -> Result<Vec<AttachmentChunk>> {
+    let mut missing: Vec<&mut AttachmentChunk> = chunks
+        .iter_mut()
+        .filter(|chunk| chunk.embedding.is_none())
+        .collect();
+    if missing.is_empty() {
+        return Ok(Vec::new());
+    }
+    let texts = missing.iter().map(|chunk| chunk.text.clone()).collect();
+    let embeddings = embedder.embed(texts).await?;
+    if embeddings.len() != missing.len() {
+        return Err(Error::Generic(format!(
+            "Got {} embeddings for {} chunks.",
+            embeddings.len(),
+            missing.len()
+        )));
+    }
+    for (chunk, embedding) in missing.iter_mut().zip(embeddings) {
+        chunk.embedding = Some(embedding);
+    }
+    Ok(missing.into_iter().map(|chunk| chunk.clone()).collect())
+}
+
+/// The chunks of one or more documents, each knows the attachment it is from
+struct DocumentIndex {
+    retriever: Retriever,
+    attachments: HashMap<String, String>,
+}
+
+impl DocumentIndex {
+    /// Chunks are searched by the embeddings they have when all have one
+    fn new(stored: Vec<AttachmentChunk>) -> Self {
+        // The same text in two documents counts for the first
+        let mut attachments: HashMap<String, String> = HashMap::new();
+        let mut chunks = Vec::with_capacity(stored.len());
+        let mut embeddings = Vec::with_capacity(stored.len());
+        for chunk in stored {
+            attachments
+                .entry(chunk.id.clone())
+                .or_insert_with(|| chunk.attachment_id.clone());
+            embeddings.extend(chunk.embedding);
+            chunks.push(Chunk {
+                id: chunk.id,
+                start: chunk.start as usize,
+                end: chunk.end as usize,
+                text: chunk.text,
+                headings: chunk.headings,
+                page: chunk.page.map(|page| page as u32),
+                tokens: chunk.tokens as usize,
+            });
+        }
+        let retriever = match embeddings.len() == chunks.len() {
+            true => Retriever::with_embeddings(chunks, embeddings),
+            false => Retriever::new(chunks),
+        };
+        Self {
+            retriever,
+            attachments,
+        }
+    }
+
+    /// The `limit` chunks whose words match the query best, the best first
+    fn top(&self, query: &str, limit: usize) -> Vec<DocumentChunk> {
+        self.found(self.retriever.top(query, limit))
+    }
+
+    /// Like [`DocumentIndex::top`], also by meaning. Only words are matched
+    /// while the embedder is unavailable.
+    async fn top_semantic<E: Embedder + Sync>(
+        &self,
+        embedder: &E,
+        query: &str,
+        limit: usize,
+    ) -> Vec<DocumentChunk> {
+        match self.retriever.top_semantic(embedder, query, limit).await {
+            Ok(ranked) => self.found(ranked),
+            Err(err) => {
+                warn!("Could not search the documents by meaning: {err}");
+                self.top(query, limit)
+            }
+        }
+    }
+
+    fn found(&self, ranked: Vec<(&Chunk, f32)>) -> Vec<DocumentChunk> {
+        ranked
+            .into_iter()
+            .map(|(chunk, score)| DocumentChunk {
+                id: chunk.id.clone(),
+                attachment_id: self.attachments[&chunk.id].clone(),
+                text: chunk.text.clone(),
+                headings: chunk.headings.clone(),
+                start: chunk.start as u32,
+                end: chunk.end as u32,
+                page: chunk.page,
+                score,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+
+    use super::*;
+
+    fn document(name: &str, media_type: &str, data: &[u8]) -> Attachment {
+        Attachment::document(
+            "session".into(),
+            Some(name.into()),
+            media_type.into(),
+            data.to_vec(),
+        )
+    }
+
+    #[test]
+    fn test_search_document() {
+        let report = document(
+            "report.docx",
+            "application/octet-stream",
+            include_bytes!("../driver/converter/fixtures/report.docx"),
+        );
+        let index = DocumentIndex::new(document_chunks(&report).unwrap());
+        let found = index.top("How many engineers were hired?", 5);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].attachment_id, report.id);
+        assert!(found[0].text.contains("Two engineers"));
+        assert_eq!(found[0].headings[0], "Quarterly report");
+        assert!(index.top("kubernetes", 5).is_empty());
+
+        let png = Attachment::png("session".into(), b"\x89PNG\r\n\x1a\n\0\0".to_vec());
+        assert!(matches!(
+            document_chunks(&png),
+            Err(Error::UnsupportedFormat(_))
+        ));
+    }
+
+    /// Texts about networking get the same vector, it fails on anything else
+    struct Networking;
+
+    #[async_trait]
+    impl Embedder for Networking {
+        async fn embed(&self, inputs: Vec<String>) -> Result<Vec<Vec<f32>>> {
+            if inputs.iter().any(|input| input.contains("fail")) {
+                return Err(Error::Generic("The model is not available.".into()));
+            }
+            Ok(inputs
+                .iter()
+                .map(|input| {
+                    let input = input.to_lowercase();
+                    let network = ["port", "socket"].iter().any(|word| input.contains(word));
+                    vec![network as u8 as f32, !network as u8 as f32]
+                })
+                .collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_documents() {
+        let setup = document(
+            "setup.md",
+            "text/markdown",
+            b"# Setup\n\nInstall the CLI with cargo.\n\n# Ports\n\nThe server listens on port 8080.",
+        );
+        let people = document(
+            "people.csv",
+            "text/csv",
+            include_bytes!("../driver/converter/fixtures/people.csv"),
+        );
+        let mut chunks: Vec<AttachmentChunk> = [&setup, &people]
+            .into_iter()
+            .flat_map(|document| document_chunks(document).unwrap())
+            .collect();
+        let index = DocumentIndex::new(chunks.clone());
+        let found = index.top("Which port does the server use?", 1);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].attachment_id, setup.id);
+        assert_eq!(found[0].headings, ["Ports"]);
+
+        // No word of it is in the documents, its meaning is
+        assert!(index.top("socket", 1).is_empty());
+        let found = index.top_semantic(&Networking, "socket", 1).await;
+        assert_eq!(found[0].headings, ["Ports"]);
+        // Without embeddings the words still count
+        let found = index.top_semantic(&Networking, "fail to install", 1).await;
+        assert_eq!(found[0].headings, ["Setup"]);
+
+        // Chunks are embedded once, then searched by the stored embeddings
+        let embedded = embed(&mut chunks, &Networking).await.unwrap();
+        assert_eq!(embedded.len(), chunks.len());
+        assert!(embed(&mut chunks, &Networking).await.unwrap().is_empty());
+        let index = DocumentIndex::new(chunks);
+        let found = index.top_semantic(&Networking, "socket", 1).await;
+        assert_eq!(found[0].headings, ["Ports"]);
+
+        let search = |query: &str, limit| {
+            parse_search(ChunkSearch {
+                query: query.into(),
+                limit,
+            })
+        };
+        assert_eq!(search(" port ", None).unwrap(), ("port".into(), 5));
+        assert_eq!(search("port", Some(500)).unwrap().1, 50);
+        assert!(search("  ", None).is_err());
+    }
+}
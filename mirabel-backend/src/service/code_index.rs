@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use crate::prelude::*;
+use mirabel_core::dto::code_index::IndexStats;
+use mirabel_core::dto::code_index::SymbolLocation;
+use mirabel_core::id;
+use mirabel_core::models::code_index::CodeFile;
+use mirabel_core::models::code_index::CodeSymbol;
+
+use actix_web::web::Data;
+use deadpool_diesel::postgres::Pool;
+use diesel::prelude::*;
+
+use crate::driver::code_index;
+use crate::driver::code_index::ParsedSymbol;
+use crate::driver::code_index::SourceFile;
+
+/// Rows per insert, Postgres takes at most 65535 parameters per statement
+const INSERT_BATCH_SIZE: usize = 1000;
+/// Lookups of common names are cut off, agents cannot use thousands of hits
+const MAX_LOCATIONS: i64 = 200;
+
+pub struct CodeIndexService {
+    repository: Data<Pool>,
+}
+
+/// What a run found on the disk, to be written to the index
+struct Changes {
+    indexed: Vec<(SourceFile, Vec<ParsedSymbol>)>,
+    removed: Vec<String>,
+    unchanged: u32,
+}
+
+impl CodeIndexService {
+    pub fn from(repository: Data<Pool>) -> Result<Self> {
+        Ok(Self { repository })
+    }
+
+    /// Brings the index of the worktree checked out at `root` up to date.
+    /// Only files that are new or changed since the last run are parsed.
+    pub async fn index(&self, worktree_id: String, root: PathBuf) -> Result<IndexStats> {
+        use mirabel_core::schema::code_files::dsl as cf;
+
+        let conn = self.repository.get().await?;
+        let worktree_id_clone = worktree_id.clone();
+        let known: HashMap<String, String> = conn
+            .interact(move |conn| {
+                cf::code_files
+                    .filter(cf::worktree_id.eq(&worktree_id_clone))
+                    .select((cf::path, cf::hash))
+                    .load::<(String, String)>(conn)
+            })
+            .await??
+            .into_iter()
+            .collect();
+
+        let changes = tokio::task::spawn_blocking(move || -> Result<Changes> {
+            let paths = code_index::walk(&root);
+            let mut changes = Changes {
+                indexed: Vec::new(),
+                removed: Vec::new(),
+                unchanged: 0,
+            };
+            let mut present = HashSet::new();
+            for path in paths {
+                let Some(file) = SourceFile::read(&root, &path)? else {
+                    continue;
+                };
+                present.insert(path);
+                if known.get(&file.path) == Some(&file.hash) {
+                    changes.unchanged += 1;
+                    continue;
+                }
+                let symbols = file.parse()?;
+                changes.indexed.push((file, symbols));
+            }
+            changes.removed = known
+                .into_keys()
+                .filter(|path| !present.contains(path))
+                .collect();
+            Ok(changes)
+        })
+        .await??;
+        self.store(worktree_id, changes).await
+    }
+
+    /// Indexes the files at `paths` again after they changed, files that no
+    /// longer exist are removed from the index
+    pub async fn update(
+        &self,
+        worktree_id: String,
+        root: PathBuf,
+        paths: Vec<String>,
+    ) -> Result<IndexStats> {
+        let changes = tokio::task::spawn_blocking(move || -> Result<Changes> {
+            let mut changes = Changes {
+                indexed: Vec::new(),
+                removed: Vec::new(),
+                unchanged: 0,
+            };
+            for path in paths {
+                match SourceFile::read(&root, &path)? {
+                    Some(file) => {
+                        let symbols = file.parse()?;
+                        changes.indexed.push((file, symbols));
+                    }
+                    None => changes.removed.push(path),
+                }
+            }
+            Ok(changes)
+        })
+        .await??;
+        self.store(worktree_id, changes).await
+    }
+
+    /// Forgets everything about the worktree, once it is gone
+    pub async fn clear(&self, worktree_id: String) -> Result<()> {
+        use mirabel_core::schema::code_files::dsl as cf;
+
+        let conn = self.repository.get().await?;
+        conn.interact(move |conn| {
+            diesel::delete(cf::code_files.filter(cf::worktree_id.eq(&worktree_id))).execute(conn)
+        })
+        .await??;
+        Ok(())
+    }
+
+    /// An outline of the definitions in the worktree of at most `budget`
+    /// characters, the most referenced files first. Paths are prefixed with
+    /// `prefix`.
+    pub async fn repo_map(
+        &self,
+        worktree_id: String,
+        prefix: String,
+        budget: usize,
+    ) -> Result<String> {
+        use mirabel_core::schema::code_files::dsl as cf;
+        use mirabel_core::schema::code_symbols::dsl as cs;
+
+        let conn = self.repository.get().await?;
+        let (definitions, references) = conn
+            .interact(move |conn| {
+                let definitions = cs::code_symbols
+                    .inner_join(cf::code_files)
+                    .filter(cs::worktree_id.eq(&worktree_id))
+                    .filter(cs::definition.eq(true))
+                    .order((cf::path.asc(), cs::line.asc()))
+                    .select((cf::path, CodeSymbol::as_select()))
+                    .load::<(String, CodeSymbol)>(conn)?;
+                let references = cs::code_symbols
+                    .filter(cs::worktree_id.eq(&worktree_id))
+                    .filter(cs::definition.eq(false))
+                    .group_by(cs::name)
+                    .select((cs::name, diesel::dsl::count_star()))
+                    .load::<(String, i64)>(conn)?;
+                QueryResult::Ok((definitions, references))
+            })
+            .await??;
+
+        let mut files: Vec<(String, Vec<CodeSymbol>)> = Vec::new();
+        for (path, definition) in definitions {
+            let path = format!("{prefix}{path}");
+            match files.last_mut() {
+                Some((last, symbols)) if *last == path => symbols.push(definition),
+                _ => files.push((path, vec![definition])),
+            }
+        }
+        let references = references
+            .into_iter()
+            .map(|(name, count)| (name, count as u32))
+            .collect();
+        Ok(code_index::repo_map(&files, &references, budget))
+    }
+
+    /// Where the symbols called `names` are defined
+    pub async fn definitions(
+        &self,
+        worktree_id: String,
+        names: Vec<String>,
+    ) -> Result<Vec<SymbolLocation>> {
+        self.find(worktree_id, names, true).await
+    }
+
+    /// Where the symbols called `names` are used
+    pub async fn references(
+        &self,
+        worktree_id: String,
+        names: Vec<String>,
+    ) -> Result<Vec<SymbolLocation>> {
+        self.find(worktree_id, names, false).await
+    }
+
+    /// What the file at `path` defines, in order
+    pub async fn definitions_in(
+        &self,
+        worktree_id: String,
+        path: String,
+    ) -> Result<Vec<SymbolLocation>> {
+        use mirabel_core::schema::code_files::dsl as cf;
+        use mirabel_core::schema::code_symbols::dsl as cs;
+
+        let conn = self.repository.get().await?;
+        let found = conn
+            .interact(move |conn| {
+                cs::code_symbols
+                    .inner_join(cf::code_files)
+                    .filter(cf::worktree_id.eq(&worktree_id))
+                    .filter(cf::path.eq(&path))
+                    .filter(cs::definition.eq(true))
+                    .order(cs::line.asc())
+                    .limit(MAX_LOCATIONS)
+                    .select((cf::path, CodeSymbol::as_select()))
+                    .load::<(String, CodeSymbol)>(conn)
+            })
+            .await??;
+        Ok(found.into_iter().map(location).collect())
+    }
+
+    async fn find(
+        &self,
+        worktree_id: String,
+        names: Vec<String>,
+        definition: bool,
+    ) -> Result<Vec<SymbolLocation>> {
+        use mirabel_core::schema::code_files::dsl as cf;
+        use mirabel_core::schema::code_symbols::dsl as cs;
+
+        let conn = self.repository.get().await?;
+        let found = conn
+            .interact(move |conn| {
+                cs::code_symbols
+                    .inner_join(cf::code_files)
+                    .filter(cs::worktree_id.eq(&worktree_id))
+                    .filter(cs::name.eq_any(&names))
+                    .filter(cs::definition.eq(definition))
+                    .order((cf::path.asc(), cs::line.asc()))
+                    .limit(MAX_LOCATIONS)
+                    .select((cf::path, CodeSymbol::as_select()))
+                    .load::<(String, CodeSymbol)>(conn)
+            })
+            .await??;
+        Ok(found.into_iter().map(location).collect())
+    }
+
+    /// Replaces what the index knows about the changed files in one go
+    async fn store(&self, worktree_id: String, changes: Changes) -> Result<IndexStats> {
+        use mirabel_core::schema::code_files::dsl as cf;
+        use mirabel_core::schema::code_symbols::dsl as cs;
+
+        let mut stats = IndexStats {
+            indexed_files: changes.indexed.len() as u32,
+            unchanged_files: changes.unchanged,
+            removed_files: changes.removed.len() as u32,
+            symbols: 0,
+        };
+        let mut stale = changes.removed;
+        let mut files = Vec::new();
+        let mut symbols = Vec::new();
+        for (source, parsed) in changes.indexed {
+            let file = CodeFile::new(
+                worktree_id.clone(),
+                source.path.clone(),
+                source.language,
+                source.hash,
+            );
+            symbols.extend(parsed.into_iter().map(|parsed| symbol(&file, parsed)));
+            stale.push(source.path);
+            files.push(file);
+        }
+        stats.symbols = symbols.len() as u32;
+        if stale.is_empty() {
+            return Ok(stats);
+        }
+
+        let conn = self.repository.get().await?;
+        conn.interact(move |conn| {
+            conn.transaction::<(), diesel::result::Error, _>(|t| {
+                // Symbols of replaced files go with them
+                for paths in stale.chunks(INSERT_BATCH_SIZE) {
+                    diesel::delete(
+                        cf::code_files
+                            .filter(cf::worktree_id.eq(&worktree_id))
+                            .filter(cf::path.eq_any(paths)),
+                    )
+                    .execute(t)?;
+                }
+                for batch in files.chunks(INSERT_BATCH_SIZE) {
+                    diesel::insert_into(cf::code_files)
+                        .values(batch)
+                        .execute(t)?;
+                }
+                for batch in symbols.chunks(INSERT_BATCH_SIZE) {
+                    diesel::insert_into(cs::code_symbols)
+                        .values(batch)
+                        .execute(t)?;
+                }
+                Ok(())
+            })
+        })
+        .await??;
+        Ok(stats)
+    }
+}
+
+fn symbol(file: &CodeFile, parsed: ParsedSymbol) -> CodeSymbol {
+    CodeSymbol {
+        id: id!(),
+        file_id: file.id.clone(),
+        worktree_id: file.worktree_id.clone(),
+        name: parsed.name,
+        kind: parsed.kind,
+        definition: parsed.definition,
+        line: parsed.line,
+        column: parsed.column,
+        end_line: parsed.end_line,
+        signature: parsed.signature,
+    }
+}
+
+fn location((path, symbol): (String, CodeSymbol)) -> SymbolLocation {
+    SymbolLocation {
+        path,
+        name: symbol.name,
+        kind: symbol.kind,
+        definition: symbol.definition,
+        line: symbol.line,
+        column: symbol.column,
+        end_line: symbol.end_line,
+        signature: symbol.signature,
+    }
+}
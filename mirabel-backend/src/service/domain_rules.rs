@@ -0,0 +1,71 @@
+use crate::prelude::*;
+use mirabel_core::dto::domain_rule::NewDomainRule;
+use mirabel_core::models::domain_rule::DomainRule;
+
+use actix_web::web::Data;
+use deadpool_diesel::postgres::Pool;
+use diesel::prelude::*;
+
+pub struct DomainRuleService {
+    repository: Data<Pool>,
+}
+
+impl DomainRuleService {
+    pub fn from(repository: Data<Pool>) -> Result<Self> {
+        Ok(Self { repository })
+    }
+
+    pub async fn get_all(&self, workspace_id: String) -> Result<Vec<DomainRule>> {
+        use mirabel_core::schema::domain_rules::dsl as dr;
+
+        let conn = self.repository.get().await?;
+        Ok(conn
+            .interact(move |conn| {
+                dr::domain_rules
+                    .filter(dr::workspace_id.eq(&workspace_id))
+                    .order(dr::domain.asc())
+                    .load::<DomainRule>(conn)
+            })
+            .await??)
+    }
+
+    /// Allows or denies a domain, a domain that already has a rule keeps it
+    /// with the new access
+    pub async fn set(&self, workspace_id: String, new_rule: NewDomainRule) -> Result<DomainRule> {
+        use mirabel_core::schema::domain_rules::dsl as dr;
+
+        let domain = DomainRule::normalize_domain(&new_rule.domain)?;
+        let rule = DomainRule::new(workspace_id, domain, new_rule.access);
+        let conn = self.repository.get().await?;
+        Ok(conn
+            .interact(move |conn| {
+                diesel::insert_into(dr::domain_rules)
+                    .values(&rule)
+                    .on_conflict((dr::workspace_id, dr::domain))
+                    .do_update()
+                    .set(dr::access.eq(rule.access))
+                    .get_result::<DomainRule>(conn)
+            })
+            .await??)
+    }
+
+    pub async fn delete(&self, workspace_id: String, rule_id: String) -> Result<()> {
+        use mirabel_core::schema::domain_rules::dsl as dr;
+
+        let conn = self.repository.get().await?;
+        let deleted = conn
+            .interact(move |conn| {
+                diesel::delete(
+                    dr::domain_rules
+                        .filter(dr::id.eq(&rule_id))
+                        .filter(dr::workspace_id.eq(&workspace_id)),
+                )
+                .execute(conn)
+            })
+            .await??;
+        if deleted == 0 {
+            return Err(Error::NotFound);
+        }
+        Ok(())
+    }
+}
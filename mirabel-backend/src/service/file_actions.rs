@@ -0,0 +1,90 @@
+use crate::prelude::*;
+use mirabel_core::models::file_action::FileAction;
+
+use actix_web::web::Data;
+use chrono::Utc;
+use deadpool_diesel::postgres::Pool;
+use diesel::prelude::*;
+
+pub struct FileActionService {
+    repository: Data<Pool>,
+}
+
+impl FileActionService {
+    pub fn from(repository: Data<Pool>) -> Result<Self> {
+        Ok(Self { repository })
+    }
+
+    pub async fn create(&self, action: FileAction) -> Result<FileAction> {
+        use mirabel_core::schema::file_actions::dsl as fa;
+
+        let conn = self.repository.get().await?;
+        Ok(conn
+            .interact(move |conn| {
+                diesel::insert_into(fa::file_actions)
+                    .values(&action)
+                    .get_result::<FileAction>(conn)
+            })
+            .await??)
+    }
+
+    pub async fn get(&self, action_id: String) -> Result<Option<FileAction>> {
+        use mirabel_core::schema::file_actions::dsl as fa;
+
+        let conn = self.repository.get().await?;
+        Ok(conn
+            .interact(move |conn| {
+                fa::file_actions
+                    .filter(fa::id.eq(&action_id))
+                    .first::<FileAction>(conn)
+                    .optional()
+            })
+            .await??)
+    }
+
+    /// Every file action of a session, oldest first
+    pub async fn get_all(&self, session_id: String) -> Result<Vec<FileAction>> {
+        use mirabel_core::schema::file_actions::dsl as fa;
+
+        let conn = self.repository.get().await?;
+        Ok(conn
+            .interact(move |conn| {
+                fa::file_actions
+                    .filter(fa::session_id.eq(&session_id))
+                    .order(fa::created_at.asc())
+                    .load::<FileAction>(conn)
+            })
+            .await??)
+    }
+
+    /// Stores `revert` and marks the action it reverts as reverted, an action
+    /// can only be reverted once.
+    pub async fn create_revert(&self, revert: FileAction) -> Result<FileAction> {
+        use mirabel_core::schema::file_actions::dsl as fa;
+
+        let reverted_id = revert.reverts.clone().ok_or(Error::BadRequest(
+            "The action does not revert anything.".into(),
+        ))?;
+        let conn = self.repository.get().await?;
+        conn.interact(move |conn| {
+            conn.transaction::<FileAction, Error, _>(|t| {
+                let updated = diesel::update(
+                    fa::file_actions
+                        .filter(fa::id.eq(&reverted_id))
+                        .filter(fa::reverted_at.is_null()),
+                )
+                .set(fa::reverted_at.eq(Some(Utc::now())))
+                .execute(t)?;
+                if updated == 0 {
+                    return Err(Error::Conflict(
+                        "The action has already been reverted.".into(),
+                    ));
+                }
+                Ok(diesel::insert_into(fa::file_actions)
+                    .values(&revert)
+                    .get_result::<FileAction>(t)?)
+            })
+        })
+        .await?
+    }
+}
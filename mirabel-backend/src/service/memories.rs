@@ -0,0 +1,285 @@
+use std::sync::Arc;
+
+use crate::prelude::*;
+use mirabel_core::dto::memory::NewMemory;
+use mirabel_core::dto::memory::UpdatedMemory;
+use mirabel_core::models::memory::Memory;
+use mirabel_core::utils::vector::cosine_similarity;
+
+use actix_web::web::Data;
+use chrono::Duration;
+use chrono::Utc;
+use deadpool_diesel::postgres::Pool;
+use diesel::prelude::*;
+use log::warn;
+
+use crate::agent::memory;
+use crate::driver::llm::Embedder;
+use crate::driver::llm::Llm;
+use crate::driver::llm::ollama::Ollama;
+
+/// Memories whose embeddings are at least this similar say the same thing
+const DUPLICATE_SIMILARITY: f32 = 0.95;
+/// Related memories the agent sees when learning, so it does not repeat them
+const KNOWN_LIMIT: usize = 20;
+
+pub struct MemoryService {
+    repository: Data<Pool>,
+    llm: Data<Ollama>,
+}
+
+impl MemoryService {
+    pub fn from(repository: Data<Pool>, llm: Data<Ollama>) -> Result<Self> {
+        Ok(Self { repository, llm })
+    }
+
+    /// Adds a memory users wrote themselves
+    pub async fn create(&self, workspace_id: String, new_memory: NewMemory) -> Result<Memory> {
+        let confidence = new_memory.confidence.unwrap_or(1.0);
+        Memory::validate_content(&new_memory.content)?;
+        Memory::validate_confidence(confidence)?;
+        let mut memory = Memory::new(
+            workspace_id,
+            new_memory.kind,
+            new_memory.content.trim().to_string(),
+            confidence,
+        );
+        memory.expires_at = new_memory.expires_at;
+        memory.embedding = self.embed(&memory.content).await;
+        self.insert(memory).await
+    }
+
+    /// All memories of a workspace including expired ones, most recently
+    /// changed first
+    pub async fn get_all(&self, workspace_id: String) -> Result<Vec<Memory>> {
+        use mirabel_core::schema::memories::dsl as m;
+
+        let conn = self.repository.get().await?;
+        Ok(conn
+            .interact(move |conn| {
+                m::memories
+                    .filter(m::workspace_id.eq(&workspace_id))
+                    .order(m::modified_at.desc())
+                    .load::<Memory>(conn)
+            })
+            .await??)
+    }
+
+    pub async fn get(&self, workspace_id: String, memory_id: String) -> Result<Option<Memory>> {
+        use mirabel_core::schema::memories::dsl as m;
+
+        let conn = self.repository.get().await?;
+        Ok(conn
+            .interact(move |conn| {
+                m::memories
+                    .filter(m::id.eq(&memory_id))
+                    .filter(m::workspace_id.eq(&workspace_id))
+                    .first::<Memory>(conn)
+                    .optional()
+            })
+            .await??)
+    }
+
+    /// Changes a memory, a changed content is embedded again
+    pub async fn update(
+        &self,
+        workspace_id: String,
+        memory_id: String,
+        updated: UpdatedMemory,
+    ) -> Result<Memory> {
+        let mut memory = self
+            .get(workspace_id, memory_id)
+            .await?
+            .ok_or(Error::NotFound)?;
+        if let Some(kind) = updated.kind {
+            memory.kind = kind;
+        }
+        if let Some(confidence) = updated.confidence {
+            Memory::validate_confidence(confidence)?;
+            memory.confidence = confidence;
+        }
+        if let Some(expires_at) = updated.expires_at {
+            memory.expires_at = expires_at;
+        }
+        if let Some(content) = updated.content {
+            Memory::validate_content(&content)?;
+            let content = content.trim().to_string();
+            if content != memory.content {
+                memory.embedding = self.embed(&content).await;
+                memory.content = content;
+            }
+        }
+        memory.modified_at = Utc::now();
+        self.save(memory).await
+    }
+
+    pub async fn delete(&self, workspace_id: String, memory_id: String) -> Result<()> {
+        use mirabel_core::schema::memories::dsl as m;
+
+        let conn = self.repository.get().await?;
+        let deleted = conn
+            .interact(move |conn| {
+                diesel::delete(
+                    m::memories
+                        .filter(m::id.eq(&memory_id))
+                        .filter(m::workspace_id.eq(&workspace_id)),
+                )
+                .execute(conn)
+            })
+            .await??;
+        if deleted == 0 {
+            return Err(Error::NotFound);
+        }
+        Ok(())
+    }
+
+    /// Stores a learned memory. When the workspace already remembers the same
+    /// thing, that memory is confirmed instead and grows more confident.
+    pub async fn remember(&self, mut memory: Memory) -> Result<Memory> {
+        if memory.embedding.is_none() {
+            memory.embedding = self.embed(&memory.content).await;
+        }
+        let now = Utc::now();
+        let duplicate = self
+            .get_all(memory.workspace_id.clone())
+            .await?
+            .into_iter()
+            .filter(|known| !known.is_expired(now))
+            .find(|known| is_duplicate(known, &memory));
+        let Some(mut known) = duplicate else {
+            return self.insert(memory).await;
+        };
+        // Two independent sightings make it likelier than either alone
+        known.confidence = 1.0 - (1.0 - known.confidence) * (1.0 - memory.confidence);
+        known.expires_at = match (known.expires_at, memory.expires_at) {
+            (Some(known), Some(new)) => Some(known.max(new)),
+            _ => None,
+        };
+        known.modified_at = now;
+        self.save(known).await
+    }
+
+    /// The memories of a workspace most relevant to `query`, best first.
+    /// Without embeddings, e.g. while the model is unavailable, only words
+    /// are matched.
+    pub async fn recall(
+        &self,
+        workspace_id: String,
+        query: String,
+        limit: usize,
+    ) -> Result<Vec<Memory>> {
+        let embedding = self.embed(&query).await;
+        let now = Utc::now();
+        let mut scored: Vec<(f32, Memory)> = self
+            .get_all(workspace_id)
+            .await?
+            .into_iter()
+            .filter(|memory| !memory.is_expired(now))
+            .map(|memory| (memory.relevance(&query, embedding.as_deref()), memory))
+            .filter(|(score, _)| *score > 0.0)
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        Ok(scored
+            .into_iter()
+            .take(limit)
+            .map(|(_, memory)| memory)
+            .collect())
+    }
+
+    /// Lets the memory agent pick out what to remember from a message users
+    /// sent in a session, the memories keep the entry as their source
+    pub async fn learn(
+        &self,
+        workspace_id: String,
+        session_id: String,
+        entry_id: String,
+        message: String,
+    ) -> Result<Vec<Memory>> {
+        let known = self
+            .recall(workspace_id.clone(), message.clone(), KNOWN_LIMIT)
+            .await?
+            .into_iter()
+            .map(|memory| memory.content)
+            .collect();
+        let llm: Arc<dyn Llm> = self.llm.clone().into_inner();
+        let drafts = memory::extract(llm, &message, known).await?.response;
+        let mut learned = Vec::new();
+        for draft in drafts {
+            let mut memory = Memory::new(
+                workspace_id.clone(),
+                draft.kind,
+                draft.content.trim().to_string(),
+                draft.confidence,
+            );
+            memory.source_session_id = Some(session_id.clone());
+            memory.source_entry_id = Some(entry_id.clone());
+            memory.expires_at = draft
+                .expires_in_days
+                .map(|days| memory.created_at + Duration::days(days.into()));
+            learned.push(self.remember(memory).await?);
+        }
+        Ok(learned)
+    }
+
+    async fn insert(&self, memory: Memory) -> Result<Memory> {
+        use mirabel_core::schema::memories::dsl as m;
+
+        let conn = self.repository.get().await?;
+        Ok(conn
+            .interact(move |conn| {
+                diesel::insert_into(m::memories)
+                    .values(memory)
+                    .get_result::<Memory>(conn)
+            })
+            .await??)
+    }
+
+    async fn save(&self, memory: Memory) -> Result<Memory> {
+        use mirabel_core::schema::memories::dsl as m;
+
+        let conn = self.repository.get().await?;
+        Ok(conn
+            .interact(move |conn| {
+                diesel::update(m::memories.filter(m::id.eq(&memory.id)))
+                    .set((
+                        m::kind.eq(memory.kind),
+                        m::content.eq(&memory.content),
+                        m::confidence.eq(memory.confidence),
+                        m::embedding.eq(&memory.embedding),
+                        m::expires_at.eq(memory.expires_at),
+                        m::modified_at.eq(memory.modified_at),
+                    ))
+                    .get_result::<Memory>(conn)
+            })
+            .await??)
+    }
+
+    /// Memories work without embeddings, a failure is only logged
+    async fn embed(&self, text: &str) -> Option<Vec<f32>> {
+        self.llm
+            .embed(vec![text.to_string()])
+            .await
+            .inspect_err(|err| warn!("Could not embed memory text: {err}"))
+            .ok()
+            .and_then(|embeddings| embeddings.into_iter().next())
+    }
+}
+
+fn is_duplicate(known: &Memory, memory: &Memory) -> bool {
+    if normalize(&known.content) == normalize(&memory.content) {
+        return true;
+    }
+    match (known.embedding.as_deref(), memory.embedding.as_deref()) {
+        (Some(a), Some(b)) => cosine_similarity(a, b) >= DUPLICATE_SIMILARITY,
+        _ => false,
+    }
+}
+
+fn normalize(content: &str) -> String {
+    content
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -1,4 +1,13 @@
+pub(crate) mod attachments;
 pub(crate) mod auth;
+pub(crate) mod code_index;
+pub(crate) mod domain_rules;
+pub(crate) mod file_actions;
+pub(crate) mod memories;
+pub(crate) mod plans;
+pub(crate) mod repositories;
+pub(crate) mod search;
 pub(crate) mod sessions;
+pub(crate) mod specs;
 pub(crate) mod users;
 pub(crate) mod workspaces;
@@ -0,0 +1,77 @@
+use crate::prelude::*;
+use mirabel_core::models::plan::Plan;
+
+use actix_web::web::Data;
+use deadpool_diesel::postgres::Pool;
+use diesel::prelude::*;
+
+pub struct PlanService {
+    repository: Data<Pool>,
+}
+
+impl PlanService {
+    pub fn from(repository: Data<Pool>) -> Result<Self> {
+        Ok(Self { repository })
+    }
+
+    pub async fn create(&self, plan: Plan) -> Result<Plan> {
+        use mirabel_core::schema::plans::dsl as pl;
+
+        let conn = self.repository.get().await?;
+        Ok(conn
+            .interact(move |conn| {
+                diesel::insert_into(pl::plans)
+                    .values(&plan)
+                    .get_result::<Plan>(conn)
+            })
+            .await??)
+    }
+
+    pub async fn get(&self, plan_id: String) -> Result<Option<Plan>> {
+        use mirabel_core::schema::plans::dsl as pl;
+
+        let conn = self.repository.get().await?;
+        Ok(conn
+            .interact(move |conn| {
+                pl::plans
+                    .filter(pl::id.eq(&plan_id))
+                    .first::<Plan>(conn)
+                    .optional()
+            })
+            .await??)
+    }
+
+    pub async fn get_latest(&self, session_id: String) -> Result<Option<Plan>> {
+        use mirabel_core::schema::plans::dsl as pl;
+
+        let conn = self.repository.get().await?;
+        Ok(conn
+            .interact(move |conn| {
+                pl::plans
+                    .filter(pl::session_id.eq(&session_id))
+                    .order(pl::created_at.desc())
+                    .first::<Plan>(conn)
+                    .optional()
+            })
+            .await??)
+    }
+
+    /// Persists the mutable parts of a plan, its status and workflow tree
+    pub async fn update(&self, plan: Plan) -> Result<Plan> {
+        use mirabel_core::schema::plans::dsl as pl;
+
+        let conn = self.repository.get().await?;
+        Ok(conn
+            .interact(move |conn| {
+                diesel::update(pl::plans.filter(pl::id.eq(&plan.id)))
+                    .set((
+                        pl::status.eq(plan.status),
+                        pl::root.eq(&plan.root),
+                        pl::approved_at.eq(plan.approved_at),
+                        pl::modified_at.eq(plan.modified_at),
+                    ))
+                    .get_result::<Plan>(conn)
+            })
+            .await??)
+    }
+}
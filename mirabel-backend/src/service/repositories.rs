@@ -0,0 +1,534 @@
+use std::env;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::prelude::*;
+use mirabel_core::dto::repository::NewRepository;
+use mirabel_core::models::repository::Repository;
+use mirabel_core::models::repository::Worktree;
+use mirabel_core::models::repository::WorktreeStatus;
+use mirabel_core::models::session::Session;
+use mirabel_core::models::user::User;
+
+use actix_web::web::Data;
+use chrono::Utc;
+use deadpool_diesel::postgres::Pool;
+use diesel::prelude::*;
+use log::warn;
+use reqwest::Url;
+
+use crate::driver::container::Sandbox;
+use crate::driver::git::GitRepository;
+use crate::driver::git::GitWorktree;
+use crate::driver::git::Signature;
+
+const REPOSITORY_ROOT_ENV: &str = "REPOSITORY_ROOT";
+const SOURCE_ROOT_ENV: &str = "REPOSITORY_SOURCE_ROOT";
+
+pub struct RepositoryService {
+    repository: Data<Pool>,
+}
+
+impl RepositoryService {
+    pub fn from(repository: Data<Pool>) -> Result<Self> {
+        Ok(Self { repository })
+    }
+
+    /// Clones the repository and adds it to the workspace
+    pub async fn create(
+        &self,
+        workspace_id: String,
+        new_repository: NewRepository,
+    ) -> Result<Repository> {
+        use mirabel_core::schema::repositories::dsl as r;
+
+        let name = match new_repository.name {
+            Some(name) => {
+                Repository::validate_name(&name)?;
+                name
+            }
+            None => Repository::name_from_source(&new_repository.source).ok_or(
+                Error::BadRequest("No name can be derived from the source, give it one.".into()),
+            )?,
+        };
+        if let Some(path) = local_source(&new_repository.source)? {
+            check_local_source(&path).await?;
+        }
+        let conn = self.repository.get().await?;
+        let (workspace_id_clone, name_clone) = (workspace_id.clone(), name.clone());
+        let taken = conn
+            .interact(move |conn| {
+                diesel::select(diesel::dsl::exists(
+                    r::repositories
+                        .filter(r::workspace_id.eq(&workspace_id_clone))
+                        .filter(r::name.eq(&name_clone)),
+                ))
+                .get_result::<bool>(conn)
+            })
+            .await??;
+        if taken {
+            return Err(Error::AlreadyExists(format!(
+                "The workspace already has a repository named {name}."
+            )));
+        }
+
+        let mut repository =
+            Repository::new(workspace_id, name, new_repository.source, String::new());
+        let path = clone_path(&repository);
+        let clone = GitRepository::clone(&repository.source, path.clone())
+            .await
+            .map_err(|err| {
+                Error::BadRequest(format!("{} cannot be cloned: {err}", repository.source))
+            })?;
+        repository.default_branch = clone.default_branch().await?;
+        repository.merge_upstream = new_repository.merge_upstream.unwrap_or(false);
+
+        let inserted = conn
+            .interact(move |conn| {
+                diesel::insert_into(r::repositories)
+                    .values(&repository)
+                    .get_result::<Repository>(conn)
+            })
+            .await?;
+        if inserted.is_err() {
+            remove_dir(path).await;
+        }
+        Ok(inserted?)
+    }
+
+    pub async fn get_all(&self, workspace_id: String) -> Result<Vec<Repository>> {
+        use mirabel_core::schema::repositories::dsl as r;
+
+        let conn = self.repository.get().await?;
+        Ok(conn
+            .interact(move |conn| {
+                r::repositories
+                    .filter(r::workspace_id.eq(&workspace_id))
+                    .order(r::name.asc())
+                    .load::<Repository>(conn)
+            })
+            .await??)
+    }
+
+    pub async fn get(
+        &self,
+        workspace_id: String,
+        repository_id: String,
+    ) -> Result<Option<Repository>> {
+        use mirabel_core::schema::repositories::dsl as r;
+
+        let conn = self.repository.get().await?;
+        Ok(conn
+            .interact(move |conn| {
+                r::repositories
+                    .filter(r::workspace_id.eq(&workspace_id))
+                    .filter(r::id.eq(&repository_id))
+                    .first::<Repository>(conn)
+                    .optional()
+            })
+            .await??)
+    }
+
+    /// Removes the repository and its clone, sessions still working in it
+    /// have to merge or discard their work first
+    pub async fn delete(&self, workspace_id: String, repository_id: String) -> Result<()> {
+        use mirabel_core::schema::repositories::dsl as r;
+        use mirabel_core::schema::worktrees::dsl as wt;
+
+        let repository = self
+            .get(workspace_id, repository_id.clone())
+            .await?
+            .ok_or(Error::NotFound)?;
+        let conn = self.repository.get().await?;
+        conn.interact(move |conn| {
+            conn.transaction::<(), Error, _>(|t| {
+                let active = diesel::select(diesel::dsl::exists(
+                    wt::worktrees
+                        .filter(wt::repository_id.eq(&repository_id))
+                        .filter(wt::status.eq(WorktreeStatus::Active)),
+                ))
+                .get_result::<bool>(t)?;
+                if active {
+                    return Err(Error::Conflict(
+                        "Sessions are still working in the repository.".into(),
+                    ));
+                }
+                diesel::delete(r::repositories.filter(r::id.eq(&repository_id))).execute(t)?;
+                Ok(())
+            })
+        })
+        .await??;
+        remove_dir(clone_path(&repository)).await;
+        Ok(())
+    }
+
+    /// Every worktree the session had, newest first
+    pub async fn get_worktrees(&self, session_id: String) -> Result<Vec<Worktree>> {
+        use mirabel_core::schema::worktrees::dsl as wt;
+
+        let conn = self.repository.get().await?;
+        Ok(conn
+            .interact(move |conn| {
+                wt::worktrees
+                    .filter(wt::session_id.eq(&session_id))
+                    .order(wt::created_at.desc())
+                    .load::<Worktree>(conn)
+            })
+            .await??)
+    }
+
+    /// The repositories the session has checked out right now, with their
+    /// worktrees
+    pub async fn get_active_worktrees(
+        &self,
+        session_id: String,
+    ) -> Result<Vec<(Worktree, Repository)>> {
+        use mirabel_core::schema::repositories::dsl as r;
+        use mirabel_core::schema::worktrees::dsl as wt;
+
+        let conn = self.repository.get().await?;
+        Ok(conn
+            .interact(move |conn| {
+                wt::worktrees
+                    .inner_join(r::repositories)
+                    .filter(wt::session_id.eq(&session_id))
+                    .filter(wt::status.eq(WorktreeStatus::Active))
+                    .order(r::name.asc())
+                    .select((Worktree::as_select(), Repository::as_select()))
+                    .load::<(Worktree, Repository)>(conn)
+            })
+            .await??)
+    }
+
+    /// Checks the repository out into the sandbox of the session, on a branch
+    /// of its own. Returns the active worktree if there already is one.
+    pub async fn create_worktree(
+        &self,
+        session: &Session,
+        repository_id: String,
+    ) -> Result<Worktree> {
+        use mirabel_core::schema::worktrees::dsl as wt;
+
+        let repository = self
+            .get(session.workspace_id.clone(), repository_id.clone())
+            .await?
+            .ok_or(Error::NotFound)?;
+        if let Some((worktree, _)) = self.get_active(&session.id, &repository_id).await? {
+            return Ok(worktree);
+        }
+        let path = worktree_path(&session.id, &repository)?;
+        if path.exists() {
+            return Err(Error::Conflict(format!(
+                "{} already exists in the sandbox of the session.",
+                repository.name
+            )));
+        }
+
+        let mut worktree = Worktree::new(
+            session.id.clone(),
+            repository.id.clone(),
+            String::new(),
+            String::new(),
+        );
+        worktree.branch = Worktree::branch_name(&session.id, &worktree.id);
+        let clone = GitRepository::open(clone_path(&repository));
+        worktree.base_commit = clone
+            .add_worktree(&path, &worktree.branch, &repository.default_branch)
+            .await?;
+
+        let branch = worktree.branch.clone();
+        let conn = self.repository.get().await?;
+        let inserted = conn
+            .interact(move |conn| {
+                diesel::insert_into(wt::worktrees)
+                    .values(&worktree)
+                    .get_result::<Worktree>(conn)
+            })
+            .await?;
+        match inserted {
+            Ok(worktree) => Ok(worktree),
+            Err(err) => {
+                if let Err(err) = clone.remove_worktree(&path, &branch).await {
+                    warn!("Failed to remove the worktree at {}: {err}", path.display());
+                }
+                Err(err.into())
+            }
+        }
+    }
+
+    /// The active worktree of the session in the repository and where it is
+    /// checked out
+    pub async fn get_checkout(
+        &self,
+        session_id: String,
+        repository_id: String,
+    ) -> Result<(Worktree, PathBuf)> {
+        let (worktree, repository) = self
+            .get_active_or_not_found(&session_id, &repository_id)
+            .await?;
+        Ok((worktree, worktree_path(&session_id, &repository)?))
+    }
+
+    /// Everything the session changed in the repository so far, committed
+    /// or not, as a unified diff
+    pub async fn diff(&self, session_id: String, repository_id: String) -> Result<String> {
+        let (worktree, repository) = self
+            .get_active_or_not_found(&session_id, &repository_id)
+            .await?;
+        GitWorktree::open(worktree_path(&session_id, &repository)?)
+            .diff(&worktree.base_commit)
+            .await
+    }
+
+    /// Commits all changes of the session in the repository, returns the
+    /// commit or `None` when nothing changed
+    pub async fn commit(
+        &self,
+        session_id: String,
+        repository_id: String,
+        message: String,
+        user: &User,
+    ) -> Result<Option<String>> {
+        if message.trim().is_empty() {
+            return Err(Error::BadRequest("The commit message is empty.".into()));
+        }
+        let (_, repository) = self
+            .get_active_or_not_found(&session_id, &repository_id)
+            .await?;
+        GitWorktree::open(worktree_path(&session_id, &repository)?)
+            .commit(&message, &signature(user))
+            .await
+    }
+
+    /// Merges the branch of the session into the default branch of the
+    /// source, or pushes the branch unless the repository allows merging
+    /// upstream. The worktree is removed either way.
+    pub async fn merge(
+        &self,
+        session_id: String,
+        repository_id: String,
+        user: &User,
+    ) -> Result<Worktree> {
+        let (mut worktree, repository) = self
+            .get_active_or_not_found(&session_id, &repository_id)
+            .await?;
+        let path = worktree_path(&session_id, &repository)?;
+        if GitWorktree::open(path.clone()).has_changes().await? {
+            return Err(Error::Conflict(
+                "There are uncommitted changes, commit or discard them first.".into(),
+            ));
+        }
+        let clone = GitRepository::open(clone_path(&repository));
+        let (status, commit) = match repository.merge_upstream {
+            true => (
+                WorktreeStatus::Merged,
+                clone
+                    .merge(
+                        &worktree.branch,
+                        &repository.default_branch,
+                        &format!("Merge {}", worktree.branch),
+                        &signature(user),
+                    )
+                    .await?,
+            ),
+            false => (
+                WorktreeStatus::Pushed,
+                clone
+                    .push(&worktree.branch, &repository.default_branch)
+                    .await?,
+            ),
+        };
+        clone.remove_worktree(&path, &worktree.branch).await?;
+
+        worktree.status = status;
+        worktree.merge_commit = Some(commit);
+        self.update_worktree(worktree).await
+    }
+
+    /// Throws away the worktree of the session with all its changes
+    pub async fn discard(&self, session_id: String, repository_id: String) -> Result<Worktree> {
+        let (mut worktree, repository) = self
+            .get_active_or_not_found(&session_id, &repository_id)
+            .await?;
+        GitRepository::open(clone_path(&repository))
+            .remove_worktree(&worktree_path(&session_id, &repository)?, &worktree.branch)
+            .await?;
+        worktree.status = WorktreeStatus::Discarded;
+        self.update_worktree(worktree).await
+    }
+
+    async fn get_active(
+        &self,
+        session_id: &str,
+        repository_id: &str,
+    ) -> Result<Option<(Worktree, Repository)>> {
+        use mirabel_core::schema::repositories::dsl as r;
+        use mirabel_core::schema::worktrees::dsl as wt;
+
+        let (session_id, repository_id) = (session_id.to_string(), repository_id.to_string());
+        let conn = self.repository.get().await?;
+        Ok(conn
+            .interact(move |conn| {
+                wt::worktrees
+                    .inner_join(r::repositories)
+                    .filter(wt::session_id.eq(&session_id))
+                    .filter(wt::repository_id.eq(&repository_id))
+                    .filter(wt::status.eq(WorktreeStatus::Active))
+                    .select((Worktree::as_select(), Repository::as_select()))
+                    .first::<(Worktree, Repository)>(conn)
+                    .optional()
+            })
+            .await??)
+    }
+
+    async fn get_active_or_not_found(
+        &self,
+        session_id: &str,
+        repository_id: &str,
+    ) -> Result<(Worktree, Repository)> {
+        self.get_active(session_id, repository_id)
+            .await?
+            .ok_or(Error::NotFound)
+    }
+
+    async fn update_worktree(&self, worktree: Worktree) -> Result<Worktree> {
+        use mirabel_core::schema::worktrees::dsl as wt;
+
+        let conn = self.repository.get().await?;
+        Ok(conn
+            .interact(move |conn| {
+                diesel::update(wt::worktrees.filter(wt::id.eq(&worktree.id)))
+                    .set((
+                        wt::status.eq(worktree.status),
+                        wt::merge_commit.eq(&worktree.merge_commit),
+                        wt::modified_at.eq(Utc::now()),
+                    ))
+                    .get_result::<Worktree>(conn)
+            })
+            .await??)
+    }
+}
+
+/// Where the bare clone of a repository lives, below `REPOSITORY_ROOT`
+fn clone_path(repository: &Repository) -> PathBuf {
+    repository_root()
+        .join(&repository.workspace_id)
+        .join(format!("{}.git", repository.id))
+}
+
+fn repository_root() -> PathBuf {
+    env::var(REPOSITORY_ROOT_ENV)
+        .map(PathBuf::from)
+        .unwrap_or(env::temp_dir().join("mirabel-repositories"))
+}
+
+/// The path of a source on this server, `None` for remote URLs like
+/// `https://host/repo.git` or `git@host:repo.git`. Other transports, e.g.
+/// `ext::`, are refused.
+fn local_source(source: &str) -> Result<Option<PathBuf>> {
+    let refused = || Error::BadRequest(format!("{source} is not a repository that can be cloned."));
+    let source = source.trim();
+    if source.is_empty() || source.starts_with('-') {
+        return Err(refused());
+    }
+    if let Some((scheme, _)) = source.split_once("://") {
+        return match scheme.to_lowercase().as_str() {
+            "https" | "http" | "ssh" | "git" => Ok(None),
+            "file" => Url::parse(source)
+                .ok()
+                .and_then(|url| url.to_file_path().ok())
+                .map(Some)
+                .ok_or_else(refused),
+            _ => Err(refused()),
+        };
+    }
+    // Like scp, `[user@]host:path`, as long as no slash comes before the colon
+    match source.split_once(':') {
+        Some((host, path)) if !host.contains('/') => {
+            match host.is_empty() || path.starts_with(':') {
+                true => Err(refused()),
+                false => Ok(None),
+            }
+        }
+        _ => Ok(Some(PathBuf::from(source))),
+    }
+}
+
+/// Local repositories are only cloned from below `REPOSITORY_SOURCE_ROOT`,
+/// never from the clones of workspaces
+async fn check_local_source(path: &Path) -> Result<()> {
+    let Ok(root) = env::var(SOURCE_ROOT_ENV) else {
+        return Err(Error::BadRequest(
+            "Repositories can only be cloned from remote URLs.".into(),
+        ));
+    };
+    let outside = || {
+        Error::BadRequest(format!(
+            "{} is not below the directory repositories are cloned from.",
+            path.display()
+        ))
+    };
+    let path = tokio::fs::canonicalize(path).await.map_err(|_| outside())?;
+    let root = tokio::fs::canonicalize(root).await?;
+    let clones = tokio::fs::canonicalize(repository_root())
+        .await
+        .unwrap_or(repository_root());
+    match path.starts_with(&root) && !path.starts_with(&clones) {
+        true => Ok(()),
+        false => Err(outside()),
+    }
+}
+
+/// Worktrees are in the sandbox, so the shell and the editor of the session
+/// work on them
+pub(crate) fn worktree_path(session_id: &str, repository: &Repository) -> Result<PathBuf> {
+    let workdir = Sandbox::workdir_of(session_id);
+    std::fs::create_dir_all(&workdir)?;
+    Ok(workdir.canonicalize()?.join(&repository.name))
+}
+
+fn signature(user: &User) -> Signature {
+    Signature {
+        name: user.username.clone(),
+        email: user.email.clone(),
+    }
+}
+
+async fn remove_dir(path: PathBuf) {
+    if let Err(err) = tokio::fs::remove_dir_all(&path).await {
+        warn!("Failed to remove {}: {err}", path.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_source() {
+        for remote in [
+            "https://github.com/rust-lang/rust.git",
+            "ssh://git@example.com/repo.git",
+            "git@github.com:owner/repo.git",
+            "example.com:repo.git",
+        ] {
+            assert_eq!(local_source(remote).unwrap(), None, "{remote}");
+        }
+        assert_eq!(
+            local_source("/srv/git/project.git").unwrap(),
+            Some(PathBuf::from("/srv/git/project.git"))
+        );
+        assert_eq!(
+            local_source("file:///srv/git/project.git").unwrap(),
+            Some(PathBuf::from("/srv/git/project.git"))
+        );
+        for refused in [
+            "ext::sh -c touch% /tmp/pwned",
+            "fd::17",
+            "--upload-pack=x",
+            "ftp://host/repo",
+        ] {
+            assert!(local_source(refused).is_err(), "{refused}");
+        }
+    }
+}
@@ -0,0 +1,449 @@
+use std::collections::HashMap;
+use std::env;
+use std::time::Duration;
+
+use crate::prelude::*;
+use mirabel_core::dto::search::HIGHLIGHT_START;
+use mirabel_core::dto::search::HIGHLIGHT_STOP;
+use mirabel_core::dto::search::SearchHit;
+use mirabel_core::dto::search::SnippetPart;
+use mirabel_core::dto::search::TimelineSearch;
+use mirabel_core::models::user::User;
+use mirabel_core::utils::vector::cosine_similarity;
+
+use actix_web::web::Data;
+use chrono::DateTime;
+use chrono::TimeDelta;
+use chrono::Utc;
+use deadpool_diesel::postgres::Pool;
+use diesel::prelude::*;
+use diesel::sql_types::Array;
+use diesel::sql_types::BigInt;
+use diesel::sql_types::Float4;
+use diesel::sql_types::Nullable;
+use diesel::sql_types::Text;
+use diesel::sql_types::Timestamptz;
+use log::debug;
+use log::warn;
+
+use crate::driver::llm::Embedder;
+use crate::driver::llm::ollama::Ollama;
+
+const TIMELINE_EMBEDDINGS_ENV: &str = "TIMELINE_EMBEDDINGS";
+
+const DEFAULT_LIMIT: i64 = 20;
+const MAX_LIMIT: i64 = 100;
+/// Embedded entries compared to a query, the most recent ones win. Without a
+/// vector index every candidate is scored in memory.
+const MAX_SEMANTIC_CANDIDATES: i64 = 5000;
+/// Keeps well written but unrelated entries out of semantic results
+const MIN_SIMILARITY: f32 = 0.5;
+/// Dampens the lead of top ranks when keyword and semantic results are fused
+const RANK_OFFSET: f32 = 60.0;
+/// Entries embedded per round, and how long the embedder waits once all are
+const EMBED_BATCH_SIZE: i64 = 32;
+const EMBED_INTERVAL: Duration = Duration::from_secs(30);
+/// Entries the model failed to embed this often are only found by their words
+const MAX_EMBED_ATTEMPTS: i32 = 5;
+/// Characters of an entry that are embedded, long specs and shell output are
+/// cut off
+const MAX_EMBEDDED_CHARS: usize = 4000;
+
+const HEADLINE_OPTIONS: &str =
+    "MaxWords=35, MinWords=15, MaxFragments=2, FragmentDelimiter=\" … \"";
+
+const KEYWORD_QUERY: &str = r#"
+    SELECT te.id AS entry_id,
+        ts_rank(to_tsvector('english', ts.text), websearch_to_tsquery('english', $2)) AS rank
+    FROM timeline_search ts
+    JOIN timeline_entries te ON te.id = ts.entry_id
+    JOIN sessions s ON s.id = te.session_id
+    WHERE s.workspace_id = $1
+        AND to_tsvector('english', ts.text) @@ websearch_to_tsquery('english', $2)
+        AND ($3::text[] IS NULL OR te.content_type = ANY($3))
+        AND ($4::timestamptz IS NULL OR te.created_at >= $4)
+        AND ($5::timestamptz IS NULL OR te.created_at < $5)
+    ORDER BY rank DESC, te.created_at DESC
+    LIMIT $6
+"#;
+
+const HIT_QUERY: &str = r#"
+    SELECT te.id AS entry_id, te.session_id, s.title AS session_title, te.content_type,
+        te.created_at, ts_headline('english', ts.text, websearch_to_tsquery('english', $2), $3) AS snippet
+    FROM timeline_search ts
+    JOIN timeline_entries te ON te.id = ts.entry_id
+    JOIN sessions s ON s.id = te.session_id
+    WHERE te.id = ANY($1)
+"#;
+
+#[derive(QueryableByName)]
+struct KeywordMatch {
+    #[diesel(sql_type = Text)]
+    entry_id: String,
+    #[diesel(sql_type = Float4)]
+    rank: f32,
+}
+
+#[derive(QueryableByName)]
+struct Hit {
+    #[diesel(sql_type = Text)]
+    entry_id: String,
+    #[diesel(sql_type = Text)]
+    session_id: String,
+    #[diesel(sql_type = Text)]
+    session_title: String,
+    #[diesel(sql_type = Text)]
+    content_type: String,
+    #[diesel(sql_type = Timestamptz)]
+    created_at: DateTime<Utc>,
+    #[diesel(sql_type = Text)]
+    snippet: String,
+}
+
+/// Finds timeline entries by their words with the full text index of
+/// Postgres, and by their meaning when `TIMELINE_EMBEDDINGS` is on
+pub struct TimelineSearchService {
+    repository: Data<Pool>,
+    llm: Data<Ollama>,
+    semantic: bool,
+}
+
+impl TimelineSearchService {
+    pub fn from(repository: Data<Pool>, llm: Data<Ollama>) -> Result<Self> {
+        let semantic = env::var(TIMELINE_EMBEDDINGS_ENV)
+            .map(|value| value == "true")
+            .unwrap_or(false);
+        Ok(Self {
+            repository,
+            llm,
+            semantic,
+        })
+    }
+
+    pub fn is_semantic(&self) -> bool {
+        self.semantic
+    }
+
+    /// The entries of the sessions of a workspace that match the search, best
+    /// first. Keyword and semantic results are fused by their ranks.
+    pub async fn search(
+        &self,
+        user: User,
+        workspace_id: String,
+        search: TimelineSearch,
+    ) -> Result<Vec<SearchHit>> {
+        use mirabel_core::schema::workspace_members::dsl as wm;
+
+        let query = search.query.trim().to_string();
+        if query.is_empty() {
+            return Err(Error::BadRequest(
+                "The search query cannot be empty.".into(),
+            ));
+        }
+        let limit = search.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+        let conn = self.repository.get().await?;
+        let workspace_id_clone = workspace_id.clone();
+        let is_member = conn
+            .interact(move |conn| {
+                diesel::select(diesel::dsl::exists(
+                    wm::workspace_members
+                        .filter(wm::user_id.eq(&user.id))
+                        .filter(wm::workspace_id.eq(&workspace_id_clone)),
+                ))
+                .get_result::<bool>(conn)
+            })
+            .await??;
+        if !is_member {
+            return Err(Error::NotFound);
+        }
+
+        let keyword = self
+            .keyword_matches(workspace_id.clone(), query.clone(), &search, limit)
+            .await?;
+        let semantic = if self.semantic && search.semantic.unwrap_or(true) {
+            self.semantic_matches(workspace_id, &query, &search, limit)
+                .await?
+        } else {
+            Vec::new()
+        };
+        let scores = fuse(&[keyword, semantic]);
+        let mut ranked: Vec<(String, f32)> = scores.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        ranked.truncate(limit as usize);
+        if ranked.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let ids: Vec<String> = ranked.iter().map(|(id, _)| id.clone()).collect();
+        let options =
+            format!("StartSel={HIGHLIGHT_START}, StopSel={HIGHLIGHT_STOP}, {HEADLINE_OPTIONS}");
+        let mut hits: HashMap<String, Hit> = conn
+            .interact(move |conn| {
+                diesel::sql_query(HIT_QUERY)
+                    .bind::<Array<Text>, _>(ids)
+                    .bind::<Text, _>(query)
+                    .bind::<Text, _>(options)
+                    .load::<Hit>(conn)
+            })
+            .await??
+            .into_iter()
+            .map(|hit| (hit.entry_id.clone(), hit))
+            .collect();
+        Ok(ranked
+            .into_iter()
+            .filter_map(|(id, score)| {
+                let hit = hits.remove(&id)?;
+                Some(SearchHit {
+                    entry_id: hit.entry_id,
+                    session_id: hit.session_id,
+                    session_title: hit.session_title,
+                    content_type: hit.content_type,
+                    created_at: hit.created_at,
+                    snippet: SnippetPart::parse(&hit.snippet),
+                    score,
+                    // Timestamps are stored with microseconds
+                    cursor: hit.created_at + TimeDelta::microseconds(1),
+                })
+            })
+            .collect())
+    }
+
+    /// Ids of the entries matching the words of the query, best first
+    async fn keyword_matches(
+        &self,
+        workspace_id: String,
+        query: String,
+        search: &TimelineSearch,
+        limit: i64,
+    ) -> Result<Vec<String>> {
+        let types = search.types();
+        let (from, to) = (search.from, search.to);
+        let conn = self.repository.get().await?;
+        Ok(conn
+            .interact(move |conn| {
+                diesel::sql_query(KEYWORD_QUERY)
+                    .bind::<Text, _>(workspace_id)
+                    .bind::<Text, _>(query)
+                    .bind::<Nullable<Array<Text>>, _>(types)
+                    .bind::<Nullable<Timestamptz>, _>(from)
+                    .bind::<Nullable<Timestamptz>, _>(to)
+                    .bind::<BigInt, _>(limit)
+                    .load::<KeywordMatch>(conn)
+            })
+            .await??
+            .into_iter()
+            .filter(|found| found.rank > 0.0)
+            .map(|found| found.entry_id)
+            .collect())
+    }
+
+    /// Ids of the entries closest in meaning to the query, best first. Only
+    /// logs when the query cannot be embedded, keywords still find entries.
+    async fn semantic_matches(
+        &self,
+        workspace_id: String,
+        query: &str,
+        search: &TimelineSearch,
+        limit: i64,
+    ) -> Result<Vec<String>> {
+        use mirabel_core::schema::sessions::dsl as s;
+        use mirabel_core::schema::timeline_entries::dsl as te;
+        use mirabel_core::schema::timeline_search::dsl as ts;
+
+        let embedding = match self.llm.embed(vec![query.to_string()]).await {
+            Ok(mut embeddings) => embeddings.pop().unwrap_or_default(),
+            Err(err) => {
+                warn!("Could not embed the search query: {err}");
+                return Ok(Vec::new());
+            }
+        };
+        let types = search.types();
+        let (from, to) = (search.from, search.to);
+        let conn = self.repository.get().await?;
+        let candidates = conn
+            .interact(move |conn| {
+                let mut candidates = ts::timeline_search
+                    .inner_join(te::timeline_entries.inner_join(s::sessions))
+                    .filter(s::workspace_id.eq(workspace_id))
+                    .filter(ts::embedding.is_not_null())
+                    .into_boxed();
+                if let Some(types) = types {
+                    candidates = candidates.filter(te::content_type.eq_any(types));
+                }
+                if let Some(from) = from {
+                    candidates = candidates.filter(te::created_at.ge(from));
+                }
+                if let Some(to) = to {
+                    candidates = candidates.filter(te::created_at.lt(to));
+                }
+                candidates
+                    .order(te::created_at.desc())
+                    .limit(MAX_SEMANTIC_CANDIDATES)
+                    .select((ts::entry_id, ts::embedding))
+                    .load::<(String, Option<Vec<f32>>)>(conn)
+            })
+            .await??;
+        let mut scored: Vec<(f32, String)> = candidates
+            .into_iter()
+            .filter_map(|(id, candidate)| {
+                let similarity = cosine_similarity(&embedding, &candidate?);
+                (similarity >= MIN_SIMILARITY).then_some((similarity, id))
+            })
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        Ok(scored
+            .into_iter()
+            .take(limit as usize)
+            .map(|(_, id)| id)
+            .collect())
+    }
+
+    /// Embeds entries that are not yet, returns how many were. Entries that
+    /// failed before come after the others, and are left alone
+    /// once they failed [`MAX_EMBED_ATTEMPTS`] times.
+    pub async fn embed_pending(&self) -> Result<usize> {
+        use mirabel_core::schema::timeline_search::dsl as ts;
+
+        let conn = self.repository.get().await?;
+        let pending = conn
+            .interact(|conn| {
+                ts::timeline_search
+                    .filter(ts::embedding.is_null())
+                    .filter(ts::attempts.lt(MAX_EMBED_ATTEMPTS))
+                    .order((ts::attempts.asc(), ts::entry_id.asc()))
+                    .select((ts::entry_id, ts::text))
+                    .limit(EMBED_BATCH_SIZE)
+                    .load::<(String, String)>(conn)
+            })
+            .await??;
+        if pending.is_empty() {
+            return Ok(0);
+        }
+        let texts = pending
+            .iter()
+            .map(|(_, text)| text.chars().take(MAX_EMBEDDED_CHARS).collect())
+            .collect();
+        let embeddings = embed_each(self.llm.as_ref(), texts).await;
+        let count = pending.len();
+        let failed = embeddings.iter().filter(|e| e.is_none()).count();
+        conn.interact(move |conn| {
+            conn.transaction::<(), Error, _>(|t| {
+                for ((entry_id, text), embedding) in pending.into_iter().zip(embeddings) {
+                    // The text may have changed while it was embedded
+                    let entry = ts::timeline_search
+                        .filter(ts::entry_id.eq(entry_id))
+                        .filter(ts::text.eq(text));
+                    match embedding {
+                        Some(embedding) => diesel::update(entry)
+                            .set(ts::embedding.eq(embedding))
+                            .execute(t)?,
+                        None => diesel::update(entry)
+                            .set(ts::attempts.eq(ts::attempts + 1))
+                            .execute(t)?,
+                    };
+                }
+                Ok(())
+            })
+        })
+        .await??;
+        if failed > 0 {
+            warn!("Could not embed {failed} of {count} timeline entries");
+        }
+        Ok(count - failed)
+    }
+
+    /// Keeps embedding new entries for as long as the server runs
+    pub async fn run_embedder(&self) {
+        loop {
+            match self.embed_pending().await {
+                Ok(count) if count as i64 == EMBED_BATCH_SIZE => continue,
+                Ok(count) => debug!("Embedded {count} timeline entries"),
+                Err(err) => warn!("Could not embed timeline entries: {err}"),
+            }
+            tokio::time::sleep(EMBED_INTERVAL).await;
+        }
+    }
+}
+
+/// Reciprocal rank fusion, entries found by several searches add up
+fn fuse(rankings: &[Vec<String>]) -> HashMap<String, f32> {
+    let mut scores = HashMap::new();
+    for ranking in rankings {
+        for (rank, id) in ranking.iter().enumerate() {
+            *scores.entry(id.clone()).or_insert(0.0) += 1.0 / (RANK_OFFSET + rank as f32 + 1.0);
+        }
+    }
+    scores
+}
+
+/// The embeddings of the texts, `None` for those the embedder failed on.
+/// When the batch fails the texts are embedded one at a time, so a text the
+/// model refuses does not hold up the others.
+async fn embed_each<E: Embedder + Sync + ?Sized>(
+    embedder: &E,
+    texts: Vec<String>,
+) -> Vec<Option<Vec<f32>>> {
+    match embedder.embed(texts.clone()).await {
+        Ok(embeddings) if embeddings.len() == texts.len() => {
+            return embeddings.into_iter().map(Some).collect();
+        }
+        Ok(_) => warn!("Got fewer embeddings than timeline entries, embedding them one by one"),
+        Err(err) => warn!("Could not embed timeline entries, embedding them one by one: {err}"),
+    }
+    let mut embeddings = Vec::with_capacity(texts.len());
+    for text in texts {
+        let embedding = match embedder.embed(vec![text]).await {
+            Ok(mut embedded) if embedded.len() == 1 => embedded.pop(),
+            _ => None,
+        };
+        embeddings.push(embedding);
+    }
+    embeddings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuse() {
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        let scores = fuse(&[ids(&["a", "b", "c"]), ids(&["c", "d"])]);
+        assert_eq!(scores.len(), 4);
+        // Found by both beats first of one
+        assert!(scores["c"] > scores["a"]);
+        assert!(scores["a"] > scores["b"]);
+        assert_eq!(scores["b"], scores["d"]);
+    }
+
+    /// Refuses every batch with a text that mentions it
+    struct Picky;
+
+    #[async_trait::async_trait]
+    impl Embedder for Picky {
+        async fn embed(&self, inputs: Vec<String>) -> Result<Vec<Vec<f32>>> {
+            match inputs.iter().any(|input| input.contains("refused")) {
+                true => Err(Error::Generic("The input is too long.".into())),
+                false => Ok(inputs
+                    .iter()
+                    .map(|input| vec![input.len() as f32])
+                    .collect()),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_embed_each() {
+        let texts = |texts: &[&str]| texts.iter().map(|text| text.to_string()).collect();
+        assert_eq!(
+            embed_each(&Picky, texts(&["a", "bb"])).await,
+            [Some(vec![1.0]), Some(vec![2.0])]
+        );
+        // One refused text does not hold up the others
+        assert_eq!(
+            embed_each(&Picky, texts(&["a", "refused", "bb"])).await,
+            [Some(vec![1.0]), None, Some(vec![2.0])]
+        );
+    }
+}
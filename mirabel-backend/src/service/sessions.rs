@@ -4,22 +4,28 @@ use mirabel_core::dto::page::PageInfo;
 use mirabel_core::dto::page::PageRequest;
 use mirabel_core::dto::page::PageResponse;
 use mirabel_core::dto::session::FullSession;
+use mirabel_core::models::plan::Plan;
 use mirabel_core::models::session::Session;
 use mirabel_core::models::timeline::TimelineEntry;
 use mirabel_core::models::timeline::TimelineEntryContent;
 use mirabel_core::models::workspace::WorkspaceMember;
+use mirabel_core::models::workspace::WorkspaceRole;
 use std::collections::HashMap;
 use std::sync::Arc;
 
 use crate::prelude::*;
 
 use crate::driver::llm::ollama::Ollama;
+use crate::driver::research::WebResearch;
+use crate::service::plans::PlanService;
+use crate::service::specs::SpecService;
 use crate::session::models::SessionWorker;
 
 use actix_web::web::Data;
 use deadpool_diesel::postgres::Pool;
 use diesel::prelude::*;
 use log::debug;
+use log::warn;
 use tokio::sync::Mutex;
 
 use mirabel_core::models::user::User;
@@ -27,14 +33,16 @@ use mirabel_core::models::user::User;
 pub struct SessionService {
     repository: Data<Pool>,
     llm: Data<Ollama>,
+    web: Data<WebResearch>,
     session_handler_registry: Data<Mutex<HashMap<String, Arc<SessionWorker>>>>,
 }
 
 impl SessionService {
-    pub fn from(repository: Data<Pool>, llm: Data<Ollama>) -> Result<Self> {
+    pub fn from(repository: Data<Pool>, llm: Data<Ollama>, web: Data<WebResearch>) -> Result<Self> {
         Ok(Self {
             repository,
             llm,
+            web,
             session_handler_registry: Data::new(Mutex::new(HashMap::new())),
         })
     }
@@ -51,7 +59,7 @@ impl SessionService {
         let conn = self.repository.get().await?;
         let user_id = user.id.clone();
         let workspace_id_clone = workspace_id.clone();
-        let is_member = conn
+        let can_write = conn
             .interact(move |conn| {
                 wm::workspace_members
                     .filter(wm::user_id.eq(&user_id))
@@ -60,9 +68,9 @@ impl SessionService {
                     .optional()
             })
             .await??
-            .is_some();
+            .is_some_and(|member| member.role.can_write());
 
-        if !is_member {
+        if !can_write {
             return Err(Error::Unauthorized(
                 "You are not authorized to create a session in this workspace.".to_string(),
             ));
@@ -78,7 +86,7 @@ impl SessionService {
             })
             .await??;
 
-        let entry = TimelineEntry::user_message(session.id.clone(), input);
+        let entry = TimelineEntry::user_message(session.id.clone(), user.id, input);
         conn.interact(move |conn| {
             diesel::insert_into(mirabel_core::schema::timeline_entries::table)
                 .values(entry)
@@ -89,12 +97,14 @@ impl SessionService {
         Ok(session)
     }
 
-    pub async fn get_user_session_by_id(
+    /// Sessions are shared with the whole workspace, this returns the session
+    /// together with the role of the user in its workspace.
+    pub async fn get_session_with_role(
         &self,
         user: User,
         workspace_id: String,
         id: String,
-    ) -> Result<Option<Session>> {
+    ) -> Result<Option<(Session, WorkspaceRole)>> {
         use mirabel_core::schema::sessions::dsl as s;
         use mirabel_core::schema::workspace_members::dsl as wm;
 
@@ -105,15 +115,46 @@ impl SessionService {
                     .inner_join(s::sessions.on(s::workspace_id.eq(wm::workspace_id)))
                     .filter(wm::user_id.eq(&user.id))
                     .filter(wm::workspace_id.eq(&workspace_id))
-                    .filter(s::user_id.eq(&user.id))
                     .filter(s::id.eq(&id))
-                    .select(Session::as_select())
-                    .first::<Session>(conn)
+                    .select((Session::as_select(), wm::role))
+                    .first::<(Session, WorkspaceRole)>(conn)
                     .optional()
             })
             .await??)
     }
 
+    pub async fn get_user_session_by_id(
+        &self,
+        user: User,
+        workspace_id: String,
+        id: String,
+    ) -> Result<Option<Session>> {
+        Ok(self
+            .get_session_with_role(user, workspace_id, id)
+            .await?
+            .map(|(session, _)| session))
+    }
+
+    /// Renaming and archiving is left to the creator and workspace admins
+    async fn get_managed_session(
+        &self,
+        user: User,
+        workspace_id: String,
+        id: String,
+    ) -> Result<Session> {
+        let user_id = user.id.clone();
+        let (session, role) = self
+            .get_session_with_role(user, workspace_id, id)
+            .await?
+            .ok_or(Error::NotFound)?;
+        if session.user_id != user_id && !role.is_at_least_admin() {
+            return Err(Error::Forbidden(
+                "Only the creator or a workspace admin can change this session.".into(),
+            ));
+        }
+        Ok(session)
+    }
+
     pub async fn get_full_user_session(
         &self,
         user: User,
@@ -147,9 +188,16 @@ impl SessionService {
             cursor_response.data,
         );
         let spec = self.get_latest_spec(id.clone()).await?;
+        let plan = self.get_latest_plan(id.clone()).await?;
         let shell = self.get_shell_state(id.clone()).await?;
 
-        Ok(Some(FullSession::new(session, page_response, spec, shell)))
+        Ok(Some(FullSession::new(
+            session,
+            page_response,
+            spec,
+            plan,
+            shell,
+        )))
     }
 
     pub async fn get_session_timeline_cursor(
@@ -196,12 +244,14 @@ impl SessionService {
                     query
                         .order(te::created_at.asc())
                         .limit(limit + 1)
+                        .select(TimelineEntry::as_select())
                         .load::<TimelineEntry>(conn)
                 } else {
                     // Loading older messages OR initial load - get entries in desc order
                     query
                         .order(te::created_at.desc())
                         .limit(limit + 1)
+                        .select(TimelineEntry::as_select())
                         .load::<TimelineEntry>(conn)
                 }
             })
@@ -277,6 +327,7 @@ impl SessionService {
                     .order(te::created_at.desc())
                     .offset(offset)
                     .limit(limit)
+                    .select(TimelineEntry::as_select())
                     .load::<TimelineEntry>(conn)
             })
             .await??;
@@ -310,9 +361,8 @@ impl SessionService {
         use mirabel_core::schema::sessions::dsl as s;
 
         let mut session = self
-            .get_user_session_by_id(user, workspace_id, id.clone())
-            .await?
-            .ok_or(Error::NotFound)?;
+            .get_managed_session(user, workspace_id, id.clone())
+            .await?;
 
         session.set_title(title);
 
@@ -340,12 +390,8 @@ impl SessionService {
     ) -> Result<()> {
         use mirabel_core::schema::sessions::dsl as s;
 
-        let session_opt = self
-            .get_user_session_by_id(user, workspace_id, id.clone())
+        self.get_managed_session(user, workspace_id, id.clone())
             .await?;
-        let Some(_session) = session_opt else {
-            return Err(Error::NotFound);
-        };
 
         let conn = self.repository.get().await?;
         let id_clone = id.clone();
@@ -359,11 +405,14 @@ impl SessionService {
         Ok(())
     }
 
+    /// Lists the sessions of the user in a workspace, or the sessions of all
+    /// members when `include_shared` is set.
     pub async fn get_user_workspace_sessions(
         &self,
         workspace_id: String,
         user: User,
         page: PageRequest,
+        include_shared: bool,
     ) -> Result<PageResponse<Session>> {
         use mirabel_core::schema::sessions::dsl as s;
         use mirabel_core::schema::workspace_members::dsl as wm;
@@ -394,10 +443,14 @@ impl SessionService {
         let page_clone = page.clone();
         let sessions = conn
             .interact(move |conn| {
-                s::sessions
-                    .filter(s::user_id.eq(&user_id))
+                let mut query = s::sessions
                     .filter(s::workspace_id.eq(&workspace_id_clone))
                     .filter(s::archived.eq(false))
+                    .into_boxed();
+                if !include_shared {
+                    query = query.filter(s::user_id.eq(user_id));
+                }
+                query
                     .offset(page_clone.offset())
                     .limit(page_clone.size())
                     .select(Session::as_select())
@@ -408,12 +461,14 @@ impl SessionService {
         let user_id = user.id.clone();
         let count = conn
             .interact(move |conn| {
-                s::sessions
-                    .filter(s::user_id.eq(&user_id))
+                let mut query = s::sessions
                     .filter(s::workspace_id.eq(&workspace_id))
                     .filter(s::archived.eq(false))
-                    .select(diesel::dsl::count(s::id))
-                    .first::<i64>(conn)
+                    .into_boxed();
+                if !include_shared {
+                    query = query.filter(s::user_id.eq(user_id));
+                }
+                query.select(diesel::dsl::count(s::id)).first::<i64>(conn)
             })
             .await??;
 
@@ -436,6 +491,7 @@ impl SessionService {
                     .filter(te::session_id.eq(&session_id))
                     .filter(te::content_type.eq(&content_type))
                     .order(te::created_at.desc())
+                    .select(TimelineEntry::as_select())
                     .first::<TimelineEntry>(conn)
                     .optional()
             })
@@ -444,40 +500,18 @@ impl SessionService {
     }
 
     pub async fn get_latest_spec(&self, session_id: String) -> Result<Option<String>> {
-        let entry = self
-            .get_timeline_entry("spec".to_string(), session_id.clone())
+        let spec = SpecService::from(self.repository.clone())?
+            .get_latest(session_id)
             .await?;
-        let Some(entry) = entry else {
-            return Ok(None);
-        };
-        match entry.content {
-            TimelineEntryContent::Spec { content } => {
-                debug!("Found spec content for session: {session_id}");
-                Ok(Some(content))
-            }
-            _ => {
-                panic!("Expected spec content type, got: {:?}", entry.content);
-            }
-        }
+        Ok(spec.map(|spec| spec.content))
     }
 
-    pub async fn get_latest_plan(&self, session_id: String) -> Result<Option<String>> {
-        let entry = self
-            .get_timeline_entry("plan".to_string(), session_id.clone())
-            .await?;
-        let Some(entry) = entry else {
-            return Ok(None);
-        };
-        match entry.content {
-            TimelineEntryContent::Plan { .. } => {
-                debug!("Found plan content for session: {session_id}");
-                Ok(Some("PLACEHOLDER".into())) // TODO: Implement real plan content retrieval
-            }
-            _ => {
-                panic!("Expected plan content type, got: {:?}", entry.content);
-            }
-        }
+    pub async fn get_latest_plan(&self, session_id: String) -> Result<Option<Plan>> {
+        PlanService::from(self.repository.clone())?
+            .get_latest(session_id)
+            .await
     }
+
     pub async fn get_shell_state(&self, session_id: String) -> Result<Option<Vec<String>>> {
         let entry = self
             .get_timeline_entry("shell".to_string(), session_id.clone())
@@ -490,22 +524,30 @@ impl SessionService {
                 debug!("Found shell state for session: {session_id}");
                 Ok(Some(lines))
             }
-            _ => {
-                panic!("Expected shell content type, got: {:?}", entry.content);
+            // The content type column and the content disagree, the entry
+            // is not usable as shell state
+            content => {
+                warn!(
+                    "Shell entry {} of session {session_id} has content {content:?}",
+                    entry.id
+                );
+                Ok(None)
             }
         }
     }
 
+    /// Returns the worker of a session together with the role of the user,
+    /// which decides whether they may interact or only follow along.
     pub async fn get_handler(
         &self,
         user: User,
         workspace_id: String,
         session_id: String,
-    ) -> Result<Arc<SessionWorker>> {
+    ) -> Result<(Arc<SessionWorker>, WorkspaceRole)> {
         let opt_session = self
-            .get_user_session_by_id(user, workspace_id.clone(), session_id.clone())
+            .get_session_with_role(user, workspace_id.clone(), session_id.clone())
             .await?;
-        let session = match opt_session {
+        let (session, role) = match opt_session {
             Some(session) => session,
             None => {
                 return Err(Error::NotFound);
@@ -520,6 +562,7 @@ impl SessionService {
                     session.clone(),
                     self.repository.clone(),
                     self.llm.clone(),
+                    self.web.clone(),
                 ));
                 registry.insert(session_id.clone(), new_handler.clone());
                 let runner_clone = new_handler.clone();
@@ -536,6 +579,6 @@ impl SessionService {
                 new_handler
             }
         };
-        Ok(handler)
+        Ok((handler, role))
     }
 }
@@ -0,0 +1,140 @@
+use crate::prelude::*;
+use mirabel_core::models::spec::Spec;
+
+use actix_web::web::Data;
+use chrono::Utc;
+use deadpool_diesel::postgres::Pool;
+use diesel::prelude::*;
+
+pub struct SpecService {
+    repository: Data<Pool>,
+}
+
+impl SpecService {
+    pub fn from(repository: Data<Pool>) -> Result<Self> {
+        Ok(Self { repository })
+    }
+
+    /// Stores `content` as the next version of the session spec, together with
+    /// the diff to the current version. Returns `None` when nothing changed.
+    pub async fn create_version(
+        &self,
+        session_id: String,
+        content: String,
+    ) -> Result<Option<Spec>> {
+        use mirabel_core::schema::specs::dsl as sp;
+
+        let conn = self.repository.get().await?;
+        conn.interact(move |conn| {
+            conn.transaction::<Option<Spec>, Error, _>(|t| {
+                let latest = sp::specs
+                    .filter(sp::session_id.eq(&session_id))
+                    .order(sp::version.desc())
+                    .first::<Spec>(t)
+                    .optional()?;
+                let spec = match latest {
+                    Some(latest) if latest.content == content => return Ok(None),
+                    Some(latest) => {
+                        let diff = diffy::create_patch(&latest.content, &content).to_string();
+                        Spec::new(session_id, latest.version + 1, content, Some(diff))
+                    }
+                    None => Spec::new(session_id, 1, content, None),
+                };
+                diesel::insert_into(sp::specs).values(&spec).execute(t)?;
+                Ok(Some(spec))
+            })
+        })
+        .await?
+    }
+
+    /// Marks a spec version as accepted, only the latest version of a session
+    /// can be accepted.
+    pub async fn accept(&self, spec_id: String) -> Result<Spec> {
+        use mirabel_core::schema::specs::dsl as sp;
+
+        let conn = self.repository.get().await?;
+        conn.interact(move |conn| {
+            conn.transaction::<Spec, Error, _>(|t| {
+                let spec = sp::specs
+                    .filter(sp::id.eq(&spec_id))
+                    .first::<Spec>(t)
+                    .optional()?
+                    .ok_or(Error::NotFound)?;
+                let latest_version = sp::specs
+                    .filter(sp::session_id.eq(&spec.session_id))
+                    .select(diesel::dsl::max(sp::version))
+                    .first::<Option<i32>>(t)?;
+                if latest_version != Some(spec.version) {
+                    return Err(Error::Conflict(format!(
+                        "Version {} of the spec has been revised since.",
+                        spec.version
+                    )));
+                }
+                Ok(diesel::update(sp::specs.filter(sp::id.eq(&spec_id)))
+                    .set(sp::accepted_at.eq(Some(Utc::now())))
+                    .get_result::<Spec>(t)?)
+            })
+        })
+        .await?
+    }
+
+    pub async fn get(&self, spec_id: String) -> Result<Option<Spec>> {
+        use mirabel_core::schema::specs::dsl as sp;
+
+        let conn = self.repository.get().await?;
+        Ok(conn
+            .interact(move |conn| {
+                sp::specs
+                    .filter(sp::id.eq(&spec_id))
+                    .first::<Spec>(conn)
+                    .optional()
+            })
+            .await??)
+    }
+
+    pub async fn get_latest(&self, session_id: String) -> Result<Option<Spec>> {
+        use mirabel_core::schema::specs::dsl as sp;
+
+        let conn = self.repository.get().await?;
+        Ok(conn
+            .interact(move |conn| {
+                sp::specs
+                    .filter(sp::session_id.eq(&session_id))
+                    .order(sp::version.desc())
+                    .first::<Spec>(conn)
+                    .optional()
+            })
+            .await??)
+    }
+
+    /// The most recently accepted spec, which is what planning works from
+    pub async fn get_accepted(&self, session_id: String) -> Result<Option<Spec>> {
+        use mirabel_core::schema::specs::dsl as sp;
+
+        let conn = self.repository.get().await?;
+        Ok(conn
+            .interact(move |conn| {
+                sp::specs
+                    .filter(sp::session_id.eq(&session_id))
+                    .filter(sp::accepted_at.is_not_null())
+                    .order(sp::version.desc())
+                    .first::<Spec>(conn)
+                    .optional()
+            })
+            .await??)
+    }
+
+    pub async fn get_versions(&self, session_id: String) -> Result<Vec<Spec>> {
+        use mirabel_core::schema::specs::dsl as sp;
+
+        let conn = self.repository.get().await?;
+        Ok(conn
+            .interact(move |conn| {
+                sp::specs
+                    .filter(sp::session_id.eq(&session_id))
+                    .order(sp::version.asc())
+                    .load::<Spec>(conn)
+            })
+            .await??)
+    }
+}
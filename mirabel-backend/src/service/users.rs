@@ -1,7 +1,10 @@
 use crate::prelude::*;
+use mirabel_core::dto::updated_user_settings::UpdatedUserSettings;
 use mirabel_core::models::user::User;
+use mirabel_core::models::user::UserSettings;
 
 use actix_web::web::Data;
+use chrono::Utc;
 use deadpool_diesel::postgres::Pool;
 use diesel::prelude::*;
 
@@ -28,4 +31,52 @@ impl UserService {
             })
             .await??)
     }
+
+    /// Settings of a user, falling back to the defaults when none are stored
+    pub async fn get_settings(&self, user_id: String) -> Result<UserSettings> {
+        use mirabel_core::schema::user_settings::dsl as us;
+
+        let conn = self.repository.get().await?;
+        let settings = conn
+            .interact({
+                let user_id = user_id.clone();
+                move |conn| {
+                    us::user_settings
+                        .filter(us::user_id.eq(user_id))
+                        .first::<UserSettings>(conn)
+                        .optional()
+                }
+            })
+            .await??;
+        Ok(settings.unwrap_or_else(|| UserSettings::new(user_id)))
+    }
+
+    pub async fn update_settings(
+        &self,
+        user_id: String,
+        updated: UpdatedUserSettings,
+    ) -> Result<UserSettings> {
+        use mirabel_core::schema::user_settings::dsl as us;
+
+        let mut settings = self.get_settings(user_id).await?;
+        if let Some(auto_approve_plans) = updated.auto_approve_plans {
+            settings.auto_approve_plans = auto_approve_plans;
+        }
+        settings.modified_at = Utc::now();
+
+        let conn = self.repository.get().await?;
+        Ok(conn
+            .interact(move |conn| {
+                diesel::insert_into(us::user_settings)
+                    .values(&settings)
+                    .on_conflict(us::user_id)
+                    .do_update()
+                    .set((
+                        us::auto_approve_plans.eq(settings.auto_approve_plans),
+                        us::modified_at.eq(settings.modified_at),
+                    ))
+                    .get_result::<UserSettings>(conn)
+            })
+            .await??)
+    }
 }
@@ -119,4 +119,25 @@ impl WorkspaceService {
 
         Ok(result.map(|w| w.into()))
     }
+
+    /// The role of the user in the workspace, `None` for non-members
+    pub async fn get_role(
+        &self,
+        user_id: String,
+        workspace_id: String,
+    ) -> Result<Option<WorkspaceRole>> {
+        use mirabel_core::schema::workspace_members::dsl as wm;
+
+        let conn = self.repository.get().await?;
+        Ok(conn
+            .interact(move |conn| {
+                wm::workspace_members
+                    .filter(wm::user_id.eq(&user_id))
+                    .filter(wm::workspace_id.eq(&workspace_id))
+                    .select(wm::role)
+                    .first::<WorkspaceRole>(conn)
+                    .optional()
+            })
+            .await??)
+    }
 }
@@ -0,0 +1,158 @@
+use std::collections::HashSet;
+use std::sync::LazyLock;
+
+use log::warn;
+use mirabel_core::dto::code_index::SymbolLocation;
+use mirabel_core::models::repository::Repository;
+use mirabel_core::models::repository::Worktree;
+use regex::Regex;
+
+use crate::prelude::*;
+
+use crate::service::code_index::CodeIndexService;
+use crate::service::repositories::RepositoryService;
+use crate::service::repositories::worktree_path;
+use crate::session::models::SessionWorker;
+
+/// Characters of repo map the planner gets, shared by all repositories
+pub const REPO_MAP_BUDGET: usize = 12_000;
+/// Names from the instructions that are looked up, and how many of the
+/// places the edited file is used at are listed
+const MAX_LOOKUPS: usize = 20;
+const MAX_USAGES: usize = 30;
+
+static IDENTIFIER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"[A-Za-z_][A-Za-z0-9_]{2,}").unwrap());
+
+impl SessionWorker {
+    /// An outline of the code of every repository the session has checked
+    /// out, `None` when there is none
+    pub(super) async fn repo_map(&self) -> Result<Option<String>> {
+        let checkouts = self.checkouts().await?;
+        if checkouts.is_empty() {
+            return Ok(None);
+        }
+        let index = CodeIndexService::from(self.pool.clone())?;
+        let budget = REPO_MAP_BUDGET / checkouts.len();
+        let mut map = String::new();
+        for (worktree, repository) in checkouts {
+            map.push_str(
+                &index
+                    .repo_map(worktree.id, format!("{}/", repository.name), budget)
+                    .await?,
+            );
+        }
+        Ok(Some(map).filter(|map| !map.is_empty()))
+    }
+
+    /// What the editor should know about the code around the file at `path`:
+    /// where the names the instructions mention are defined and where the
+    /// definitions of the file are used. `None` outside of repositories.
+    pub(super) async fn code_context(
+        &self,
+        path: &str,
+        instructions: &str,
+    ) -> Result<Option<String>> {
+        let checkouts = self.checkouts().await?;
+        let Some((worktree, repository, file)) = checkout_of(path, &checkouts) else {
+            return Ok(None);
+        };
+        let index = CodeIndexService::from(self.pool.clone())?;
+        let prefix = format!("{}/", repository.name);
+
+        let mut seen = HashSet::new();
+        let names: Vec<String> = IDENTIFIER
+            .find_iter(instructions)
+            .map(|name| name.as_str().to_string())
+            .filter(|name| seen.insert(name.clone()))
+            .take(MAX_LOOKUPS)
+            .collect();
+        let definitions: Vec<SymbolLocation> = index
+            .definitions(worktree.id.clone(), names)
+            .await?
+            .into_iter()
+            .filter(|location| location.path != file)
+            .collect();
+
+        let defined: Vec<String> = index
+            .definitions_in(worktree.id.clone(), file.clone())
+            .await?
+            .into_iter()
+            .map(|location| location.name)
+            .collect();
+        let usages: Vec<SymbolLocation> = index
+            .references(worktree.id.clone(), defined)
+            .await?
+            .into_iter()
+            .filter(|location| location.path != file)
+            .take(MAX_USAGES)
+            .collect();
+
+        let mut context = String::new();
+        if !definitions.is_empty() {
+            context.push_str("Definitions of names in the instructions:\n");
+            for location in &definitions {
+                let signature = location.signature.as_deref().unwrap_or(&location.name);
+                context.push_str(&format!(
+                    "  {prefix}{}:{}: {signature}\n",
+                    location.path, location.line
+                ));
+            }
+        }
+        if !usages.is_empty() {
+            context.push_str(&format!("Places that use definitions of {path}:\n"));
+            for location in &usages {
+                context.push_str(&format!(
+                    "  {prefix}{}:{}: {}\n",
+                    location.path, location.line, location.name
+                ));
+            }
+        }
+        Ok(Some(context).filter(|context| !context.is_empty()))
+    }
+
+    /// Updates the code index after the file at `path` changed. An outdated
+    /// index only makes agents less informed, so failures are just logged.
+    pub(super) async fn reindex(&self, path: &str) {
+        if let Err(err) = self.try_reindex(path).await {
+            warn!("Failed to update the code index for {path}: {err}");
+        }
+    }
+
+    async fn try_reindex(&self, path: &str) -> Result<()> {
+        let checkouts = self.checkouts().await?;
+        let Some((worktree, repository, file)) = checkout_of(path, &checkouts) else {
+            return Ok(());
+        };
+        let root = worktree_path(&worktree.session_id, repository)?;
+        CodeIndexService::from(self.pool.clone())?
+            .update(worktree.id.clone(), root, vec![file])
+            .await?;
+        Ok(())
+    }
+
+    async fn checkouts(&self) -> Result<Vec<(Worktree, Repository)>> {
+        let session_id = self.session.lock().await.id.clone();
+        RepositoryService::from(self.pool.clone())?
+            .get_active_worktrees(session_id)
+            .await
+    }
+}
+
+/// The checkout a path relative to the sandbox is in, with the path
+/// relative to the checkout
+fn checkout_of<'a>(
+    path: &str,
+    checkouts: &'a [(Worktree, Repository)],
+) -> Option<(&'a Worktree, &'a Repository, String)> {
+    let mut parts = path
+        .split('/')
+        .filter(|part| !part.is_empty() && *part != ".");
+    let name = parts.next()?;
+    let file = parts.collect::<Vec<_>>().join("/");
+    checkouts
+        .iter()
+        .find(|(_, repository)| repository.name == name)
+        .filter(|_| !file.is_empty())
+        .map(|(worktree, repository)| (worktree, repository, file))
+}
@@ -0,0 +1,136 @@
+use std::sync::Arc;
+
+use log::warn;
+use mirabel_core::models::file_action::FileAction;
+use mirabel_core::models::timeline::TimelineEntry;
+
+use crate::prelude::*;
+
+use crate::agent::editor;
+use crate::driver::editor::FileChange;
+use crate::driver::editor::FileEdit;
+use crate::driver::editor::FileEditor;
+use crate::driver::llm::Llm;
+use crate::service::file_actions::FileActionService;
+use crate::session::models::SessionWorker;
+
+impl SessionWorker {
+    /// Has the editor agent change the file at `path` and records the change,
+    /// returns what was done
+    pub(super) async fn edit_file(&self, path: &str, instructions: &str) -> Result<String> {
+        let editor = self.editor().await?;
+        let file = path.to_string();
+        let base = blocking(&editor, move |editor| editor.read(&file)).await?;
+        let related = self
+            .code_context(path, instructions)
+            .await
+            .inspect_err(|err| warn!("No code context for {path}: {err}"))
+            .ok()
+            .flatten();
+        let memories = self
+            .recall(instructions)
+            .await
+            .inspect_err(|err| warn!("No memories for {path}: {err}"))
+            .ok()
+            .flatten();
+        let llm: Arc<dyn Llm> = self.llm.clone().into_inner();
+        let operation = editor::edit(
+            llm,
+            path,
+            base.as_deref(),
+            instructions,
+            related.as_deref(),
+            memories.as_deref(),
+        )
+        .await?
+        .response;
+        let actions = FileActionService::from(self.pool.clone())?;
+        let edit = FileEdit {
+            path: path.to_string(),
+            operation,
+            base,
+        };
+        let change = blocking(&editor, move |editor| editor.apply(&edit)).await?;
+        let session_id = self.session.lock().await.id.clone();
+        let action = file_action(session_id, &change, None);
+        let action = match actions.create(action.clone()).await {
+            Ok(action) => action,
+            Err(err) => {
+                // Without a record the change could never be reverted
+                let path = action.path.clone();
+                if let Err(undo) = blocking(&editor, move |editor| editor.revert(&action)).await {
+                    warn!("Failed to undo the edit of {path}: {undo}");
+                }
+                return Err(err);
+            }
+        };
+        self.reindex(&action.path).await;
+        self.broadcast_save(TimelineEntry::file_action(&action))
+            .await?;
+        Ok(action.summary())
+    }
+
+    /// Undoes a file action of this session. Fails with a conflict when the
+    /// lines it changed were changed again since.
+    pub async fn revert_action(&self, action_id: String) -> Result<FileAction> {
+        let actions = FileActionService::from(self.pool.clone())?;
+        let session_id = self.session.lock().await.id.clone();
+        let action = actions
+            .get(action_id)
+            .await?
+            .filter(|action| action.session_id == session_id)
+            .ok_or(Error::NotFound)?;
+        if action.is_reverted() {
+            return Err(Error::Conflict(
+                "The action has already been reverted.".into(),
+            ));
+        }
+
+        let editor = self.editor().await?;
+        let reverted = action.clone();
+        let change = blocking(&editor, move |editor| editor.revert(&reverted)).await?;
+        let revert = file_action(session_id, &change, Some(action.id));
+        let revert = match actions.create_revert(revert.clone()).await {
+            Ok(revert) => revert,
+            Err(err) => {
+                // Without a record the file has to be as it was
+                let path = revert.path.clone();
+                if let Err(undo) = blocking(&editor, move |editor| editor.revert(&revert)).await {
+                    warn!("Failed to undo the revert of {path}: {undo}");
+                }
+                return Err(err);
+            }
+        };
+        self.reindex(&revert.path).await;
+        self.broadcast_save(TimelineEntry::file_action(&revert))
+            .await?;
+        Ok(revert)
+    }
+
+    async fn editor(&self) -> Result<Arc<FileEditor>> {
+        self.tools.lock().await.editor.clone().ok_or(Error::Generic(
+            "There is no editor available in this session.".into(),
+        ))
+    }
+}
+
+/// Runs file work of the editor off the async threads
+async fn blocking<T, F>(editor: &Arc<FileEditor>, work: F) -> Result<T>
+where
+    F: FnOnce(&FileEditor) -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    let editor = editor.clone();
+    tokio::task::spawn_blocking(move || work(&editor)).await?
+}
+
+fn file_action(session_id: String, change: &FileChange, reverts: Option<String>) -> FileAction {
+    FileAction::new(
+        session_id,
+        change.path.clone(),
+        change.before.clone(),
+        change.after.clone(),
+        change.diff(),
+        reverts,
+    )
+}
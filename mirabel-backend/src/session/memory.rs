@@ -0,0 +1,58 @@
+use log::debug;
+use log::warn;
+
+use crate::prelude::*;
+
+use crate::service::memories::MemoryService;
+use crate::session::models::SessionWorker;
+
+/// Memories the agents get along with their task
+const RECALL_LIMIT: usize = 10;
+
+impl SessionWorker {
+    /// What the workspace remembers about `query` as a list for prompts,
+    /// `None` when nothing relevant is remembered
+    pub(super) async fn recall(&self, query: &str) -> Result<Option<String>> {
+        let workspace_id = self.session.lock().await.workspace_id.clone();
+        let memories = MemoryService::from(self.pool.clone(), self.llm.clone())?
+            .recall(workspace_id, query.to_string(), RECALL_LIMIT)
+            .await?;
+        if memories.is_empty() {
+            return Ok(None);
+        }
+        let list = memories
+            .iter()
+            .map(|memory| {
+                let kind = serde_json::to_value(memory.kind)?;
+                Ok(format!(
+                    "- [{}] {}",
+                    kind.as_str().unwrap_or_default(),
+                    memory.content
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Some(list.join("\n")))
+    }
+
+    /// Learns from a message of a user in the background, the session does
+    /// not wait for it and learning failures are only logged
+    pub(super) async fn learn(&self, entry_id: String, message: String) {
+        let (workspace_id, session_id) = {
+            let session = self.session.lock().await;
+            (session.workspace_id.clone(), session.id.clone())
+        };
+        let pool = self.pool.clone();
+        let llm = self.llm.clone();
+        actix_web::rt::spawn(async move {
+            let learned = async {
+                MemoryService::from(pool, llm)?
+                    .learn(workspace_id, session_id, entry_id.clone(), message)
+                    .await
+            };
+            match learned.await {
+                Ok(learned) => debug!("Learned {} memories from {entry_id}", learned.len()),
+                Err(err) => warn!("Could not learn from {entry_id}: {err}"),
+            }
+        });
+    }
+}
@@ -1,7 +1,14 @@
+use mirabel_core::models::plan::Plan;
+use mirabel_core::models::plan::PlanAction;
+use mirabel_core::models::plan::PlanStatus;
 use mirabel_core::models::session::Session;
+use mirabel_core::models::spec::Spec;
 use mirabel_core::models::timeline::AcknowledgmentType;
 use mirabel_core::models::timeline::AgentStatus;
+use mirabel_core::models::timeline::MessageSender;
+use mirabel_core::models::timeline::PromptAnswer;
 use mirabel_core::models::timeline::TimelineEntry;
+use mirabel_core::models::timeline::TimelineEntryContent;
 use std::collections::HashMap;
 use std::collections::VecDeque;
 use std::sync::Arc;
@@ -9,40 +16,83 @@ use std::time::Duration;
 use tokio::sync::Mutex;
 use tokio::sync::mpsc::UnboundedSender;
 use tokio::sync::mpsc::unbounded_channel;
+use tokio::sync::oneshot;
 
 use crate::prelude::*;
 use mirabel_core::id;
 
+use crate::agent::planner;
+use crate::agent::spec_creator;
+use crate::agent::spec_creator::SpecStep;
+use crate::driver::llm::Llm;
 use crate::driver::llm::ollama::Ollama;
+use crate::driver::research::WebResearch;
+use crate::service::plans::PlanService;
+use crate::service::specs::SpecService;
+use crate::service::users::UserService;
+use crate::session::models::Interaction;
 use crate::session::models::Interupt;
+use crate::session::models::LastSeen;
+use crate::session::models::Participant;
+use crate::session::models::PendingPrompt;
+use crate::session::models::Question;
 use crate::session::models::Queueable;
+use crate::session::models::Subscriber;
 use crate::session::models::UserInteraction;
+use crate::session::shell::ShellScreen;
+use crate::session::tools::Toolbox;
 
 use actix_web::web::Data;
 use deadpool_diesel::postgres::Pool;
-use diesel::RunQueryDsl;
+use diesel::prelude::*;
 use log::warn;
+use mirabel_core::dto::session::event::ErrorCode;
+use mirabel_core::dto::session::event::Presence;
+use mirabel_core::dto::session::event::PresenceStatus;
+use models::ServerEvent;
 use models::SessionWorker;
 use models::SessionWorkerState;
+use models::ShellDriver;
 use models::WorkerEvent;
-use tokio::time::sleep;
 
+pub mod code;
+mod edits;
+mod memory;
 pub mod models;
+mod orchestrator;
+mod research;
+pub mod shell;
+pub mod tools;
+
+const SPEC_APPROVAL_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+const CONVERSATION_LIMIT: i64 = 50;
 
 impl SessionWorker {
-    pub fn new(session: Session, pool: Data<Pool>, llm: Data<Ollama>) -> Self {
+    pub fn new(
+        session: Session,
+        pool: Data<Pool>,
+        llm: Data<Ollama>,
+        web: Data<WebResearch>,
+    ) -> Self {
         let (event_sender, event_receiver) = unbounded_channel::<WorkerEvent>();
+        let tools = Toolbox::for_session(&session.id);
         Self {
             session: Arc::new(Mutex::new(session)),
             pool,
             llm,
+            web,
             receiver: Arc::new(Mutex::new(event_receiver)),
             sender: event_sender,
             subscribers: Arc::new(Mutex::new(HashMap::new())),
+            prompts: Arc::new(Mutex::new(HashMap::new())),
             state: Arc::new(Mutex::new(SessionWorkerState::Stopped)),
             is_processing: Arc::new(Mutex::new(false)),
             queue: Arc::new(Mutex::new(VecDeque::new())),
             interupts: Arc::new(Mutex::new(VecDeque::new())),
+            tools: Arc::new(Mutex::new(tools)),
+            shell: Arc::new(Mutex::new(None)),
+            shell_driver: Arc::new(Mutex::new(ShellDriver::default())),
+            screen: Arc::new(Mutex::new(ShellScreen::default())),
         }
     }
 
@@ -78,15 +128,16 @@ impl SessionWorker {
         loop {
             // Check for interrupts first
             if let Err(e) = self.clone().handle_pending_interrupts().await {
-                *self.state.lock().await = SessionWorkerState::Error(e.to_string().as_str().into());
+                self.set_state(SessionWorkerState::Error(e.to_string().as_str().into()))
+                    .await;
                 break;
             }
 
             // Process next work item
             if let Some(work_item) = self.clone().queue.lock().await.pop_front() {
                 if let Err(err) = self.clone().execute_work_item(work_item).await {
-                    *self.state.lock().await =
-                        SessionWorkerState::Error(err.to_string().as_str().into());
+                    self.set_state(SessionWorkerState::Error(err.to_string().as_str().into()))
+                        .await;
                 }
             } else {
                 tokio::time::sleep(Duration::from_millis(100)).await;
@@ -96,12 +147,16 @@ impl SessionWorker {
 
     async fn execute_work_item(self: Arc<Self>, work_item: Queueable) -> Result<()> {
         match work_item {
+            Queueable::Interupt(Interupt::AgentInterrupt(interrupt)) => {
+                self.handle_agent_interrupt(interrupt).await?;
+            }
             Queueable::Interupt(interrupt) => {
                 // self.handle_interrupt(interrupt).await?;
             }
             Queueable::UserInteraction(interaction) => {
                 // self.handle_event(event).await?;
             }
+            Queueable::ExecutePlan(plan_id) => self.execute_plan(plan_id).await?,
         }
         Ok(())
     }
@@ -131,36 +186,218 @@ impl SessionWorker {
 
     pub async fn subscribe(
         &self,
-        sender: UnboundedSender<TimelineEntry>,
+        sender: UnboundedSender<ServerEvent>,
+        participant: Participant,
     ) -> Result<(String, UnboundedSender<WorkerEvent>)> {
         let id = id!();
         let mut subscribers = self.subscribers.lock().await;
         if subscribers.contains_key(&id) {
             return Err(Error::DoubleSubscription);
         }
-        subscribers.insert(id.clone(), sender);
+        // A new subscriber starts out knowing the current state and who else
+        // is already here
+        let state = self.state.lock().await.clone();
+        let mut present: Vec<Participant> = Vec::new();
+        for subscriber in subscribers.values() {
+            if !present.contains(&subscriber.participant) {
+                present.push(subscriber.participant.clone());
+            }
+        }
+        let driver = *self.shell_driver.lock().await;
+        let mut greeting = vec![
+            ServerEvent::StateChanged(state),
+            ServerEvent::ShellDriver(driver),
+        ];
+        greeting.extend(
+            present
+                .iter()
+                .filter(|other| **other != participant)
+                .map(|other| presence(other, PresenceStatus::Joined)),
+        );
+        for event in greeting {
+            if sender.send(event).is_err() {
+                return Err(Error::SocketClosed);
+            }
+        }
+        let joined = !present.contains(&participant);
+        subscribers.insert(
+            id.clone(),
+            Subscriber {
+                participant: participant.clone(),
+                sender,
+            },
+        );
+        drop(subscribers);
+
+        if joined {
+            self.broadcast(presence(&participant, PresenceStatus::Joined))
+                .await;
+        }
         Ok((id, self.sender.clone()))
     }
 
-    async fn handle_event(&self, event: WorkerEvent) -> Result<()> {
+    async fn unsubscribe(&self, id: &str) {
+        let mut subscribers = self.subscribers.lock().await;
+        let Some(removed) = subscribers.remove(id) else {
+            warn!("Tried to unsubscribe non-existing subscriber: {id}");
+            return;
+        };
+        let left = !subscribers
+            .values()
+            .any(|subscriber| subscriber.participant == removed.participant);
+        drop(subscribers);
+
+        if left {
+            self.broadcast(presence(&removed.participant, PresenceStatus::Left))
+                .await;
+        }
+    }
+
+    /// Subscribes and returns every entry written after `last_seen`.
+    ///
+    /// The subscription is registered before the timeline is queried, so an
+    /// entry is either part of the replay, delivered live, or both. Callers
+    /// should skip live entries whose id was already replayed.
+    pub async fn subscribe_from(
+        &self,
+        sender: UnboundedSender<ServerEvent>,
+        participant: Participant,
+        last_seen: Option<LastSeen>,
+    ) -> Result<(String, UnboundedSender<WorkerEvent>, Vec<TimelineEntry>)> {
+        let (id, worker_sender) = self.subscribe(sender, participant).await?;
+        let Some(last_seen) = last_seen else {
+            return Ok((id, worker_sender, Vec::new()));
+        };
+        match self.missed_entries(last_seen).await {
+            Ok(missed) => Ok((id, worker_sender, missed)),
+            Err(e) => {
+                self.unsubscribe(&id).await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn missed_entries(&self, last_seen: LastSeen) -> Result<Vec<TimelineEntry>> {
+        use mirabel_core::schema::timeline_entries::dsl as te;
+
+        let session_id = self.session.lock().await.id.clone();
+        let conn = self.pool.get().await?;
+        let entries = conn
+            .interact(move |conn| {
+                let mut query = te::timeline_entries
+                    .filter(te::session_id.eq(&session_id))
+                    .into_boxed();
+                // Entries are numbered as they are stored, timestamps may
+                // be shared and are only compared for clients without one
+                query = match last_seen {
+                    LastSeen::Entry(entry_id) => {
+                        let seq = te::timeline_entries
+                            .filter(te::session_id.eq(&session_id))
+                            .filter(te::id.eq(&entry_id))
+                            .select(te::seq)
+                            .first::<i64>(conn)
+                            .optional()?;
+                        let Some(seq) = seq else {
+                            return Ok(None);
+                        };
+                        query.filter(te::seq.gt(seq))
+                    }
+                    LastSeen::Timestamp(timestamp) => query.filter(te::created_at.gt(timestamp)),
+                };
+                query
+                    .order(te::seq.asc())
+                    .select(TimelineEntry::as_select())
+                    .load::<TimelineEntry>(conn)
+                    .map(Some)
+            })
+            .await??;
+        entries.ok_or(Error::BadRequest(
+            "The last seen timeline entry does not exist in this session.".into(),
+        ))
+    }
+
+    async fn handle_event(self: &Arc<Self>, event: WorkerEvent) -> Result<()> {
         match event {
             WorkerEvent::UserInteraction(event) => {
-                self.handle_user_interaction(event).await?;
+                let Interaction {
+                    user_id,
+                    subscriber_id,
+                    correlation_id,
+                    interaction,
+                } = event;
+                if let Err(err) = self.handle_user_interaction(user_id, interaction).await {
+                    if let Some(subscriber_id) = subscriber_id {
+                        let code = match err {
+                            Error::BadRequest(_)
+                            | Error::MirabelCore(mirabel_core::Error::BadRequest(_)) => {
+                                ErrorCode::BadRequest
+                            }
+                            _ => ErrorCode::Internal,
+                        };
+                        self.send_to(
+                            &subscriber_id,
+                            ServerEvent::error(code, err.to_string(), correlation_id),
+                        )
+                        .await;
+                    }
+                    return Err(err);
+                }
             }
-            WorkerEvent::Unsubscribe(id) => {
-                let sub = self.subscribers.lock().await.remove(&id);
-                if sub.is_none() {
-                    warn!("Tried to unsubscribe non-existing subscriber: {id}");
+            WorkerEvent::Typing(subscriber_id) => {
+                let participant = self
+                    .subscribers
+                    .lock()
+                    .await
+                    .get(&subscriber_id)
+                    .map(|subscriber| subscriber.participant.clone());
+                if let Some(participant) = participant {
+                    self.broadcast(presence(&participant, PresenceStatus::Typing))
+                        .await;
                 }
             }
+            WorkerEvent::Unsubscribe(id) => self.unsubscribe(&id).await,
+            WorkerEvent::Shell {
+                subscriber_id,
+                correlation_id,
+                input,
+            } => {
+                if let Err(err) = self.shell_input(input).await {
+                    let code = match err {
+                        Error::Forbidden(_) => ErrorCode::Forbidden,
+                        _ => ErrorCode::Internal,
+                    };
+                    self.send_to(
+                        &subscriber_id,
+                        ServerEvent::error(code, err.to_string(), correlation_id),
+                    )
+                    .await;
+                    return Err(err);
+                }
+            }
+            WorkerEvent::RevisePlan { spec_id, feedback } => {
+                let spec = SpecService::from(self.pool.clone())?
+                    .get(spec_id)
+                    .await?
+                    .ok_or(Error::NotFound)?;
+                self.create_plan(spec, feedback).await?;
+            }
         };
         Ok(())
     }
 
-    async fn handle_user_interaction(&self, interaction: UserInteraction) -> Result<()> {
-        let mut queue = self.queue.lock().await;
-        if queue.is_empty() {
-            queue.push_back(Queueable::UserInteraction(interaction.clone()));
+    async fn handle_user_interaction(
+        &self,
+        user_id: String,
+        interaction: UserInteraction,
+    ) -> Result<()> {
+        // The queue lock must not be held while handling, answering a prompt
+        // has to get through while another interaction waits for it
+        let idle = self.queue.lock().await.is_empty();
+        if idle {
+            self.queue
+                .lock()
+                .await
+                .push_back(Queueable::UserInteraction(interaction.clone()));
         } else {
             self.interupts
                 .lock()
@@ -169,86 +406,462 @@ impl SessionWorker {
         }
         match interaction {
             UserInteraction::Message { content } => {
-                self.broadcast_save(TimelineEntry::user_message(
+                let entry = TimelineEntry::user_message(
                     self.session.lock().await.id.clone(),
+                    user_id,
                     content.clone(),
-                ))
-                .await?;
+                );
+                let entry_id = entry.id.clone();
+                self.broadcast_save(entry).await?;
+                self.learn(entry_id, content.clone()).await;
                 self.handle_message_content(content).await?;
             }
-            UserInteraction::PromptResponse {
-                prompt_id,
-                response,
-            } => {
-                self.broadcast_save(TimelineEntry::prompt_response(
-                    self.session.lock().await.id.clone(),
-                    prompt_id,
-                    response,
-                ))
-                .await?
+            UserInteraction::PromptResponse { prompt_id, answer } => {
+                self.answer_prompt(user_id, prompt_id, answer).await?
+            }
+            UserInteraction::Plan { plan_id, action } => {
+                self.plan_action(plan_id, action).await?;
             }
         }
         Ok(())
     }
 
-    async fn handle_message_content(&self, message: String) -> Result<()> {
-        let session_id = self.session.lock().await.id.clone();
-        sleep(Duration::from_secs(1)).await;
-        self.broadcast_save(TimelineEntry::acknowledgment(
-            session_id.clone(),
-            AcknowledgmentType::Delivered,
+    /// Asks the users a question and waits until one of them answers it, or
+    /// `timeout` passes. Only answers that fit the question are accepted.
+    pub async fn ask(&self, question: Question, timeout: Duration) -> Result<PromptAnswer> {
+        let prompt_id = id!();
+        let (responder, answer) = oneshot::channel();
+        // Registered before the prompt goes out, so a quick answer finds it
+        self.prompts.lock().await.insert(
+            prompt_id.clone(),
+            PendingPrompt {
+                kind: question.kind,
+                options: question.options.clone(),
+                responder,
+            },
+        );
+        let entry = TimelineEntry::prompt(
+            self.session.lock().await.id.clone(),
+            prompt_id.clone(),
+            question.question,
+            question.kind,
+            question.options,
+        );
+        if let Err(err) = self.broadcast_save(entry).await {
+            self.prompts.lock().await.remove(&prompt_id);
+            return Err(err);
+        }
+
+        let answer = tokio::time::timeout(timeout, answer).await;
+        self.prompts.lock().await.remove(&prompt_id);
+        match answer {
+            Ok(Ok(answer)) => Ok(answer),
+            Ok(Err(_)) => Err(Error::Generic(format!(
+                "Prompt {prompt_id} was dropped without an answer"
+            ))),
+            Err(_) => Err(Error::PromptTimeout(prompt_id)),
+        }
+    }
+
+    async fn answer_prompt(
+        &self,
+        user_id: String,
+        prompt_id: String,
+        answer: PromptAnswer,
+    ) -> Result<()> {
+        let mut prompts = self.prompts.lock().await;
+        let Some(pending) = prompts.get(&prompt_id) else {
+            return Err(Error::BadRequest(
+                "There is no open prompt with this id.".into(),
+            ));
+        };
+        answer
+            .validate(pending.kind, &pending.options)
+            .map_err(|e| Error::BadRequest(e.to_string()))?;
+        let Some(pending) = prompts.remove(&prompt_id) else {
+            return Ok(());
+        };
+        drop(prompts);
+
+        self.broadcast_save(TimelineEntry::prompt_response(
+            self.session.lock().await.id.clone(),
+            prompt_id,
+            user_id,
+            answer.clone(),
         ))
         .await?;
-        sleep(Duration::from_secs(1)).await;
+        // The asking task may have given up in the meantime
+        let _ = pending.responder.send(answer);
+        Ok(())
+    }
+
+    async fn handle_message_content(&self, _message: String) -> Result<()> {
+        let session_id = self.session.lock().await.id.clone();
         self.broadcast_save(TimelineEntry::acknowledgment(
             session_id.clone(),
-            AcknowledgmentType::Seen,
+            AcknowledgmentType::Delivered,
         ))
         .await?;
-        sleep(Duration::from_secs(1)).await;
         self.broadcast_save(TimelineEntry::status(
             session_id.clone(),
             AgentStatus::Thinking,
         ))
         .await?;
-        sleep(Duration::from_secs(2)).await;
-        self.broadcast_save(TimelineEntry::status(
-            session_id.clone(),
-            AgentStatus::Typing,
+        if let Err(err) = self.create_spec(session_id.clone()).await {
+            self.broadcast_save(TimelineEntry::status(session_id, AgentStatus::Error))
+                .await?;
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    /// One round of the Spec Creator loop: either ask the user a question or
+    /// draft the next version of the spec and ask for its approval.
+    async fn create_spec(&self, session_id: String) -> Result<()> {
+        let specs = SpecService::from(self.pool.clone())?;
+        let current = specs.get_latest(session_id.clone()).await?;
+        let messages = self.conversation(session_id.clone()).await?;
+        let llm: Arc<dyn Llm> = self.llm.clone().into_inner();
+        let step = spec_creator::spec_step(llm, current.map(|spec| spec.content), messages)
+            .await?
+            .response;
+
+        let spec = match step {
+            SpecStep::Question(question) => {
+                return self
+                    .broadcast_save(TimelineEntry::agent_message(session_id, question))
+                    .await;
+            }
+            SpecStep::Draft(content) => {
+                match specs.create_version(session_id.clone(), content).await? {
+                    Some(spec) => spec,
+                    None => {
+                        return self
+                            .broadcast_save(TimelineEntry::agent_message(
+                                session_id,
+                                "The spec already covers this, nothing changed.".into(),
+                            ))
+                            .await;
+                    }
+                }
+            }
+        };
+        self.broadcast_save(TimelineEntry::spec(&spec)).await?;
+
+        let question = Question::confirm(format!(
+            "Do you accept version {} of the spec?",
+            spec.version
+        ));
+        let answer = match self.ask(question, SPEC_APPROVAL_TIMEOUT).await {
+            Ok(answer) => answer,
+            // Unanswered drafts stay open for revision
+            Err(Error::PromptTimeout(_)) => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        let spec = match answer {
+            PromptAnswer::Confirm(true) => match specs.accept(spec.id).await {
+                Ok(spec) => spec,
+                // A newer draft replaced this one while waiting for the answer
+                Err(Error::Conflict(_)) => return Ok(()),
+                Err(err) => return Err(err),
+            },
+            _ => {
+                return self
+                    .broadcast_save(TimelineEntry::agent_message(
+                        session_id,
+                        "What should be changed?".into(),
+                    ))
+                    .await;
+            }
+        };
+        self.broadcast_save(TimelineEntry::agent_message(
+            session_id,
+            format!("Version {} of the spec is accepted.", spec.version),
         ))
         .await?;
-        sleep(Duration::from_secs(3)).await;
-        self.broadcast_save(TimelineEntry::agent_message(
-            self.session.lock().await.id.clone(),
-            format!("Echo: {message}"),
+        self.create_plan(spec, None).await
+    }
+
+    /// Drafts a plan for an accepted spec. It is approved right away when the
+    /// session creator opted into that, otherwise it waits for a user.
+    async fn create_plan(&self, spec: Spec, feedback: Option<String>) -> Result<()> {
+        let (session_id, creator_id) = {
+            let session = self.session.lock().await;
+            (session.id.clone(), session.user_id.clone())
+        };
+        self.broadcast_save(TimelineEntry::status(
+            session_id.clone(),
+            AgentStatus::Thinking,
         ))
         .await?;
-        Ok(())
+        // Plans can be drafted without knowing the code, just not as well
+        let repo_map = self
+            .repo_map()
+            .await
+            .inspect_err(|err| warn!("No repo map for the plan: {err}"))
+            .ok()
+            .flatten();
+        let memories = self
+            .recall(&spec.content)
+            .await
+            .inspect_err(|err| warn!("No memories for the plan: {err}"))
+            .ok()
+            .flatten();
+        let llm: Arc<dyn Llm> = self.llm.clone().into_inner();
+        let root = planner::plan(llm, spec.content, feedback, repo_map, memories)
+            .await?
+            .response;
+
+        let plans = PlanService::from(self.pool.clone())?;
+        let mut plan = plans
+            .create(Plan::new(session_id.clone(), spec.id, root))
+            .await?;
+        self.broadcast_save(TimelineEntry::plan(&plan)).await?;
+
+        let settings = UserService::from(self.pool.clone())?
+            .get_settings(creator_id)
+            .await?;
+        let reply = if settings.auto_approve_plans {
+            plan.approve()?;
+            plan = plans.update(plan).await?;
+            self.broadcast_save(TimelineEntry::plan(&plan)).await?;
+            self.queue
+                .lock()
+                .await
+                .push_back(Queueable::ExecutePlan(plan.id.clone()));
+            "The plan was approved automatically."
+        } else {
+            "Please review the plan, then approve, edit or reject it."
+        };
+        self.broadcast_save(TimelineEntry::agent_message(session_id, reply.into()))
+            .await
+    }
+
+    /// Approves, rejects or edits a draft plan of this session. A rejected
+    /// plan is replaced by a new draft that takes the reason into account.
+    pub async fn plan_action(&self, plan_id: String, action: PlanAction) -> Result<Plan> {
+        let session_id = self.session.lock().await.id.clone();
+        let plans = PlanService::from(self.pool.clone())?;
+        let mut plan = plans
+            .get(plan_id)
+            .await?
+            .filter(|plan| plan.session_id == session_id)
+            .ok_or(Error::NotFound)?;
+        let revision = match action {
+            PlanAction::Approve => {
+                plan.approve()?;
+                None
+            }
+            PlanAction::Reject { reason } => {
+                plan.reject()?;
+                Some(reason)
+            }
+            PlanAction::Edit { edit } => {
+                plan.edit(edit)?;
+                None
+            }
+        };
+        let plan = plans.update(plan).await?;
+        self.broadcast_save(TimelineEntry::plan(&plan)).await?;
+        if plan.status == PlanStatus::Approved {
+            self.queue
+                .lock()
+                .await
+                .push_back(Queueable::ExecutePlan(plan.id.clone()));
+        }
+        // Queued rather than awaited, drafting takes a while and the caller
+        // only waits for the rejection
+        if let Some(feedback) = revision {
+            self.sender
+                .send(WorkerEvent::RevisePlan {
+                    spec_id: plan.spec_id.clone(),
+                    feedback,
+                })
+                .map_err(|_| Error::InternalServer)?;
+        }
+        Ok(plan)
+    }
+
+    /// The latest messages of the session in chronological order, as
+    /// (author, message) pairs for agent prompts.
+    async fn conversation(&self, session_id: String) -> Result<Vec<(String, String)>> {
+        use mirabel_core::schema::timeline_entries::dsl as te;
+
+        let entries = self
+            .pool
+            .get()
+            .await?
+            .interact(move |conn| {
+                te::timeline_entries
+                    .filter(te::session_id.eq(&session_id))
+                    .filter(te::content_type.eq("message"))
+                    .order(te::created_at.desc())
+                    .limit(CONVERSATION_LIMIT)
+                    .select(TimelineEntry::as_select())
+                    .load::<TimelineEntry>(conn)
+            })
+            .await??;
+        Ok(entries
+            .into_iter()
+            .rev()
+            .filter_map(|entry| match entry.content {
+                TimelineEntryContent::Message {
+                    sender, message, ..
+                } => {
+                    let author = match sender {
+                        MessageSender::User => "User",
+                        MessageSender::Agent => "Mirabel",
+                    };
+                    Some((author.to_string(), message))
+                }
+                _ => None,
+            })
+            .collect())
     }
 
     pub async fn broadcast_save(&self, event: TimelineEntry) -> Result<()> {
         use mirabel_core::schema::timeline_entries::dsl as te;
-        self.broadcast(&event).await?;
+        // Persist before broadcasting, a resuming subscriber relies on every
+        // entry it did not receive live being queryable
+        let entry = event.clone();
         self.pool
             .get()
             .await?
             .interact(|conn| {
                 diesel::insert_into(te::timeline_entries)
-                    .values(event)
+                    .values(entry)
                     .execute(conn)
             })
             .await??;
+        self.broadcast(ServerEvent::TimelineEntry(event)).await;
         Ok(())
     }
 
-    pub async fn broadcast(&self, event: &TimelineEntry) -> Result<()> {
+    /// Sends an event to every subscriber. Subscribers that stopped receiving
+    /// are left alone here, their transport unsubscribes them on close.
+    pub async fn broadcast(&self, event: ServerEvent) {
+        for subscriber in self.subscribers.lock().await.values() {
+            let _ = subscriber.sender.send(event.clone());
+        }
+    }
+
+    pub async fn send_to(&self, subscriber_id: &str, event: ServerEvent) {
         let subscribers = self.subscribers.lock().await;
-        for (id, subscriber) in subscribers.iter() {
-            if let Err(e) = subscriber.send(event.clone()) {
-                log::warn!("Failed to send event to subscriber: {e}");
-                self.subscribers.lock().await.remove(id);
+        let Some(subscriber) = subscribers.get(subscriber_id) else {
+            warn!("Tried to send an event to non-existing subscriber: {subscriber_id}");
+            return;
+        };
+        let _ = subscriber.sender.send(event);
+    }
+
+    pub async fn set_state(&self, state: SessionWorkerState) {
+        *self.state.lock().await = state.clone();
+        self.broadcast(ServerEvent::StateChanged(state)).await;
+    }
+}
+
+fn presence(participant: &Participant, status: PresenceStatus) -> ServerEvent {
+    ServerEvent::Presence(Presence {
+        user_id: participant.user_id.clone(),
+        username: participant.username.clone(),
+        status,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use deadpool_diesel::postgres::Manager;
+    use deadpool_diesel::postgres::Runtime;
+    use tokio::sync::mpsc::UnboundedReceiver;
+
+    use crate::driver::browser::BrowserConfig;
+    use crate::driver::browser::Browsers;
+    use crate::driver::policy::FetchConfig;
+    use crate::driver::policy::FetchPolicy;
+    use crate::driver::research::cache::CacheTtls;
+    use crate::driver::research::cache::WebCache;
+    use crate::driver::search::SearchEngines;
+    use crate::driver::search::SearchMode;
+    use crate::driver::search::local::LocalIndex;
+
+    /// A worker whose database, model and browsers are never reached
+    pub(super) fn worker() -> Arc<SessionWorker> {
+        let manager = Manager::new("postgres://localhost/unused", Runtime::Tokio1);
+        let pool = Pool::builder(manager).build().unwrap();
+        let web = WebResearch::new(
+            SearchEngines::new(Vec::new(), SearchMode::First),
+            Browsers::new(BrowserConfig::default()).unwrap(),
+            FetchPolicy::new(pool.clone(), FetchConfig::default()).unwrap(),
+            LocalIndex::new(pool.clone()),
+            WebCache::new(pool.clone(), CacheTtls::default()),
+        );
+        let session = Session::new("workspace".into(), "alice".into(), "Session".into());
+        Arc::new(SessionWorker::new(
+            session,
+            Data::new(pool),
+            Data::new(Ollama::default()),
+            Data::new(web),
+        ))
+    }
+
+    fn participant(name: &str) -> Participant {
+        Participant {
+            user_id: name.into(),
+            username: name.into(),
+        }
+    }
+
+    /// The presence events received so far
+    fn presences(receiver: &mut UnboundedReceiver<ServerEvent>) -> Vec<(String, PresenceStatus)> {
+        let mut presences = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            if let ServerEvent::Presence(presence) = event {
+                presences.push((presence.user_id, presence.status));
             }
         }
-        Ok(())
+        presences
+    }
+
+    #[tokio::test]
+    async fn test_presence() {
+        let worker = worker();
+        let (alice, bob) = (participant("alice"), participant("bob"));
+        let joined = |name: &str| (name.to_string(), PresenceStatus::Joined);
+
+        let (sender, mut first_tab) = unbounded_channel();
+        let (first_id, _) = worker.subscribe(sender, alice.clone()).await.unwrap();
+        assert_eq!(presences(&mut first_tab), vec![joined("alice")]);
+
+        // Newcomers learn who is already here
+        let (sender, mut other) = unbounded_channel();
+        let (bob_id, _) = worker.subscribe(sender, bob.clone()).await.unwrap();
+        assert_eq!(presences(&mut other), vec![joined("alice"), joined("bob")]);
+        assert_eq!(presences(&mut first_tab), vec![joined("bob")]);
+
+        // A second tab does not announce the same user again
+        let (sender, mut second_tab) = unbounded_channel();
+        let (second_id, _) = worker.subscribe(sender, alice).await.unwrap();
+        assert_eq!(presences(&mut second_tab), vec![joined("bob")]);
+        assert_eq!(presences(&mut other), vec![]);
+
+        worker
+            .handle_event(WorkerEvent::Typing(bob_id))
+            .await
+            .unwrap();
+        let typing = vec![("bob".to_string(), PresenceStatus::Typing)];
+        assert_eq!(presences(&mut first_tab), typing);
+        assert_eq!(presences(&mut second_tab), typing);
+        assert_eq!(presences(&mut other), typing);
+
+        // Alice only leaves with her last tab
+        worker.unsubscribe(&first_id).await;
+        assert_eq!(presences(&mut other), vec![]);
+        worker.unsubscribe(&second_id).await;
+        assert_eq!(
+            presences(&mut other),
+            vec![("alice".to_string(), PresenceStatus::Left)]
+        );
     }
 }
@@ -1,5 +1,6 @@
+use chrono::DateTime;
+use chrono::Utc;
 use mirabel_core::models::session::Session;
-use mirabel_core::models::timeline::TimelineEntry;
 use std::collections::HashMap;
 use std::collections::VecDeque;
 use std::sync::Arc;
@@ -11,68 +12,176 @@ use serde::Serialize;
 use tokio::sync::Mutex;
 use tokio::sync::mpsc::UnboundedReceiver;
 use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::oneshot;
 
+use crate::driver::container::pty::PtyShell;
 use crate::driver::llm::ollama::Ollama;
+use crate::driver::research::WebResearch;
+use crate::session::shell::ShellScreen;
+use crate::session::tools::Toolbox;
+use mirabel_core::models::timeline::PromptAnswer;
+use mirabel_core::models::timeline::PromptKind;
+use mirabel_core::models::user::User;
 
-#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
-pub enum SessionWorkerState {
-    Stopped,
-    Initializing,
-    Idle,
-    Paused,
-    Running,
-    Stopping,
-    Error(SessionWorkerError),
+pub use mirabel_core::dto::session::event::ServerEvent;
+pub use mirabel_core::dto::session::event::SessionWorkerState;
+pub use mirabel_core::dto::session::event::ShellDriver;
+pub use mirabel_core::dto::session::event::ShellInput;
+pub use mirabel_core::dto::session::event::UserInteraction;
+
+/// The last timeline entry a reconnecting subscriber has seen, used to replay
+/// everything it missed before switching to live events.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LastSeen {
+    Entry(String),
+    Timestamp(DateTime<Utc>),
+}
+
+pub enum WorkerEvent {
+    UserInteraction(Interaction),
+    Typing(String),
+    Unsubscribe(String),
+    /// Input for the session shell, from the subscriber with the given id
+    Shell {
+        subscriber_id: String,
+        correlation_id: Option<String>,
+        input: ShellInput,
+    },
+    /// Draft a new plan for the accepted spec, with why the last one was rejected
+    RevisePlan {
+        spec_id: String,
+        feedback: Option<String>,
+    },
+}
+
+/// A [`UserInteraction`] together with where it came from, so failures can be
+/// reported back to the subscriber that sent it. Interactions posted over
+/// plain HTTP may not be tied to any subscriber.
+#[derive(Debug, Clone)]
+pub struct Interaction {
+    pub user_id: String,
+    pub subscriber_id: Option<String>,
+    pub correlation_id: Option<String>,
+    pub interaction: UserInteraction,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
-pub enum SessionWorkerError {
-    Generic(String),
+/// The user behind a subscription, one user can be subscribed several times
+/// (e.g. from multiple tabs) but is only present once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Participant {
+    pub user_id: String,
+    pub username: String,
 }
 
-impl From<&str> for SessionWorkerError {
-    fn from(err: &str) -> Self {
-        SessionWorkerError::Generic(err.to_string())
+impl From<&User> for Participant {
+    fn from(user: &User) -> Self {
+        Self {
+            user_id: user.id.clone(),
+            username: user.username.clone(),
+        }
     }
 }
 
-pub enum WorkerEvent {
-    UserInteraction(UserInteraction),
-    Unsubscribe(String),
+pub struct Subscriber {
+    pub participant: Participant,
+    pub sender: UnboundedSender<ServerEvent>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-#[serde(tag = "type")]
-pub enum UserInteraction {
-    Message { content: String },
-    PromptResponse { prompt_id: String, response: String },
+/// A question for the users of a session, see [`SessionWorker::ask`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Question {
+    pub question: String,
+    pub kind: PromptKind,
+    pub options: Vec<String>,
+}
+
+impl Question {
+    pub fn free_text(question: impl Into<String>) -> Self {
+        Self {
+            question: question.into(),
+            kind: PromptKind::FreeText,
+            options: Vec::new(),
+        }
+    }
+
+    pub fn single_choice(question: impl Into<String>, options: Vec<String>) -> Self {
+        Self {
+            question: question.into(),
+            kind: PromptKind::SingleChoice,
+            options,
+        }
+    }
+
+    pub fn multiple_choice(question: impl Into<String>, options: Vec<String>) -> Self {
+        Self {
+            question: question.into(),
+            kind: PromptKind::MultipleChoice,
+            options,
+        }
+    }
+
+    pub fn confirm(question: impl Into<String>) -> Self {
+        Self {
+            question: question.into(),
+            kind: PromptKind::Confirm,
+            options: Vec::new(),
+        }
+    }
+}
+
+/// A prompt that was issued and is waiting for its answer
+pub struct PendingPrompt {
+    pub kind: PromptKind,
+    pub options: Vec<String>,
+    pub responder: oneshot::Sender<PromptAnswer>,
 }
 
 pub struct SessionWorker {
     pub session: Arc<Mutex<Session>>,
     pub pool: Data<Pool>,
     pub llm: Data<Ollama>,
+    // Searches and reads the web for research steps
+    pub web: Data<WebResearch>,
     pub state: Arc<Mutex<SessionWorkerState>>,
     // Listener for events from the subscribers
     pub receiver: Arc<Mutex<UnboundedReceiver<WorkerEvent>>>,
     // Sender for events to be processed by the worker, given to the subscribers
     pub sender: UnboundedSender<WorkerEvent>,
-    // All websockets at the other side
-    pub subscribers: Arc<Mutex<HashMap<String, UnboundedSender<TimelineEntry>>>>,
+    // All websockets and event streams at the other side
+    pub subscribers: Arc<Mutex<HashMap<String, Subscriber>>>,
+    // Prompts waiting for an answer, by prompt id
+    pub prompts: Arc<Mutex<HashMap<String, PendingPrompt>>>,
     // Queuing and processing state
     pub is_processing: Arc<Mutex<bool>>,
     pub queue: Arc<Mutex<VecDeque<Queueable>>>,
     pub interupts: Arc<Mutex<VecDeque<Interupt>>>,
+    // Where plan steps are dispatched to
+    pub tools: Arc<Mutex<Toolbox>>,
+    // The interactive shell, started once a user types into it
+    pub shell: Arc<Mutex<Option<Arc<PtyShell>>>>,
+    pub shell_driver: Arc<Mutex<ShellDriver>>,
+    pub screen: Arc<Mutex<ShellScreen>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Interupt {
     UserInteraction(UserInteraction),
+    AgentInterrupt(AgentInterrupt),
+}
+
+/// Raised when a step of a running plan failed, execution is paused until it
+/// is handled by a detour, a retry, skipping the step or stopping the plan.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentInterrupt {
+    pub plan_id: String,
+    pub step_id: String,
+    pub reason: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Queueable {
     UserInteraction(UserInteraction),
     Interupt(Interupt),
+    /// Runs the remaining steps of an approved plan
+    ExecutePlan(String),
 }
@@ -0,0 +1,403 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use log::warn;
+use mirabel_core::models::plan::NodeStatus;
+use mirabel_core::models::plan::Plan;
+use mirabel_core::models::plan::PlanStatus;
+use mirabel_core::models::plan::Step;
+use mirabel_core::models::plan::StepAction;
+use mirabel_core::models::timeline::AgentStatus;
+use mirabel_core::models::timeline::PromptAnswer;
+use mirabel_core::models::timeline::TimelineEntry;
+use tokio::sync::mpsc::unbounded_channel;
+
+use crate::prelude::*;
+
+use crate::agent::planner;
+use crate::driver::llm::Llm;
+use crate::service::plans::PlanService;
+use crate::session::models::AgentInterrupt;
+use crate::session::models::Interupt;
+use crate::session::models::Question;
+use crate::session::models::Queueable;
+use crate::session::models::ServerEvent;
+use crate::session::models::SessionWorker;
+use crate::session::models::SessionWorkerState;
+use crate::session::models::ShellDriver;
+use crate::session::tools::CommandOutput;
+use crate::session::tools::ShellTool;
+
+const MANUAL_STEP_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+const INTERRUPT_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+const OUTPUT_LIMIT: usize = 2000;
+
+const DETOUR: &str = "Take a detour";
+const RETRY: &str = "Retry the step";
+const SKIP: &str = "Skip the step";
+const STOP: &str = "Stop the plan";
+
+impl SessionWorker {
+    /// Runs the remaining steps of a plan in dependency order. A failing step
+    /// pauses execution and raises an [`AgentInterrupt`], which queues the
+    /// plan again once it is handled. Any other error fails the plan.
+    pub(super) async fn execute_plan(&self, plan_id: String) -> Result<()> {
+        let plans = PlanService::from(self.pool.clone())?;
+        let mut plan = plans.get(plan_id).await?.ok_or(Error::NotFound)?;
+        plan.start()?;
+        let plan_id = plan.id.clone();
+        let result = self.run_steps(&plans, plan).await;
+        if let Err(err) = &result {
+            self.abort_plan(plan_id, err).await;
+        }
+        result
+    }
+
+    async fn run_steps(&self, plans: &PlanService, plan: Plan) -> Result<()> {
+        let mut plan = plans.update(plan).await?;
+        self.set_state(SessionWorkerState::Running).await;
+        self.set_shell_driver(ShellDriver::Agent).await;
+        self.broadcast_save(TimelineEntry::plan(&plan)).await?;
+
+        while let Some(step) = plan.root.next_step().cloned() {
+            update_step(&mut plan, &step.id, Step::start);
+            plan = plans.update(plan).await?;
+            self.broadcast(ServerEvent::PlanProgress(plan.progress(Utc::now())))
+                .await;
+
+            let outcome = self.dispatch(&step).await;
+            let status = match outcome {
+                Ok(_) => NodeStatus::Completed,
+                Err(_) => NodeStatus::Failed,
+            };
+            update_step(&mut plan, &step.id, |step| step.finish(status));
+            plan = plans.update(plan).await?;
+            self.broadcast_save(TimelineEntry::plan(&plan)).await?;
+            self.broadcast(ServerEvent::PlanProgress(plan.progress(Utc::now())))
+                .await;
+
+            if let Err(err) = outcome {
+                self.interupts
+                    .lock()
+                    .await
+                    .push_back(Interupt::AgentInterrupt(AgentInterrupt {
+                        plan_id: plan.id.clone(),
+                        step_id: step.id,
+                        reason: err.to_string(),
+                    }));
+                self.set_state(SessionWorkerState::Paused).await;
+                self.set_shell_driver(ShellDriver::User).await;
+                return Ok(());
+            }
+        }
+
+        // Steps still pending at this point wait on something that never ran
+        let completed = plan
+            .root
+            .steps_in_order()
+            .iter()
+            .all(|step| step.status.is_done());
+        let (status, reply) = if completed {
+            (PlanStatus::Completed, "The plan is completed.".to_string())
+        } else {
+            (
+                PlanStatus::Failed,
+                "The plan stopped, some steps depend on steps that did not run.".to_string(),
+            )
+        };
+        self.finish_plan(plan, status, reply).await
+    }
+
+    /// Fails a plan that ran into an error, so it does not stay running and
+    /// the users get the shell back even when the plan cannot be saved
+    async fn abort_plan(&self, plan_id: String, err: &Error) {
+        let failed = async {
+            let plans = PlanService::from(self.pool.clone())?;
+            let plan = plans.get(plan_id).await?.ok_or(Error::NotFound)?;
+            self.finish_plan(plan, PlanStatus::Failed, format!("The plan stopped: {err}"))
+                .await
+        };
+        if let Err(err) = failed.await {
+            warn!("Could not mark the plan as failed: {err}");
+            self.set_state(SessionWorkerState::Idle).await;
+            self.set_shell_driver(ShellDriver::User).await;
+        }
+    }
+
+    /// Handles a failed step by asking the users how to continue
+    pub(super) async fn handle_agent_interrupt(&self, interrupt: AgentInterrupt) -> Result<()> {
+        let plans = PlanService::from(self.pool.clone())?;
+        let mut plan = plans.get(interrupt.plan_id).await?.ok_or(Error::NotFound)?;
+        let step = plan
+            .root
+            .find_step(&interrupt.step_id)
+            .cloned()
+            .ok_or(Error::NotFound)?;
+
+        let question = Question::single_choice(
+            format!(
+                "The step \"{}\" failed: {}\nHow should I continue?",
+                step.description, interrupt.reason
+            ),
+            vec![DETOUR.into(), RETRY.into(), SKIP.into(), STOP.into()],
+        );
+        let recovery = Recovery::from_answer(self.ask(question, INTERRUPT_TIMEOUT).await)?;
+        let detour = match recovery {
+            Recovery::Stop => {
+                return self
+                    .finish_plan(plan, PlanStatus::Failed, "The plan was stopped.".into())
+                    .await;
+            }
+            Recovery::Detour => {
+                let llm: Arc<dyn Llm> = self.llm.clone().into_inner();
+                Some(
+                    planner::detour(llm, &step, &interrupt.reason)
+                        .await?
+                        .response,
+                )
+            }
+            Recovery::Retry | Recovery::Skip => None,
+        };
+        recover(&mut plan, &step.id, recovery, detour);
+        let plan = plans.update(plan).await?;
+        self.broadcast_save(TimelineEntry::plan(&plan)).await?;
+        self.queue
+            .lock()
+            .await
+            .push_front(Queueable::ExecutePlan(plan.id));
+        Ok(())
+    }
+
+    /// Hands a step to the tool for its kind of action
+    async fn dispatch(&self, step: &Step) -> Result<String> {
+        let tools = self.tools.lock().await.clone();
+        match &step.action {
+            StepAction::Shell { command } | StepAction::Verify { command } => {
+                let shell = tools.shell.ok_or(Error::Generic(
+                    "There is no shell available in this session.".into(),
+                ))?;
+                let output = self.run_streamed(shell.as_ref(), command).await?;
+                if !output.success() {
+                    return Err(Error::StepFailed(format!(
+                        "`{command}` exited with {}: {}",
+                        output.exit_code,
+                        tail(&output.output)
+                    )));
+                }
+                Ok(output.output)
+            }
+            StepAction::Edit { path, instructions } => self.edit_file(path, instructions).await,
+            StepAction::Research { query } => self.research(query).await,
+            StepAction::Screenshot { url } => self.screenshot(url).await,
+            StepAction::Documents { query } => self.search_documents(query).await,
+            // Nothing can do these yet, so they are left to the users
+            StepAction::Other => {
+                let question = Question::confirm(format!(
+                    "The step \"{}\" has to be done by hand. Is it done?",
+                    step.description
+                ));
+                match self.ask(question, MANUAL_STEP_TIMEOUT).await? {
+                    PromptAnswer::Confirm(true) => Ok(String::new()),
+                    _ => Err(Error::StepFailed("It was not done by hand.".into())),
+                }
+            }
+        }
+    }
+
+    /// Runs a command while its output goes out live on the session shell.
+    /// Once it is done, the screen is saved as the latest shell state.
+    async fn run_streamed(&self, shell: &dyn ShellTool, command: &str) -> Result<CommandOutput> {
+        let (sender, mut receiver) = unbounded_channel::<String>();
+        let forward = async {
+            self.shell_output(format!("$ {command}\r\n")).await;
+            while let Some(line) = receiver.recv().await {
+                self.shell_output(format!("{line}\r\n")).await;
+            }
+        };
+        // The sender is dropped with the finished command, which ends forwarding
+        let (output, _) = tokio::join!(shell.run(command, sender), forward);
+        self.save_shell_snapshot().await?;
+        output
+    }
+
+    async fn finish_plan(&self, mut plan: Plan, status: PlanStatus, reply: String) -> Result<()> {
+        let plans = PlanService::from(self.pool.clone())?;
+        plan.finish(status);
+        let plan = plans.update(plan).await?;
+        self.broadcast_save(TimelineEntry::plan(&plan)).await?;
+        if status == PlanStatus::Failed {
+            self.broadcast_save(TimelineEntry::status(
+                plan.session_id.clone(),
+                AgentStatus::Error,
+            ))
+            .await?;
+        }
+        self.broadcast_save(TimelineEntry::agent_message(plan.session_id, reply))
+            .await?;
+        self.set_state(SessionWorkerState::Idle).await;
+        self.set_shell_driver(ShellDriver::User).await;
+        Ok(())
+    }
+}
+
+/// How the users chose to go on after a step failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Recovery {
+    /// Run a new step that clears the way, then the failed one again
+    Detour,
+    Retry,
+    Skip,
+    Stop,
+}
+
+impl Recovery {
+    /// Prompts left unanswered stop the plan
+    fn from_answer(answer: Result<PromptAnswer>) -> Result<Self> {
+        let choice = match answer {
+            Ok(PromptAnswer::Choice(choice)) => choice,
+            Ok(_) | Err(Error::PromptTimeout(_)) => return Ok(Recovery::Stop),
+            Err(err) => return Err(err),
+        };
+        Ok(match choice.as_str() {
+            DETOUR => Recovery::Detour,
+            RETRY => Recovery::Retry,
+            SKIP => Recovery::Skip,
+            _ => Recovery::Stop,
+        })
+    }
+}
+
+/// Prepares the plan to go on past the failed step, a detour is inserted
+/// right before it
+fn recover(plan: &mut Plan, step_id: &str, recovery: Recovery, detour: Option<Step>) {
+    if let Some(detour) = detour {
+        plan.root.insert_before(step_id, detour);
+    }
+    match recovery {
+        Recovery::Detour | Recovery::Retry => update_step(plan, step_id, Step::reset),
+        Recovery::Skip => update_step(plan, step_id, |step| step.finish(NodeStatus::Skipped)),
+        Recovery::Stop => {}
+    }
+}
+
+fn update_step(plan: &mut Plan, step_id: &str, update: impl FnOnce(&mut Step)) {
+    if let Some(step) = plan.root.find_step_mut(step_id) {
+        update(step);
+    }
+    plan.root.refresh();
+    plan.modified_at = Utc::now();
+}
+
+/// The end of a command output, which is where the error usually is
+fn tail(output: &str) -> &str {
+    let start = output.len().saturating_sub(OUTPUT_LIMIT);
+    let start = (start..output.len())
+        .find(|index| output.is_char_boundary(*index))
+        .unwrap_or(output.len());
+    &output[start..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use mirabel_core::models::plan::Workflow;
+
+    /// A plan whose build step failed, the tests wait for it
+    fn failed_plan() -> (Plan, String) {
+        let mut root = Workflow::new("Release".into());
+        let mut build = Step::new(
+            "Build".into(),
+            StepAction::Shell {
+                command: "cargo build".into(),
+            },
+        );
+        build.start();
+        build.finish(NodeStatus::Failed);
+        let mut test = Step::new(
+            "Test".into(),
+            StepAction::Verify {
+                command: "cargo test".into(),
+            },
+        );
+        test.depends_on.push(build.id.clone());
+        let build_id = build.id.clone();
+        root.steps = vec![build, test];
+        root.refresh();
+        let plan = Plan::new("session".into(), "spec".into(), root);
+        (plan, build_id)
+    }
+
+    #[tokio::test]
+    async fn test_abort_plan() {
+        // The plan cannot be loaded, the worker still has to stop running it
+        let worker = crate::session::tests::worker();
+        worker.set_state(SessionWorkerState::Running).await;
+        worker.set_shell_driver(ShellDriver::Agent).await;
+        worker.abort_plan("plan".into(), &Error::NotFound).await;
+        assert_eq!(*worker.state.lock().await, SessionWorkerState::Idle);
+        assert_eq!(*worker.shell_driver.lock().await, ShellDriver::User);
+    }
+
+    fn next(plan: &Plan) -> Option<&str> {
+        plan.root.next_step().map(|step| step.description.as_str())
+    }
+
+    #[test]
+    fn test_from_answer() {
+        let choice = |choice: &str| Recovery::from_answer(Ok(PromptAnswer::Choice(choice.into())));
+        assert_eq!(choice(DETOUR).unwrap(), Recovery::Detour);
+        assert_eq!(choice(RETRY).unwrap(), Recovery::Retry);
+        assert_eq!(choice(SKIP).unwrap(), Recovery::Skip);
+        assert_eq!(choice(STOP).unwrap(), Recovery::Stop);
+
+        let timeout = Recovery::from_answer(Err(Error::PromptTimeout("prompt".into())));
+        assert_eq!(timeout.unwrap(), Recovery::Stop);
+        let text = Recovery::from_answer(Ok(PromptAnswer::Text(RETRY.into())));
+        assert_eq!(text.unwrap(), Recovery::Stop);
+        assert!(Recovery::from_answer(Err(Error::NotFound)).is_err());
+    }
+
+    #[test]
+    fn test_recover() {
+        let (plan, build) = failed_plan();
+        assert_eq!(plan.root.status, NodeStatus::Failed);
+        assert_eq!(next(&plan), None);
+
+        let mut retried = plan.clone();
+        recover(&mut retried, &build, Recovery::Retry, None);
+        assert_eq!(next(&retried), Some("Build"));
+        assert_eq!(retried.root.status, NodeStatus::Pending);
+
+        let mut detoured = plan.clone();
+        let detour = Step::new(
+            "Install the toolchain".into(),
+            StepAction::Shell {
+                command: "rustup install stable".into(),
+            },
+        );
+        recover(&mut detoured, &build, Recovery::Detour, Some(detour));
+        let order: Vec<&str> = detoured
+            .root
+            .steps_in_order()
+            .iter()
+            .map(|step| step.description.as_str())
+            .collect();
+        assert_eq!(order, vec!["Install the toolchain", "Build", "Test"]);
+        assert_eq!(next(&detoured), Some("Install the toolchain"));
+
+        // Steps waiting on a skipped step run anyway
+        let mut skipped = plan.clone();
+        recover(&mut skipped, &build, Recovery::Skip, None);
+        assert_eq!(
+            skipped.root.find_step(&build).unwrap().status,
+            NodeStatus::Skipped
+        );
+        assert_eq!(next(&skipped), Some("Test"));
+
+        let mut stopped = plan.clone();
+        recover(&mut stopped, &build, Recovery::Stop, None);
+        assert_eq!(stopped.root, plan.root);
+    }
+}
@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use mirabel_core::dto::document::ChunkSearch;
+use mirabel_core::models::attachment::Attachment;
+use mirabel_core::models::timeline::Citation;
+use mirabel_core::models::timeline::TimelineEntry;
+
+use crate::prelude::*;
+
+use crate::agent::researcher;
+use crate::agent::researcher::Excerpt;
+use crate::driver::converter::Converter;
+use crate::driver::converter::chunk::Chunk;
+use crate::driver::converter::chunk::ChunkOptions;
+use crate::driver::converter::markdown::Markdown;
+use crate::driver::llm::Llm;
+use crate::driver::research::Source;
+use crate::driver::retrieval::Retriever;
+use crate::service::attachments::AttachmentService;
+use crate::session::models::SessionWorker;
+
+/// Pages read for a single query
+const MAX_SOURCES: usize = 5;
+/// Characters of passages the researcher gets to answer from
+const PASSAGE_BUDGET: usize = 8000;
+/// Passages of the attached documents a lookup returns
+const DOCUMENT_PASSAGES: i64 = 5;
+/// Pages are cut into passages of about 1200 characters
+const PASSAGE_OPTIONS: ChunkOptions = ChunkOptions {
+    max_tokens: 300,
+    overlap_tokens: 0,
+};
+
+impl SessionWorker {
+    /// Looks up `query` on the web and answers it from the pages found. The
+    /// answer goes on the timeline with the sources it cites and is returned.
+    pub(super) async fn research(&self, query: &str) -> Result<String> {
+        let (workspace_id, session_id) = {
+            let session = self.session.lock().await;
+            (session.workspace_id.clone(), session.id.clone())
+        };
+        let (results, mut denied) = self.web.search(&workspace_id, query, MAX_SOURCES).await?;
+        let (sources, unread) = self.web.read(&workspace_id, results).await;
+        denied.extend(unread);
+        if sources.is_empty() {
+            return Err(Error::StepFailed(with_denials(
+                format!("The web search for \"{query}\" found nothing that may be read."),
+                &denied,
+            )));
+        }
+        let excerpts = excerpts(&sources, query, PASSAGE_BUDGET);
+
+        let llm: Arc<dyn Llm> = self.llm.clone().into_inner();
+        let answer = researcher::answer(llm, query, &excerpts).await?.response;
+        let cited = researcher::cited(&answer);
+        let citations = excerpts
+            .into_iter()
+            .filter(|excerpt| cited.is_empty() || cited.contains(&excerpt.index))
+            .map(|excerpt| Citation {
+                index: excerpt.index,
+                title: excerpt.title,
+                url: excerpt.url,
+            })
+            .collect();
+
+        self.broadcast_save(TimelineEntry::research(
+            session_id,
+            query.to_string(),
+            answer.clone(),
+            citations,
+        ))
+        .await?;
+        Ok(with_denials(answer, &denied))
+    }
+
+    /// The passages of the documents attached to the session that match
+    /// `query` best, the best first
+    pub(super) async fn search_documents(&self, query: &str) -> Result<String> {
+        let session_id = self.session.lock().await.id.clone();
+        let search = ChunkSearch {
+            query: query.to_string(),
+            limit: Some(DOCUMENT_PASSAGES),
+        };
+        let chunks = AttachmentService::from(self.pool.clone())?
+            .search_documents(session_id, search, self.llm.as_ref())
+            .await?;
+        if chunks.is_empty() {
+            return Err(Error::StepFailed(format!(
+                "Nothing in the documents of the session is about \"{query}\"."
+            )));
+        }
+        Ok(chunks
+            .into_iter()
+            .map(|chunk| chunk.text)
+            .collect::<Vec<_>>()
+            .join("\n\n"))
+    }
+
+    /// Puts a screenshot of the page at `url` on the timeline, and returns
+    /// what the page says
+    pub(super) async fn screenshot(&self, url: &str) -> Result<String> {
+        let (workspace_id, session_id) = {
+            let session = self.session.lock().await;
+            (session.workspace_id.clone(), session.id.clone())
+        };
+        let page = self.web.capture(&workspace_id, url).await?;
+        let png = page.screenshot.clone().ok_or(Error::StepFailed(format!(
+            "No screenshot could be taken of {url}."
+        )))?;
+        let attachment = AttachmentService::from(self.pool.clone())?
+            .create(Attachment::png(session_id.clone(), png))
+            .await?;
+        let article = page.article();
+        self.broadcast_save(TimelineEntry::screenshot(
+            session_id,
+            page.url,
+            article.title,
+            attachment.id,
+        ))
+        .await?;
+        Ok(article.text)
+    }
+}
+
+/// Adds why pages found were not read, so the agent knows what it is missing
+fn with_denials(text: String, denied: &[String]) -> String {
+    match denied {
+        [] => text,
+        denied => format!("{text}\n\nNot read:\n- {}", denied.join("\n- ")),
+    }
+}
+
+/// The passages of the sources that match the query best, as many as fit
+/// into `budget` characters. When nothing matches, the start of every source
+/// is taken instead. Only sources with passages are numbered, in the order
+/// of the search.
+fn excerpts(sources: &[Source], query: &str, budget: usize) -> Vec<Excerpt> {
+    let mut source_of: Vec<usize> = Vec::new();
+    let mut chunks: Vec<Chunk> = Vec::new();
+    for (index, source) in sources.iter().enumerate() {
+        let markdown = Converter::<Markdown>::from_md(source.markdown.clone());
+        for chunk in markdown.chunks(PASSAGE_OPTIONS) {
+            source_of.push(index);
+            chunks.push(chunk);
+        }
+    }
+    let retriever = Retriever::new(chunks);
+    // The same text on two pages counts for the first
+    let mut sources_by_id: HashMap<&str, usize> = HashMap::new();
+    for (chunk, source) in retriever.chunks().iter().zip(source_of) {
+        sources_by_id.entry(chunk.id.as_str()).or_insert(source);
+    }
+
+    let mut ranked: Vec<&Chunk> = retriever
+        .top(query, retriever.chunks().len())
+        .into_iter()
+        .map(|(chunk, _)| chunk)
+        .collect();
+    if ranked.is_empty() {
+        let mut seen = HashSet::new();
+        ranked = retriever
+            .chunks()
+            .iter()
+            .filter(|chunk| seen.insert(sources_by_id[chunk.id.as_str()]))
+            .collect();
+    }
+
+    let mut used = 0;
+    let mut passages: Vec<Vec<String>> = vec![Vec::new(); sources.len()];
+    for chunk in ranked {
+        if used + chunk.text.len() > budget {
+            continue;
+        }
+        used += chunk.text.len();
+        passages[sources_by_id[chunk.id.as_str()]].push(chunk.text.clone());
+    }
+
+    let mut excerpts: Vec<Excerpt> = Vec::new();
+    for (source, passages) in sources.iter().zip(passages) {
+        if passages.is_empty() {
+            continue;
+        }
+        excerpts.push(Excerpt {
+            index: excerpts.len() as u32 + 1,
+            title: source.title.clone(),
+            url: source.url.clone(),
+            passages,
+        });
+    }
+    excerpts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(url: &str, markdown: &str) -> Source {
+        Source {
+            title: "Title".into(),
+            url: url.into(),
+            markdown: markdown.into(),
+        }
+    }
+
+    fn urls(excerpts: &[Excerpt]) -> Vec<&str> {
+        excerpts
+            .iter()
+            .map(|excerpt| excerpt.url.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_excerpts() {
+        let sources = [
+            source(
+                "https://rust-lang.org",
+                "# Memory\n\nRust has no garbage collector.",
+            ),
+            source(
+                "https://tokio.rs",
+                "# Tokio\n\nTokio is an async runtime for Rust.",
+            ),
+        ];
+        let found = excerpts(&sources, "How does the tokio runtime work?", 1000);
+        assert_eq!(urls(&found), ["https://tokio.rs"]);
+        assert_eq!(found[0].index, 1);
+
+        // Rare words decide, "rust" is everywhere
+        let found = excerpts(&sources, "rust garbage", 1000);
+        assert_eq!(urls(&found), ["https://rust-lang.org", "https://tokio.rs"]);
+        assert_eq!(
+            found[0].passages,
+            ["# Memory\n\nRust has no garbage collector."]
+        );
+
+        // Without a match every source starts
+        let found = excerpts(&sources, "python", 1000);
+        assert_eq!(urls(&found), ["https://rust-lang.org", "https://tokio.rs"]);
+        assert_eq!(found[1].index, 2);
+
+        // The better passage comes first, the other one no longer fits
+        let found = excerpts(&sources, "garbage runtime", 42);
+        assert_eq!(urls(&found), ["https://rust-lang.org"]);
+    }
+
+    #[test]
+    fn test_with_denials() {
+        assert_eq!(with_denials("Answer".into(), &[]), "Answer");
+        let denied = [
+            "Fetching https://a.example is not allowed: a.example is on the deny list".to_string(),
+            "Fetching https://b.example is not allowed: the robots.txt disallows it".to_string(),
+        ];
+        assert_eq!(
+            with_denials("Answer".into(), &denied),
+            format!("Answer\n\nNot read:\n- {}\n- {}", denied[0], denied[1])
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::warn;
+use mirabel_core::dto::session::event::ShellDriver;
+use mirabel_core::dto::session::event::ShellInput;
+use mirabel_core::dto::session::event::ShellOutput;
+use mirabel_core::models::timeline::TimelineEntry;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use crate::prelude::*;
+
+use crate::driver::container::Sandbox;
+use crate::driver::container::pty::PtyShell;
+use crate::session::models::ServerEvent;
+use crate::session::models::SessionWorker;
+
+const DEFAULT_ROWS: u16 = 24;
+const DEFAULT_COLS: u16 = 80;
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(5);
+/// Lines kept in the snapshots saved as `Shell` timeline entries
+pub const SCREEN_LINES: usize = 500;
+
+impl SessionWorker {
+    /// Sends what a user typed to the session shell, which is started on the
+    /// first input. Users cannot type while an agent is driving.
+    pub async fn shell_input(self: &Arc<Self>, input: ShellInput) -> Result<()> {
+        if *self.shell_driver.lock().await == ShellDriver::Agent {
+            return Err(Error::Forbidden(
+                "An agent is driving the shell, it is read-only until it is done.".into(),
+            ));
+        }
+        let shell = self.shell().await?;
+        match input {
+            ShellInput::Data { data } => shell.write(&data),
+            ShellInput::Resize { rows, cols } => shell.resize(rows, cols),
+        }
+    }
+
+    pub async fn set_shell_driver(&self, driver: ShellDriver) {
+        *self.shell_driver.lock().await = driver;
+        self.broadcast(ServerEvent::ShellDriver(driver)).await;
+    }
+
+    /// Output that belongs on the shell but was not produced by it, like the
+    /// commands agents run
+    pub(super) async fn shell_output(&self, data: String) {
+        self.screen.lock().await.push(&data);
+        self.broadcast(ServerEvent::ShellOutput(ShellOutput { data }))
+            .await;
+    }
+
+    /// Saves the compacted screen as the latest shell state
+    pub(super) async fn save_shell_snapshot(&self) -> Result<()> {
+        let session_id = self.session.lock().await.id.clone();
+        let lines = self.screen.lock().await.lines();
+        self.broadcast_save(TimelineEntry::shell(session_id, lines))
+            .await
+    }
+
+    async fn shell(self: &Arc<Self>) -> Result<Arc<PtyShell>> {
+        let mut shell = self.shell.lock().await;
+        if let Some(running) = shell.as_ref().filter(|shell| shell.is_running()) {
+            return Ok(running.clone());
+        }
+        let session_id = self.session.lock().await.id.clone();
+        let (started, output) =
+            Sandbox::from_env(&session_id)?.shell(DEFAULT_ROWS, DEFAULT_COLS)?;
+        let started = Arc::new(started);
+        *shell = Some(started.clone());
+
+        let worker = self.clone();
+        actix_web::rt::spawn(async move { worker.forward_shell(output).await });
+        Ok(started)
+    }
+
+    /// Streams the shell output to the subscribers until the shell exits,
+    /// saving a snapshot whenever something changed in a while
+    async fn forward_shell(&self, mut output: UnboundedReceiver<String>) {
+        let mut interval = tokio::time::interval(SNAPSHOT_INTERVAL);
+        let mut changed = false;
+        loop {
+            tokio::select! {
+                data = output.recv() => match data {
+                    Some(data) => {
+                        self.shell_output(data).await;
+                        changed = true;
+                    }
+                    None => break,
+                },
+                _ = interval.tick(), if changed => {
+                    changed = false;
+                    self.try_save_shell_snapshot().await;
+                }
+            }
+        }
+        if changed {
+            self.try_save_shell_snapshot().await;
+        }
+    }
+
+    async fn try_save_shell_snapshot(&self) {
+        if let Err(err) = self.save_shell_snapshot().await {
+            warn!("Failed to save a shell snapshot: {err}");
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Escape {
+    None,
+    Start,
+    Csi,
+    Osc,
+    OscEnd,
+}
+
+/// The last lines of a terminal as plain text. Escape sequences are dropped
+/// and carriage returns overwrite the current line, which is enough for
+/// command output but not for full screen programs.
+#[derive(Debug, Clone)]
+pub struct ShellScreen {
+    lines: VecDeque<String>,
+    current: String,
+    carriage_return: bool,
+    escape: Escape,
+    limit: usize,
+}
+
+impl Default for ShellScreen {
+    fn default() -> Self {
+        Self::new(SCREEN_LINES)
+    }
+}
+
+impl ShellScreen {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            lines: VecDeque::new(),
+            current: String::new(),
+            carriage_return: false,
+            escape: Escape::None,
+            limit,
+        }
+    }
+
+    pub fn push(&mut self, data: &str) {
+        for c in data.chars() {
+            self.escape = match self.escape {
+                Escape::Start if c == '[' => Escape::Csi,
+                Escape::Start if c == ']' => Escape::Osc,
+                Escape::Start => Escape::None,
+                Escape::Csi if ('@'..='~').contains(&c) => Escape::None,
+                Escape::Osc if c == '\x07' => Escape::None,
+                Escape::Osc if c == '\x1b' => Escape::OscEnd,
+                Escape::OscEnd if c == '\\' => Escape::None,
+                Escape::OscEnd => Escape::Osc,
+                Escape::None if c == '\x1b' => Escape::Start,
+                Escape::None => {
+                    self.put(c);
+                    Escape::None
+                }
+                escape => escape,
+            };
+        }
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self.lines.iter().cloned().collect();
+        if !self.current.is_empty() {
+            lines.push(self.current.clone());
+        }
+        lines
+    }
+
+    fn put(&mut self, c: char) {
+        if self.carriage_return && c != '\n' {
+            self.current.clear();
+        }
+        self.carriage_return = false;
+        match c {
+            '\n' => {
+                self.lines.push_back(std::mem::take(&mut self.current));
+                while self.lines.len() > self.limit {
+                    self.lines.pop_front();
+                }
+            }
+            '\r' => self.carriage_return = true,
+            '\x08' => {
+                self.current.pop();
+            }
+            '\t' => self.current.push(c),
+            c if c.is_control() => {}
+            c => self.current.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shell_screen() {
+        let mut screen = ShellScreen::new(2);
+        screen.push("\x1b]0;title\x07$ ls\r\n");
+        screen.push("\x1b[01;34msrc\x1b[0m  Cargo.toml\r\n");
+        screen.push("progress 10%\rprogress 100%\r\n$ ech");
+        screen.push("x\x08o");
+        assert_eq!(
+            screen.lines(),
+            vec!["src  Cargo.toml", "progress 100%", "$ echo"]
+        );
+    }
+}
@@ -0,0 +1,57 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::prelude::*;
+
+use crate::driver::container::Sandbox;
+use crate::driver::editor::FileEditor;
+use log::warn;
+
+/// Output of a command that ran to completion, successful or not
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandOutput {
+    pub exit_code: i64,
+    pub output: String,
+}
+
+impl CommandOutput {
+    pub fn success(&self) -> bool {
+        self.exit_code == 0
+    }
+}
+
+#[async_trait]
+pub trait ShellTool: Send + Sync {
+    /// Runs `command`, sending each line of output to `output` as it comes
+    async fn run(&self, command: &str, output: UnboundedSender<String>) -> Result<CommandOutput>;
+}
+
+/// What plan steps get dispatched to, the tools become available once the
+/// session has a sandbox to work in.
+#[derive(Clone, Default)]
+pub struct Toolbox {
+    pub shell: Option<Arc<dyn ShellTool>>,
+    pub editor: Option<Arc<FileEditor>>,
+}
+
+impl Toolbox {
+    /// Tools working in the sandbox of the session, a session without a
+    /// sandbox can still plan but not execute commands
+    pub fn for_session(session_id: &str) -> Self {
+        match Sandbox::from_env(session_id) {
+            Ok(sandbox) => Self {
+                editor: FileEditor::new(sandbox.workdir())
+                    .inspect_err(|err| warn!("No editor for session {session_id}: {err}"))
+                    .ok()
+                    .map(Arc::new),
+                shell: Some(Arc::new(sandbox)),
+            },
+            Err(err) => {
+                warn!("No sandbox for session {session_id}: {err}");
+                Self::default()
+            }
+        }
+    }
+}
@@ -0,0 +1,17 @@
+use std::env;
+use std::str::FromStr;
+
+use crate::prelude::*;
+
+/// The value of an environment variable, `None` when it is not set. A value
+/// that does not parse is an error rather than silently ignored.
+pub fn parse_env<T>(name: &str) -> Result<Option<T>>
+where
+    T: FromStr,
+    Error: From<T::Err>,
+{
+    match env::var(name) {
+        Ok(value) => Ok(Some(value.parse()?)),
+        Err(_) => Ok(None),
+    }
+}
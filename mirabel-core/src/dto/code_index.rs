@@ -0,0 +1,35 @@
+use serde::Deserialize;
+use serde::Serialize;
+use ts_rs::TS;
+
+use crate::models::code_index::SymbolKind;
+
+/// A definition of or a reference to a symbol, with the file it is in
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct SymbolLocation {
+    /// Relative to the worktree
+    pub path: String,
+    pub name: String,
+    pub kind: SymbolKind,
+    pub definition: bool,
+    pub line: i32,
+    pub column: i32,
+    pub end_line: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub signature: Option<String>,
+}
+
+/// What an indexing run of a worktree did
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct IndexStats {
+    /// Files that were new or changed and got parsed
+    pub indexed_files: u32,
+    pub unchanged_files: u32,
+    pub removed_files: u32,
+    pub symbols: u32,
+}
@@ -0,0 +1,36 @@
+use serde::Deserialize;
+use serde::Serialize;
+use ts_rs::TS;
+
+/// A search for the parts of a document that answer a question
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct ChunkSearch {
+    pub query: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub limit: Option<i64>,
+}
+
+/// A part of a document that matched a search
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct DocumentChunk {
+    /// Stays the same for as long as the text of the chunk does
+    pub id: String,
+    /// The attachment of the session the document is
+    pub attachment_id: String,
+    pub text: String,
+    /// The headings the text is under, the outermost first
+    pub headings: Vec<String>,
+    /// Byte offsets of the text in the markdown the document converts to
+    pub start: u32,
+    pub end: u32,
+    /// The page the text starts on, for documents with pages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub page: Option<u32>,
+    pub score: f32,
+}
@@ -0,0 +1,16 @@
+use serde::Deserialize;
+use serde::Serialize;
+use ts_rs::TS;
+
+use crate::models::domain_rule::DomainAccess;
+
+/// Allows or denies fetching a domain for a workspace, a rule for the same
+/// domain is replaced
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct NewDomainRule {
+    /// A domain, or a URL on it
+    pub domain: String,
+    pub access: DomainAccess,
+}
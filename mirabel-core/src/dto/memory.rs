@@ -0,0 +1,85 @@
+use chrono::DateTime;
+use chrono::Utc;
+use serde::Deserialize;
+use serde::Serialize;
+use ts_rs::TS;
+
+use crate::models::memory::MemoryKind;
+
+/// A memory users add to a workspace by hand
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct NewMemory {
+    pub kind: MemoryKind,
+    pub content: String,
+    /// Memories from users are trusted fully when left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub confidence: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct UpdatedMemory {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub kind: Option<MemoryKind>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub content: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub confidence: Option<f32>,
+    /// `null` removes the expiry
+    #[serde(default, with = "present", skip_serializing_if = "Option::is_none")]
+    #[ts(optional, as = "Option<Option<DateTime<Utc>>>")]
+    pub expires_at: Option<Option<DateTime<Utc>>>,
+}
+
+/// Tells a field set to `null` apart from one that was left out
+mod present {
+    use serde::Deserialize;
+    use serde::Deserializer;
+    use serde::Serialize;
+    use serde::Serializer;
+
+    pub fn serialize<S, T>(value: &Option<Option<T>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: Serialize,
+    {
+        value
+            .as_ref()
+            .and_then(Option::as_ref)
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Deserialize<'de>,
+    {
+        Option::<T>::deserialize(deserializer).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_updated_memory_expiry() {
+        let parse = |json: &str| serde_json::from_str::<UpdatedMemory>(json).unwrap();
+        assert_eq!(parse("{}").expires_at, None);
+        assert_eq!(parse(r#"{"expiresAt":null}"#).expires_at, Some(None));
+        assert!(matches!(
+            parse(r#"{"expiresAt":"2030-01-01T00:00:00Z"}"#).expires_at,
+            Some(Some(_))
+        ));
+    }
+}
@@ -1,12 +1,19 @@
 pub mod api_response;
 pub mod avatar;
+pub mod code_index;
+pub mod document;
+pub mod domain_rule;
 pub mod error_response;
 pub mod frontend_user;
 pub mod login_user;
+pub mod memory;
 pub mod page;
 pub mod register_user;
+pub mod repository;
+pub mod search;
 pub mod session;
 pub mod token;
 pub mod updated_session;
 pub mod updated_user;
+pub mod updated_user_settings;
 pub mod workspace;
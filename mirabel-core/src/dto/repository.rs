@@ -0,0 +1,20 @@
+use serde::Deserialize;
+use serde::Serialize;
+use ts_rs::TS;
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct NewRepository {
+    /// A local path or a URL to clone from
+    pub source: String,
+    /// Taken from the source when left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub name: Option<String>,
+    /// Merge sessions straight into the default branch of the source instead
+    /// of pushing their branches, off when left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub merge_upstream: Option<bool>,
+}
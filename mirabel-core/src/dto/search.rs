@@ -0,0 +1,187 @@
+use chrono::DateTime;
+use chrono::Utc;
+use serde::Deserialize;
+use serde::Serialize;
+use ts_rs::TS;
+
+/// Mark the matched words of a snippet until it is split into parts. Control
+/// characters do not show up in timeline text.
+pub const HIGHLIGHT_START: char = '\u{2}';
+pub const HIGHLIGHT_STOP: char = '\u{3}';
+
+/// A search over the timelines of the sessions of a workspace
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct TimelineSearch {
+    pub query: String,
+    /// Comma separated content types to search in, e.g. `message,spec`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub types: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub from: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub to: Option<DateTime<Utc>>,
+    /// Also finds entries that mean the same in other words, when the server
+    /// embeds timelines. On unless turned off.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub semantic: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub limit: Option<i64>,
+}
+
+impl TimelineSearch {
+    pub fn types(&self) -> Option<Vec<String>> {
+        self.types.as_ref().map(|types| {
+            types
+                .split(',')
+                .map(str::trim)
+                .filter(|kind| !kind.is_empty())
+                .map(String::from)
+                .collect()
+        })
+    }
+}
+
+/// A timeline entry that matched a search
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct SearchHit {
+    pub entry_id: String,
+    pub session_id: String,
+    pub session_title: String,
+    pub content_type: String,
+    pub created_at: DateTime<Utc>,
+    /// The text of the entry around the matches
+    pub snippet: Vec<SnippetPart>,
+    pub score: f32,
+    /// Loading the session timeline `before` this cursor gives the page that
+    /// ends with the entry
+    pub cursor: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct SnippetPart {
+    pub text: String,
+    /// The part matched the query
+    pub highlighted: bool,
+}
+
+impl SnippetPart {
+    /// Splits text with matches between `HIGHLIGHT_START` and
+    /// `HIGHLIGHT_STOP` into parts
+    pub fn parse(marked: &str) -> Vec<Self> {
+        let mut parts = Vec::new();
+        let mut rest = marked;
+        while let Some(start) = rest.find(HIGHLIGHT_START) {
+            push(&mut parts, &rest[..start], false);
+            rest = &rest[start + HIGHLIGHT_START.len_utf8()..];
+            let stop = rest.find(HIGHLIGHT_STOP).unwrap_or(rest.len());
+            push(&mut parts, &rest[..stop], true);
+            rest = rest
+                .get(stop + HIGHLIGHT_STOP.len_utf8()..)
+                .unwrap_or_default();
+        }
+        push(&mut parts, rest, false);
+        parts
+    }
+}
+
+/// Adjacent parts alike are merged, e.g. two matched words in a row
+fn push(parts: &mut Vec<SnippetPart>, text: &str, highlighted: bool) {
+    if text.is_empty() {
+        return;
+    }
+    match parts.last_mut() {
+        Some(last) if last.highlighted == highlighted => last.text.push_str(text),
+        _ => parts.push(SnippetPart {
+            text: text.to_string(),
+            highlighted,
+        }),
+    }
+}
+
+/// Whether a search engine is asked, see [`EngineStatus`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub enum CircuitState {
+    /// The engine answers and is asked
+    #[default]
+    Closed,
+    /// The engine failed repeatedly and is left alone for a while
+    Open,
+    /// The pause is over, the next search tries the engine again
+    HalfOpen,
+}
+
+/// The health of a search engine and how it did since the server started
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct EngineStatus {
+    pub name: String,
+    pub state: CircuitState,
+    /// Failures since the engine last answered
+    pub consecutive_failures: u32,
+    pub searches: u64,
+    /// Searches that failed, including the ones that timed out
+    pub failures: u64,
+    pub timeouts: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub average_latency_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub last_latency_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub last_error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub last_success_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub last_failure_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub last_probe_at: Option<DateTime<Utc>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_snippet() {
+        let part = |text: &str, highlighted| SnippetPart {
+            text: text.into(),
+            highlighted,
+        };
+        assert_eq!(
+            SnippetPart::parse("\u{2}deploy\u{3} the backend with \u{2}Kubernetes\u{3}"),
+            vec![
+                part("deploy", true),
+                part(" the backend with ", false),
+                part("Kubernetes", true),
+            ]
+        );
+        assert_eq!(
+            SnippetPart::parse("\u{2}helm\u{3}\u{2}chart\u{3} <b>"),
+            vec![part("helmchart", true), part(" <b>", false)]
+        );
+        assert_eq!(
+            SnippetPart::parse("no match"),
+            vec![part("no match", false)]
+        );
+        assert!(SnippetPart::parse("").is_empty());
+    }
+}
@@ -1 +1,293 @@
+use chrono::DateTime;
+use chrono::Utc;
+use serde::Deserialize;
+use serde::Serialize;
+use ts_rs::TS;
 
+use crate::models::plan::PlanAction;
+use crate::models::plan::PlanProgress;
+use crate::models::timeline::PromptAnswer;
+use crate::models::timeline::TimelineEntry;
+
+/// Version of the session socket protocol, bumped on breaking changes to the
+/// envelopes or events below.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Envelope for everything the server sends to a session subscriber.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct ServerMessage {
+    pub version: u32,
+    pub event: ServerEvent,
+}
+
+impl ServerMessage {
+    pub fn new(event: ServerEvent) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            event,
+        }
+    }
+}
+
+impl From<ServerEvent> for ServerMessage {
+    fn from(event: ServerEvent) -> Self {
+        Self::new(event)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[serde(tag = "type", content = "data")]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub enum ServerEvent {
+    TimelineEntry(TimelineEntry),
+    StateChanged(SessionWorkerState),
+    Error(ProtocolError),
+    Ack(Ack),
+    Presence(Presence),
+    Heartbeat(Heartbeat),
+    PlanProgress(PlanProgress),
+    /// Raw terminal output of the session shell, as it arrives
+    ShellOutput(ShellOutput),
+    /// Who controls the session shell, users can only type while they do
+    ShellDriver(ShellDriver),
+}
+
+impl ServerEvent {
+    pub fn error(
+        code: ErrorCode,
+        message: impl Into<String>,
+        correlation_id: Option<String>,
+    ) -> Self {
+        ServerEvent::Error(ProtocolError {
+            code,
+            message: message.into(),
+            correlation_id,
+        })
+    }
+
+    pub fn ack(correlation_id: String) -> Self {
+        ServerEvent::Ack(Ack { correlation_id })
+    }
+
+    pub fn heartbeat() -> Self {
+        ServerEvent::Heartbeat(Heartbeat {
+            timestamp: Utc::now(),
+        })
+    }
+}
+
+/// Envelope for everything a subscriber sends to the server. When a
+/// `correlation_id` is given, the server answers with an [`Ack`] once the
+/// message is accepted, and with a [`ProtocolError`] carrying the same id if
+/// it is rejected or fails later on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct ClientMessage {
+    pub version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub correlation_id: Option<String>,
+    pub event: ClientEvent,
+}
+
+impl ClientMessage {
+    pub fn new(event: ClientEvent, correlation_id: Option<String>) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            correlation_id,
+            event,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[serde(tag = "type", content = "data")]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub enum ClientEvent {
+    Interaction(UserInteraction),
+    Heartbeat(Heartbeat),
+    /// The user is typing, relayed to everyone else as [`Presence`]
+    Typing,
+    Shell(ShellInput),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(tag = "type", rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub enum ShellInput {
+    /// Keystrokes, sent to the terminal as they are
+    Data {
+        data: String,
+    },
+    Resize {
+        rows: u16,
+        cols: u16,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct ShellOutput {
+    pub data: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub enum ShellDriver {
+    #[default]
+    User,
+    /// An agent is running commands, the shell is read-only for users
+    Agent,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[serde(tag = "type")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub enum UserInteraction {
+    Message {
+        content: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    PromptResponse {
+        prompt_id: String,
+        answer: PromptAnswer,
+    },
+    #[serde(rename_all = "camelCase")]
+    Plan {
+        plan_id: String,
+        action: PlanAction,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct ProtocolError {
+    pub code: ErrorCode,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub correlation_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub enum ErrorCode {
+    InvalidMessage,
+    UnsupportedVersion,
+    SessionClosed,
+    Forbidden,
+    BadRequest,
+    Internal,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct Ack {
+    pub correlation_id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct Presence {
+    pub user_id: String,
+    pub username: String,
+    pub status: PresenceStatus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub enum PresenceStatus {
+    Joined,
+    Left,
+    Typing,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct Heartbeat {
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub enum SessionWorkerState {
+    Stopped,
+    Initializing,
+    Idle,
+    Paused,
+    Running,
+    Stopping,
+    Error(SessionWorkerError),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub enum SessionWorkerError {
+    Generic(String),
+}
+
+impl From<&str> for SessionWorkerError {
+    fn from(err: &str) -> Self {
+        SessionWorkerError::Generic(err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_message_roundtrip() {
+        let json = r#"{
+            "version": 1,
+            "correlationId": "abc",
+            "event": {
+                "type": "interaction",
+                "data": { "type": "message", "content": "Hello" }
+            }
+        }"#;
+        let message: ClientMessage = serde_json::from_str(json).unwrap();
+        assert_eq!(message.correlation_id.as_deref(), Some("abc"));
+        assert_eq!(
+            message.event,
+            ClientEvent::Interaction(UserInteraction::Message {
+                content: "Hello".into()
+            })
+        );
+        let serialized = serde_json::to_string(&message).unwrap();
+        assert_eq!(
+            serde_json::from_str::<ClientMessage>(&serialized).unwrap(),
+            message
+        );
+    }
+
+    #[test]
+    fn test_server_error_shape() {
+        let message = ServerMessage::new(ServerEvent::error(
+            ErrorCode::InvalidMessage,
+            "Nope",
+            Some("abc".into()),
+        ));
+        let value = serde_json::to_value(&message).unwrap();
+        assert_eq!(value["version"], PROTOCOL_VERSION);
+        assert_eq!(value["event"]["type"], "error");
+        assert_eq!(value["event"]["data"]["code"], "invalidMessage");
+        assert_eq!(value["event"]["data"]["correlationId"], "abc");
+    }
+}
@@ -3,7 +3,7 @@ use serde::Serialize;
 
 use crate::{
     dto::page::PageResponse,
-    models::{session::Session, timeline::TimelineEntry},
+    models::{plan::Plan, session::Session, timeline::TimelineEntry},
 };
 
 pub mod event;
@@ -15,6 +15,7 @@ pub struct FullSession {
     pub archived: bool,
     pub timeline: PageResponse<TimelineEntry>,
     pub spec: Option<String>,
+    pub plan: Option<Plan>,
     pub shell: Option<Vec<String>>,
 }
 
@@ -23,6 +24,7 @@ impl FullSession {
         session: Session,
         timeline: PageResponse<TimelineEntry>,
         spec: Option<String>,
+        plan: Option<Plan>,
         shell: Option<Vec<String>>,
     ) -> FullSession {
         FullSession {
@@ -31,6 +33,7 @@ impl FullSession {
             archived: session.archived,
             timeline,
             spec,
+            plan,
             shell,
         }
     }
@@ -0,0 +1,11 @@
+use serde::Deserialize;
+use ts_rs::TS;
+
+#[derive(Deserialize, Debug, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct UpdatedUserSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub auto_approve_plans: Option<bool>,
+}
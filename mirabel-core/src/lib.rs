@@ -14,7 +14,23 @@ mod tests {
         frontend_user::FrontendUser,
         login_user::LoginUser,
         register_user::RegisterUser,
+        session::event::{ClientMessage, ServerMessage},
     };
+    use crate::dto::code_index::{IndexStats, SymbolLocation};
+    use crate::dto::domain_rule::NewDomainRule;
+    use crate::dto::memory::{NewMemory, UpdatedMemory};
+    use crate::dto::repository::NewRepository;
+    use crate::dto::search::{EngineStatus, SearchHit, TimelineSearch};
+    use crate::dto::updated_user_settings::UpdatedUserSettings;
+    use crate::models::code_index::{CodeFile, CodeSymbol};
+    use crate::models::domain_rule::DomainRule;
+    use crate::models::file_action::FileAction;
+    use crate::models::memory::Memory;
+    use crate::models::plan::{Plan, PlanEdit};
+    use crate::models::repository::{Repository, Worktree};
+    use crate::models::spec::Spec;
+    use crate::models::user::UserSettings;
+    use crate::models::timeline::TimelineEntry;
     use crate::models::workspace::{Workspace, WorkspaceRole, WorkspaceMember};
     use ts_rs::TS;
 
@@ -26,5 +42,29 @@ mod tests {
         Workspace::export().unwrap();
         WorkspaceRole::export().unwrap();
         WorkspaceMember::export().unwrap();
+        TimelineEntry::export_all().unwrap();
+        Spec::export_all().unwrap();
+        Plan::export_all().unwrap();
+        FileAction::export_all().unwrap();
+        Repository::export_all().unwrap();
+        Worktree::export_all().unwrap();
+        NewRepository::export_all().unwrap();
+        CodeFile::export_all().unwrap();
+        CodeSymbol::export_all().unwrap();
+        SymbolLocation::export_all().unwrap();
+        IndexStats::export_all().unwrap();
+        Memory::export_all().unwrap();
+        NewMemory::export_all().unwrap();
+        UpdatedMemory::export_all().unwrap();
+        TimelineSearch::export_all().unwrap();
+        SearchHit::export_all().unwrap();
+        EngineStatus::export_all().unwrap();
+        DomainRule::export_all().unwrap();
+        NewDomainRule::export_all().unwrap();
+        PlanEdit::export_all().unwrap();
+        UserSettings::export_all().unwrap();
+        UpdatedUserSettings::export_all().unwrap();
+        ClientMessage::export_all().unwrap();
+        ServerMessage::export_all().unwrap();
     }
 }
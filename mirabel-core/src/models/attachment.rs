@@ -0,0 +1,79 @@
+use chrono::DateTime;
+use chrono::Utc;
+use diesel::{
+    Selectable,
+    prelude::{Insertable, Queryable},
+};
+
+use crate::utils::id::id;
+
+pub const PNG: &str = "image/png";
+
+/// A file that belongs to a session, e.g. a screenshot shown on its timeline
+/// or a document uploaded for the agents to read. Timeline entries refer to
+/// it by id, the file itself is served on its own.
+#[derive(Debug, Clone, PartialEq, Eq, Queryable, Selectable, Insertable)]
+#[diesel(table_name = crate::schema::attachments)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Attachment {
+    pub id: String,
+    pub session_id: String,
+    pub media_type: String,
+    pub data: Vec<u8>,
+    pub created_at: DateTime<Utc>,
+    /// The file name it was uploaded with
+    pub name: Option<String>,
+}
+
+/// A part of a document attachment. Documents are cut into chunks when they
+/// are uploaded, so searching them needs no conversion.
+#[derive(Debug, Clone, PartialEq, Queryable, Selectable, Insertable)]
+#[diesel(table_name = crate::schema::attachment_chunks)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct AttachmentChunk {
+    pub attachment_id: String,
+    /// Stays the same for as long as the text of the chunk does
+    pub id: String,
+    /// Where the chunk is in the document, the first one is 0
+    pub position: i32,
+    /// Byte offsets of the text in the markdown the document converts to
+    pub start: i32,
+    pub end: i32,
+    pub text: String,
+    /// The headings the text is under, the outermost first
+    pub headings: Vec<String>,
+    /// The page the text starts on, for documents with pages
+    pub page: Option<i32>,
+    pub tokens: i32,
+    /// Made the first time the chunk is searched by meaning
+    pub embedding: Option<Vec<f32>>,
+}
+
+impl Attachment {
+    pub fn png(session_id: String, data: Vec<u8>) -> Self {
+        Self {
+            id: id!(),
+            session_id,
+            media_type: PNG.to_string(),
+            data,
+            created_at: Utc::now(),
+            name: None,
+        }
+    }
+
+    pub fn document(
+        session_id: String,
+        name: Option<String>,
+        media_type: String,
+        data: Vec<u8>,
+    ) -> Self {
+        Self {
+            id: id!(),
+            session_id,
+            media_type,
+            data,
+            created_at: Utc::now(),
+            name,
+        }
+    }
+}
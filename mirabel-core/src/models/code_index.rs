@@ -0,0 +1,259 @@
+use std::io::Write;
+
+use chrono::DateTime;
+use chrono::Utc;
+use diesel::{
+    Selectable,
+    deserialize::{FromSql, FromSqlRow},
+    expression::AsExpression,
+    pg::{Pg, PgValue},
+    prelude::{Insertable, Queryable},
+    serialize::{IsNull, ToSql},
+    sql_types::Integer,
+};
+
+use serde::Deserialize;
+use serde::Serialize;
+use ts_rs::TS;
+
+use crate::utils::id::id;
+
+/// A source file of a worktree as it was when it was last indexed
+#[derive(
+    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Queryable, Selectable, Insertable, TS,
+)]
+#[diesel(table_name = crate::schema::code_files)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct CodeFile {
+    pub id: String,
+    pub worktree_id: String,
+    /// Relative to the worktree
+    pub path: String,
+    pub language: CodeLanguage,
+    /// Hash of the content, files with the same hash are not parsed again
+    pub hash: String,
+    pub indexed_at: DateTime<Utc>,
+}
+
+impl CodeFile {
+    pub fn new(worktree_id: String, path: String, language: CodeLanguage, hash: String) -> Self {
+        Self {
+            id: id!(),
+            worktree_id,
+            path,
+            language,
+            hash,
+            indexed_at: Utc::now(),
+        }
+    }
+}
+
+/// Where a symbol is defined or referenced
+#[derive(
+    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Queryable, Selectable, Insertable, TS,
+)]
+#[diesel(table_name = crate::schema::code_symbols)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct CodeSymbol {
+    pub id: String,
+    pub file_id: String,
+    pub worktree_id: String,
+    pub name: String,
+    pub kind: SymbolKind,
+    /// A definition of the symbol, otherwise a reference to it
+    pub definition: bool,
+    /// Lines and columns start at 1
+    pub line: i32,
+    pub column: i32,
+    pub end_line: i32,
+    /// First line of a definition, e.g. the signature of a function
+    pub signature: Option<String>,
+}
+
+#[repr(i32)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, AsExpression, FromSqlRow, Serialize, Deserialize, TS,
+)]
+#[diesel(sql_type = Integer)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub enum CodeLanguage {
+    Rust = 0,
+    Python = 1,
+    JavaScript = 2,
+    TypeScript = 3,
+    Go = 4,
+}
+
+impl CodeLanguage {
+    /// The language of a file by its extension, `None` for files that are not
+    /// indexed
+    pub fn from_path(path: &str) -> Option<Self> {
+        let (_, extension) = path.rsplit_once('.')?;
+        match extension {
+            "rs" => Some(CodeLanguage::Rust),
+            "py" | "pyi" => Some(CodeLanguage::Python),
+            "js" | "jsx" | "mjs" | "cjs" => Some(CodeLanguage::JavaScript),
+            "ts" | "tsx" | "mts" | "cts" => Some(CodeLanguage::TypeScript),
+            "go" => Some(CodeLanguage::Go),
+            _ => None,
+        }
+    }
+
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(CodeLanguage::Rust),
+            1 => Some(CodeLanguage::Python),
+            2 => Some(CodeLanguage::JavaScript),
+            3 => Some(CodeLanguage::TypeScript),
+            4 => Some(CodeLanguage::Go),
+            _ => None,
+        }
+    }
+
+    pub fn to_i32(self) -> i32 {
+        match self {
+            CodeLanguage::Rust => 0,
+            CodeLanguage::Python => 1,
+            CodeLanguage::JavaScript => 2,
+            CodeLanguage::TypeScript => 3,
+            CodeLanguage::Go => 4,
+        }
+    }
+}
+
+impl FromSql<Integer, Pg> for CodeLanguage {
+    fn from_sql(bytes: PgValue) -> diesel::deserialize::Result<Self> {
+        let value = i32::from_sql(bytes)?;
+        match CodeLanguage::from_i32(value) {
+            Some(language) => Ok(language),
+            None => Err(format!("Invalid CodeLanguage value: {value}").into()),
+        }
+    }
+}
+
+impl ToSql<Integer, Pg> for CodeLanguage {
+    fn to_sql<'b>(
+        &'b self,
+        out: &mut diesel::serialize::Output<'b, '_, Pg>,
+    ) -> diesel::serialize::Result {
+        let value = self.to_i32();
+        out.write_all(&value.to_be_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+#[repr(i32)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, AsExpression, FromSqlRow, Serialize, Deserialize, TS,
+)]
+#[diesel(sql_type = Integer)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub enum SymbolKind {
+    Function = 0,
+    Method = 1,
+    Class = 2,
+    Interface = 3,
+    Module = 4,
+    Macro = 5,
+    Constant = 6,
+    Type = 7,
+    Call = 8,
+    Implementation = 9,
+}
+
+impl SymbolKind {
+    /// The kind of a tree-sitter tag, the part after `definition.` or
+    /// `reference.` in tag queries
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        match tag {
+            "function" => Some(SymbolKind::Function),
+            "method" => Some(SymbolKind::Method),
+            "class" => Some(SymbolKind::Class),
+            "interface" => Some(SymbolKind::Interface),
+            "module" => Some(SymbolKind::Module),
+            "macro" => Some(SymbolKind::Macro),
+            "constant" => Some(SymbolKind::Constant),
+            "type" => Some(SymbolKind::Type),
+            "call" => Some(SymbolKind::Call),
+            "implementation" => Some(SymbolKind::Implementation),
+            _ => None,
+        }
+    }
+
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(SymbolKind::Function),
+            1 => Some(SymbolKind::Method),
+            2 => Some(SymbolKind::Class),
+            3 => Some(SymbolKind::Interface),
+            4 => Some(SymbolKind::Module),
+            5 => Some(SymbolKind::Macro),
+            6 => Some(SymbolKind::Constant),
+            7 => Some(SymbolKind::Type),
+            8 => Some(SymbolKind::Call),
+            9 => Some(SymbolKind::Implementation),
+            _ => None,
+        }
+    }
+
+    pub fn to_i32(self) -> i32 {
+        match self {
+            SymbolKind::Function => 0,
+            SymbolKind::Method => 1,
+            SymbolKind::Class => 2,
+            SymbolKind::Interface => 3,
+            SymbolKind::Module => 4,
+            SymbolKind::Macro => 5,
+            SymbolKind::Constant => 6,
+            SymbolKind::Type => 7,
+            SymbolKind::Call => 8,
+            SymbolKind::Implementation => 9,
+        }
+    }
+}
+
+impl FromSql<Integer, Pg> for SymbolKind {
+    fn from_sql(bytes: PgValue) -> diesel::deserialize::Result<Self> {
+        let value = i32::from_sql(bytes)?;
+        match SymbolKind::from_i32(value) {
+            Some(kind) => Ok(kind),
+            None => Err(format!("Invalid SymbolKind value: {value}").into()),
+        }
+    }
+}
+
+impl ToSql<Integer, Pg> for SymbolKind {
+    fn to_sql<'b>(
+        &'b self,
+        out: &mut diesel::serialize::Output<'b, '_, Pg>,
+    ) -> diesel::serialize::Result {
+        let value = self.to_i32();
+        out.write_all(&value.to_be_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_language_from_path() {
+        assert_eq!(
+            CodeLanguage::from_path("src/main.rs"),
+            Some(CodeLanguage::Rust)
+        );
+        assert_eq!(
+            CodeLanguage::from_path("web/app.component.tsx"),
+            Some(CodeLanguage::TypeScript)
+        );
+        assert_eq!(CodeLanguage::from_path("README.md"), None);
+        assert_eq!(CodeLanguage::from_path("Makefile"), None);
+    }
+}
@@ -0,0 +1,164 @@
+use std::io::Write;
+
+use chrono::DateTime;
+use chrono::Utc;
+use diesel::{
+    Selectable,
+    deserialize::{FromSql, FromSqlRow},
+    expression::AsExpression,
+    pg::{Pg, PgValue},
+    prelude::{Insertable, Queryable},
+    serialize::{IsNull, ToSql},
+    sql_types::Integer,
+};
+
+use serde::Deserialize;
+use serde::Serialize;
+use ts_rs::TS;
+
+use crate::Error;
+use crate::Result;
+use crate::utils::id::id;
+
+/// Whether the pages of a domain and its subdomains may be fetched for a
+/// workspace. Once a workspace allows any domain, only allowed domains are
+/// fetched, and denied domains never are.
+#[derive(
+    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Queryable, Selectable, Insertable, TS,
+)]
+#[diesel(table_name = crate::schema::domain_rules)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct DomainRule {
+    pub id: String,
+    pub workspace_id: String,
+    /// Lowercase, without scheme, port or path, e.g. `docs.rs`
+    pub domain: String,
+    pub access: DomainAccess,
+    pub created_at: DateTime<Utc>,
+}
+
+impl DomainRule {
+    pub fn new(workspace_id: String, domain: String, access: DomainAccess) -> Self {
+        Self {
+            id: id!(),
+            workspace_id,
+            domain,
+            access,
+            created_at: Utc::now(),
+        }
+    }
+
+    /// Whether the rule covers `host`, which is the domain or a subdomain
+    pub fn covers(&self, host: &str) -> bool {
+        let host = host.trim_end_matches('.').to_lowercase();
+        host == self.domain
+            || host
+                .strip_suffix(&self.domain)
+                .is_some_and(|sub| sub.ends_with('.'))
+    }
+
+    /// Turns what users paste, e.g. `https://*.Docs.rs/std`, into the domain
+    pub fn normalize_domain(input: &str) -> Result<String> {
+        let input = input.trim().to_lowercase();
+        let input = input
+            .split_once("://")
+            .map_or(input.as_str(), |(_, rest)| rest);
+        let host = input
+            .split(['/', '?', '#'])
+            .next()
+            .unwrap_or_default()
+            .rsplit('@')
+            .next()
+            .unwrap_or_default();
+        let host = host.split(':').next().unwrap_or_default();
+        let domain = host.trim_start_matches("*.").trim_matches('.');
+        let valid = !domain.is_empty()
+            && domain
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.');
+        if !valid {
+            return Err(Error::BadRequest(format!(
+                "'{}' is not a domain.",
+                input.trim()
+            )));
+        }
+        Ok(domain.to_string())
+    }
+}
+
+#[repr(i32)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow, Serialize, Deserialize, TS,
+)]
+#[diesel(sql_type = Integer)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub enum DomainAccess {
+    Allow = 0,
+    Deny = 1,
+}
+
+impl DomainAccess {
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(DomainAccess::Allow),
+            1 => Some(DomainAccess::Deny),
+            _ => None,
+        }
+    }
+
+    pub fn to_i32(self) -> i32 {
+        match self {
+            DomainAccess::Allow => 0,
+            DomainAccess::Deny => 1,
+        }
+    }
+}
+
+impl FromSql<Integer, Pg> for DomainAccess {
+    fn from_sql(bytes: PgValue) -> diesel::deserialize::Result<Self> {
+        let value = i32::from_sql(bytes)?;
+        match DomainAccess::from_i32(value) {
+            Some(access) => Ok(access),
+            None => Err(format!("Invalid DomainAccess value: {value}").into()),
+        }
+    }
+}
+
+impl ToSql<Integer, Pg> for DomainAccess {
+    fn to_sql<'b>(
+        &'b self,
+        out: &mut diesel::serialize::Output<'b, '_, Pg>,
+    ) -> diesel::serialize::Result {
+        let value = self.to_i32();
+        out.write_all(&value.to_be_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_domain() {
+        let domain = |input| DomainRule::normalize_domain(input).unwrap();
+        assert_eq!(domain("docs.rs"), "docs.rs");
+        assert_eq!(domain(" https://*.Docs.RS:443/std?q=1 "), "docs.rs");
+        assert_eq!(domain("http://user@example.com."), "example.com");
+        assert!(DomainRule::normalize_domain("").is_err());
+        assert!(DomainRule::normalize_domain("https:///path").is_err());
+        assert!(DomainRule::normalize_domain("exa mple.com").is_err());
+    }
+
+    #[test]
+    fn test_covers() {
+        let rule = DomainRule::new("w".into(), "example.com".into(), DomainAccess::Deny);
+        assert!(rule.covers("example.com"));
+        assert!(rule.covers("docs.Example.com."));
+        assert!(!rule.covers("badexample.com"));
+        assert!(!rule.covers("example.com.evil.org"));
+    }
+}
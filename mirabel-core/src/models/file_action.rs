@@ -0,0 +1,113 @@
+use chrono::DateTime;
+use chrono::Utc;
+use diesel::{
+    Selectable,
+    prelude::{Insertable, Queryable},
+};
+
+use serde::Deserialize;
+use serde::Serialize;
+use ts_rs::TS;
+
+use crate::models::timeline::ActionType;
+use crate::utils::id::id;
+
+/// A change to a file in the sandbox of a session. The content on both sides
+/// is kept, so the change can be reverted as long as nobody changed the same
+/// lines since.
+#[derive(
+    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Queryable, Selectable, Insertable, TS,
+)]
+#[diesel(table_name = crate::schema::file_actions)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct FileAction {
+    pub id: String,
+    pub session_id: String,
+    pub action_type: ActionType,
+    /// Relative to the sandbox
+    pub path: String,
+    /// Absent when the file was created
+    pub before: Option<String>,
+    /// Absent when the file was deleted
+    pub after: Option<String>,
+    /// Unified diff of the change
+    pub diff: String,
+    /// The action this one reverted
+    pub reverts: Option<String>,
+    pub reverted_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl FileAction {
+    pub fn new(
+        session_id: String,
+        path: String,
+        before: Option<String>,
+        after: Option<String>,
+        diff: String,
+        reverts: Option<String>,
+    ) -> Self {
+        let action_type = match (&before, &after) {
+            (None, _) => ActionType::NewFile,
+            (_, None) => ActionType::DeleteFile,
+            _ => ActionType::EditFile,
+        };
+        Self {
+            id: id!(),
+            session_id,
+            action_type,
+            path,
+            before,
+            after,
+            diff,
+            reverts,
+            reverted_at: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn is_reverted(&self) -> bool {
+        self.reverted_at.is_some()
+    }
+
+    /// One line describing the action for the timeline
+    pub fn summary(&self) -> String {
+        let verb = match (self.action_type, self.reverts.is_some()) {
+            (ActionType::NewFile, false) => "Created",
+            (ActionType::NewFile, true) => "Restored",
+            (ActionType::DeleteFile, false) => "Deleted",
+            (ActionType::DeleteFile, true) => "Removed",
+            (_, false) => "Edited",
+            (_, true) => "Reverted the changes to",
+        };
+        format!("{verb} {}", self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_action_type() {
+        let action = |before: Option<&str>, after: Option<&str>| {
+            FileAction::new(
+                "session".into(),
+                "src/main.rs".into(),
+                before.map(String::from),
+                after.map(String::from),
+                String::new(),
+                None,
+            )
+        };
+        assert_eq!(action(None, Some("a")).action_type, ActionType::NewFile);
+        assert_eq!(
+            action(Some("a"), Some("b")).action_type,
+            ActionType::EditFile
+        );
+        assert_eq!(action(Some("a"), None).action_type, ActionType::DeleteFile);
+        assert_eq!(action(Some("a"), None).summary(), "Deleted src/main.rs");
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::HashSet;
+use std::io::Write;
+
+use chrono::DateTime;
+use chrono::Utc;
+use diesel::{
+    Selectable,
+    deserialize::{FromSql, FromSqlRow},
+    expression::AsExpression,
+    pg::{Pg, PgValue},
+    prelude::{Insertable, Queryable},
+    serialize::{IsNull, ToSql},
+    sql_types::Integer,
+};
+
+use serde::Deserialize;
+use serde::Serialize;
+use ts_rs::TS;
+
+use crate::Error;
+use crate::Result;
+use crate::utils::id::id;
+use crate::utils::vector::cosine_similarity;
+
+/// How much the embedding similarity counts against the keyword match when
+/// both are available
+const SIMILARITY_WEIGHT: f32 = 0.7;
+
+/// Something Mirabel learned about a workspace, e.g. that it formats with
+/// tabs. Memories are recalled for agents working in the workspace.
+#[derive(
+    Debug, Clone, PartialEq, Serialize, Deserialize, Queryable, Selectable, Insertable, TS,
+)]
+#[diesel(table_name = crate::schema::memories)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct Memory {
+    pub id: String,
+    pub workspace_id: String,
+    pub kind: MemoryKind,
+    pub content: String,
+    /// The session the memory was learned in, absent for memories users
+    /// added themselves
+    pub source_session_id: Option<String>,
+    /// The timeline entry the memory was learned from
+    pub source_entry_id: Option<String>,
+    /// Between 0 and 1, how sure Mirabel is that the memory holds
+    pub confidence: f32,
+    #[serde(skip)]
+    #[ts(skip)]
+    pub embedding: Option<Vec<f32>>,
+    /// Memories are forgotten after this, e.g. for temporary decisions
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub modified_at: DateTime<Utc>,
+}
+
+impl Memory {
+    pub fn new(workspace_id: String, kind: MemoryKind, content: String, confidence: f32) -> Self {
+        Self {
+            id: id!(),
+            workspace_id,
+            kind,
+            content,
+            source_session_id: None,
+            source_entry_id: None,
+            confidence: confidence.clamp(0.0, 1.0),
+            embedding: None,
+            expires_at: None,
+            created_at: Utc::now(),
+            modified_at: Utc::now(),
+        }
+    }
+
+    pub fn validate_content(content: &str) -> Result<()> {
+        if content.trim().is_empty() {
+            return Err(Error::BadRequest("A memory cannot be empty.".into()));
+        }
+        Ok(())
+    }
+
+    pub fn validate_confidence(confidence: f32) -> Result<()> {
+        if !(0.0..=1.0).contains(&confidence) {
+            return Err(Error::BadRequest(
+                "The confidence has to be between 0 and 1.".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    /// How relevant the memory is to a query, between 0 and 1. Embeddings
+    /// are compared when both sides have one, words always count, and the
+    /// result is weighed by the confidence.
+    pub fn relevance(&self, query: &str, embedding: Option<&[f32]>) -> f32 {
+        let keywords = keyword_match(query, &self.content);
+        let score = match (embedding, self.embedding.as_deref()) {
+            (Some(query), Some(memory)) => {
+                SIMILARITY_WEIGHT * cosine_similarity(query, memory).max(0.0)
+                    + (1.0 - SIMILARITY_WEIGHT) * keywords
+            }
+            _ => keywords,
+        };
+        score * self.confidence
+    }
+}
+
+#[repr(i32)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow, Serialize, Deserialize, TS,
+)]
+#[diesel(sql_type = Integer)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub enum MemoryKind {
+    /// How things are, e.g. which database the project uses
+    Fact = 0,
+    /// What users like, e.g. short answers
+    Preference = 1,
+    /// How things are done, e.g. naming or formatting rules
+    Convention = 2,
+}
+
+impl MemoryKind {
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(MemoryKind::Fact),
+            1 => Some(MemoryKind::Preference),
+            2 => Some(MemoryKind::Convention),
+            _ => None,
+        }
+    }
+
+    pub fn to_i32(self) -> i32 {
+        match self {
+            MemoryKind::Fact => 0,
+            MemoryKind::Preference => 1,
+            MemoryKind::Convention => 2,
+        }
+    }
+}
+
+impl FromSql<Integer, Pg> for MemoryKind {
+    fn from_sql(bytes: PgValue) -> diesel::deserialize::Result<Self> {
+        let value = i32::from_sql(bytes)?;
+        match MemoryKind::from_i32(value) {
+            Some(kind) => Ok(kind),
+            None => Err(format!("Invalid MemoryKind value: {value}").into()),
+        }
+    }
+}
+
+impl ToSql<Integer, Pg> for MemoryKind {
+    fn to_sql<'b>(
+        &'b self,
+        out: &mut diesel::serialize::Output<'b, '_, Pg>,
+    ) -> diesel::serialize::Result {
+        let value = self.to_i32();
+        out.write_all(&value.to_be_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+/// The share of the words of `query` that appear in `content`, short words
+/// like "the" are not counted
+fn keyword_match(query: &str, content: &str) -> f32 {
+    let query = words(query);
+    if query.is_empty() {
+        return 0.0;
+    }
+    let content = words(content);
+    query.intersection(&content).count() as f32 / query.len() as f32
+}
+
+fn words(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|word| word.chars().count() > 3)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relevance() {
+        let mut memory = Memory::new(
+            "workspace".into(),
+            MemoryKind::Convention,
+            "Indent Python code with tabs".into(),
+            1.0,
+        );
+        assert_eq!(memory.relevance("How is python indented?", None), 0.5);
+        assert_eq!(memory.relevance("Deploy the frontend", None), 0.0);
+
+        memory.embedding = Some(vec![1.0, 0.0]);
+        let similar = memory.relevance("Deploy the frontend", Some(&[1.0, 0.0]));
+        assert!((similar - SIMILARITY_WEIGHT).abs() < 1e-6);
+
+        memory.confidence = 0.5;
+        assert_eq!(memory.relevance("python", None), 0.5);
+        assert!(!memory.is_expired(Utc::now()));
+        memory.expires_at = Some(Utc::now());
+        assert!(memory.is_expired(Utc::now()));
+    }
+}
@@ -1,6 +1,15 @@
+pub mod attachment;
+pub mod code_index;
+pub mod domain_rule;
+pub mod file_action;
 pub mod job;
+pub mod memory;
+pub mod plan;
 pub mod prompts;
+pub mod repository;
 pub mod session;
+pub mod spec;
 pub mod timeline;
 pub mod user;
+pub mod web_cache;
 pub mod workspace;
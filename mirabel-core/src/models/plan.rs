@@ -0,0 +1,820 @@
+use std::collections::HashSet;
+use std::io::Write;
+
+use chrono::DateTime;
+use chrono::Utc;
+use diesel::{
+    Selectable,
+    deserialize::{FromSql, FromSqlRow},
+    expression::AsExpression,
+    pg::{Pg, PgValue},
+    prelude::{Insertable, Queryable},
+    serialize::{IsNull, ToSql},
+    sql_types::{Integer, Jsonb},
+};
+
+use serde::Deserialize;
+use serde::Serialize;
+use ts_rs::TS;
+
+use crate::Error;
+use crate::Result;
+use crate::utils::id::id;
+
+/// A hierarchical plan for an accepted spec, the root workflow carries the
+/// overall goal.
+#[derive(
+    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Queryable, Selectable, Insertable, TS,
+)]
+#[diesel(table_name = crate::schema::plans)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct Plan {
+    pub id: String,
+    pub session_id: String,
+    pub spec_id: String,
+    pub status: PlanStatus,
+    pub root: Workflow,
+    pub approved_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub modified_at: DateTime<Utc>,
+}
+
+impl Plan {
+    pub fn new(session_id: String, spec_id: String, root: Workflow) -> Self {
+        let now = Utc::now();
+        Self {
+            id: id!(),
+            session_id,
+            spec_id,
+            status: PlanStatus::Draft,
+            root,
+            approved_at: None,
+            created_at: now,
+            modified_at: now,
+        }
+    }
+
+    pub fn approve(&mut self) -> Result<()> {
+        self.ensure_draft()?;
+        self.status = PlanStatus::Approved;
+        self.approved_at = Some(Utc::now());
+        self.modified_at = Utc::now();
+        Ok(())
+    }
+
+    pub fn reject(&mut self) -> Result<()> {
+        self.ensure_draft()?;
+        self.status = PlanStatus::Rejected;
+        self.modified_at = Utc::now();
+        Ok(())
+    }
+
+    /// Plans can only be edited before they are approved or rejected
+    pub fn edit(&mut self, edit: PlanEdit) -> Result<()> {
+        self.ensure_draft()?;
+        self.root.apply(edit)?;
+        self.modified_at = Utc::now();
+        Ok(())
+    }
+
+    /// Moves an approved plan into execution, a running plan that was paused
+    /// by a failed step simply continues
+    pub fn start(&mut self) -> Result<()> {
+        if !matches!(self.status, PlanStatus::Approved | PlanStatus::Running) {
+            return Err(Error::BadRequest(format!(
+                "The plan is {:?} and cannot be executed.",
+                self.status
+            )));
+        }
+        if self.status == PlanStatus::Approved {
+            self.root.timing.started_at = Some(Utc::now());
+        }
+        self.status = PlanStatus::Running;
+        self.modified_at = Utc::now();
+        Ok(())
+    }
+
+    pub fn finish(&mut self, status: PlanStatus) {
+        self.status = status;
+        self.root.refresh();
+        self.root.timing.finished_at = Some(Utc::now());
+        self.modified_at = Utc::now();
+    }
+
+    /// The Tracker: how far the plan got and how long the rest will take.
+    /// Estimates are scaled by how far off they were for the finished steps,
+    /// steps without an estimate count as long as a finished step on average.
+    pub fn progress(&self, now: DateTime<Utc>) -> PlanProgress {
+        let steps = self.root.steps_in_order();
+        let durations: Vec<(i64, Option<u32>)> = steps
+            .iter()
+            .filter(|step| step.status == NodeStatus::Completed)
+            .filter_map(|step| {
+                step.timing
+                    .duration_secs()
+                    .map(|duration| (duration, step.estimate_secs))
+            })
+            .collect();
+        let (actual, estimated) = durations
+            .iter()
+            .filter_map(|(duration, estimate)| estimate.map(|estimate| (*duration, estimate)))
+            .fold((0i64, 0i64), |(actual, estimated), (duration, estimate)| {
+                (actual + duration, estimated + estimate as i64)
+            });
+        let ratio = if estimated > 0 {
+            actual as f64 / estimated as f64
+        } else {
+            1.0
+        };
+        let average = (!durations.is_empty()).then(|| {
+            durations.iter().map(|(duration, _)| *duration).sum::<i64>() as f64
+                / durations.len() as f64
+        });
+
+        let remaining = steps
+            .iter()
+            .filter(|step| matches!(step.status, NodeStatus::Pending | NodeStatus::Running))
+            .map(|step| {
+                let expected = step
+                    .estimate_secs
+                    .map(|estimate| estimate as f64 * ratio)
+                    .or(average)?;
+                let spent = match step.timing.started_at {
+                    Some(started_at) if step.status == NodeStatus::Running => {
+                        (now - started_at).num_seconds() as f64
+                    }
+                    _ => 0.0,
+                };
+                Some((expected - spent).max(0.0))
+            })
+            .sum::<Option<f64>>();
+
+        PlanProgress {
+            plan_id: self.id.clone(),
+            current_step_id: steps
+                .iter()
+                .find(|step| step.status == NodeStatus::Running)
+                .map(|step| step.id.clone()),
+            completed_steps: steps.iter().filter(|step| step.status.is_done()).count() as u32,
+            total_steps: steps.len() as u32,
+            elapsed_secs: self
+                .root
+                .timing
+                .started_at
+                .map(|started_at| (now - started_at).num_seconds().max(0) as u32)
+                .unwrap_or(0),
+            remaining_secs: remaining.map(|remaining| remaining.round() as u32),
+        }
+    }
+
+    fn ensure_draft(&self) -> Result<()> {
+        if self.status != PlanStatus::Draft {
+            return Err(Error::BadRequest(format!(
+                "The plan is {:?} and can no longer be changed.",
+                self.status
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[repr(i32)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow, Serialize, Deserialize, TS,
+)]
+#[diesel(sql_type = Integer)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub enum PlanStatus {
+    Draft = 0,
+    Approved = 1,
+    Rejected = 2,
+    Running = 3,
+    Completed = 4,
+    Failed = 5,
+}
+
+impl PlanStatus {
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(PlanStatus::Draft),
+            1 => Some(PlanStatus::Approved),
+            2 => Some(PlanStatus::Rejected),
+            3 => Some(PlanStatus::Running),
+            4 => Some(PlanStatus::Completed),
+            5 => Some(PlanStatus::Failed),
+            _ => None,
+        }
+    }
+
+    pub fn to_i32(self) -> i32 {
+        match self {
+            PlanStatus::Draft => 0,
+            PlanStatus::Approved => 1,
+            PlanStatus::Rejected => 2,
+            PlanStatus::Running => 3,
+            PlanStatus::Completed => 4,
+            PlanStatus::Failed => 5,
+        }
+    }
+}
+
+impl FromSql<Integer, Pg> for PlanStatus {
+    fn from_sql(bytes: PgValue) -> diesel::deserialize::Result<Self> {
+        let value = i32::from_sql(bytes)?;
+        match PlanStatus::from_i32(value) {
+            Some(status) => Ok(status),
+            None => Err(format!("Invalid PlanStatus value: {value}").into()),
+        }
+    }
+}
+
+impl ToSql<Integer, Pg> for PlanStatus {
+    fn to_sql<'b>(
+        &'b self,
+        out: &mut diesel::serialize::Output<'b, '_, Pg>,
+    ) -> diesel::serialize::Result {
+        let value = self.to_i32();
+        out.write_all(&value.to_be_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub enum NodeStatus {
+    #[default]
+    Pending,
+    Running,
+    Completed,
+    Failed,
+    Skipped,
+}
+
+impl NodeStatus {
+    /// Nothing is left to do for the node
+    pub fn is_done(self) -> bool {
+        matches!(self, NodeStatus::Completed | NodeStatus::Skipped)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct Timing {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub started_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl Timing {
+    pub fn duration_secs(&self) -> Option<i64> {
+        match (self.started_at, self.finished_at) {
+            (Some(start), Some(end)) => Some((end - start).num_seconds()),
+            _ => None,
+        }
+    }
+}
+
+/// A goal, achieved by its child workflows first and then its own steps
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow, TS)]
+#[diesel(sql_type = Jsonb)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct Workflow {
+    pub id: String,
+    pub goal: String,
+    #[serde(default)]
+    pub status: NodeStatus,
+    #[serde(default)]
+    pub timing: Timing,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub estimate_secs: Option<u32>,
+    #[serde(default)]
+    pub workflows: Vec<Workflow>,
+    #[serde(default)]
+    pub steps: Vec<Step>,
+}
+
+/// An atomic action, steps of a workflow run in order unless `depends_on`
+/// says otherwise
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct Step {
+    pub id: String,
+    pub description: String,
+    pub action: StepAction,
+    #[serde(default)]
+    pub status: NodeStatus,
+    #[serde(default)]
+    pub timing: Timing,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub estimate_secs: Option<u32>,
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(tag = "type", rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub enum StepAction {
+    Shell { command: String },
+    Edit { path: String, instructions: String },
+    Verify { command: String },
+    /// Look something up on the web, e.g. the documentation of a library
+    Research { query: String },
+    /// Take a screenshot of a web page, e.g. to check how it looks
+    Screenshot { url: String },
+    /// Look something up in the documents attached to the session
+    Documents { query: String },
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(tag = "type", rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub enum PlanEdit {
+    #[serde(rename_all = "camelCase")]
+    SetGoal { workflow_id: String, goal: String },
+    #[serde(rename_all = "camelCase")]
+    SetStep {
+        step_id: String,
+        description: String,
+        action: StepAction,
+    },
+    #[serde(rename_all = "camelCase")]
+    SetEstimate {
+        node_id: String,
+        estimate_secs: Option<u32>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Remove { node_id: String },
+}
+
+/// Progress of a running plan, broadcast whenever a step starts or finishes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct PlanProgress {
+    pub plan_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub current_step_id: Option<String>,
+    pub completed_steps: u32,
+    pub total_steps: u32,
+    pub elapsed_secs: u32,
+    /// Unknown until there is something to extrapolate from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub remaining_secs: Option<u32>,
+}
+
+/// What a user can do with a draft plan
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(tag = "type", rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub enum PlanAction {
+    Approve,
+    Reject {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        reason: Option<String>,
+    },
+    Edit {
+        edit: PlanEdit,
+    },
+}
+
+impl Workflow {
+    pub fn new(goal: String) -> Self {
+        Self {
+            id: id!(),
+            goal,
+            status: NodeStatus::Pending,
+            timing: Timing::default(),
+            estimate_secs: None,
+            workflows: Vec::new(),
+            steps: Vec::new(),
+        }
+    }
+
+    pub fn find_workflow_mut(&mut self, id: &str) -> Option<&mut Workflow> {
+        if self.id == id {
+            return Some(self);
+        }
+        self.workflows
+            .iter_mut()
+            .find_map(|workflow| workflow.find_workflow_mut(id))
+    }
+
+    pub fn find_step_mut(&mut self, id: &str) -> Option<&mut Step> {
+        if let Some(step) = self.steps.iter_mut().find(|step| step.id == id) {
+            return Some(step);
+        }
+        self.workflows
+            .iter_mut()
+            .find_map(|workflow| workflow.find_step_mut(id))
+    }
+
+    /// All steps in execution order, children before the workflow's own steps
+    pub fn steps_in_order(&self) -> Vec<&Step> {
+        let mut steps: Vec<&Step> = self
+            .workflows
+            .iter()
+            .flat_map(|workflow| workflow.steps_in_order())
+            .collect();
+        steps.extend(self.steps.iter());
+        steps
+    }
+
+    /// The own estimate, or the sum of everything below when there is none
+    pub fn estimate(&self) -> Option<u32> {
+        if self.estimate_secs.is_some() {
+            return self.estimate_secs;
+        }
+        let estimates: Vec<u32> = self
+            .workflows
+            .iter()
+            .filter_map(Workflow::estimate)
+            .chain(self.steps.iter().filter_map(|step| step.estimate_secs))
+            .collect();
+        if estimates.is_empty() {
+            return None;
+        }
+        Some(estimates.iter().sum())
+    }
+
+    pub fn find_step(&self, id: &str) -> Option<&Step> {
+        self.steps_in_order().into_iter().find(|step| step.id == id)
+    }
+
+    /// The first pending step whose dependencies are all done
+    pub fn next_step(&self) -> Option<&Step> {
+        let steps = self.steps_in_order();
+        let done: HashSet<&str> = steps
+            .iter()
+            .filter(|step| step.status.is_done())
+            .map(|step| step.id.as_str())
+            .collect();
+        steps.into_iter().find(|step| {
+            step.status == NodeStatus::Pending
+                && step
+                    .depends_on
+                    .iter()
+                    .all(|dependency| done.contains(dependency.as_str()))
+        })
+    }
+
+    /// Inserts a detour right before the step it has to clear the way for
+    pub fn insert_before(&mut self, step_id: &str, detour: Step) -> bool {
+        if let Some(index) = self.steps.iter().position(|step| step.id == step_id) {
+            self.steps.insert(index, detour);
+            return true;
+        }
+        let mut detour = Some(detour);
+        for workflow in &mut self.workflows {
+            if workflow.find_step_mut(step_id).is_some() {
+                return workflow.insert_before(step_id, detour.take().unwrap());
+            }
+        }
+        false
+    }
+
+    /// Derives status and timing of the workflows from their steps
+    pub fn refresh(&mut self) {
+        for workflow in &mut self.workflows {
+            workflow.refresh();
+        }
+        let statuses: Vec<NodeStatus> = self
+            .workflows
+            .iter()
+            .map(|workflow| workflow.status)
+            .chain(self.steps.iter().map(|step| step.status))
+            .collect();
+        let timings: Vec<&Timing> = self
+            .workflows
+            .iter()
+            .map(|workflow| &workflow.timing)
+            .chain(self.steps.iter().map(|step| &step.timing))
+            .collect();
+        self.status = if statuses.contains(&NodeStatus::Failed) {
+            NodeStatus::Failed
+        } else if statuses.iter().all(|status| status.is_done()) {
+            NodeStatus::Completed
+        } else if statuses.iter().all(|status| *status == NodeStatus::Pending) {
+            NodeStatus::Pending
+        } else {
+            NodeStatus::Running
+        };
+        self.timing.started_at = timings.iter().filter_map(|timing| timing.started_at).min();
+        self.timing.finished_at = match self.status {
+            NodeStatus::Completed | NodeStatus::Failed => {
+                timings.iter().filter_map(|timing| timing.finished_at).max()
+            }
+            _ => None,
+        };
+    }
+
+    pub fn apply(&mut self, edit: PlanEdit) -> Result<()> {
+        match edit {
+            PlanEdit::SetGoal { workflow_id, goal } => {
+                let workflow = self
+                    .find_workflow_mut(&workflow_id)
+                    .ok_or(Error::NotFound)?;
+                workflow.goal = goal;
+            }
+            PlanEdit::SetStep {
+                step_id,
+                description,
+                action,
+            } => {
+                let step = self.find_step_mut(&step_id).ok_or(Error::NotFound)?;
+                step.description = description;
+                step.action = action;
+            }
+            PlanEdit::SetEstimate {
+                node_id,
+                estimate_secs,
+            } => {
+                if let Some(step) = self.find_step_mut(&node_id) {
+                    step.estimate_secs = estimate_secs;
+                } else {
+                    let workflow = self.find_workflow_mut(&node_id).ok_or(Error::NotFound)?;
+                    workflow.estimate_secs = estimate_secs;
+                }
+            }
+            PlanEdit::Remove { node_id } => {
+                if node_id == self.id {
+                    return Err(Error::BadRequest(
+                        "The root workflow of a plan cannot be removed.".into(),
+                    ));
+                }
+                if !self.remove(&node_id) {
+                    return Err(Error::NotFound);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes a node with everything below it. Steps anywhere in the plan
+    /// can no longer wait on something that is gone.
+    fn remove(&mut self, id: &str) -> bool {
+        let Some(removed) = self.take(id) else {
+            return false;
+        };
+        self.forget(&removed.into_iter().collect());
+        true
+    }
+
+    /// Takes a node out of the tree, the ids of it and everything below it
+    /// are returned
+    fn take(&mut self, id: &str) -> Option<Vec<String>> {
+        if let Some(index) = self.steps.iter().position(|step| step.id == id) {
+            return Some(vec![self.steps.remove(index).id]);
+        }
+        if let Some(index) = self.workflows.iter().position(|workflow| workflow.id == id) {
+            return Some(self.workflows.remove(index).ids());
+        }
+        self.workflows
+            .iter_mut()
+            .find_map(|workflow| workflow.take(id))
+    }
+
+    fn ids(&self) -> Vec<String> {
+        let mut ids = vec![self.id.clone()];
+        ids.extend(self.steps.iter().map(|step| step.id.clone()));
+        ids.extend(self.workflows.iter().flat_map(Workflow::ids));
+        ids
+    }
+
+    fn forget(&mut self, removed: &HashSet<String>) {
+        for step in &mut self.steps {
+            step.depends_on
+                .retain(|dependency| !removed.contains(dependency));
+        }
+        for workflow in &mut self.workflows {
+            workflow.forget(removed);
+        }
+    }
+}
+
+impl Step {
+    pub fn new(description: String, action: StepAction) -> Self {
+        Self {
+            id: id!(),
+            description,
+            action,
+            status: NodeStatus::Pending,
+            timing: Timing::default(),
+            estimate_secs: None,
+            depends_on: Vec::new(),
+        }
+    }
+
+    pub fn start(&mut self) {
+        self.status = NodeStatus::Running;
+        self.timing = Timing {
+            started_at: Some(Utc::now()),
+            finished_at: None,
+        };
+    }
+
+    pub fn finish(&mut self, status: NodeStatus) {
+        self.status = status;
+        self.timing.finished_at = Some(Utc::now());
+    }
+
+    /// Makes a failed step run again
+    pub fn reset(&mut self) {
+        self.status = NodeStatus::Pending;
+        self.timing = Timing::default();
+    }
+}
+
+impl FromSql<Jsonb, Pg> for Workflow {
+    fn from_sql(bytes: PgValue) -> diesel::deserialize::Result<Self> {
+        let value = <serde_json::Value as FromSql<Jsonb, Pg>>::from_sql(bytes)?;
+        serde_json::from_value(value).map_err(|e| e.into())
+    }
+}
+
+impl ToSql<Jsonb, Pg> for Workflow {
+    fn to_sql<'b>(
+        &'b self,
+        out: &mut diesel::serialize::Output<'b, '_, Pg>,
+    ) -> diesel::serialize::Result {
+        let value = serde_json::to_value(self)?;
+        <serde_json::Value as ToSql<Jsonb, Pg>>::to_sql(&value, &mut out.reborrow())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan() -> Workflow {
+        let mut root = Workflow::new("History page".into());
+        let mut database = Workflow::new("Database".into());
+        let mut migration = Step::new(
+            "Create migration".into(),
+            StepAction::Shell {
+                command: "diesel migration generate history".into(),
+            },
+        );
+        migration.estimate_secs = Some(60);
+        database.steps.push(migration);
+        let mut api = Step::new("Add route".into(), StepAction::Other);
+        api.estimate_secs = Some(120);
+        root.workflows.push(database);
+        root.steps.push(api);
+        root
+    }
+
+    #[test]
+    fn test_estimate_rolls_up() {
+        let mut root = plan();
+        assert_eq!(root.estimate(), Some(180));
+        root.estimate_secs = Some(30);
+        assert_eq!(root.estimate(), Some(30));
+    }
+
+    #[test]
+    fn test_steps_in_order() {
+        let root = plan();
+        let descriptions: Vec<&str> = root
+            .steps_in_order()
+            .iter()
+            .map(|step| step.description.as_str())
+            .collect();
+        assert_eq!(descriptions, vec!["Create migration", "Add route"]);
+    }
+
+    #[test]
+    fn test_edits() {
+        let mut root = plan();
+        let database = root.workflows[0].id.clone();
+        let step = root.workflows[0].steps[0].id.clone();
+        root.apply(PlanEdit::SetGoal {
+            workflow_id: database.clone(),
+            goal: "Schema".into(),
+        })
+        .unwrap();
+        assert_eq!(root.workflows[0].goal, "Schema");
+        root.apply(PlanEdit::SetEstimate {
+            node_id: step.clone(),
+            estimate_secs: Some(10),
+        })
+        .unwrap();
+        assert_eq!(root.estimate(), Some(130));
+        root.apply(PlanEdit::Remove { node_id: database }).unwrap();
+        assert!(root.workflows.is_empty());
+        assert!(root.apply(PlanEdit::Remove { node_id: step }).is_err());
+        let root_id = root.id.clone();
+        assert!(root.apply(PlanEdit::Remove { node_id: root_id }).is_err());
+    }
+
+    #[test]
+    fn test_remove_prunes_dependencies() {
+        let mut root = plan();
+        let mut frontend = Workflow::new("Frontend".into());
+        frontend
+            .steps
+            .push(Step::new("Add page".into(), StepAction::Other));
+        root.workflows.push(frontend);
+        let database = root.workflows[0].id.clone();
+        let migration = root.workflows[0].steps[0].id.clone();
+        let page = root.workflows[1].steps[0].id.clone();
+        // Both wait on a step of another workflow
+        root.steps[0].depends_on = vec![migration.clone(), page.clone()];
+        root.workflows[1].steps[0].depends_on = vec![migration.clone()];
+
+        root.apply(PlanEdit::Remove { node_id: database }).unwrap();
+        assert_eq!(root.steps[0].depends_on, vec![page.clone()]);
+        assert!(root.workflows[0].steps[0].depends_on.is_empty());
+        assert_eq!(root.next_step().unwrap().id, page);
+    }
+
+    #[test]
+    fn test_next_step_and_refresh() {
+        let mut root = plan();
+        let migration = root.workflows[0].steps[0].id.clone();
+        let route = root.steps[0].id.clone();
+        root.steps[0].depends_on = vec![migration.clone()];
+        assert_eq!(root.next_step().unwrap().id, migration);
+
+        root.find_step_mut(&migration).unwrap().start();
+        assert!(root.next_step().is_none());
+        root.refresh();
+        assert_eq!(root.status, NodeStatus::Running);
+
+        let detour = Step::new("Install diesel".into(), StepAction::Other);
+        let detour_id = detour.id.clone();
+        assert!(root.insert_before(&migration, detour));
+        assert_eq!(root.next_step().unwrap().id, detour_id);
+
+        root.find_step_mut(&detour_id)
+            .unwrap()
+            .finish(NodeStatus::Skipped);
+        root.find_step_mut(&migration)
+            .unwrap()
+            .finish(NodeStatus::Completed);
+        assert_eq!(root.next_step().unwrap().id, route);
+        root.find_step_mut(&route)
+            .unwrap()
+            .finish(NodeStatus::Completed);
+        root.refresh();
+        assert_eq!(root.workflows[0].status, NodeStatus::Completed);
+        assert_eq!(root.status, NodeStatus::Completed);
+    }
+
+    #[test]
+    fn test_progress_extrapolates() {
+        let mut plan = Plan::new("session".into(), "spec".into(), plan());
+        plan.approve().unwrap();
+        plan.start().unwrap();
+        let now = Utc::now();
+        let progress = plan.progress(now);
+        assert_eq!(progress.total_steps, 2);
+        assert_eq!(progress.remaining_secs, Some(180));
+
+        // The migration took twice as long as estimated, so will the route
+        let migration = &mut plan.root.workflows[0].steps[0];
+        migration.status = NodeStatus::Completed;
+        migration.timing = Timing {
+            started_at: Some(now - chrono::Duration::seconds(120)),
+            finished_at: Some(now),
+        };
+        let progress = plan.progress(now);
+        assert_eq!(progress.completed_steps, 1);
+        assert_eq!(progress.remaining_secs, Some(240));
+
+        plan.root.steps[0].estimate_secs = None;
+        assert_eq!(plan.progress(now).remaining_secs, Some(120));
+    }
+
+    #[test]
+    fn test_only_drafts_change() {
+        let mut plan = Plan::new("session".into(), "spec".into(), plan());
+        plan.approve().unwrap();
+        assert_eq!(plan.status, PlanStatus::Approved);
+        assert!(plan.reject().is_err());
+        let id = plan.root.id.clone();
+        assert!(
+            plan.edit(PlanEdit::SetGoal {
+                workflow_id: id,
+                goal: "Other".into()
+            })
+            .is_err()
+        );
+    }
+}
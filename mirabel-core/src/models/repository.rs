@@ -0,0 +1,209 @@
+use std::io::Write;
+
+use chrono::DateTime;
+use chrono::Utc;
+use diesel::{
+    Selectable,
+    deserialize::{FromSql, FromSqlRow},
+    expression::AsExpression,
+    pg::{Pg, PgValue},
+    prelude::{Insertable, Queryable},
+    serialize::{IsNull, ToSql},
+    sql_types::Integer,
+};
+
+use serde::Deserialize;
+use serde::Serialize;
+use ts_rs::TS;
+
+use crate::Error;
+use crate::Result;
+use crate::utils::id::id;
+
+/// A git repository of a workspace, cloned from `source`, a local path or a
+/// URL
+#[derive(
+    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Queryable, Selectable, Insertable, TS,
+)]
+#[diesel(table_name = crate::schema::repositories)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct Repository {
+    pub id: String,
+    pub workspace_id: String,
+    /// Unique in the workspace, it is also the directory sessions check the
+    /// repository out to
+    pub name: String,
+    pub source: String,
+    pub default_branch: String,
+    pub created_at: DateTime<Utc>,
+    /// Whether sessions merge into the default branch of the source. When
+    /// not, their branches are pushed for someone to review and merge.
+    pub merge_upstream: bool,
+}
+
+impl Repository {
+    pub fn new(workspace_id: String, name: String, source: String, default_branch: String) -> Self {
+        Self {
+            id: id!(),
+            workspace_id,
+            name,
+            source,
+            default_branch,
+            created_at: Utc::now(),
+            merge_upstream: false,
+        }
+    }
+
+    /// The name a repository gets when none is given, the last part of its
+    /// source without `.git`
+    pub fn name_from_source(source: &str) -> Option<String> {
+        let name = source
+            .trim_end_matches(['/', '\\'])
+            .rsplit(['/', '\\', ':'])
+            .next()?;
+        let name = name.strip_suffix(".git").unwrap_or(name);
+        Self::validate_name(name).ok()?;
+        Some(name.to_string())
+    }
+
+    /// Names become directories, so they are kept to a safe set of characters
+    pub fn validate_name(name: &str) -> Result<()> {
+        let valid = !name.is_empty()
+            && !name.starts_with('.')
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid {
+            return Err(Error::BadRequest(format!(
+                "'{name}' is not a valid repository name, use letters, digits, '-', '_' and '.'."
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// The checkout of a repository a session works in, on a branch of its own
+#[derive(
+    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Queryable, Selectable, Insertable, TS,
+)]
+#[diesel(table_name = crate::schema::worktrees)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct Worktree {
+    pub id: String,
+    pub session_id: String,
+    pub repository_id: String,
+    pub branch: String,
+    /// The commit the branch started at, diffs are against it
+    pub base_commit: String,
+    pub status: WorktreeStatus,
+    /// The merge commit, or the last commit of the branch when it was pushed
+    pub merge_commit: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub modified_at: DateTime<Utc>,
+}
+
+impl Worktree {
+    pub fn new(
+        session_id: String,
+        repository_id: String,
+        branch: String,
+        base_commit: String,
+    ) -> Self {
+        Self {
+            id: id!(),
+            session_id,
+            repository_id,
+            branch,
+            base_commit,
+            status: WorktreeStatus::Active,
+            merge_commit: None,
+            created_at: Utc::now(),
+            modified_at: Utc::now(),
+        }
+    }
+
+    /// The branch of a session, every new worktree of a session needs a new
+    /// one as the old branches are deleted
+    pub fn branch_name(session_id: &str, worktree_id: &str) -> String {
+        format!("mirabel/{session_id}-{worktree_id}")
+    }
+}
+
+#[repr(i32)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow, Serialize, Deserialize, TS,
+)]
+#[diesel(sql_type = Integer)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub enum WorktreeStatus {
+    Active = 0,
+    Merged = 1,
+    Discarded = 2,
+    /// The branch is in the source, waiting to be reviewed
+    Pushed = 3,
+}
+
+impl WorktreeStatus {
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(WorktreeStatus::Active),
+            1 => Some(WorktreeStatus::Merged),
+            2 => Some(WorktreeStatus::Discarded),
+            3 => Some(WorktreeStatus::Pushed),
+            _ => None,
+        }
+    }
+
+    pub fn to_i32(self) -> i32 {
+        match self {
+            WorktreeStatus::Active => 0,
+            WorktreeStatus::Merged => 1,
+            WorktreeStatus::Discarded => 2,
+            WorktreeStatus::Pushed => 3,
+        }
+    }
+}
+
+impl FromSql<Integer, Pg> for WorktreeStatus {
+    fn from_sql(bytes: PgValue) -> diesel::deserialize::Result<Self> {
+        let value = i32::from_sql(bytes)?;
+        match WorktreeStatus::from_i32(value) {
+            Some(status) => Ok(status),
+            None => Err(format!("Invalid WorktreeStatus value: {value}").into()),
+        }
+    }
+}
+
+impl ToSql<Integer, Pg> for WorktreeStatus {
+    fn to_sql<'b>(
+        &'b self,
+        out: &mut diesel::serialize::Output<'b, '_, Pg>,
+    ) -> diesel::serialize::Result {
+        let value = self.to_i32();
+        out.write_all(&value.to_be_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_from_source() {
+        let name = |source: &str| Repository::name_from_source(source);
+        assert_eq!(
+            name("https://github.com/mirabel/mirabel.git"),
+            Some("mirabel".into())
+        );
+        assert_eq!(name("git@github.com:mirabel/web"), Some("web".into()));
+        assert_eq!(name("/srv/git/project.git/"), Some("project".into()));
+        assert_eq!(name("/srv/git/.hidden"), None);
+        assert!(Repository::validate_name("../escape").is_err());
+    }
+}
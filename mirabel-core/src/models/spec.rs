@@ -0,0 +1,49 @@
+use chrono::DateTime;
+use chrono::Utc;
+use diesel::{
+    Selectable,
+    prelude::{Insertable, Queryable},
+};
+
+use serde::Deserialize;
+use serde::Serialize;
+use ts_rs::TS;
+
+use crate::utils::id::id;
+
+/// One version of the spec of a session. Versions start at 1, every version
+/// after the first one carries the unified diff to its predecessor.
+#[derive(
+    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Queryable, Selectable, Insertable, TS,
+)]
+#[diesel(table_name = crate::schema::specs)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct Spec {
+    pub id: String,
+    pub session_id: String,
+    pub version: i32,
+    pub content: String,
+    pub diff: Option<String>,
+    pub accepted_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Spec {
+    pub fn new(session_id: String, version: i32, content: String, diff: Option<String>) -> Self {
+        Self {
+            id: id!(),
+            session_id,
+            version,
+            content,
+            diff,
+            accepted_at: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn is_accepted(&self) -> bool {
+        self.accepted_at.is_some()
+    }
+}
@@ -6,22 +6,33 @@ use diesel::{
     expression::AsExpression,
     pg::{Pg, PgValue},
     prelude::{Insertable, Queryable},
-    serialize::ToSql,
-    sql_types::Jsonb,
+    serialize::{IsNull, ToSql},
+    sql_types::{Integer, Jsonb},
 };
 
 use serde::Deserialize;
 use serde::Serialize;
+use std::fmt;
+use std::io::Write;
+use ts_rs::TS;
 
+use crate::Error;
+use crate::Result;
+use crate::models::file_action::FileAction;
+use crate::models::plan::Plan;
+use crate::models::plan::PlanStatus;
+use crate::models::plan::Workflow;
+use crate::models::spec::Spec;
 use crate::utils::id::id;
 
 #[derive(
-    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Queryable, Selectable, Insertable,
+    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Queryable, Selectable, Insertable, TS,
 )]
 #[diesel(belongs_to(Session))]
 #[diesel(table_name = crate::schema::timeline_entries)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 #[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
 pub struct TimelineEntry {
     pub id: String,
     pub session_id: String,
@@ -31,12 +42,13 @@ pub struct TimelineEntry {
 }
 
 impl TimelineEntry {
-    pub fn user_message(session_id: String, content: String) -> Self {
+    pub fn user_message(session_id: String, user_id: String, content: String) -> Self {
         TimelineEntry {
             id: id!(),
             session_id,
             content: TimelineEntryContent::Message {
                 sender: MessageSender::User,
+                user_id: Some(user_id),
                 message: content,
             },
             content_type: "message".to_string(),
@@ -50,6 +62,7 @@ impl TimelineEntry {
             session_id,
             content: TimelineEntryContent::Message {
                 sender: MessageSender::Agent,
+                user_id: None,
                 message: content,
             },
             content_type: "message".to_string(),
@@ -77,15 +90,137 @@ impl TimelineEntry {
         }
     }
 
-    pub fn prompt_response(session_id: String, prompt_id: String, response: String) -> Self {
+    pub fn spec(spec: &Spec) -> Self {
+        TimelineEntry {
+            id: id!(),
+            session_id: spec.session_id.clone(),
+            content: TimelineEntryContent::Spec {
+                content: spec.content.clone(),
+                spec_id: Some(spec.id.clone()),
+                version: Some(spec.version),
+                diff: spec.diff.clone(),
+            },
+            content_type: "spec".to_string(),
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn plan(plan: &Plan) -> Self {
+        TimelineEntry {
+            id: id!(),
+            session_id: plan.session_id.clone(),
+            content: TimelineEntryContent::Plan {
+                plan_id: Some(plan.id.clone()),
+                status: Some(plan.status),
+                root: Some(plan.root.clone()),
+            },
+            content_type: "plan".to_string(),
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn shell(session_id: String, lines: Vec<String>) -> Self {
+        TimelineEntry {
+            id: id!(),
+            session_id,
+            content: TimelineEntryContent::Shell { lines },
+            content_type: "shell".to_string(),
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn research(
+        session_id: String,
+        query: String,
+        answer: String,
+        sources: Vec<Citation>,
+    ) -> Self {
+        TimelineEntry {
+            id: id!(),
+            session_id,
+            content: TimelineEntryContent::Research {
+                query,
+                answer,
+                sources,
+            },
+            content_type: "research".to_string(),
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn screenshot(
+        session_id: String,
+        url: String,
+        title: String,
+        attachment_id: String,
+    ) -> Self {
+        TimelineEntry {
+            id: id!(),
+            session_id,
+            content: TimelineEntryContent::Screenshot {
+                url,
+                title,
+                attachment_id,
+            },
+            content_type: "screenshot".to_string(),
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn file_action(action: &FileAction) -> Self {
+        TimelineEntry {
+            id: id!(),
+            session_id: action.session_id.clone(),
+            content: TimelineEntryContent::Action {
+                action_type: action.action_type,
+                message: action.summary(),
+                action_id: Some(action.id.clone()),
+                path: Some(action.path.clone()),
+                diff: Some(action.diff.clone()),
+                reverts: action.reverts.clone(),
+            },
+            content_type: "action".to_string(),
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn prompt(
+        session_id: String,
+        prompt_id: String,
+        question: String,
+        kind: PromptKind,
+        options: Vec<String>,
+    ) -> Self {
+        TimelineEntry {
+            id: id!(),
+            session_id,
+            content: TimelineEntryContent::Prompt {
+                prompt_id,
+                question,
+                kind,
+                options,
+            },
+            content_type: "prompt".to_string(),
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn prompt_response(
+        session_id: String,
+        prompt_id: String,
+        user_id: String,
+        answer: PromptAnswer,
+    ) -> Self {
         TimelineEntry {
             id: id!(),
             session_id,
             content: TimelineEntryContent::PromptResponse {
                 prompt_id,
-                response,
+                response: answer.to_string(),
+                answer: Some(answer),
+                user_id: Some(user_id),
             },
-            content_type: "prompt_response".to_string(),
+            content_type: "promptResponse".to_string(),
             created_at: Utc::now(),
         }
     }
@@ -104,49 +239,185 @@ impl TimelineEntry {
             TimelineEntryContent::Spec { .. } => "spec".to_string(),
             TimelineEntryContent::Plan { .. } => "plan".to_string(),
             TimelineEntryContent::Shell { .. } => "shell".to_string(),
+            TimelineEntryContent::Research { .. } => "research".to_string(),
+            TimelineEntryContent::Screenshot { .. } => "screenshot".to_string(),
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
 #[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
 pub enum MessageSender {
     User,
     Agent,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
 #[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
 pub enum AcknowledgmentType {
     Sent,
     Delivered,
     Seen,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
 #[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
 pub enum AgentStatus {
     Thinking,
     Typing,
     Error,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(i32)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow, Serialize, Deserialize, TS,
+)]
+#[diesel(sql_type = Integer)]
 #[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
 pub enum ActionType {
-    Command,
-    NewFile,
-    EditFile,
+    Command = 0,
+    NewFile = 1,
+    EditFile = 2,
+    DeleteFile = 3,
+}
+
+impl ActionType {
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(ActionType::Command),
+            1 => Some(ActionType::NewFile),
+            2 => Some(ActionType::EditFile),
+            3 => Some(ActionType::DeleteFile),
+            _ => None,
+        }
+    }
+
+    pub fn to_i32(self) -> i32 {
+        match self {
+            ActionType::Command => 0,
+            ActionType::NewFile => 1,
+            ActionType::EditFile => 2,
+            ActionType::DeleteFile => 3,
+        }
+    }
+}
+
+impl FromSql<Integer, Pg> for ActionType {
+    fn from_sql(bytes: PgValue) -> diesel::deserialize::Result<Self> {
+        let value = i32::from_sql(bytes)?;
+        match ActionType::from_i32(value) {
+            Some(action_type) => Ok(action_type),
+            None => Err(format!("Invalid ActionType value: {value}").into()),
+        }
+    }
+}
+
+impl ToSql<Integer, Pg> for ActionType {
+    fn to_sql<'b>(
+        &'b self,
+        out: &mut diesel::serialize::Output<'b, '_, Pg>,
+    ) -> diesel::serialize::Result {
+        let value = self.to_i32();
+        out.write_all(&value.to_be_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub enum PromptKind {
+    FreeText,
+    // Prompts only had options before there were other kinds
+    #[default]
+    SingleChoice,
+    MultipleChoice,
+    Confirm,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(tag = "type", content = "value")]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub enum PromptAnswer {
+    Text(String),
+    Choice(String),
+    Choices(Vec<String>),
+    Confirm(bool),
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow)]
+impl PromptAnswer {
+    /// Checks that the answer fits the kind of prompt and only uses the
+    /// offered options.
+    pub fn validate(&self, kind: PromptKind, options: &[String]) -> Result<()> {
+        let offered = |choice: &String| {
+            if options.contains(choice) {
+                Ok(())
+            } else {
+                Err(Error::BadRequest(format!(
+                    "'{choice}' is not one of the offered options."
+                )))
+            }
+        };
+        match (kind, self) {
+            (PromptKind::FreeText, PromptAnswer::Text(text)) => {
+                if text.trim().is_empty() {
+                    return Err(Error::BadRequest("The answer must not be empty.".into()));
+                }
+                Ok(())
+            }
+            (PromptKind::SingleChoice, PromptAnswer::Choice(choice)) => offered(choice),
+            (PromptKind::MultipleChoice, PromptAnswer::Choices(choices)) => {
+                if choices.is_empty() {
+                    return Err(Error::BadRequest("Choose at least one option.".into()));
+                }
+                for (i, choice) in choices.iter().enumerate() {
+                    offered(choice)?;
+                    if choices[..i].contains(choice) {
+                        return Err(Error::BadRequest(format!(
+                            "'{choice}' was chosen more than once."
+                        )));
+                    }
+                }
+                Ok(())
+            }
+            (PromptKind::Confirm, PromptAnswer::Confirm(_)) => Ok(()),
+            (kind, _) => Err(Error::BadRequest(format!(
+                "The answer does not match the prompt kind {kind:?}."
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for PromptAnswer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PromptAnswer::Text(text) | PromptAnswer::Choice(text) => write!(f, "{text}"),
+            PromptAnswer::Choices(choices) => write!(f, "{}", choices.join(", ")),
+            PromptAnswer::Confirm(true) => write!(f, "Yes"),
+            PromptAnswer::Confirm(false) => write!(f, "No"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow, TS)]
 #[diesel(sql_type = Jsonb)]
 #[serde(tag = "type")]
 #[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
 pub enum TimelineEntryContent {
     #[serde(rename_all = "camelCase")]
     Message {
         sender: MessageSender,
+        /// The user who wrote the message, absent for agent messages and
+        /// messages written before sessions could be shared
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        user_id: Option<String>,
         message: String,
     },
     #[serde(rename_all = "camelCase")]
@@ -156,21 +427,99 @@ pub enum TimelineEntryContent {
     #[serde(rename_all = "camelCase")]
     Prompt {
         prompt_id: String,
+        #[serde(default)]
+        question: String,
+        #[serde(default)]
+        kind: PromptKind,
         options: Vec<String>,
     },
     #[serde(rename_all = "camelCase")]
-    PromptResponse { prompt_id: String, response: String },
+    PromptResponse {
+        prompt_id: String,
+        /// Human readable form of the answer
+        response: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        answer: Option<PromptAnswer>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        user_id: Option<String>,
+    },
     #[serde(rename_all = "camelCase")]
     Action {
         action_type: ActionType,
         message: String,
+        /// The recorded file action, which can be reverted
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        action_id: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        path: Option<String>,
+        /// Unified diff of the change to the file
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        diff: Option<String>,
+        /// The action that was reverted by this one
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        reverts: Option<String>,
     },
     #[serde(rename_all = "camelCase")]
-    Spec { content: String },
+    Spec {
+        content: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        spec_id: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        version: Option<i32>,
+        /// Unified diff to the previous version
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        diff: Option<String>,
+    },
+    /// Snapshot of a plan whenever it is created or changes
     #[serde(rename_all = "camelCase")]
-    Plan { placeholder: bool },
+    Plan {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        plan_id: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        status: Option<PlanStatus>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        root: Option<Workflow>,
+    },
     #[serde(rename_all = "camelCase")]
     Shell { lines: Vec<String> },
+    /// An answer researched on the web, citing its sources as `[1]`
+    #[serde(rename_all = "camelCase")]
+    Research {
+        query: String,
+        answer: String,
+        sources: Vec<Citation>,
+    },
+    /// What a web page looked like, the PNG is served as an attachment of
+    /// the session
+    #[serde(rename_all = "camelCase")]
+    Screenshot {
+        url: String,
+        title: String,
+        attachment_id: String,
+    },
+}
+
+/// A web page an answer is based on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct Citation {
+    /// The number the answer cites the source with, starting at 1
+    pub index: u32,
+    pub title: String,
+    pub url: String,
 }
 
 impl FromSql<Jsonb, Pg> for TimelineEntryContent {
@@ -189,3 +538,54 @@ impl ToSql<Jsonb, Pg> for TimelineEntryContent {
         <serde_json::Value as ToSql<Jsonb, Pg>>::to_sql(&value, &mut out.reborrow())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> Vec<String> {
+        vec!["red".into(), "green".into()]
+    }
+
+    #[test]
+    fn test_prompt_answer_validation() {
+        let choice = PromptAnswer::Choice("red".into());
+        assert!(
+            choice
+                .validate(PromptKind::SingleChoice, &options())
+                .is_ok()
+        );
+        let unknown = PromptAnswer::Choice("blue".into());
+        assert!(
+            unknown
+                .validate(PromptKind::SingleChoice, &options())
+                .is_err()
+        );
+        let twice = PromptAnswer::Choices(vec!["red".into(), "red".into()]);
+        assert!(
+            twice
+                .validate(PromptKind::MultipleChoice, &options())
+                .is_err()
+        );
+        let empty = PromptAnswer::Text("  ".into());
+        assert!(empty.validate(PromptKind::FreeText, &[]).is_err());
+        let confirm = PromptAnswer::Confirm(true);
+        assert!(confirm.validate(PromptKind::Confirm, &[]).is_ok());
+        assert!(confirm.validate(PromptKind::FreeText, &[]).is_err());
+    }
+
+    #[test]
+    fn test_legacy_prompt_content() {
+        let json = r#"{ "type": "prompt", "promptId": "p", "options": ["a"] }"#;
+        let content: TimelineEntryContent = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            content,
+            TimelineEntryContent::Prompt {
+                prompt_id: "p".into(),
+                question: String::new(),
+                kind: PromptKind::SingleChoice,
+                options: vec!["a".into()],
+            }
+        );
+    }
+}
@@ -12,6 +12,7 @@ use diesel::Selectable;
 use diesel::prelude::Queryable;
 use serde::Deserialize;
 use serde::Serialize;
+use ts_rs::TS;
 
 #[derive(
     Debug, Queryable, Selectable, Insertable, Clone, PartialEq, Eq, Serialize, Deserialize,
@@ -89,3 +90,28 @@ pub struct DeletedUser {
     pub id: String,
     pub deleted_at: DateTime<Utc>,
 }
+
+#[derive(
+    Debug, Queryable, Selectable, Insertable, Clone, PartialEq, Eq, Serialize, Deserialize, TS,
+)]
+#[diesel(table_name = crate::schema::user_settings)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[serde(rename_all = "camelCase")]
+#[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
+pub struct UserSettings {
+    #[serde(skip)]
+    pub user_id: String,
+    /// Plans are approved right away instead of waiting for the user
+    pub auto_approve_plans: bool,
+    pub modified_at: DateTime<Utc>,
+}
+
+impl UserSettings {
+    pub fn new(user_id: String) -> Self {
+        Self {
+            user_id,
+            auto_approve_plans: false,
+            modified_at: Utc::now(),
+        }
+    }
+}
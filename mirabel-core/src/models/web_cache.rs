@@ -0,0 +1,89 @@
+use chrono::DateTime;
+use chrono::TimeDelta;
+use chrono::Utc;
+use diesel::Selectable;
+use diesel::prelude::{Insertable, Queryable};
+
+/// How usable a cached answer from the web still is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Freshness {
+    /// Younger than its time to live, used as is
+    Fresh,
+    /// Too old, but used while it is fetched again in the background
+    Stale,
+    /// Too old to be used, fetched again before answering
+    Expired,
+}
+
+impl Freshness {
+    /// An answer fetched at `fetched_at` lives for `ttl`, and is served
+    /// stale for `stale` after that
+    pub fn of(
+        fetched_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+        ttl: TimeDelta,
+        stale: TimeDelta,
+    ) -> Self {
+        let age = now - fetched_at;
+        if age < ttl {
+            Freshness::Fresh
+        } else if age < ttl + stale {
+            Freshness::Stale
+        } else {
+            Freshness::Expired
+        }
+    }
+}
+
+/// A page of search results, shared by the sessions of a workspace
+#[derive(Debug, Clone, PartialEq, Queryable, Selectable, Insertable)]
+#[diesel(table_name = crate::schema::search_cache)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CachedSearch {
+    pub workspace_id: String,
+    /// The query with case and spacing normalized
+    pub query_key: String,
+    pub page: i32,
+    /// The query as it was first searched for
+    pub query: String,
+    pub results: serde_json::Value,
+    pub fetched_at: DateTime<Utc>,
+}
+
+/// A scraped page, shared by the sessions of a workspace. The HTML is kept
+/// next to its markdown, so pages can be converted again without a browser.
+#[derive(Debug, Clone, PartialEq, Eq, Queryable, Selectable, Insertable)]
+#[diesel(table_name = crate::schema::page_cache)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CachedPage {
+    pub workspace_id: String,
+    /// The URL normalized, so the same page under different URLs is cached once
+    pub url_key: String,
+    pub url: String,
+    pub title: String,
+    pub html: String,
+    pub markdown: String,
+    pub fetched_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_freshness() {
+        let fetched_at = Utc::now();
+        let ttl = TimeDelta::hours(1);
+        let stale = TimeDelta::days(1);
+        let at = |age| Freshness::of(fetched_at, fetched_at + age, ttl, stale);
+        assert_eq!(at(TimeDelta::minutes(59)), Freshness::Fresh);
+        assert_eq!(at(TimeDelta::hours(1)), Freshness::Stale);
+        assert_eq!(at(TimeDelta::hours(24)), Freshness::Stale);
+        assert_eq!(at(TimeDelta::hours(25)), Freshness::Expired);
+        // Without a stale window answers expire with their time to live
+        assert_eq!(
+            Freshness::of(fetched_at, fetched_at + ttl, ttl, TimeDelta::zero()),
+            Freshness::Expired
+        );
+    }
+}
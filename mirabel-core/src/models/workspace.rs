@@ -71,13 +71,16 @@ impl WorkspaceMember {
 }
 
 #[repr(i32)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow, Serialize, Deserialize, TS)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow, Serialize, Deserialize, TS,
+)]
 #[diesel(sql_type = Integer)]
 #[ts(export, export_to = "../../mirabel-web/src/lib/generated/")]
 pub enum WorkspaceRole {
     Owner = 0,
     Admin = 1,
     Member = 2,
+    Viewer = 3,
 }
 
 impl WorkspaceRole {
@@ -88,11 +91,21 @@ impl WorkspaceRole {
         )
     }
 
+    pub fn is_at_least_admin(&self) -> bool {
+        matches!(self, WorkspaceRole::Admin | WorkspaceRole::Owner)
+    }
+
+    /// Viewers can follow sessions but not interact with them
+    pub fn can_write(&self) -> bool {
+        self.is_at_least_member()
+    }
+
     pub fn from_i32(value: i32) -> Option<Self> {
         match value {
             0 => Some(WorkspaceRole::Owner),
             1 => Some(WorkspaceRole::Admin),
             2 => Some(WorkspaceRole::Member),
+            3 => Some(WorkspaceRole::Viewer),
             _ => None,
         }
     }
@@ -102,6 +115,7 @@ impl WorkspaceRole {
             WorkspaceRole::Owner => 0,
             WorkspaceRole::Admin => 1,
             WorkspaceRole::Member => 2,
+            WorkspaceRole::Viewer => 3,
         }
     }
 }
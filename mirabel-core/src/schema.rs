@@ -1,5 +1,31 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    attachment_chunks (attachment_id, id) {
+        attachment_id -> Text,
+        id -> Text,
+        position -> Int4,
+        start -> Int4,
+        end -> Int4,
+        text -> Text,
+        headings -> Array<Text>,
+        page -> Nullable<Int4>,
+        tokens -> Int4,
+        embedding -> Nullable<Array<Float4>>,
+    }
+}
+
+diesel::table! {
+    attachments (id) {
+        id -> Text,
+        session_id -> Text,
+        media_type -> Text,
+        data -> Bytea,
+        created_at -> Timestamptz,
+        name -> Nullable<Text>,
+    }
+}
+
 diesel::table! {
     auth_options (id) {
         id -> Text,
@@ -15,6 +41,32 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    code_files (id) {
+        id -> Text,
+        worktree_id -> Text,
+        path -> Text,
+        language -> Int4,
+        hash -> Text,
+        indexed_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    code_symbols (id) {
+        id -> Text,
+        file_id -> Text,
+        worktree_id -> Text,
+        name -> Text,
+        kind -> Int4,
+        definition -> Bool,
+        line -> Int4,
+        column -> Int4,
+        end_line -> Int4,
+        signature -> Nullable<Text>,
+    }
+}
+
 diesel::table! {
     deleted_users (id) {
         id -> Text,
@@ -22,6 +74,31 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    domain_rules (id) {
+        id -> Text,
+        workspace_id -> Text,
+        domain -> Text,
+        access -> Int4,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    file_actions (id) {
+        id -> Text,
+        session_id -> Text,
+        action_type -> Int4,
+        path -> Text,
+        before -> Nullable<Text>,
+        after -> Nullable<Text>,
+        diff -> Text,
+        reverts -> Nullable<Text>,
+        reverted_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     jobs (id) {
         id -> Text,
@@ -33,6 +110,47 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    memories (id) {
+        id -> Text,
+        workspace_id -> Text,
+        kind -> Int4,
+        content -> Text,
+        source_session_id -> Nullable<Text>,
+        source_entry_id -> Nullable<Text>,
+        confidence -> Float4,
+        embedding -> Nullable<Array<Float4>>,
+        expires_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        modified_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    page_cache (workspace_id, url_key) {
+        workspace_id -> Text,
+        url_key -> Text,
+        url -> Text,
+        title -> Text,
+        html -> Text,
+        markdown -> Text,
+        fetched_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    plans (id) {
+        id -> Text,
+        session_id -> Text,
+        spec_id -> Text,
+        status -> Int4,
+        root -> Jsonb,
+        approved_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        modified_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     prompt_evaluations (id) {
         id -> Text,
@@ -44,6 +162,29 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    repositories (id) {
+        id -> Text,
+        workspace_id -> Text,
+        name -> Text,
+        source -> Text,
+        default_branch -> Text,
+        created_at -> Timestamptz,
+        merge_upstream -> Bool,
+    }
+}
+
+diesel::table! {
+    search_cache (workspace_id, query_key, page) {
+        workspace_id -> Text,
+        query_key -> Text,
+        page -> Int4,
+        query -> Text,
+        results -> Jsonb,
+        fetched_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     sessions (id) {
         id -> Text,
@@ -56,6 +197,18 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    specs (id) {
+        id -> Text,
+        session_id -> Text,
+        version -> Int4,
+        content -> Text,
+        diff -> Nullable<Text>,
+        accepted_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     timeline_entries (id) {
         id -> Text,
@@ -63,6 +216,24 @@ diesel::table! {
         content -> Jsonb,
         created_at -> Timestamptz,
         content_type -> Text,
+        seq -> Int8,
+    }
+}
+
+diesel::table! {
+    timeline_search (entry_id) {
+        entry_id -> Text,
+        text -> Text,
+        embedding -> Nullable<Array<Float4>>,
+        attempts -> Int4,
+    }
+}
+
+diesel::table! {
+    user_settings (user_id) {
+        user_id -> Text,
+        auto_approve_plans -> Bool,
+        modified_at -> Timestamptz,
     }
 }
 
@@ -77,6 +248,16 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    web_pages (workspace_id, url) {
+        workspace_id -> Text,
+        url -> Text,
+        title -> Text,
+        markdown -> Text,
+        scraped_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     workspace_members (id) {
         id -> Text,
@@ -87,6 +268,20 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    worktrees (id) {
+        id -> Text,
+        session_id -> Text,
+        repository_id -> Text,
+        branch -> Text,
+        base_commit -> Text,
+        status -> Int4,
+        merge_commit -> Nullable<Text>,
+        created_at -> Timestamptz,
+        modified_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     workspaces (id) {
         id -> Text,
@@ -97,26 +292,62 @@ diesel::table! {
     }
 }
 
+diesel::joinable!(attachment_chunks -> attachments (attachment_id));
+diesel::joinable!(attachments -> sessions (session_id));
 diesel::joinable!(auth_options -> users (user_id));
 diesel::joinable!(avatars -> users (user_id));
+diesel::joinable!(code_files -> worktrees (worktree_id));
+diesel::joinable!(code_symbols -> code_files (file_id));
 diesel::joinable!(deleted_users -> users (id));
+diesel::joinable!(domain_rules -> workspaces (workspace_id));
+diesel::joinable!(file_actions -> sessions (session_id));
 diesel::joinable!(jobs -> sessions (session_id));
+diesel::joinable!(memories -> sessions (source_session_id));
+diesel::joinable!(memories -> timeline_entries (source_entry_id));
+diesel::joinable!(memories -> workspaces (workspace_id));
+diesel::joinable!(page_cache -> workspaces (workspace_id));
+diesel::joinable!(plans -> sessions (session_id));
+diesel::joinable!(plans -> specs (spec_id));
 diesel::joinable!(prompt_evaluations -> jobs (job_id));
+diesel::joinable!(repositories -> workspaces (workspace_id));
+diesel::joinable!(search_cache -> workspaces (workspace_id));
 diesel::joinable!(sessions -> users (user_id));
 diesel::joinable!(sessions -> workspaces (workspace_id));
+diesel::joinable!(specs -> sessions (session_id));
 diesel::joinable!(timeline_entries -> sessions (session_id));
+diesel::joinable!(timeline_search -> timeline_entries (entry_id));
+diesel::joinable!(user_settings -> users (user_id));
+diesel::joinable!(web_pages -> workspaces (workspace_id));
 diesel::joinable!(workspace_members -> users (user_id));
 diesel::joinable!(workspace_members -> workspaces (workspace_id));
+diesel::joinable!(worktrees -> repositories (repository_id));
+diesel::joinable!(worktrees -> sessions (session_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    attachment_chunks,
+    attachments,
     auth_options,
     avatars,
+    code_files,
+    code_symbols,
     deleted_users,
+    domain_rules,
+    file_actions,
     jobs,
+    memories,
+    page_cache,
+    plans,
     prompt_evaluations,
+    repositories,
+    search_cache,
     sessions,
+    specs,
     timeline_entries,
+    timeline_search,
+    user_settings,
     users,
+    web_pages,
     workspace_members,
     workspaces,
+    worktrees,
 );
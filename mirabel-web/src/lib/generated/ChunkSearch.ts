@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A search for the parts of a document that answer a question
+ */
+export type ChunkSearch = { query: string, limit?: bigint, };
@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A web page an answer is based on
+ */
+export type Citation = { 
+/**
+ * The number the answer cites the source with, starting at 1
+ */
+index: number, title: string, url: string, };
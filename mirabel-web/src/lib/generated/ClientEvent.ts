@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Heartbeat } from "./Heartbeat";
+import type { ShellInput } from "./ShellInput";
+import type { UserInteraction } from "./UserInteraction";
+
+export type ClientEvent = { "type": "interaction", "data": UserInteraction } | { "type": "heartbeat", "data": Heartbeat } | { "type": "typing" } | { "type": "shell", "data": ShellInput };
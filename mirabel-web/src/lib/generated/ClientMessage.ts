@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ClientEvent } from "./ClientEvent";
+
+/**
+ * Envelope for everything a subscriber sends to the server. When a
+ * `correlation_id` is given, the server answers with an [`Ack`] once the
+ * message is accepted, and with a [`ProtocolError`] carrying the same id if
+ * it is rejected or fails later on.
+ */
+export type ClientMessage = { version: number, correlationId?: string, event: ClientEvent, };
@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CodeLanguage } from "./CodeLanguage";
+
+/**
+ * A source file of a worktree as it was when it was last indexed
+ */
+export type CodeFile = { id: string, worktreeId: string, 
+/**
+ * Relative to the worktree
+ */
+path: string, language: CodeLanguage, 
+/**
+ * Hash of the content, files with the same hash are not parsed again
+ */
+hash: string, indexedAt: string, };
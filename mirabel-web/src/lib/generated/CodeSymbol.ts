@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SymbolKind } from "./SymbolKind";
+
+/**
+ * Where a symbol is defined or referenced
+ */
+export type CodeSymbol = { id: string, fileId: string, worktreeId: string, name: string, kind: SymbolKind, 
+/**
+ * A definition of the symbol, otherwise a reference to it
+ */
+definition: boolean, 
+/**
+ * Lines and columns start at 1
+ */
+line: number, column: number, endLine: number, 
+/**
+ * First line of a definition, e.g. the signature of a function
+ */
+signature: string | null, };
@@ -0,0 +1,26 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A part of a document that matched a search
+ */
+export type DocumentChunk = { 
+/**
+ * Stays the same for as long as the text of the chunk does
+ */
+id: string, 
+/**
+ * The attachment of the session the document is
+ */
+attachmentId: string, text: string, 
+/**
+ * The headings the text is under, the outermost first
+ */
+headings: Array<string>, 
+/**
+ * Byte offsets of the text in the markdown the document converts to
+ */
+start: number, end: number, 
+/**
+ * The page the text starts on, for documents with pages
+ */
+page?: number, score: number, };
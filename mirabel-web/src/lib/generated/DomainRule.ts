@@ -0,0 +1,13 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DomainAccess } from "./DomainAccess";
+
+/**
+ * Whether the pages of a domain and its subdomains may be fetched for a
+ * workspace. Once a workspace allows any domain, only allowed domains are
+ * fetched, and denied domains never are.
+ */
+export type DomainRule = { id: string, workspaceId: string, 
+/**
+ * Lowercase, without scheme, port or path, e.g. `docs.rs`
+ */
+domain: string, access: DomainAccess, createdAt: string, };
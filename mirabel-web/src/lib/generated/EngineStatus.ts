@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CircuitState } from "./CircuitState";
+
+/**
+ * The health of a search engine and how it did since the server started
+ */
+export type EngineStatus = { name: string, state: CircuitState, 
+/**
+ * Failures since the engine last answered
+ */
+consecutiveFailures: number, searches: bigint, 
+/**
+ * Searches that failed, including the ones that timed out
+ */
+failures: bigint, timeouts: bigint, averageLatencyMs?: bigint, lastLatencyMs?: bigint, lastError?: string, lastSuccessAt?: string, lastFailureAt?: string, lastProbeAt?: string, };
@@ -0,0 +1,29 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ActionType } from "./ActionType";
+
+/**
+ * A change to a file in the sandbox of a session. The content on both sides
+ * is kept, so the change can be reverted as long as nobody changed the same
+ * lines since.
+ */
+export type FileAction = { id: string, sessionId: string, actionType: ActionType, 
+/**
+ * Relative to the sandbox
+ */
+path: string, 
+/**
+ * Absent when the file was created
+ */
+before: string | null, 
+/**
+ * Absent when the file was deleted
+ */
+after: string | null, 
+/**
+ * Unified diff of the change
+ */
+diff: string, 
+/**
+ * The action this one reverted
+ */
+reverts: string | null, revertedAt: string | null, createdAt: string, };
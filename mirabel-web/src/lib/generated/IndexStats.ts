@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * What an indexing run of a worktree did
+ */
+export type IndexStats = { 
+/**
+ * Files that were new or changed and got parsed
+ */
+indexedFiles: number, unchangedFiles: number, removedFiles: number, symbols: number, };
@@ -0,0 +1,25 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { MemoryKind } from "./MemoryKind";
+
+/**
+ * Something Mirabel learned about a workspace, e.g. that it formats with
+ * tabs. Memories are recalled for agents working in the workspace.
+ */
+export type Memory = { id: string, workspaceId: string, kind: MemoryKind, content: string, 
+/**
+ * The session the memory was learned in, absent for memories users
+ * added themselves
+ */
+sourceSessionId: string | null, 
+/**
+ * The timeline entry the memory was learned from
+ */
+sourceEntryId: string | null, 
+/**
+ * Between 0 and 1, how sure Mirabel is that the memory holds
+ */
+confidence: number, 
+/**
+ * Memories are forgotten after this, e.g. for temporary decisions
+ */
+expiresAt: string | null, createdAt: string, modifiedAt: string, };
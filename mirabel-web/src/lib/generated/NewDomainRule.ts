@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DomainAccess } from "./DomainAccess";
+
+/**
+ * Allows or denies fetching a domain for a workspace, a rule for the same
+ * domain is replaced
+ */
+export type NewDomainRule = { 
+/**
+ * A domain, or a URL on it
+ */
+domain: string, access: DomainAccess, };
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { MemoryKind } from "./MemoryKind";
+
+/**
+ * A memory users add to a workspace by hand
+ */
+export type NewMemory = { kind: MemoryKind, content: string, 
+/**
+ * Memories from users are trusted fully when left out
+ */
+confidence?: number, expiresAt?: string, };
@@ -0,0 +1,16 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type NewRepository = { 
+/**
+ * A local path or a URL to clone from
+ */
+source: string, 
+/**
+ * Taken from the source when left out
+ */
+name?: string, 
+/**
+ * Merge sessions straight into the default branch of the source instead
+ * of pushing their branches, off when left out
+ */
+mergeUpstream?: boolean, };
@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { PlanStatus } from "./PlanStatus";
+import type { Workflow } from "./Workflow";
+
+/**
+ * A hierarchical plan for an accepted spec, the root workflow carries the
+ * overall goal.
+ */
+export type Plan = { id: string, sessionId: string, specId: string, status: PlanStatus, root: Workflow, approvedAt: string | null, createdAt: string, modifiedAt: string, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { PlanEdit } from "./PlanEdit";
+
+/**
+ * What a user can do with a draft plan
+ */
+export type PlanAction = { "type": "approve" } | { "type": "reject", reason?: string, } | { "type": "edit", edit: PlanEdit, };
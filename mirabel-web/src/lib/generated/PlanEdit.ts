@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { StepAction } from "./StepAction";
+
+export type PlanEdit = { "type": "setGoal", workflowId: string, goal: string, } | { "type": "setStep", stepId: string, description: string, action: StepAction, } | { "type": "setEstimate", nodeId: string, estimateSecs: number | null, } | { "type": "remove", nodeId: string, };
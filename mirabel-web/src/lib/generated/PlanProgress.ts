@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Progress of a running plan, broadcast whenever a step starts or finishes
+ */
+export type PlanProgress = { planId: string, currentStepId?: string, completedSteps: number, totalSteps: number, elapsedSecs: number, 
+/**
+ * Unknown until there is something to extrapolate from
+ */
+remainingSecs?: number, };
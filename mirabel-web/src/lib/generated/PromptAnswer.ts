@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type PromptAnswer = { "type": "text", "value": string } | { "type": "choice", "value": string } | { "type": "choices", "value": Array<string> } | { "type": "confirm", "value": boolean };
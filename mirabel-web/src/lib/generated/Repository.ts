@@ -0,0 +1,17 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A git repository of a workspace, cloned from `source`, a local path or a
+ * URL
+ */
+export type Repository = { id: string, workspaceId: string, 
+/**
+ * Unique in the workspace, it is also the directory sessions check the
+ * repository out to
+ */
+name: string, source: string, defaultBranch: string, createdAt: string, 
+/**
+ * Whether sessions merge into the default branch of the source. When
+ * not, their branches are pushed for someone to review and merge.
+ */
+mergeUpstream: boolean, };
@@ -0,0 +1,16 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SnippetPart } from "./SnippetPart";
+
+/**
+ * A timeline entry that matched a search
+ */
+export type SearchHit = { entryId: string, sessionId: string, sessionTitle: string, contentType: string, createdAt: string, 
+/**
+ * The text of the entry around the matches
+ */
+snippet: Array<SnippetPart>, score: number, 
+/**
+ * Loading the session timeline `before` this cursor gives the page that
+ * ends with the entry
+ */
+cursor: string, };
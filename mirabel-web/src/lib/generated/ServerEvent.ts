@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Ack } from "./Ack";
+import type { Heartbeat } from "./Heartbeat";
+import type { PlanProgress } from "./PlanProgress";
+import type { Presence } from "./Presence";
+import type { ProtocolError } from "./ProtocolError";
+import type { SessionWorkerState } from "./SessionWorkerState";
+import type { ShellDriver } from "./ShellDriver";
+import type { ShellOutput } from "./ShellOutput";
+import type { TimelineEntry } from "./TimelineEntry";
+
+export type ServerEvent = { "type": "timelineEntry", "data": TimelineEntry } | { "type": "stateChanged", "data": SessionWorkerState } | { "type": "error", "data": ProtocolError } | { "type": "ack", "data": Ack } | { "type": "presence", "data": Presence } | { "type": "heartbeat", "data": Heartbeat } | { "type": "planProgress", "data": PlanProgress } | { "type": "shellOutput", "data": ShellOutput } | { "type": "shellDriver", "data": ShellDriver };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ServerEvent } from "./ServerEvent";
+
+/**
+ * Envelope for everything the server sends to a session subscriber.
+ */
+export type ServerMessage = { version: number, event: ServerEvent, };
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SessionWorkerError } from "./SessionWorkerError";
+
+export type SessionWorkerState = "stopped" | "initializing" | "idle" | "paused" | "running" | "stopping" | { "error": SessionWorkerError };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type SnippetPart = { text: string, 
+/**
+ * The part matched the query
+ */
+highlighted: boolean, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * One version of the spec of a session. Versions start at 1, every version
+ * after the first one carries the unified diff to its predecessor.
+ */
+export type Spec = { id: string, sessionId: string, version: number, content: string, diff: string | null, acceptedAt: string | null, createdAt: string, };
@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { NodeStatus } from "./NodeStatus";
+import type { StepAction } from "./StepAction";
+import type { Timing } from "./Timing";
+
+/**
+ * An atomic action, steps of a workflow run in order unless `depends_on`
+ * says otherwise
+ */
+export type Step = { id: string, description: string, action: StepAction, status: NodeStatus, timing: Timing, estimateSecs?: number, dependsOn: Array<string>, };
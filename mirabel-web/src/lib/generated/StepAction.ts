@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type StepAction = { "type": "shell", command: string, } | { "type": "edit", path: string, instructions: string, } | { "type": "verify", command: string, } | { "type": "research", query: string, } | { "type": "screenshot", url: string, } | { "type": "documents", query: string, } | { "type": "other" };
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SymbolKind } from "./SymbolKind";
+
+/**
+ * A definition of or a reference to a symbol, with the file it is in
+ */
+export type SymbolLocation = { 
+/**
+ * Relative to the worktree
+ */
+path: string, name: string, kind: SymbolKind, definition: boolean, line: number, column: number, endLine: number, signature?: string, };
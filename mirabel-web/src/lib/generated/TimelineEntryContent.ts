@@ -0,0 +1,37 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AcknowledgmentType } from "./AcknowledgmentType";
+import type { ActionType } from "./ActionType";
+import type { AgentStatus } from "./AgentStatus";
+import type { Citation } from "./Citation";
+import type { MessageSender } from "./MessageSender";
+import type { PlanStatus } from "./PlanStatus";
+import type { PromptAnswer } from "./PromptAnswer";
+import type { PromptKind } from "./PromptKind";
+import type { Workflow } from "./Workflow";
+
+export type TimelineEntryContent = { "type": "message", sender: MessageSender, 
+/**
+ * The user who wrote the message, absent for agent messages and
+ * messages written before sessions could be shared
+ */
+userId?: string, message: string, } | { "type": "acknowledgment", ackType: AcknowledgmentType, } | { "type": "agentStatus", status: AgentStatus, } | { "type": "prompt", promptId: string, question: string, kind: PromptKind, options: Array<string>, } | { "type": "promptResponse", promptId: string, 
+/**
+ * Human readable form of the answer
+ */
+response: string, answer?: PromptAnswer, userId?: string, } | { "type": "action", actionType: ActionType, message: string, 
+/**
+ * The recorded file action, which can be reverted
+ */
+actionId?: string, path?: string, 
+/**
+ * Unified diff of the change to the file
+ */
+diff?: string, 
+/**
+ * The action that was reverted by this one
+ */
+reverts?: string, } | { "type": "spec", content: string, specId?: string, version?: number, 
+/**
+ * Unified diff to the previous version
+ */
+diff?: string, } | { "type": "plan", planId?: string, status?: PlanStatus, root?: Workflow, } | { "type": "shell", lines: Array<string>, } | { "type": "research", query: string, answer: string, sources: Array<Citation>, } | { "type": "screenshot", url: string, title: string, attachmentId: string, };
@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A search over the timelines of the sessions of a workspace
+ */
+export type TimelineSearch = { query: string, 
+/**
+ * Comma separated content types to search in, e.g. `message,spec`
+ */
+types?: string, from?: string, to?: string, 
+/**
+ * Also finds entries that mean the same in other words, when the server
+ * embeds timelines. On unless turned off.
+ */
+semantic?: boolean, limit?: bigint, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { MemoryKind } from "./MemoryKind";
+
+export type UpdatedMemory = { kind?: MemoryKind, content?: string, confidence?: number, 
+/**
+ * `null` removes the expiry
+ */
+expiresAt?: string | null, };
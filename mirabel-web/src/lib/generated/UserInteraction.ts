@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { PlanAction } from "./PlanAction";
+import type { PromptAnswer } from "./PromptAnswer";
+
+export type UserInteraction = { "type": "message", content: string, } | { "type": "promptResponse", promptId: string, answer: PromptAnswer, } | { "type": "plan", planId: string, action: PlanAction, };
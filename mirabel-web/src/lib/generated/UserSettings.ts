@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type UserSettings = { 
+/**
+ * Plans are approved right away instead of waiting for the user
+ */
+autoApprovePlans: boolean, modifiedAt: string, };
@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { NodeStatus } from "./NodeStatus";
+import type { Step } from "./Step";
+import type { Timing } from "./Timing";
+
+/**
+ * A goal, achieved by its child workflows first and then its own steps
+ */
+export type Workflow = { id: string, goal: string, status: NodeStatus, timing: Timing, estimateSecs?: number, workflows: Array<Workflow>, steps: Array<Step>, };
@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { WorktreeStatus } from "./WorktreeStatus";
+
+/**
+ * The checkout of a repository a session works in, on a branch of its own
+ */
+export type Worktree = { id: string, sessionId: string, repositoryId: string, branch: string, 
+/**
+ * The commit the branch started at, diffs are against it
+ */
+baseCommit: string, status: WorktreeStatus, 
+/**
+ * The merge commit, or the last commit of the branch when it was pushed
+ */
+mergeCommit: string | null, createdAt: string, modifiedAt: string, };
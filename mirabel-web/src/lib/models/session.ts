@@ -1,4 +1,7 @@
 import type { PageResponse } from './page';
+import type { PromptAnswer } from '../generated/PromptAnswer';
+import type { PromptKind } from '../generated/PromptKind';
+import type { ServerEvent } from '../generated/ServerEvent';
 
 export interface ShallowSession {
     id: string;
@@ -33,11 +36,17 @@ export function emptySession() {
     };
 }
 
-export interface UserInteraction {
-    type: 'message' | 'promptResponse';
-    content?: string;
-    promptId?: string;
-    response?: string;
+export type { UserInteraction } from '../generated/UserInteraction';
+
+/**
+ * Envelope of everything the session socket sends, see `ServerMessage`.
+ * Timeline entries are typed with the models of the web client.
+ */
+export interface SessionMessage {
+    version: number;
+    event:
+        | Exclude<ServerEvent, { type: 'timelineEntry' }>
+        | { type: 'timelineEntry'; data: TimelineEntry };
 }
 
 export interface TimelineEntry {
@@ -89,6 +98,8 @@ export interface AgentStatusContent {
 export interface Prompt {
     type: 'prompt';
     promptId: string;
+    question: string;
+    kind: PromptKind;
     options: string[];
 }
 
@@ -96,6 +107,8 @@ export interface PromptResponse {
     type: 'promptResponse';
     promptId: string;
     response: string;
+    answer?: PromptAnswer;
+    userId?: string;
 }
 
 export interface ActionContent {
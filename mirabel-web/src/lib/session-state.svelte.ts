@@ -1,19 +1,27 @@
 import {
     emptySession,
     type Session,
-    type TimelineEntry,
-    type UserInteraction
+    type SessionMessage,
+    type TimelineEntry
 } from './models/session';
 import type { SocketHandler } from './socket.svelte';
+import type { ClientMessage } from './generated/ClientMessage';
+import type { PromptAnswer } from './generated/PromptAnswer';
+import type { UserInteraction } from './generated/UserInteraction';
 import { emptyUser, type User } from './models/user';
 import { getSessionTimelineCursor } from './api/session';
 
+// Version of the session socket protocol, `PROTOCOL_VERSION` on the server
+export const PROTOCOL_VERSION = 1;
+
+export type SessionSocket = SocketHandler<SessionMessage, ClientMessage>;
+
 export class SessionState {
     user: User = $state(emptyUser());
     timeline: TimelineEntry[] = $state([]);
     timelineKnownTotal: number = $state(0);
     session: Session = $state(emptySession());
-    socket: SocketHandler<TimelineEntry, UserInteraction> | undefined = $state();
+    socket: SessionSocket | undefined = $state();
     
     // Infinite scroll state
     isLoadingOlder: boolean = $state(false);
@@ -31,14 +39,14 @@ export class SessionState {
     constructor(
         user: User,
         session: Session,
-        socket: SocketHandler<TimelineEntry, UserInteraction>
+        socket: SessionSocket
     ) {
         this.user = user;
         this.session = session;
         this.timeline = session.timeline.data;
         this.timelineKnownTotal = session.timeline.pageInfo.total;
         this.socket = socket;
-        this.socket.setMessageHandler(this.onEvent.bind(this));
+        this.socket.setMessageHandler(this.onMessage.bind(this));
         
         // Set initial cursor to the oldest message (first in chronological order)
         if (this.timeline.length > 0) {
@@ -93,6 +101,33 @@ export class SessionState {
         this.newMessageCallback = () => {};
     }
 
+    public send(interaction: UserInteraction): void {
+        this.socket?.send({
+            version: PROTOCOL_VERSION,
+            event: { type: 'interaction', data: interaction }
+        });
+    }
+
+    // The answer has to suit the kind of the prompt, e.g. `confirm` for a yes or no question
+    public answerPrompt(promptId: string, answer: PromptAnswer): void {
+        this.send({ type: 'promptResponse', promptId, answer });
+    }
+
+    private onMessage(message: SessionMessage): void {
+        if (message.version !== PROTOCOL_VERSION) {
+            console.warn(`Unsupported session protocol version: ${message.version}`);
+            return;
+        }
+        switch (message.event.type) {
+            case 'timelineEntry':
+                this.onEvent(message.event.data);
+                break;
+            case 'error':
+                console.error(`Session error: ${message.event.data.message}`);
+                break;
+        }
+    }
+
     private onEvent(event: TimelineEntry): void {
         this.timeline.push(event);
         this.timelineKnownTotal += 1;
@@ -1,10 +1,10 @@
 import type { PageLoad } from './$types';
 
 import { connectWebSocket, get } from '$lib/request';
-import type { Session, ShallowSession, TimelineEntry, UserInteraction } from '$lib/models/session';
+import type { Session, ShallowSession } from '$lib/models/session';
 import type { Workspace } from '$lib/models/workspace';
 import { error } from '@sveltejs/kit';
-import type { SocketHandler } from '$lib/socket.svelte';
+import type { SessionSocket } from '$lib/session-state.svelte';
 import type Result from '$lib/models/result';
 
 export async function load({ params, fetch, parent }: PageLoad): Promise<{
@@ -13,7 +13,7 @@ export async function load({ params, fetch, parent }: PageLoad): Promise<{
     sessions: ShallowSession[];
     session_id: string;
     session: Session;
-    socket: SocketHandler<TimelineEntry, UserInteraction>;
+    socket: SessionSocket;
 }> {
     const session: Result<Session> = await get(
         `v1/workspace/${params.workspace_id}/session/${params.session_id}`,